@@ -0,0 +1,77 @@
+//! This module contains population alarms, which pause the simulation or
+//! show a notification when the population crosses a threshold.
+
+use std::fmt;
+
+/// What happens when an alarm goes off.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum AlarmAction {
+    /// Pause the simulation and show a notification
+    Pause,
+    /// Only show a notification
+    Notify,
+}
+
+impl fmt::Display for AlarmAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlarmAction::Pause => write!(f, "pause"),
+            AlarmAction::Notify => write!(f, "notify"),
+        }
+    }
+}
+
+/// Population thresholds. An alarm goes off once each time the population
+/// crosses one of the thresholds, not on every generation it stays beyond it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alarms {
+    /// Alarm when the population rises above this
+    pub above: Option<usize>,
+    /// Alarm when the population falls below this
+    pub below: Option<usize>,
+    /// What happens when an alarm goes off
+    pub action: AlarmAction,
+    /// Population at the previous check
+    last: Option<usize>,
+}
+
+impl Alarms {
+    pub fn new(above: Option<usize>, below: Option<usize>, action: AlarmAction) -> Alarms {
+        Alarms {
+            above,
+            below,
+            action,
+            last: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        //! Forgets the last population, e.g. after the grid was replaced.
+        self.last = None;
+    }
+
+    pub fn check(&mut self, population: usize) -> Option<String> {
+        //! Records the population of a new generation, returning a message
+        //! if it crossed one of the thresholds.
+        let last: Option<usize> = self.last.replace(population);
+        let crossed =
+            |beyond: &dyn Fn(usize) -> bool| beyond(population) && !last.is_some_and(beyond);
+        match (self.above, self.below) {
+            (Some(above), _) if crossed(&|p| p > above) => Some(format!(
+                "Alarm: population {} rose above {}",
+                population, above
+            )),
+            (_, Some(below)) if crossed(&|p| p < below) => Some(format!(
+                "Alarm: population {} fell below {}",
+                population, below
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Alarms {
+    fn default() -> Self {
+        Alarms::new(None, None, AlarmAction::Pause)
+    }
+}
@@ -0,0 +1,136 @@
+//! This module contains attract mode, a demo loop for meetups and shop
+//! windows. Every so often, or once the run stagnates, the grid starts over
+//! with a new soup under another of the well-known rules, at another density
+//! and in another color theme, and a caption says which.
+
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use super::conway::Cell;
+use super::kiosk::Stagnation;
+use super::rule::{Rule, RULES};
+use super::theme::{Theme, BUILTIN_THEMES};
+
+/// Names the well-known rules are announced by, in the order of `RULES`.
+const RULE_NAMES: [&str; RULES.len()] = [
+    "Life",
+    "HighLife",
+    "Day & Night",
+    "Just Friends",
+    "tlife",
+    "Critters",
+    "Falling sand",
+];
+
+/// Fractions of the grid soups are filled to.
+const DENSITIES: [f64; 5] = [0.1, 0.2, 0.3, 0.4, 0.5];
+
+/// Number of seconds the caption announcing a scene stays up.
+const CAPTION_SECONDS: u64 = 4;
+
+/// A rule, density and theme a soup is shown in.
+#[derive(Debug, Clone)]
+pub struct Scene {
+    /// Rule the soup follows
+    pub rule: Rule,
+    /// Name the rule is announced by
+    pub rule_name: String,
+    /// Fraction of the grid the soup fills
+    pub density: f64,
+    /// Color theme the soup is drawn in
+    pub theme: Theme,
+}
+
+/// Attract mode, switching scenes on its own.
+#[derive(Debug, Clone)]
+pub struct Attract {
+    /// Longest a scene is shown for
+    interval: Duration,
+    /// When the current scene started
+    started: Instant,
+    /// Whether the current scene's caption is still up
+    captioned: bool,
+    /// Recent frames, to tell when the run has stagnated
+    stagnation: Stagnation,
+    /// Picks the scenes
+    rng: StdRng,
+}
+
+impl Scene {
+    pub fn caption(&self) -> String {
+        //! Announces the scene.
+        format!(
+            "{} ({}), {}% alive, {} theme",
+            self.rule_name,
+            self.rule,
+            (self.density * 100.0).round(),
+            self.theme.name
+        )
+    }
+
+    pub fn alive(&self, grid: &[Vec<Cell>]) -> usize {
+        //! Number of cells a soup at the scene's density brings to life on
+        //! the grid.
+        (self.density * (grid.len() * grid[0].len()) as f64).round() as usize
+    }
+}
+
+impl Attract {
+    pub fn new(interval: u64, seed: u64) -> Attract {
+        Attract {
+            interval: Duration::from_secs(interval),
+            started: Instant::now(),
+            captioned: false,
+            stagnation: Stagnation::default(),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn is_over(&mut self, grid: &[Vec<Cell>], generation: u64) -> bool {
+        //! Records a frame, returning true once the scene has been shown for
+        //! long enough or the run has stagnated.
+        self.stagnation.is_stagnant(grid, generation) || self.started.elapsed() >= self.interval
+    }
+
+    pub fn next_scene(&mut self, current: &Rule) -> Scene {
+        //! Picks the next scene, under another rule than the current one,
+        //! and starts showing it.
+        let rules: Vec<(Rule, &str)> = RULES
+            .iter()
+            .zip(RULE_NAMES)
+            .filter_map(|(rule, name)| Some((rule.parse::<Rule>().ok()?, name)))
+            .filter(|(rule, _)| rule != current)
+            .collect();
+        let (rule, rule_name) = match rules.choose(&mut self.rng) {
+            Some((rule, name)) => (rule.clone(), name.to_string()),
+            None => (current.clone(), current.to_string()),
+        };
+        let density: f64 = *DENSITIES.choose(&mut self.rng).unwrap_or(&0.3);
+        let theme: Theme = BUILTIN_THEMES
+            .choose(&mut self.rng)
+            .and_then(|name| Theme::builtin(name))
+            .unwrap_or_default();
+        self.started = Instant::now();
+        self.captioned = true;
+        self.stagnation.clear();
+        Scene {
+            rule,
+            rule_name,
+            density,
+            theme,
+        }
+    }
+
+    pub fn caption_expired(&mut self) -> bool {
+        //! Returns true once, when the current scene's caption has been up
+        //! for long enough to be taken down.
+        if self.captioned && self.started.elapsed() >= Duration::from_secs(CAPTION_SECONDS) {
+            self.captioned = false;
+            return true;
+        }
+        false
+    }
+}
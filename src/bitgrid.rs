@@ -0,0 +1,501 @@
+//! This module contains the bit-packed engine the grid is stepped with.
+//!
+//! Each row of cells is packed into 64-bit words, one bit per cell, and the
+//! next generation is computed 64 cells at a time: the eight neighbors of
+//! every cell in a word are lined up by shifting the rows above, below and
+//! beside it, and added together with bitwise adders.
+//!
+//! The packed grid has a border of one cell on every side, filled with the
+//! cells that lie across the edges of the grid according to its topology, so
+//! that the cells along the edges need no special handling.
+//!
+//! Life's rule is worked out with bitwise adders; other rules look up each
+//! cell's arrangement of neighbors in the rule's table, one cell at a time.
+//!
+//! Once a soup settles, most of the grid stays the same from one generation
+//! to the next. `Activity` remembers which words changed in the last
+//! generation, and only works out the words around them; every other word
+//! can't change either.
+
+use super::conway::Cell;
+use super::rule::Rule;
+use super::topology::Topology;
+
+/// Fraction of the words that may be active before the whole grid is worked
+/// out instead, which is faster than checking which words are active.
+const MAX_ACTIVE_FRACTION: f64 = 0.5;
+
+/// Alive state of every cell of a grid, packed into words.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitGrid {
+    /// Number of rows of the grid, without the border
+    nrows: usize,
+    /// Number of columns of the grid, without the border
+    ncols: usize,
+    /// Number of words each row, border included, is packed into
+    words: usize,
+    /// Rows of words, border included; bit `j % 64` of word `j / 64` of a row
+    /// holds the cell in column `j`
+    bits: Vec<u64>,
+}
+
+impl BitGrid {
+    fn empty(nrows: usize, ncols: usize) -> BitGrid {
+        let words: usize = (ncols + 2).div_ceil(64);
+        BitGrid {
+            nrows,
+            ncols,
+            words,
+            bits: vec![0; (nrows + 2) * words],
+        }
+    }
+
+    fn from_cells(grid: &[Vec<Cell>]) -> BitGrid {
+        //! Packs the alive state of the grid's cells, leaving the border
+        //! empty.
+        let ncols: usize = grid.first().map_or(0, |row| row.len());
+        let mut packed: BitGrid = BitGrid::empty(grid.len(), ncols);
+        for (i, row) in grid.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                if cell.is_alive() {
+                    packed.set(i + 1, j + 1);
+                }
+            }
+        }
+        packed
+    }
+
+    pub fn from_fn<F: Fn(usize, usize) -> bool>(nrows: usize, ncols: usize, alive: F) -> BitGrid {
+        //! Packs the cells of a grid of `nrows` by `ncols` cells for which
+        //! `alive` holds, leaving the border empty.
+        let mut packed: BitGrid = BitGrid::empty(nrows, ncols);
+        for i in 0..nrows {
+            for j in (0..ncols).filter(|j| alive(i, *j)) {
+                packed.set(i + 1, j + 1);
+            }
+        }
+        packed
+    }
+
+    pub fn pack(grid: &[Vec<Cell>], topology: Topology) -> BitGrid {
+        //! Packs the alive state of the grid's cells, with the cells across
+        //! the edges in the border.
+        let mut packed: BitGrid = BitGrid::from_cells(grid);
+        packed.fill_border(grid, topology);
+        packed
+    }
+
+    #[cfg(feature = "gpu")]
+    pub fn size(&self) -> (usize, usize) {
+        (self.nrows, self.ncols)
+    }
+
+    #[cfg(feature = "gpu")]
+    pub fn to_words32(&self) -> Vec<u32> {
+        //! Splits the words into 32-bit words, low half first, which keeps
+        //! cell `j` of a row at bit `j % 32` of word `j / 32`.
+        self.bits
+            .iter()
+            .flat_map(|word| [*word as u32, (word >> 32) as u32])
+            .collect()
+    }
+
+    #[cfg(feature = "gpu")]
+    pub fn from_words32(nrows: usize, ncols: usize, words: &[u32]) -> BitGrid {
+        //! Joins 32-bit words split by `to_words32`, and clears the border.
+        let mut packed: BitGrid = BitGrid::empty(nrows, ncols);
+        for (word, halves) in packed.bits.iter_mut().zip(words.chunks_exact(2)) {
+            *word = halves[0] as u64 | (halves[1] as u64) << 32;
+        }
+        packed.clear_border();
+        packed
+    }
+
+    fn fill_border(&mut self, grid: &[Vec<Cell>], topology: Topology) {
+        //! Fills the border with the cells across the edges of the grid.
+        let (nrows, ncols) = (self.nrows, self.ncols);
+        if nrows == 0 || ncols == 0 {
+            return;
+        }
+        let border = (0..ncols + 2)
+            .flat_map(|j| [(0, j), (nrows + 1, j)])
+            .chain((1..=nrows).flat_map(|i| [(i, 0), (i, ncols + 1)]));
+        for (i, j) in border {
+            let across = topology.locate(i as isize - 1, j as isize - 1, nrows, ncols);
+            if across.is_some_and(|(x, y)| grid[x][y].is_alive()) {
+                self.set(i, j);
+            }
+        }
+    }
+
+    fn clear_border(&mut self) {
+        //! Clears the border, and the unused bits after it.
+        let words: usize = self.words;
+        self.bits[..words].fill(0);
+        self.bits[(self.nrows + 1) * words..].fill(0);
+        for i in 1..=self.nrows {
+            let row: &mut [u64] = &mut self.bits[i * words..(i + 1) * words];
+            row[0] &= !1;
+            let last: usize = self.ncols / 64;
+            row[last] &= u64::MAX >> (63 - self.ncols % 64);
+            row[last + 1..].fill(0);
+        }
+    }
+
+    fn set(&mut self, i: usize, j: usize) {
+        //! Sets a cell alive, by its position including the border.
+        self.bits[i * self.words + j / 64] |= 1 << (j % 64);
+    }
+
+    fn get(&self, i: usize, j: usize) -> bool {
+        //! Whether a cell is alive, by its position including the border.
+        self.bits[i * self.words + j / 64] & (1 << (j % 64)) != 0
+    }
+
+    pub fn is_alive(&self, i: usize, j: usize) -> bool {
+        //! Whether the cell at row `i` and column `j` of the grid is alive.
+        self.get(i + 1, j + 1)
+    }
+
+    fn row(&self, i: usize) -> &[u64] {
+        &self.bits[i * self.words..(i + 1) * self.words]
+    }
+
+    fn next_word(&self, i: usize, k: usize, rule: &Rule, life: bool) -> u64 {
+        //! Calculates which cells of word `k` of row `i` are alive in the next
+        //! generation, `life` telling whether the rule is Life's.
+        let (above, middle, below) = (self.row(i - 1), self.row(i), self.row(i + 1));
+        let (above_left, above_right) = shifted(above, k);
+        let (left, right) = shifted(middle, k);
+        let (below_left, below_right) = shifted(below, k);
+        if !life {
+            // in the order of the bits of the index of an arrangement
+            let neighborhood: [u64; 9] = [
+                above_left,
+                above[k],
+                above_right,
+                left,
+                middle[k],
+                right,
+                below_left,
+                below[k],
+                below_right,
+            ];
+            let mut next: u64 = 0;
+            for bit in 0..64 {
+                let index: usize = neighborhood
+                    .iter()
+                    .fold(0, |index, word| index << 1 | (word >> bit & 1) as usize);
+                if rule.is_alive_next(index) {
+                    next |= 1 << bit;
+                }
+            }
+            return next;
+        }
+
+        // add up the eight neighbors, one bit of the count at a time
+        let (sum_above, carry_above) = full_add(above_left, above[k], above_right);
+        let (sum_beside, carry_beside) = full_add(left, right, below_left);
+        let (sum_below, carry_below) = (below[k] ^ below_right, below[k] & below_right);
+        let (ones, carry_ones) = full_add(sum_above, sum_beside, sum_below);
+        let (twos, carry_twos) = full_add(carry_above, carry_beside, carry_below);
+        let (twos, carry) = (twos ^ carry_ones, twos & carry_ones);
+        let fours_or_more: u64 = carry_twos | carry;
+
+        // alive with 3 neighbors, or with 2 if the cell already is
+        !fours_or_more & twos & (ones | middle[k])
+    }
+
+    fn touches_edges(&self) -> bool {
+        //! Whether any cell along the edges of the grid is alive, which cells
+        //! across the edges may see.
+        let last: usize = self.ncols;
+        self.row(1).iter().any(|word| *word != 0)
+            || self.row(self.nrows).iter().any(|word| *word != 0)
+            || (1..=self.nrows).any(|i| self.get(i, 1) || self.get(i, last))
+    }
+}
+
+/// Which words changed in the last generation worked out, so that the next
+/// one only has to be worked out around them.
+#[derive(Debug, Clone, Default)]
+pub struct Activity {
+    /// Cells alive at the end of the last generation worked out
+    last: Option<BitGrid>,
+    /// Cells that changed in the last generation worked out
+    changed: Option<BitGrid>,
+    /// Topology the last generation was worked out on
+    topology: Topology,
+    /// Rule the last generation was worked out with
+    rule: Rule,
+}
+
+impl Activity {
+    pub fn next_generation(
+        &mut self,
+        grid: &[Vec<Cell>],
+        topology: Topology,
+        rule: &Rule,
+    ) -> &BitGrid {
+        //! Calculates which cells of the grid are alive in the next
+        //! generation. If the grid is still what the last generation ended
+        //! with, only the words around the cells that changed are worked out;
+        //! after an edit, a resize, a change of rule, or a change along the
+        //! edges, all of them.
+        let mut current: BitGrid = BitGrid::from_cells(grid);
+        let active: Option<Vec<bool>> = match (&self.last, &self.changed) {
+            (Some(last), Some(changed))
+                if *last == current && topology == self.topology && *rule == self.rule =>
+            {
+                active_words(changed)
+            }
+            _ => None,
+        };
+        let life: bool = rule.is_life();
+        let unchanged: BitGrid = current.clone();
+        current.fill_border(grid, topology);
+
+        let mut next: BitGrid = BitGrid::empty(current.nrows, current.ncols);
+        for i in 1..=current.nrows {
+            for k in 0..current.words {
+                let index: usize = i * current.words + k;
+                next.bits[index] = match &active {
+                    Some(active) if !active[index] => unchanged.bits[index],
+                    _ => current.next_word(i, k, rule, life),
+                };
+            }
+        }
+        next.clear_border();
+
+        let mut changed: BitGrid = unchanged;
+        for (word, next_word) in changed.bits.iter_mut().zip(&next.bits) {
+            *word ^= next_word;
+        }
+        self.changed = Some(changed);
+        self.topology = topology;
+        self.rule.clone_from(rule);
+        self.last.insert(next)
+    }
+}
+
+fn active_words(changed: &BitGrid) -> Option<Vec<bool>> {
+    //! Finds the words next to a word that changed, which are the only ones
+    //! that can change next. Returns `None` if all of them should be worked
+    //! out, because too many are active, or cells changed along the edges,
+    //! which cells across the edges see.
+    if changed.touches_edges() {
+        return None;
+    }
+    let words: usize = changed.words;
+    let mut active: Vec<bool> = vec![false; changed.bits.len()];
+    let mut count: usize = 0;
+    for i in 1..=changed.nrows {
+        for k in 0..words {
+            if changed.bits[i * words + k] == 0 {
+                continue;
+            }
+            for row in i - 1..=i + 1 {
+                for word in k.saturating_sub(1)..(k + 2).min(words) {
+                    let index: usize = row * words + word;
+                    if !active[index] {
+                        active[index] = true;
+                        count += 1;
+                    }
+                }
+            }
+        }
+    }
+    if count as f64 > changed.bits.len() as f64 * MAX_ACTIVE_FRACTION {
+        return None;
+    }
+    Some(active)
+}
+
+fn shifted(row: &[u64], k: usize) -> (u64, u64) {
+    //! Returns word `k` of the row shifted so that each bit holds its left
+    //! neighbor, and shifted so that each bit holds its right neighbor.
+    let previous: u64 = if k > 0 { row[k - 1] >> 63 } else { 0 };
+    let following: u64 = row.get(k + 1).map_or(0, |word| word << 63);
+    ((row[k] << 1) | previous, (row[k] >> 1) | following)
+}
+
+fn full_add(a: u64, b: u64, c: u64) -> (u64, u64) {
+    //! Adds three words bit by bit, returning the sum and carry bits.
+    let partial: u64 = a ^ b;
+    (partial ^ c, (a & b) | (partial & c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conway::new_grid;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const TOPOLOGIES: [Topology; 5] = [
+        Topology::Torus,
+        Topology::Plane,
+        Topology::Klein,
+        Topology::CrossSurface,
+        Topology::Mirror,
+    ];
+
+    /// Sizes of grids stepped, some of them with rows just short of, filling,
+    /// or just over a word.
+    const SIZES: [(usize, usize); 7] = [
+        (4, 5),
+        (9, 62),
+        (8, 63),
+        (7, 64),
+        (6, 65),
+        (10, 66),
+        (5, 130),
+    ];
+
+    fn soup(nrows: usize, ncols: usize, rng: &mut StdRng) -> Vec<Vec<Cell>> {
+        let mut grid: Vec<Vec<Cell>> = new_grid(nrows, ncols);
+        for cell in grid.iter_mut().flatten() {
+            if rng.gen_bool(0.4) {
+                cell.set_alive();
+            }
+        }
+        grid
+    }
+
+    fn naive_generation(grid: &[Vec<Cell>], topology: Topology, rule: &Rule) -> BitGrid {
+        //! Works out the next generation one cell at a time, looking up each
+        //! neighbor across the edges.
+        let (nrows, ncols) = (grid.len(), grid[0].len());
+        let life: bool = rule.is_life();
+        BitGrid::from_fn(nrows, ncols, |i, j| {
+            let mut index: usize = 0;
+            let mut neighbors: usize = 0;
+            for di in -1..=1 {
+                for dj in -1..=1 {
+                    let alive: bool = topology
+                        .locate(i as isize + di, j as isize + dj, nrows, ncols)
+                        .is_some_and(|(x, y)| grid[x][y].is_alive());
+                    index = index << 1 | alive as usize;
+                    if alive && (di, dj) != (0, 0) {
+                        neighbors += 1;
+                    }
+                }
+            }
+            if life {
+                neighbors == 3 || (neighbors == 2 && grid[i][j].is_alive())
+            } else {
+                rule.is_alive_next(index)
+            }
+        })
+    }
+
+    fn full_generation(grid: &[Vec<Cell>], topology: Topology, rule: &Rule) -> BitGrid {
+        //! Works out every word of the next generation, with nothing known
+        //! about the last one.
+        Activity::default()
+            .next_generation(grid, topology, rule)
+            .clone()
+    }
+
+    fn apply(grid: &mut [Vec<Cell>], next: &BitGrid) {
+        for cell in grid.iter_mut().flatten() {
+            match next.is_alive(cell.x, cell.y) {
+                true => cell.set_alive(),
+                false => cell.set_dead(),
+            }
+        }
+    }
+
+    #[test]
+    fn words_match_cell_by_cell_counts() {
+        let mut rng: StdRng = StdRng::seed_from_u64(831);
+        for rule in ["B3/S23", "B36/S23", "B2-a/S12"] {
+            let rule: Rule = rule.parse().unwrap();
+            for topology in TOPOLOGIES {
+                for (nrows, ncols) in SIZES {
+                    let mut grid: Vec<Vec<Cell>> = soup(nrows, ncols, &mut rng);
+                    for generation in 0..6 {
+                        let expected: BitGrid = naive_generation(&grid, topology, &rule);
+                        let actual: BitGrid = full_generation(&grid, topology, &rule);
+                        assert!(
+                            actual == expected,
+                            "{} on a {} of {}x{}, generation {}",
+                            rule,
+                            topology,
+                            ncols,
+                            nrows,
+                            generation
+                        );
+                        apply(&mut grid, &expected);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_border_holds_the_cells_across_the_edges() {
+        let mut rng: StdRng = StdRng::seed_from_u64(8310);
+        for topology in TOPOLOGIES {
+            for (nrows, ncols) in SIZES {
+                let grid: Vec<Vec<Cell>> = soup(nrows, ncols, &mut rng);
+                let packed: BitGrid = BitGrid::pack(&grid, topology);
+                for i in 0..nrows + 2 {
+                    for j in 0..ncols + 2 {
+                        let across: bool = topology
+                            .locate(i as isize - 1, j as isize - 1, nrows, ncols)
+                            .is_some_and(|(x, y)| grid[x][y].is_alive());
+                        assert_eq!(packed.get(i, j), across, "{} at {},{}", topology, i, j);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn activity_matches_full_generations() {
+        let life: Rule = Rule::default();
+        let highlife: Rule = "B36/S23".parse().unwrap();
+        let mut rng: StdRng = StdRng::seed_from_u64(832);
+        for (first, second) in [
+            (Topology::Torus, Topology::Klein),
+            (Topology::Plane, Topology::Mirror),
+            (Topology::CrossSurface, Topology::Torus),
+        ] {
+            // a soup in the middle of a wide grid settles, so that most words
+            // are left alone, while gliders it lets out reach the edges
+            let mut grid: Vec<Vec<Cell>> = new_grid(40, 150);
+            for cell in grid[15..25].iter_mut().flat_map(|row| &mut row[65..85]) {
+                if rng.gen_bool(0.4) {
+                    cell.set_alive();
+                }
+            }
+            // and a glider heads for the corner
+            for (i, j) in [(30, 131), (31, 132), (32, 130), (32, 131), (32, 132)] {
+                grid[i][j].set_alive();
+            }
+            let mut activity: Activity = Activity::default();
+            for generation in 0..400 {
+                let (topology, rule) = match generation {
+                    0..=149 => (first, &life),
+                    150..=249 => (second, &life),
+                    _ => (second, &highlife),
+                };
+                if generation == 320 {
+                    grid[20][75].set_alive();
+                    grid[0][0].set_alive();
+                }
+                let expected: BitGrid = full_generation(&grid, topology, rule);
+                let actual: &BitGrid = activity.next_generation(&grid, topology, rule);
+                assert!(
+                    *actual == expected,
+                    "{} to {}, generation {}",
+                    first,
+                    second,
+                    generation
+                );
+                apply(&mut grid, &expected);
+            }
+        }
+    }
+}
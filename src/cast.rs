@@ -0,0 +1,143 @@
+//! This module contains the recording of runs as asciinema v2 cast files,
+//! which can be replayed with `asciinema play` or shared on asciinema.org.
+//!
+//! The screen isn't captured as it is written to the terminal: every drawn
+//! frame is read back from its window and written to the cast as a full
+//! redraw, with the colors and attributes of each cell.
+
+use anyhow::{anyhow, Result};
+use ncurses::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use super::window::Window;
+
+/// An asciinema cast being recorded.
+pub struct Cast {
+    /// File the cast is written to
+    file: BufWriter<File>,
+    /// Path of the file, for errors
+    path: String,
+    /// When the first frame was recorded, which the header is written for
+    start: Option<Instant>,
+}
+
+impl Cast {
+    pub fn create(path: &str) -> Result<Cast> {
+        //! Creates the cast file. The header is written with the first frame,
+        //! once the size of the terminal is known.
+        let file: File =
+            File::create(path).map_err(|e| anyhow!("Failed to create cast '{}': {}", path, e))?;
+        Ok(Cast {
+            file: BufWriter::new(file),
+            path: path.to_string(),
+            start: None,
+        })
+    }
+
+    pub fn frame(&mut self, window: &Window) -> Result<()> {
+        //! Records the contents of a window that has just been drawn.
+        let mut output: String = String::new();
+        let start: Instant = match self.start {
+            Some(start) => start,
+            None => {
+                let timestamp: u64 = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |time| time.as_secs());
+                let header: String = format!(
+                    r#"{{"version": 2, "width": {}, "height": {}, "timestamp": {}, "title": "conway"}}"#,
+                    COLS(),
+                    LINES(),
+                    timestamp
+                );
+                self.write_line(&header)?;
+                output.push_str("\x1b[2J");
+                *self.start.insert(Instant::now())
+            }
+        };
+
+        let (top, left) = window.position();
+        let mut styles: HashMap<attr_t, String> = HashMap::new();
+        for row in 0..window.get_rows() {
+            output.push_str(&format!("\x1b[{};{}H", top + row + 1, left + 1));
+            let mut current: Option<attr_t> = None;
+            for (c, attrs) in window.read_row(row) {
+                if current != Some(attrs) {
+                    output.push_str(styles.entry(attrs).or_insert_with(|| style(attrs)));
+                    current = Some(attrs);
+                }
+                output.push(c);
+            }
+            output.push_str("\x1b[0m");
+        }
+
+        let event: String = format!(
+            "[{:.6}, \"o\", \"{}\"]",
+            start.elapsed().as_secs_f64(),
+            escape(&output)
+        );
+        self.write_line(&event)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        writeln!(self.file, "{}", line)
+            .and_then(|_| self.file.flush())
+            .map_err(|e| anyhow!("Failed to write cast '{}': {}", self.path, e))
+    }
+}
+
+fn style(attrs: attr_t) -> String {
+    //! Returns the escape sequence that switches to the colors and attributes
+    //! of a cell.
+    let mut codes: Vec<String> = vec!["0".to_string()];
+    if attrs & A_BOLD != 0 {
+        codes.push("1".to_string());
+    }
+    if attrs & A_DIM != 0 {
+        codes.push("2".to_string());
+    }
+    if attrs & A_REVERSE != 0 {
+        codes.push("7".to_string());
+    }
+    let pair: i16 = PAIR_NUMBER(attrs as i32) as i16;
+    if pair != 0 {
+        let (mut foreground, mut background) = (0, 0);
+        pair_content(pair, &mut foreground, &mut background);
+        codes.push(color_code(foreground, 30));
+        codes.push(color_code(background, 40));
+    }
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+fn color_code(color: i16, base: u8) -> String {
+    //! Returns the SGR code of a foreground (base 30) or background (base 40)
+    //! color number.
+    match color {
+        c if c < 0 => format!("{}", base + 9),
+        c if c < 8 => format!("{}", base + c as u8),
+        c if c < 16 => format!("{}", base + 60 + (c - 8) as u8),
+        c => {
+            // colors redefined for true color have to be looked up
+            let (mut r, mut g, mut b) = (0, 0, 0);
+            color_content(c, &mut r, &mut g, &mut b);
+            let scale = |v: i16| (v.clamp(0, 1000) as u32 * 255 / 1000) as u8;
+            format!("{};2;{};{};{}", base + 8, scale(r), scale(g), scale(b))
+        }
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    //! Escapes text for a JSON string.
+    let mut escaped: String = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,48 @@
+//! This module contains the integration with Catagolue, the online census of
+//! objects found by soup searches (https://catagolue.hatsya.com).
+
+use anyhow::{anyhow, Result};
+use std::process::Command;
+
+use super::pattern::Pattern;
+
+/// Base URL of the Catagolue website
+const CATAGOLUE_URL: &str = "https://catagolue.hatsya.com";
+
+pub fn object_url(apgcode: &str, rule: &str) -> String {
+    //! Returns the URL of the census page for the object with the given
+    //! apgcode under the given rule, e.g. `b3s23`.
+    format!(
+        "{}/object/{}/{}",
+        CATAGOLUE_URL,
+        apgcode,
+        rule.to_lowercase()
+    )
+}
+
+pub fn run(apgcode: &str, rule: &str, open: bool) -> Result<()> {
+    //! Prints the census page URL for an apgcode, and optionally opens it with
+    //! the system's default browser.
+    // decode the apgcode first so typos are caught before going online
+    Pattern::from_apgcode(apgcode)?;
+    let rule: String = rule.replace('/', "").to_lowercase();
+    let url: String = object_url(apgcode, &rule);
+    println!("{}", url);
+
+    if open {
+        let opener: &str = if cfg!(target_os = "macos") {
+            "open"
+        } else {
+            "xdg-open"
+        };
+        let status = Command::new(opener)
+            .arg(&url)
+            .status()
+            .map_err(|e| anyhow!("Failed to run '{}': {}", opener, e))?;
+        if !status.success() {
+            return Err(anyhow!("'{}' exited with {}", opener, status));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,236 @@
+//! This module contains the census of the objects left on the grid: the
+//! live cells are split into connected objects, which are named by matching
+//! them against the common still lifes, oscillators and spaceships of the
+//! pattern library, in any phase and orientation.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use super::conway::{new_grid, Cell, Stepper};
+use super::library::census_objects;
+use super::topology::Topology;
+
+/// Library objects that are rare in random soups.
+const RARE_OBJECTS: [&str; 1] = ["lightweight spaceship"];
+
+/// Number of generations each library object is simulated for to find all
+/// of its phases. This covers the longest period in the library.
+const MAX_PERIOD: usize = 4;
+
+/// Number of each kind of object found on the grid, most common first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Census {
+    /// Name of each kind of object, and how many were found. Objects that
+    /// aren't in the library are named by their number of cells.
+    pub counts: Vec<(String, usize)>,
+}
+
+impl fmt::Display for Census {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Census:")?;
+        if self.counts.is_empty() {
+            write!(f, " nothing left")?;
+        }
+        for (name, count) in &self.counts {
+            write!(f, "\n  {} {}", count, name)?;
+        }
+        Ok(())
+    }
+}
+
+impl Census {
+    pub fn take(grid: &[Vec<Cell>], topology: Topology) -> Census {
+        //! Counts the objects on the grid.
+        let library: HashMap<Vec<(isize, isize)>, String> = library();
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for object in objects(grid, topology) {
+            let name: String = match library.get(&canonical(&object)) {
+                Some(name) => name.clone(),
+                None => format!("unrecognized {}-cell object", object.len()),
+            };
+            *counts.entry(name).or_insert(0) += 1;
+        }
+        let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+        counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        Census { counts }
+    }
+}
+
+pub fn is_rare(name: &str) -> bool {
+    //! Whether an object, named as in a census, is worth reporting from a
+    //! soup search: it isn't in the library, or it's rare in random soups.
+    !census_objects().iter().any(|(object, _)| object == name) || RARE_OBJECTS.contains(&name)
+}
+
+pub fn objects(grid: &[Vec<Cell>], topology: Topology) -> Vec<Vec<(isize, isize)>> {
+    //! Splits the live cells into objects of cells that touch, including
+    //! diagonally and across the edges. The cells of each object are given
+    //! relative to the first one found, so objects split by the edges of the
+    //! grid stay in one piece.
+    let (nrows, ncols) = (grid.len(), grid.first().map_or(0, |row| row.len()));
+    let mut seen: HashSet<(usize, usize)> = HashSet::new();
+    let mut objects: Vec<Vec<(isize, isize)>> = vec![];
+    for cell in grid.iter().flatten().filter(|cell| cell.is_alive()) {
+        if !seen.insert((cell.x, cell.y)) {
+            continue;
+        }
+        let mut object: Vec<(isize, isize)> = vec![];
+        let mut queue: VecDeque<((usize, usize), (isize, isize))> = VecDeque::new();
+        queue.push_back(((cell.x, cell.y), (0, 0)));
+        while let Some(((i, j), (a, b))) = queue.pop_front() {
+            object.push((a, b));
+            for di in -1..=1 {
+                for dj in -1..=1 {
+                    let neighbor: Option<(usize, usize)> =
+                        topology.locate(i as isize + di, j as isize + dj, nrows, ncols);
+                    let Some((x, y)) = neighbor else {
+                        continue;
+                    };
+                    if grid[x][y].is_alive() && seen.insert((x, y)) {
+                        queue.push_back(((x, y), (a + di, b + dj)));
+                    }
+                }
+            }
+        }
+        objects.push(object);
+    }
+    objects
+}
+
+pub fn canonical(cells: &[(isize, isize)]) -> Vec<(isize, isize)> {
+    //! Returns the same form for an object in any position, rotation or
+    //! reflection: the smallest of its eight orientations, moved to the
+    //! top-left corner.
+    (0..8)
+        .map(|orientation: u8| {
+            // bit 2 transposes, bits 0 and 1 flip the rows and columns
+            let cells: Vec<(isize, isize)> = cells
+                .iter()
+                .map(|&(i, j)| if orientation & 4 != 0 { (j, i) } else { (i, j) })
+                .map(|(i, j)| {
+                    (
+                        if orientation & 1 != 0 { -i } else { i },
+                        if orientation & 2 != 0 { -j } else { j },
+                    )
+                })
+                .collect();
+            let top: isize = cells.iter().map(|(i, _)| *i).min().unwrap_or(0);
+            let left: isize = cells.iter().map(|(_, j)| *j).min().unwrap_or(0);
+            let mut cells: Vec<(isize, isize)> =
+                cells.iter().map(|(i, j)| (i - top, j - left)).collect();
+            cells.sort();
+            cells
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+pub fn library() -> HashMap<Vec<(isize, isize)>, String> {
+    //! Maps the canonical form of every phase of every library object to its
+    //! name. Phases that fall apart into separate pieces, like the beacon's
+    //! two corners, are left out, since each piece alone isn't the object.
+    let mut library: HashMap<Vec<(isize, isize)>, String> = HashMap::new();
+    for (name, pattern) in census_objects() {
+        // leave room for spaceships to move while finding their phases
+        let margin: usize = MAX_PERIOD;
+        let mut grid: Vec<Vec<Cell>> =
+            new_grid(pattern.rows + 2 * margin, pattern.cols + 2 * margin);
+        for (i, j) in &pattern.cells {
+            grid[i + margin][j + margin].set_alive();
+        }
+        let mut stepper: Stepper = Stepper::default();
+        for _ in 0..MAX_PERIOD {
+            if let [object] = objects(&grid, Topology::Plane).as_slice() {
+                library.insert(canonical(object), name.clone());
+            }
+            stepper.step(&mut grid, Topology::Plane);
+        }
+    }
+    library
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::Pattern;
+
+    fn grid(seed: &str) -> Vec<Vec<Cell>> {
+        let pattern: Pattern = Pattern::from_seed_text(seed).unwrap();
+        let mut grid: Vec<Vec<Cell>> = new_grid(pattern.rows, pattern.cols);
+        for (i, j) in &pattern.cells {
+            grid[*i][*j].set_alive();
+        }
+        grid
+    }
+
+    fn counts(census: &Census) -> Vec<(&str, usize)> {
+        census
+            .counts
+            .iter()
+            .map(|(name, count)| (name.as_str(), *count))
+            .collect()
+    }
+
+    #[test]
+    fn objects_are_named_in_any_orientation() {
+        let census: Census = Census::take(
+            &grid(
+                "**.....*...\n\
+                 **.....*...\n\
+                 .......*...\n\
+                 ...........\n\
+                 .*.....***.\n\
+                 *.*........\n\
+                 **.........\n\
+                 ...........\n\
+                 ...*.....**\n\
+                 ..*.....*.*\n\
+                 ..***.....*",
+            ),
+            Topology::Plane,
+        );
+        assert_eq!(
+            counts(&census),
+            vec![("blinker", 2), ("glider", 2), ("block", 1), ("boat", 1)]
+        );
+    }
+
+    #[test]
+    fn a_beacon_falls_apart_in_its_second_phase() {
+        let mut beacon: Vec<Vec<Cell>> = grid("**..\n**..\n..**\n..**");
+        let mut stepper: Stepper = Stepper::default();
+        assert_eq!(
+            counts(&Census::take(&beacon, Topology::Plane)),
+            vec![("beacon", 1)]
+        );
+        stepper.step(&mut beacon, Topology::Plane);
+        // each corner of the beacon on its own isn't a beacon
+        assert_eq!(
+            counts(&Census::take(&beacon, Topology::Plane)),
+            vec![("unrecognized 3-cell object", 2)]
+        );
+    }
+
+    #[test]
+    fn objects_stay_whole_across_the_edges() {
+        let census: Census = Census::take(&grid("*..*\n....\n....\n*..*"), Topology::Torus);
+        assert_eq!(counts(&census), vec![("block", 1)]);
+        let census: Census = Census::take(&grid("*..*\n....\n....\n*..*"), Topology::Plane);
+        assert_eq!(counts(&census), vec![("unrecognized 1-cell object", 4)]);
+    }
+
+    #[test]
+    fn censuses_list_the_most_common_objects_first() {
+        assert_eq!(
+            Census::take(&grid("*.*\n*.*\n*.*"), Topology::Plane).to_string(),
+            "Census:\n  2 blinker"
+        );
+        assert_eq!(
+            Census::take(&new_grid(3, 3), Topology::Plane).to_string(),
+            "Census: nothing left"
+        );
+        assert!(!is_rare("block"));
+        assert!(is_rare("lightweight spaceship"));
+        assert!(is_rare("unrecognized 7-cell object"));
+    }
+}
@@ -0,0 +1,51 @@
+//! This module contains the integration with the system clipboard, through
+//! whichever of the usual command line clipboard tools is installed.
+
+use anyhow::{anyhow, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Clipboard tools and their arguments, in the order they are tried.
+const CLIPBOARD_TOOLS: [(&str, &[&str]); 5] = [
+    ("pbcopy", &[]),
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("clip.exe", &[]),
+];
+
+pub fn copy(text: &str) -> Result<&'static str> {
+    //! Copies text to the system clipboard, returning the name of the tool
+    //! that was used.
+    for (tool, args) in CLIPBOARD_TOOLS {
+        let child = Command::new(tool)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        // the tool isn't installed, try the next one
+        let Ok(mut child) = child else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(text.as_bytes())
+                .map_err(|e| anyhow!("Failed to write to '{}': {}", tool, e))?;
+        }
+        let status = child
+            .wait()
+            .map_err(|e| anyhow!("Failed to run '{}': {}", tool, e))?;
+        if status.success() {
+            return Ok(tool);
+        }
+    }
+    Err(anyhow!(
+        "No clipboard tool found: install one of {}",
+        CLIPBOARD_TOOLS
+            .iter()
+            .map(|(tool, _)| *tool)
+            .collect::<Vec<&str>>()
+            .join(", ")
+    ))
+}
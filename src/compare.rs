@@ -0,0 +1,230 @@
+//! This module contains the comparison mode, started with --compare-rule or
+//! --compare-topology: the same starting grid is run twice side by side,
+//! under the rule and topology of the run on the left, and under the ones it
+//! is compared with on the right. Both halves are stepped in lockstep, so
+//! they always show the same generation.
+
+use anyhow::{anyhow, Result};
+use ncurses::*;
+
+use super::conway::{self, Cell, State};
+use super::lineage;
+use super::neighborhood::Neighborhood;
+use super::pattern::Pattern;
+use super::status::{fit_to_width, StatusBar, StatusField};
+use super::terminal::{self, Screen};
+use super::theme::Theme;
+use super::topology::Topology;
+use super::window::Window;
+use super::Cli;
+
+/// Fields shown in the status bar of each half, which tell them apart. The
+/// generation, which they share, is on the bottom row.
+const STATUS_FIELDS: [StatusField; 3] =
+    [StatusField::Rule, StatusField::Topology, StatusField::Alive];
+
+/// Keys listed on the bottom row, after the generation.
+const FOOTER: &str = "p: pause, .: step, a/s: slower/faster, q: quit";
+
+/// One half of the screen, with its own grid and simulation.
+struct Pane {
+    window: Window,
+    grid: Vec<Vec<Cell>>,
+    state: State,
+}
+
+impl Pane {
+    fn draw(&mut self) -> Result<()> {
+        if self.state.get_neighbor_glyphs() {
+            let topology: Topology = self.state.get_topology();
+            let neighborhood: Neighborhood = self.state.get_rule().get_neighborhood();
+            conway::count_neighbors(&mut self.grid, topology, neighborhood);
+        }
+        self.window.erase();
+        conway::draw(&mut self.window, &self.grid, &self.state)?;
+        self.window.refresh();
+        Ok(())
+    }
+
+    fn population(&self) -> usize {
+        self.grid
+            .iter()
+            .flatten()
+            .filter(|cell| cell.is_alive())
+            .count()
+    }
+}
+
+fn lay_out() -> (Window, Window, Window, Window) {
+    //! Splits the terminal between the halves, with a column between them
+    //! and the bottom row: returns the left and right halves, the divider
+    //! and the footer.
+    let rows: i32 = LINES() - 1;
+    let cols: i32 = (COLS() - 2) / 2;
+    (
+        Window::new(rows, cols, 0, 0),
+        Window::new(rows, cols, 0, cols + 1),
+        Window::new(rows, 1, 0, cols),
+        Window::new(1, COLS() - 1, rows, 0),
+    )
+}
+
+pub fn run(
+    args: &Cli,
+    theme: Theme,
+    pattern: Option<&Pattern>,
+    stepper: conway::Stepper,
+    compared: conway::Stepper,
+    topology: Topology,
+) -> Result<()> {
+    //! Runs the grid described by the arguments, or the given pattern, with
+    //! the stepper on the left and the compared stepper and topology on the
+    //! right, until 'q' is pressed, then prints how far each got.
+    ncurses::setlocale(ncurses::LcCategory::all, "")?;
+
+    // draw on the terminal even if the output is piped
+    let piped_stdout: Option<i32> = terminal::redirect_stdout()?;
+    let screen: Screen = Screen::start(false, piped_stdout);
+
+    start_color();
+
+    noecho();
+
+    refresh();
+
+    let (left_window, right_window, mut divider, mut footer) = lay_out();
+    let cols: i32 = left_window.get_cols();
+
+    // the status bar is set before the grid is sized to fit above it
+    let mut left: Pane = Pane {
+        window: left_window,
+        grid: vec![],
+        state: args.build_state(theme.clone()),
+    };
+    left.state.set_stepper(stepper);
+    left.state
+        .set_status_bar(StatusBar::new(STATUS_FIELDS.to_vec()));
+    // soups are as dense as they would be on the whole terminal, unless the
+    // size of the grid is given
+    let alive: Option<usize> = match args.width {
+        None => args
+            .alive
+            .map(|alive| alive * cols as usize / (COLS() - 1).max(1) as usize),
+        Some(_) => args.alive,
+    };
+    left.grid = match pattern {
+        Some(pattern) => conway::initialize_from_pattern(&mut left.window, &left.state, pattern)?,
+        None => conway::initialize(
+            &mut left.window,
+            &mut left.state,
+            alive,
+            &args.seed_files,
+            &args.place,
+            &args.soup_options(),
+        )?,
+    };
+    if args.immigration {
+        conway::color_randomly(&mut left.grid, args.soup_options().seed);
+    }
+    if args.lineage {
+        lineage::tag_regions(&mut left.grid);
+    }
+
+    let mut right: Pane = Pane {
+        window: right_window,
+        grid: left.grid.clone(),
+        state: args.build_state(theme),
+    };
+    right.state.set_stepper(compared);
+    right.state.set_topology(topology);
+    right
+        .state
+        .set_status_bar(StatusBar::new(STATUS_FIELDS.to_vec()));
+    if right.state.is_lopsided(right.grid.len()) {
+        return Err(anyhow!(
+            "A hexagonal grid on a {} needs an even number of rows, not {}; try an even --height.",
+            topology,
+            right.grid.len()
+        ));
+    }
+    loop {
+        left.draw()?;
+        right.draw()?;
+        for row in 0..divider.get_rows() {
+            divider.print(0, row, "│", left.state.get_theme().status.as_ref())?;
+        }
+        divider.refresh();
+        let paused: &str = if left.state.is_paused() {
+            ", paused"
+        } else {
+            ""
+        };
+        let width: usize = (footer.get_cols() as usize).saturating_sub(1);
+        let line: String = format!(
+            "Gen: {}{} | {}",
+            left.state.get_generation(),
+            paused,
+            FOOTER
+        );
+        footer.erase();
+        footer.print(
+            0,
+            0,
+            &fit_to_width(&line, width),
+            left.state.get_theme().status.as_ref(),
+        )?;
+        footer.refresh();
+
+        // a key cuts the wait for the next frame short, and the frame waits
+        // for the next turn
+        timeout(left.state.frame_duration().as_millis() as i32);
+        let c: i32 = getch();
+        let generations: usize = match char::from_u32(c as u32) {
+            _ if c == ERR && !left.state.is_paused() => left.state.generations_per_frame(),
+            // the halves are laid out again to fit the terminal
+            _ if c == KEY_RESIZE => {
+                (left.window, right.window, divider, footer) = lay_out();
+                clear();
+                refresh();
+                0
+            }
+            Some('q') => break,
+            Some('p') => {
+                let paused: bool = !left.state.is_paused();
+                left.state.set_paused(paused);
+                right.state.set_paused(paused);
+                0
+            }
+            Some('.') => {
+                left.state.set_paused(true);
+                right.state.set_paused(true);
+                1
+            }
+            Some('a') | Some('s') => {
+                let change: i32 = if c == 'a' as i32 { 10 } else { -10 };
+                let timeout: i32 = (left.state.get_timeout() + change).clamp(10, 1000);
+                left.state.set_timeout(timeout);
+                right.state.set_timeout(timeout);
+                0
+            }
+            _ => 0,
+        };
+        if generations > 0 {
+            conway::advance(&mut left.grid, &mut left.state, generations);
+            conway::advance(&mut right.grid, &mut right.state, generations);
+        }
+    }
+
+    drop(screen);
+
+    for pane in [&left, &right] {
+        println!(
+            "{} on a {}: population {} at generation {}",
+            pane.state.get_rule(),
+            pane.state.get_topology(),
+            pane.population(),
+            pane.state.get_generation()
+        );
+    }
+    Ok(())
+}
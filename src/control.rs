@@ -0,0 +1,194 @@
+//! This module contains the control socket, a Unix domain socket through
+//! which other programs drive a running game. Clients send one command per
+//! line, and each command is answered with `ok`, or `error: ` and the reason,
+//! after any output it has:
+//!
+//! - `pause`, `resume` and `step`, or any input saved in macros, like
+//!   `toggle-heatmap`, are applied as if their key had been pressed
+//! - `set-rule RULE` changes the rule, written in any notation `--rule` takes
+//! - `load FILE` replaces the grid with a seed or pattern file
+//! - `dump` prints the grid in the seed file format
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::time::Duration;
+
+use super::conway::InputType;
+
+/// How long a client has to take a reply before it is dropped, so one that
+/// stops reading can't hold up the game.
+const REPLY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A command sent over the control socket.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// An input, applied like a key press
+    Input(InputType),
+    /// Change the rule
+    SetRule(String),
+    /// Replace the grid with a pattern file
+    Load(String),
+    /// Print the grid
+    Dump,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command> {
+        let (name, arg) = match line.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (line, ""),
+        };
+        let needs_arg = |arg: &str| -> Result<String> {
+            if arg.is_empty() {
+                return Err(anyhow!("'{}' needs an argument", name));
+            }
+            Ok(arg.to_string())
+        };
+        match name {
+            "pause" => Ok(Command::Input(InputType::Pause)),
+            "resume" => Ok(Command::Input(InputType::Resume)),
+            "step" => Ok(Command::Input(InputType::StepForward)),
+            "set-rule" => Ok(Command::SetRule(needs_arg(arg)?)),
+            "load" => Ok(Command::Load(needs_arg(arg)?)),
+            "dump" => Ok(Command::Dump),
+            _ => Ok(Command::Input(name.parse()?)),
+        }
+    }
+}
+
+/// A connected client, and what it has sent that isn't a full line yet.
+struct Client {
+    /// Number the client is known by, which doesn't change as other clients
+    /// come and go
+    id: u64,
+    stream: UnixStream,
+    buffer: Vec<u8>,
+    /// Whether the client hung up; it is dropped at the next poll, once its
+    /// last commands have been answered
+    closed: bool,
+    /// Whether a reply couldn't be written in time; the client gets no
+    /// more, and is dropped at the next poll
+    stalled: bool,
+}
+
+/// Listens on the control socket, without ever blocking the game.
+pub struct ControlSocket {
+    /// Path of the socket file, removed when the game exits
+    path: String,
+    listener: UnixListener,
+    clients: Vec<Client>,
+    /// Number given to the next client
+    next_id: u64,
+}
+
+impl ControlSocket {
+    pub fn bind(path: &str) -> Result<ControlSocket> {
+        //! Creates the socket, replacing a socket file left behind by a
+        //! previous run.
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(anyhow!("'{}' exists and is not a socket", path));
+            }
+            fs::remove_file(path)
+                .map_err(|e| anyhow!("Failed to remove old socket '{}': {}", path, e))?;
+        }
+        let listener: UnixListener = UnixListener::bind(path)
+            .map_err(|e| anyhow!("Failed to create control socket '{}': {}", path, e))?;
+        listener.set_nonblocking(true)?;
+        Ok(ControlSocket {
+            path: path.to_string(),
+            listener,
+            clients: vec![],
+            next_id: 0,
+        })
+    }
+
+    pub fn poll(&mut self) -> Vec<(u64, Command)> {
+        //! Accepts new clients and returns the commands received since the
+        //! last poll, with the client each came from. Lines that aren't valid
+        //! commands are answered with an error straight away.
+        self.clients
+            .retain(|client| !client.closed && !client.stalled);
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok()
+                && stream.set_write_timeout(Some(REPLY_TIMEOUT)).is_ok()
+            {
+                self.next_id += 1;
+                self.clients.push(Client {
+                    id: self.next_id,
+                    stream,
+                    buffer: vec![],
+                    closed: false,
+                    stalled: false,
+                });
+            }
+        }
+
+        let mut commands: Vec<(u64, Command)> = vec![];
+        let mut errors: Vec<(u64, String)> = vec![];
+        for client in &mut self.clients {
+            let mut chunk: [u8; 1024] = [0; 1024];
+            loop {
+                match client.stream.read(&mut chunk) {
+                    Ok(0) => {
+                        client.closed = true;
+                        break;
+                    }
+                    Ok(n) => client.buffer.extend_from_slice(&chunk[..n]),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(_) => {
+                        client.closed = true;
+                        break;
+                    }
+                }
+            }
+            while let Some(end) = client.buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = client.buffer.drain(..=end).collect();
+                let line: String = String::from_utf8_lossy(&line).trim().to_string();
+                if line.is_empty() {
+                    continue;
+                }
+                match Command::parse(&line) {
+                    Ok(command) => commands.push((client.id, command)),
+                    Err(e) => errors.push((client.id, e.to_string())),
+                }
+            }
+        }
+        for (id, error) in errors {
+            self.reply(id, &Err(anyhow!(error)));
+        }
+        commands
+    }
+
+    pub fn reply(&mut self, client: u64, result: &Result<String>) {
+        //! Answers a command with its output, followed by `ok`, or with its
+        //! error. Clients that have gone are ignored, and those that can't be
+        //! written to in time are dropped.
+        let Some(client) = self
+            .clients
+            .iter_mut()
+            .find(|c| c.id == client && !c.stalled)
+        else {
+            return;
+        };
+        let text: String = match result {
+            Ok(output) => format!("{}ok\n", output),
+            Err(e) => format!("error: {}\n", e),
+        };
+        // write the whole reply, even if the client is slow to read it
+        let _ = client.stream.set_nonblocking(false);
+        if client.stream.write_all(text.as_bytes()).is_err() {
+            client.stalled = true;
+        }
+        let _ = client.stream.set_nonblocking(true);
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
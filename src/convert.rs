@@ -0,0 +1,36 @@
+//! This module contains the `convert` subcommand, which converts pattern
+//! files between formats without touching the terminal.
+
+use anyhow::{Context, Result};
+
+use super::pattern::{ExportFormat, Pattern};
+
+pub fn run(
+    input: &str,
+    output: Option<&str>,
+    from: Option<ExportFormat>,
+    to: Option<ExportFormat>,
+    rule: &str,
+) -> Result<()> {
+    //! Reads a pattern file and writes it in another format, to a file or to
+    //! stdout. Formats that aren't given are guessed from the file names.
+    let text: String = std::fs::read_to_string(input)
+        .with_context(|| format!("Failed to read pattern file '{}'", input))?;
+    let from: ExportFormat = from.unwrap_or_else(|| ExportFormat::detect(input, &text));
+    let pattern: Pattern =
+        Pattern::parse(&text, from).with_context(|| format!("Invalid pattern file '{}'", input))?;
+
+    match output {
+        Some(output) => {
+            let to: ExportFormat = to.unwrap_or_else(|| ExportFormat::detect(output, ""));
+            pattern.write(output, to, rule)
+        }
+        None => {
+            print!(
+                "{}",
+                pattern.to_text(to.unwrap_or(ExportFormat::Seed), rule)
+            );
+            Ok(())
+        }
+    }
+}
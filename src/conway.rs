@@ -1,10 +1,51 @@
 use crate::window::ArrowKeys;
 
-use super::window::Window;
-use anyhow::Result;
+use super::alarm::{AlarmAction, Alarms};
+use super::bitgrid::{Activity, BitGrid};
+use super::cast::Cast;
+use super::clipboard;
+use super::control::{Command, ControlSocket};
+use super::csvlog::CsvLog;
+use super::dna::Dna;
+use super::editor::LineEditor;
+use super::events::{Event, Events};
+#[cfg(feature = "gpu")]
+use super::gpu::GpuEngine;
+use super::heatmap::Heatmap;
+use super::help;
+use super::history::History;
+use super::kiosk::Kiosk;
+use super::library::Placed;
+use super::lineage;
+use super::margolus;
+use super::menu::{Menu, MenuItem};
+use super::metrics::SimilarityTracker;
+use super::minimap::{self, Minimap};
+use super::neighborhood::{Neighborhood, Shape};
+use super::paint::{self, Brush, Stroke, BRUSHES};
+use super::pattern::{self, ExportFormat, Metadata, Pattern, Placement, Transform};
+use super::power::PowerProfile;
+use super::recording::{Playback, Recorder};
+use super::rule::{Rule, RULES};
+use super::script::Script;
+use super::soup::{random_cells, SoupOptions};
+use super::stats::Stats;
+use super::status::{fit_to_width, StatusBar, StatusField};
+use super::theme::{Theme, BUILTIN_THEMES};
+use super::topology::Topology;
+use super::undo::UndoHistory;
+use super::window::{glyph_width, supports_rgb, Color, ColorPair, Window};
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
 use ncurses::*;
-use rand::{rngs::ThreadRng, Rng};
-use std::{collections::HashSet, path::Path};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    path::Path,
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
 /// A cell in the grid of the game.
 /// Contains the x and y coordinates of the cell, and whether the cell is alive or dead.
@@ -16,62 +57,141 @@ pub struct Cell {
     pub y: usize,
     /// Whether the cell is alive or dead
     pub alive: bool,
+    /// Number of generations the cell has been alive for
+    pub age: u32,
+    /// Number of generations since the cell died, if it has ever been alive
+    pub since_death: Option<u32>,
+    /// Which of the two colors of the Immigration game the cell has, 0 or 1
+    pub color: u8,
+    /// Region of the starting grid the cell's ancestors came from, while
+    /// cells are colored by lineage
+    pub lineage: Option<u8>,
+    /// Number of alive neighbors the cell has, kept up to date only while
+    /// cells are drawn by it
+    pub neighbors: u16,
 }
 
 impl Cell {
     pub fn new(x: usize, y: usize, alive: bool) -> Cell {
-        Cell { x, y, alive }
+        Cell {
+            x,
+            y,
+            alive,
+            age: 0,
+            since_death: None,
+            color: 0,
+            lineage: None,
+            neighbors: 0,
+        }
     }
 
     pub fn is_alive(&self) -> bool {
         self.alive
     }
 
+    pub fn get_age(&self) -> u32 {
+        self.age
+    }
+
+    pub fn get_since_death(&self) -> Option<u32> {
+        self.since_death
+    }
+
     pub fn set_alive(&mut self) {
         self.alive = true;
+        self.age = 0;
+        self.since_death = None;
+        self.color = 0;
+        self.lineage = None;
     }
 
     pub fn set_dead(&mut self) {
+        if self.alive {
+            self.since_death = Some(0);
+        }
         self.alive = false;
+        self.age = 0;
     }
 
-    pub fn count_alive_neighbors(&self, grid: &[Vec<Cell>]) -> usize {
+    pub fn count_alive_neighbors(
+        &self,
+        grid: &[Vec<Cell>],
+        topology: Topology,
+        neighborhood: Neighborhood,
+    ) -> usize {
         //! Counts the number of alive neighbors of the cell.
-        //! A neighbor can be immediately next to the cell, or diagonally adjacent to it.
-        //! A neighbor can also wrap around the edges of the grid.
+        //! Which cells are neighbors is given by the neighborhood; Life's
+        //! neighbors are immediately next to the cell, or diagonally adjacent to it.
+        //! Neighbors across the edges of the grid are found according to the topology.
         let nrows: usize = grid.len();
         let ncols: usize = grid[0].len();
         let mut count: usize = 0;
-        for i in -1..=1 {
-            for j in -1..=1 {
-                // Skip the cell itself
-                if i == 0 && j == 0 {
-                    continue;
-                }
-                // calculate the next cell to check
-                let mut x = self.x as i32 + j;
-                let mut y = self.y as i32 + i;
-
-                // wrap around the edges
-                if x < 0 {
-                    x = nrows as i32 - 1;
-                } else if x >= nrows as i32 {
-                    x = 0;
-                }
-                if y < 0 {
-                    y = ncols as i32 - 1;
-                } else if y >= ncols as i32 {
-                    y = 0;
-                }
+        for (i, j) in neighborhood.offsets(self.x) {
+            // find the neighbor, if there is one across the edges
+            let Some((x, y)) =
+                topology.locate(self.x as isize + i, self.y as isize + j, nrows, ncols)
+            else {
+                continue;
+            };
 
-                // check if the cell is alive
-                if grid[x as usize][y as usize].is_alive() {
-                    count += 1;
-                }
+            // check if the cell is alive
+            if grid[x][y].is_alive() {
+                count += 1;
             }
         }
         count
     }
+
+    fn parents_color(
+        &self,
+        grid: &[Vec<Cell>],
+        topology: Topology,
+        neighborhood: Neighborhood,
+    ) -> u8 {
+        //! Returns the color most of the cell's alive neighbors have, which
+        //! the cell takes when it is born in the Immigration game.
+        let nrows: usize = grid.len();
+        let ncols: usize = grid[0].len();
+        let mut counts: [usize; 2] = [0, 0];
+        for (i, j) in neighborhood.offsets(self.x) {
+            let Some((x, y)) =
+                topology.locate(self.x as isize + i, self.y as isize + j, nrows, ncols)
+            else {
+                continue;
+            };
+            if grid[x][y].is_alive() {
+                counts[grid[x][y].color as usize] += 1;
+            }
+        }
+        u8::from(counts[1] > counts[0])
+    }
+}
+
+pub fn count_neighbors(grid: &mut [Vec<Cell>], topology: Topology, neighborhood: Neighborhood) {
+    //! Counts the alive neighbors of every cell, for drawing cells by their
+    //! number of neighbors.
+    let counts: Vec<Vec<u16>> = grid
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| cell.count_alive_neighbors(grid, topology, neighborhood) as u16)
+                .collect()
+        })
+        .collect();
+    for (row, counts) in grid.iter_mut().zip(counts) {
+        for (cell, count) in row.iter_mut().zip(counts) {
+            cell.neighbors = count;
+        }
+    }
+}
+
+pub fn color_randomly(grid: &mut [Vec<Cell>], seed: u64) {
+    //! Gives every alive cell one of the two colors of the Immigration game
+    //! at random.
+    let mut rng: StdRng = StdRng::seed_from_u64(seed);
+    for cell in grid.iter_mut().flatten().filter(|cell| cell.is_alive()) {
+        cell.color = u8::from(rng.gen_bool(0.5));
+    }
 }
 
 pub fn draw(window: &mut Window, grid: &[Vec<Cell>], state: &State) -> Result<()> {
@@ -82,39 +202,791 @@ pub fn draw(window: &mut Window, grid: &[Vec<Cell>], state: &State) -> Result<()
     //! * `nrows` - Number of rows in the grid
     //! * `ncols` - Number of columns in the grid
     //! * `input_handler` - Input handler to get the character to draw for alive cells
-    for i in 0..grid.len() {
-        for j in 0..grid[0].len() {
-            let output = format!(
-                "{}",
-                if grid[i][j].is_alive() {
-                    state.draw_char
-                } else {
-                    ' '
-                }
-            );
-            window.print(j as i32 * 2, i as i32, &output, None)?;
-        }
+    //!
+    //! Only the part of the grid inside the viewport is drawn, unless it is
+    //! zoomed out.
+    let top: usize = state.viewport.0;
+    let alive_char = |cell: &Cell| -> char { alive_char(cell, state) };
+    let nrows: usize = visible_size(window, grid, state).0;
+    if is_zoomed_out(window, grid, state) {
+        draw_zoomed_out(window, grid, state)?;
+    } else {
+        draw_cells(window, grid, state)?;
+    }
+    // leave the last column free, writing to it would wrap the line
+    let width: usize = (window.get_cols() as usize).saturating_sub(1);
+    // messages replace the status bar, or the last row if it is hidden
+    let row: usize = (nrows + state.status_bar.height()).saturating_sub(1);
+    if let Some(prompt) = &state.prompt {
+        window.print(
+            0,
+            row as i32,
+            &fit_to_width(&format!("Jump to generation: {}", prompt), width),
+            state.theme.status.as_ref(),
+        )?;
+    } else if let Some((editor, _)) = &state.rule_editor {
+        editor.draw(window, row as i32, width, state.theme.status.as_ref())?;
+    } else if let Some(message) = &state.message {
+        window.print(
+            0,
+            row as i32,
+            &fit_to_width(message, width),
+            state.theme.status.as_ref(),
+        )?;
+    } else if let Some((i, j)) = state.cursor {
+        let cell: &Cell = &grid[i][j];
+        // the cursor swaps the colors of the cell, which are already swapped
+        // for solid blocks
+        let (output, attr) = match (cell.is_alive(), state.inverse) {
+            (true, true) => (alive_char(cell), A_NORMAL),
+            (true, false) => (alive_char(cell), A_REVERSE),
+            (false, _) => (state.dead_char, A_REVERSE),
+        };
+        window.print_attr(
+            cell_x(state, i, j),
+            i.saturating_sub(top) as i32,
+            &output.to_string(),
+            None,
+            attr,
+        )?;
+        window.print(
+            0,
+            row as i32,
+            &fit_to_width(&inspect_line(grid, cell, state), width),
+            state.theme.status.as_ref(),
+        )?;
+    } else if state.show_code {
+        let text: String = match state.code() {
+            Some(code) => format!("Code: {}", code),
+            None => "No code: only random soups can be shared".to_string(),
+        };
+        window.print(
+            0,
+            row as i32,
+            &fit_to_width(&text, width),
+            state.theme.status.as_ref(),
+        )?;
+    } else if state.status_bar.is_visible() {
+        draw_status_bar(window, grid, state)?;
+    }
+    Ok(())
+}
+
+fn draw_status_bar(window: &Window, grid: &[Vec<Cell>], state: &State) -> Result<()> {
+    //! Draws the status bar on its row below the grid, over what was there.
+    let (nrows, _) = visible_size(window, grid, state);
+    let row: usize = (nrows + state.status_bar.height()).saturating_sub(1);
+    let width: usize = (window.get_cols() as usize).saturating_sub(1);
+    let mut populations: [usize; 2] = [0, 0];
+    for cell in grid.iter().flatten().filter(|cell| cell.is_alive()) {
+        populations[cell.color as usize] += 1;
     }
-    let num_alive: usize = grid.iter().flatten().filter(|cell| cell.is_alive()).count();
     window.print(
         0,
-        grid.len() as i32,
+        row as i32,
         &format!(
-            "Alive: {}, Timeout: {} | q: Quit, a: increase timeout, s: decrease timeout",
-            num_alive, state.timeout
+            "{:width$}",
+            fit_to_width(&status_line(state, populations), width)
         ),
-        None,
+        state.theme.status.as_ref(),
+    )
+}
+
+fn alive_char(cell: &Cell, state: &State) -> char {
+    //! Character an alive cell is drawn with, which shows its number of
+    //! neighbors with --neighbor-glyphs.
+    if state.neighbor_glyphs {
+        neighbor_char(cell, state.get_rule().get_neighborhood().size())
+    } else {
+        state.draw_char
+    }
+}
+
+fn draw_cells(window: &Window, grid: &[Vec<Cell>], state: &State) -> Result<()> {
+    //! Draws the cells inside the viewport, and the selection over them.
+    let (top, left) = state.viewport;
+    let alive_char = |cell: &Cell| -> char { alive_char(cell, state) };
+    let (nrows, ncols) = visible_size(window, grid, state);
+    for (i, row) in grid.iter().enumerate().skip(top).take(nrows) {
+        let y: i32 = (i - top) as i32;
+        for (j, cell) in row.iter().enumerate().skip(left).take(ncols) {
+            let x: i32 = cell_x(state, i, j);
+            let gradient: ColorPair;
+            let heat: Option<ColorPair>;
+            let (output, color) = if state.heatmap.is_enabled() {
+                // color the position by activity, keeping the cell's character
+                let output: char = if cell.is_alive() {
+                    alive_char(cell)
+                } else {
+                    state.dead_char
+                };
+                heat = state.heatmap.color(i, j, Color::Black);
+                (
+                    output.to_string(),
+                    heat.as_ref().or(state.theme.dead.as_ref()),
+                )
+            } else if cell.is_alive() {
+                let color: Option<&ColorPair> = if state.age_gradient {
+                    gradient = age_color(cell.get_age(), state.theme.alive.as_ref());
+                    Some(&gradient)
+                } else if state.immigration {
+                    gradient = immigration_color(cell.color, state.theme.alive.as_ref());
+                    Some(&gradient)
+                } else if let Some(tag) = cell.lineage.filter(|_| state.lineage) {
+                    gradient = lineage::color(tag, state.theme.alive.as_ref());
+                    Some(&gradient)
+                } else {
+                    state.theme.alive.as_ref()
+                };
+                if state.inverse {
+                    // a blank as wide as the cell, in the colors swapped
+                    window.print_attr(x, y, &" ".repeat(state.cell_width()), color, A_REVERSE)?;
+                    continue;
+                }
+                (format!("{}", alive_char(cell)), color)
+            } else if let Some(ghost) = ghost_char(cell, state.trail) {
+                // recently dead cells fade out as dimmed ghosts
+                let alive: Option<&ColorPair> = state.theme.alive.as_ref();
+                window.print_attr(x, y, &ghost.to_string(), alive, A_DIM)?;
+                continue;
+            } else if let Some(line) = grid_line_char(i, j).filter(|_| state.grid_lines) {
+                let dead: Option<&ColorPair> = state.theme.dead.as_ref();
+                window.print_attr(x, y, &line.to_string(), dead, A_DIM)?;
+                continue;
+            } else if state.texture {
+                let dead: Option<&ColorPair> = state.theme.dead.as_ref();
+                window.print_attr(x, y, &texture_char(state).to_string(), dead, A_DIM)?;
+                continue;
+            } else {
+                (state.dead_char.to_string(), state.theme.dead.as_ref())
+            };
+            window.print(x, y, &output, color)?;
+        }
+    }
+    if state.grid_lines {
+        draw_grid_lines(window, (nrows, ncols), state)?;
+    }
+    if let Some((top_left, bottom_right)) = state.selection() {
+        // underline the selected cells
+        for (i, row) in grid
+            .iter()
+            .enumerate()
+            .take((bottom_right.0 + 1).min(top + nrows))
+            .skip(top_left.0.max(top))
+        {
+            for (j, cell) in row
+                .iter()
+                .enumerate()
+                .take((bottom_right.1 + 1).min(left + ncols))
+                .skip(top_left.1.max(left))
+            {
+                let (output, attr) = match (cell.is_alive(), state.inverse) {
+                    (true, true) => (' ', A_UNDERLINE | A_REVERSE),
+                    (true, false) => (alive_char(cell), A_UNDERLINE),
+                    (false, _) => (state.dead_char, A_UNDERLINE),
+                };
+                let color: Option<&ColorPair> = state.theme.alive.as_ref();
+                window.print_attr(
+                    cell_x(state, i, j),
+                    (i - top) as i32,
+                    &output.to_string(),
+                    color,
+                    attr,
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_zoomed_out(window: &Window, grid: &[Vec<Cell>], state: &State) -> bool {
+    //! Whether the grid is shown shrunk, which it only is zoomed out while it
+    //! doesn't fit in the window.
+    let (nrows, ncols) = visible_size(window, grid, state);
+    state.zoomed_out && (grid.len() > nrows || grid[0].len() > ncols)
+}
+
+fn draw_zoomed_out(window: &Window, grid: &[Vec<Cell>], state: &State) -> Result<()> {
+    //! Draws the whole grid shrunk to fit the window, each character showing
+    //! how crowded a block of cells is, as on the minimap.
+    let rows: usize = (window.get_rows() as usize).saturating_sub(state.status_bar.height());
+    // leave the last column free, writing to it would wrap the line
+    let cols: usize = (window.get_cols() as usize).saturating_sub(1);
+    let block: (usize, usize) = minimap::block_size(grid.len(), grid[0].len(), rows, cols);
+    for (bi, row) in minimap::downsample(grid, block).iter().enumerate() {
+        let line: String = row
+            .iter()
+            .map(|density| minimap::density_char(*density))
+            .collect();
+        window.print(0, bi as i32, &line, state.theme.alive.as_ref())?;
+    }
+    Ok(())
+}
+
+fn inspect_line(grid: &[Vec<Cell>], cell: &Cell, state: &State) -> String {
+    //! Describes the cell under the inspect cursor.
+    let status: String = if cell.is_alive() {
+        format!("alive, age {}", cell.get_age())
+    } else {
+        "dead".to_string()
+    };
+    format!(
+        "Inspect row {}, col {}: {}, {} live neighbors | arrows: move, v: select, w: write, i: exit",
+        cell.x,
+        cell.y,
+        status,
+        cell.count_alive_neighbors(grid, state.topology, state.get_rule().get_neighborhood())
+    )
+}
+
+/// Number of generations plotted by the similarity sparkline.
+const SPARKLINE_WIDTH: usize = 20;
+
+fn status_line(state: &State, populations: [usize; 2]) -> String {
+    //! Builds the text of the status bar from the configured fields, given
+    //! the number of alive cells of each color.
+    let mut values: Vec<String> = vec![];
+    let mut hints: Option<&str> = None;
+    for field in state.status_bar.fields() {
+        match field {
+            StatusField::Alive if state.immigration => values.push(format!(
+                "Alive: {} ({} {}, {} {})",
+                populations[0] + populations[1],
+                IMMIGRATION_COLORS[0].1,
+                populations[0],
+                IMMIGRATION_COLORS[1].1,
+                populations[1]
+            )),
+            StatusField::Alive => {
+                values.push(format!("Alive: {}", populations[0] + populations[1]))
+            }
+            StatusField::Generation => values.push(format!("Gen: {}", state.generation)),
+            StatusField::Step => values.push(format!("Step: {}", state.step)),
+            StatusField::Timeout => values.push(state.speed()),
+            StatusField::Rule => values.push(format!("Rule: {}", state.get_rule())),
+            StatusField::RuleNoise => {
+                let noise: f64 = state.stepper.get_noise();
+                if noise > 0.0 {
+                    values.push(format!("Rule noise: {}", noise));
+                }
+            }
+            StatusField::Topology => values.push(format!("Topology: {}", state.topology)),
+            StatusField::Power => values.push(format!("Power: {}", state.power)),
+            StatusField::Density => values.push(format!("Density: {:.2}", state.density)),
+            StatusField::Seed => {
+                if let Some(seed) = state.soup_seed {
+                    values.push(format!("Seed: {}", seed));
+                }
+            }
+            StatusField::Pattern => {
+                if let Some(title) = state.metadata.title() {
+                    values.push(format!("Pattern: {}", title));
+                }
+            }
+            StatusField::Similarity => {
+                if let Some(similarity) = &state.similarity {
+                    if let Some(latest) = similarity.latest() {
+                        values.push(format!(
+                            "Hamming: {}, Jaccard: {:.2} {}",
+                            latest.hamming,
+                            latest.jaccard,
+                            similarity.sparkline(SPARKLINE_WIDTH)
+                        ));
+                    }
+                }
+            }
+            StatusField::Hints if state.kiosk.is_some() => (),
+            StatusField::Hints => {
+                hints = Some("q: quit, p: pause, ?: help");
+            }
+        }
+    }
+    if state.paused {
+        values.insert(0, "Paused".to_string());
+    }
+    match hints {
+        Some(hints) if values.is_empty() => hints.to_string(),
+        Some(hints) => format!("{} | {}", values.join(", "), hints),
+        None => values.join(", "),
+    }
+}
+
+/// Spacing, in cells, between the grid lines.
+const GRID_LINE_SPACING: usize = 10;
+
+fn grid_line_char(i: usize, j: usize) -> Option<char> {
+    //! Returns the character drawn at a dead cell that lies on a grid line.
+    match (
+        i.is_multiple_of(GRID_LINE_SPACING),
+        j.is_multiple_of(GRID_LINE_SPACING),
+    ) {
+        (true, true) => Some('+'),
+        (true, false) => Some('-'),
+        (false, true) => Some('|'),
+        (false, false) => None,
+    }
+}
+
+/// Character dead cells are dimmed to when the dead-cell character is blank.
+const TEXTURE_CHAR: char = '.';
+
+fn texture_char(state: &State) -> char {
+    //! Returns the character dead cells are drawn with, dimmed, while the
+    //! background texture is on.
+    if state.dead_char == ' ' {
+        TEXTURE_CHAR
+    } else {
+        state.dead_char
+    }
+}
+
+fn draw_grid_lines(window: &Window, visible: (usize, usize), state: &State) -> Result<()> {
+    //! Draws the parts of the grid lines between the visible cells, and the
+    //! row and column coordinates along the top and left edges.
+    let dead: Option<&ColorPair> = state.theme.dead.as_ref();
+    let (top, left) = state.viewport;
+    let (nrows, ncols) = visible;
+    for i in (top..top + nrows).filter(|i| i.is_multiple_of(GRID_LINE_SPACING)) {
+        let y: i32 = (i - top) as i32;
+        // fill in the gaps between cells on horizontal lines
+        let width: i32 = state.cell_width() as i32;
+        for x in 0..ncols as i32 {
+            window.print_attr(x * width + width - 1, y, "-", dead, A_DIM)?;
+        }
+        if i > 0 {
+            window.print_attr(0, y, &i.to_string(), state.theme.status.as_ref(), A_DIM)?;
+        }
+    }
+    for j in (left..left + ncols).filter(|j| j.is_multiple_of(GRID_LINE_SPACING) && *j > 0) {
+        window.print_attr(
+            ((j - left) * state.cell_width()) as i32,
+            0,
+            &j.to_string(),
+            state.theme.status.as_ref(),
+            A_DIM,
+        )?;
+    }
+    Ok(())
+}
+
+fn cell_x(state: &State, i: usize, j: usize) -> i32 {
+    //! Column of the window the cell at row `i` and column `j` of the grid
+    //! is drawn at. Cells are `cell_width` columns apart; on hexagonal grids,
+    //! odd rows are shifted right by half a cell, so that the rows interlock.
+    let width: usize = state.cell_width();
+    let shift: usize = if state.is_hexagonal() && i % 2 == 1 {
+        width / 2
+    } else {
+        0
+    };
+    (j.saturating_sub(state.viewport.1) * width + shift) as i32
+}
+
+fn visible_size(window: &Window, grid: &[Vec<Cell>], state: &State) -> (usize, usize) {
+    //! Number of rows and columns of the grid that fit in the window, apart
+    //! from the status bar.
+    let rows: usize = (window.get_rows() as usize).saturating_sub(state.status_bar.height());
+    let cols: usize = window.get_cols() as usize / state.cell_width();
+    (
+        grid.len().min(rows),
+        grid.first().map_or(0, |row| row.len()).min(cols),
     )
 }
 
+/// Characters alive cells are drawn with by their number of neighbors, from
+/// the loneliest to the most crowded.
+const NEIGHBOR_CHARS: [char; 4] = ['.', 'o', 'O', '@'];
+
+fn neighbor_char(cell: &Cell, neighborhood_size: usize) -> char {
+    //! Returns the character an alive cell is drawn with, showing how
+    //! crowded it is. Half the neighborhood alive counts as most crowded;
+    //! in Life, lone cells are dots and cells with 2, 3, or 4 or more
+    //! neighbors are `o`, `O` and `@`.
+    let index: usize = cell.neighbors as usize * NEIGHBOR_CHARS.len() / (neighborhood_size / 2 + 1);
+    NEIGHBOR_CHARS[index.min(NEIGHBOR_CHARS.len() - 1)]
+}
+
+/// Characters recently dead cells fade through, from just died to almost gone.
+const GHOST_CHARS: [char; 3] = ['+', ':', '.'];
+
+fn ghost_char(cell: &Cell, trail: u32) -> Option<char> {
+    //! Returns the character a dead cell is drawn with while its ghost fades
+    //! out, or `None` once it died more than `trail` generations ago.
+    let since_death: u32 = cell.get_since_death()?;
+    if since_death >= trail {
+        return None;
+    }
+    let index: usize = since_death as usize * GHOST_CHARS.len() / trail as usize;
+    Some(GHOST_CHARS[index])
+}
+
+/// Number of generations after which a cell reaches the end of the age gradient.
+const GRADIENT_MAX_AGE: u32 = 40;
+
+/// Number of distinct colors the age gradient is quantized to, which keeps
+/// the number of color pairs in use small.
+const GRADIENT_STEPS: u32 = 16;
+
+fn age_color(age: u32, alive: Option<&ColorPair>) -> ColorPair {
+    //! Returns the color of a live cell of the given age, going from white for
+    //! newborn cells through orange to red for old cells. The background of
+    //! the theme's alive color is kept.
+    let background: Color = alive.map(|c| c.background()).unwrap_or(Color::Black);
+    let step: u32 = age.min(GRADIENT_MAX_AGE) * GRADIENT_STEPS / GRADIENT_MAX_AGE;
+    let t: f32 = step as f32 / GRADIENT_STEPS as f32;
+    let foreground: Color = if supports_rgb() {
+        if t < 0.5 {
+            Color::lerp((255, 255, 255), (255, 140, 0), t * 2.0)
+        } else {
+            Color::lerp((255, 140, 0), (200, 0, 0), (t - 0.5) * 2.0)
+        }
+    } else if t < 0.25 {
+        Color::White
+    } else if t < 0.6 {
+        Color::Yellow
+    } else {
+        Color::Red
+    };
+    ColorPair::new(foreground, background)
+}
+
+/// Colors of the two kinds of cells in the Immigration game, with the names
+/// the status bar gives them.
+const IMMIGRATION_COLORS: [(Color, &str); 2] = [(Color::Red, "red"), (Color::Cyan, "cyan")];
+
+fn immigration_color(color: u8, alive: Option<&ColorPair>) -> ColorPair {
+    //! Returns the color a live cell of the Immigration game is drawn in,
+    //! keeping the background of the theme's alive color.
+    let background: Color = alive.map(|c| c.background()).unwrap_or(Color::Black);
+    ColorPair::new(IMMIGRATION_COLORS[color as usize].0, background)
+}
+
 pub struct State {
     timeout: i32,
+    /// Frames per second aimed for instead of the timeout, if set
+    fps: Option<u32>,
     draw_char: char,
+    /// Character dead cells are drawn with
+    dead_char: char,
+    theme: Theme,
+    age_gradient: bool,
+    /// Whether alive cells are drawn as solid blocks in reverse video rather
+    /// than as characters
+    inverse: bool,
+    /// Whether alive cells are drawn with characters showing their number of
+    /// neighbors rather than the cell character
+    neighbor_glyphs: bool,
+    /// Whether cells are drawn in the two colors of the Immigration game
+    immigration: bool,
+    /// Whether cells are colored by the region of the starting grid their
+    /// ancestors came from
+    lineage: bool,
+    heatmap: Heatmap,
+    /// Number of generations dead cells leave a fading ghost for
+    trail: u32,
+    /// Whether grid lines and coordinate rulers are shown
+    grid_lines: bool,
+    /// Whether dead cells are drawn dimmed, to show the extent of the grid
+    texture: bool,
+    /// Overview of the whole grid, shown while it doesn't fit in the window
+    minimap: Minimap,
+    /// Whether a grid larger than the window is shown whole, shrunk to fit,
+    /// instead of the part in view
+    zoomed_out: bool,
+    /// Births and deaths of the run, and the panel showing them
+    stats: Stats,
+    status_bar: StatusBar,
+    /// Number of generations simulated since the grid was initialized
+    generation: u64,
+    /// How the edges of the grid are joined
+    topology: Topology,
+    /// Topology 'W' goes back to from the plane: the last one whose edges
+    /// were joined
+    wrapped_topology: Topology,
+    /// Where loaded seeds are placed on the grid
+    seed_placement: Placement,
+    /// How loaded seeds are mirrored, rotated and scaled before being placed
+    seed_transform: Transform,
+    /// Number of dead cells between the copies of loaded seeds tiled
+    /// across the grid, if they are
+    seed_tiling: Option<usize>,
+    /// Whether loaded seeds larger than the grid are cut down to fit it
+    /// rather than refused
+    seed_crop: bool,
+    /// Name, author and comments of the loaded seed, if its file gives them
+    metadata: Metadata,
+    /// Seed of the random soup the grid was generated from, if it was
+    soup_seed: Option<u64>,
+    /// Number of generations advanced per displayed frame
+    step: usize,
+    /// Number of generations skipped at once with 'n'
+    skip: u64,
+    /// Position (row, column) of the inspect cursor, if inspect mode is on.
+    /// The simulation is paused while inspecting.
+    cursor: Option<(usize, usize)>,
+    /// Number of rows and columns of the grid, where they don't follow the
+    /// size of the window
+    size: (Option<usize>, Option<usize>),
+    /// Top-left cell (row, column) of the part of the grid shown in the window
+    viewport: (usize, usize),
+    /// How the run was generated, if it started from a random soup
+    dna: Option<Dna>,
+    /// Whether the DNA code is shown instead of the status bar
+    show_code: bool,
+    /// Corner of the selected region opposite to the cursor, if selecting
+    selection_anchor: Option<(usize, usize)>,
+    /// Format selected regions are written in
+    export_format: ExportFormat,
+    /// Fraction of cells brought to life in random fills: new soups started
+    /// with 'N', the selected region randomized with 'r', and the noise
+    /// brought with 'z'
+    density: f64,
+    /// Number of random cells brought to life at once with 'z' at the
+    /// default density; scaled by the density of random fills
+    noise: usize,
+    /// Whether random cells are born every `rain_interval` generations
+    raining: bool,
+    rain_interval: u64,
+    /// How the cells brought to life by 'z' and by the rain are picked
+    noise_soup: SoupOptions,
+    /// Random number generator picking them, seeded from the soup options
+    noise_rng: StdRng,
+    /// Edits made to the grid since the simulation last advanced
+    undo: UndoHistory,
+    /// Kiosk mode, if the controls are locked
+    kiosk: Option<Kiosk>,
+    /// Recent frames, for stepping backward while paused
+    history: History,
+    /// Generation and grid that later generations can be replayed from, to
+    /// jump backward. Reset to the current frame whenever the grid is edited.
+    origin: Option<(u64, Vec<Vec<Cell>>)>,
+    /// Generations and grids stored in the numbered bookmark slots
+    bookmarks: HashMap<u8, (u64, Vec<Vec<Cell>>)>,
+    /// Generation number typed so far, while the jump prompt is open
+    prompt: Option<String>,
+    /// Rule being typed, opened with ':', and whether ncurses decoded keys
+    /// before it was opened
+    rule_editor: Option<(LineEditor, bool)>,
+    /// Text the demo's seeds are filtered by, typed after '/'
+    search: String,
+    /// Whether the search is being typed, which takes every key
+    searching: bool,
+    /// Whether '/' opens the search, which only the demo has
+    searchable: bool,
+    /// Stroke of the brush, while a mouse button is held
+    painting: Option<Stroke>,
+    /// Which cells around the pointer are painted with the mouse
+    brush: Brush,
+    /// Fraction of the cells under the spray brush that are painted
+    spray_density: f64,
+    /// Whether the help window is open. The simulation is paused meanwhile.
+    help: bool,
+    /// Settings menu, and the theme in use when it was opened, while it is
+    /// open. The theme is kept to switch back to if it isn't built in.
+    menu: Option<(Menu, Theme)>,
+    /// Message shown instead of the status bar until the next keypress
+    message: Option<String>,
+    /// Whether the simulation is paused
+    paused: bool,
+    alarms: Alarms,
+    /// Reference run the current run is compared against, if any
+    similarity: Option<SimilarityTracker>,
+    /// User script whose hooks are called as the run goes on, if any
+    script: Option<Script>,
+    /// asciinema cast every drawn frame is recorded to, if any
+    cast: Option<Cast>,
+    /// CSV log a row of metrics is written to for every generation, if any
+    csv_log: Option<CsvLog>,
+    /// Stream the events of the run are reported to
+    events: Events,
+    power: PowerProfile,
+    /// Steps the grid from one generation to the next
+    stepper: Stepper,
+    /// Alive state of every cell the last time the grid was drawn
+    last_drawn: Vec<bool>,
+    /// Whether the screen must be redrawn even if the grid hasn't changed
+    dirty: bool,
+    /// When the next frame is due, however many inputs are handled before
+    /// then
+    next_frame: Option<Instant>,
+    /// Whether frames follow each other as fast as they can be worked out,
+    /// only some of them drawn
+    turbo: bool,
+    /// Number of frames gone by since the grid was last drawn
+    frames_undrawn: u32,
 }
 
+/// Default number of generations the activity heatmap is taken over.
+pub const DEFAULT_HEATMAP_GENERATIONS: usize = 100;
+
+/// Step sizes cycled through with '[' and ']'.
+pub const STEP_SIZES: [usize; 8] = [1, 2, 5, 10, 20, 50, 100, 500];
+
+/// Number of cells the viewport moves with H, J, K and L.
+const PAN_STEP: usize = 10;
+
+/// Default number of generations skipped at once with 'n'.
+pub const DEFAULT_SKIP: u64 = 1000;
+
+/// Default fraction of cells set alive in random fills, when it isn't set
+/// or taken from the starting soup.
+pub const DEFAULT_FILL_DENSITY: f64 = 0.5;
+
+/// Amount the density of random fills changes by with '+' and '-'.
+const DENSITY_STEP: f64 = 0.05;
+
+/// Default number of random cells brought to life at once with 'z', at the
+/// default density.
+pub const DEFAULT_NOISE: usize = 100;
+
+/// Default number of generations between rain drops, when rain is turned on
+/// with 'R'.
+pub const DEFAULT_RAIN_INTERVAL: u64 = 10;
+
+/// Number of random cells born each time it rains.
+const RAIN_CELLS: usize = 3;
+
 impl State {
-    pub fn new(timeout: i32, draw_char: char) -> State {
-        State { timeout, draw_char }
+    pub fn new(timeout: i32, draw_char: char, theme: Theme) -> State {
+        State {
+            timeout,
+            fps: None,
+            draw_char,
+            dead_char: ' ',
+            theme,
+            age_gradient: false,
+            inverse: false,
+            neighbor_glyphs: false,
+            immigration: false,
+            lineage: false,
+            heatmap: Heatmap::new(DEFAULT_HEATMAP_GENERATIONS),
+            trail: 0,
+            grid_lines: false,
+            texture: false,
+            minimap: Minimap::default(),
+            zoomed_out: false,
+            stats: Stats::default(),
+            status_bar: StatusBar::default(),
+            generation: 0,
+            topology: Topology::Torus,
+            wrapped_topology: Topology::Torus,
+            seed_placement: Placement::default(),
+            seed_transform: Transform::default(),
+            seed_tiling: None,
+            seed_crop: false,
+            metadata: Metadata::default(),
+            soup_seed: None,
+            step: 1,
+            skip: DEFAULT_SKIP,
+            cursor: None,
+            size: (None, None),
+            viewport: (0, 0),
+            dna: None,
+            show_code: false,
+            selection_anchor: None,
+            export_format: ExportFormat::Seed,
+            density: DEFAULT_FILL_DENSITY,
+            noise: DEFAULT_NOISE,
+            raining: false,
+            rain_interval: DEFAULT_RAIN_INTERVAL,
+            noise_soup: SoupOptions::default(),
+            noise_rng: StdRng::seed_from_u64(0),
+            undo: UndoHistory::new(),
+            kiosk: None,
+            history: History::default(),
+            origin: None,
+            bookmarks: HashMap::new(),
+            prompt: None,
+            rule_editor: None,
+            search: String::new(),
+            searching: false,
+            searchable: false,
+            painting: None,
+            brush: Brush::default(),
+            spray_density: paint::DEFAULT_SPRAY_DENSITY,
+            help: false,
+            menu: None,
+            message: None,
+            paused: false,
+            alarms: Alarms::default(),
+            similarity: None,
+            script: None,
+            cast: None,
+            csv_log: None,
+            events: Events::default(),
+            power: PowerProfile::Normal,
+            stepper: Stepper::default(),
+            last_drawn: vec![],
+            dirty: true,
+            next_frame: None,
+            turbo: false,
+            frames_undrawn: 0,
+        }
+    }
+
+    pub fn frame_duration(&self) -> Duration {
+        //! Time between frames: a second over the frame rate, or else the
+        //! timeout, stretched by the number of generations simulated per
+        //! frame in eco mode. In turbo mode, frames don't wait at all.
+        if self.is_turbo() {
+            return Duration::ZERO;
+        }
+        let frame: Duration = match self.fps {
+            Some(fps) => Duration::from_secs(1) / fps.max(1),
+            None => Duration::from_millis(self.timeout.max(0) as u64),
+        };
+        frame * self.power.batch() as u32
+    }
+
+    pub fn get_fps(&self) -> Option<u32> {
+        self.fps
+    }
+
+    pub fn set_fps(&mut self, fps: Option<u32>) {
+        self.fps = fps;
+    }
+
+    fn speed(&self) -> String {
+        //! Describes how fast frames go by, for the status bar and the help
+        //! window.
+        match self.fps {
+            _ if self.turbo => "Turbo".to_string(),
+            Some(fps) => format!("FPS: {}", fps),
+            None => format!("Timeout: {} ms", self.timeout),
+        }
+    }
+
+    pub fn set_turbo(&mut self, turbo: bool) {
+        self.turbo = turbo;
+    }
+
+    fn is_turbo(&self) -> bool {
+        //! Whether frames go by in turbo mode right now: not while the run is
+        //! held up by a pause, inspect mode, a prompt or the help window.
+        self.turbo
+            && !(self.is_inspecting()
+                || self.paused
+                || self.prompt.is_some()
+                || self.rule_editor.is_some()
+                || self.help)
+    }
+
+    fn watches_generations(&self) -> bool {
+        //! Whether every generation has to be looked at as it is worked out,
+        //! to report or log it, or to let it rain.
+        self.has_events() || self.csv_log.is_some() || self.raining
+    }
+
+    fn shows_history(&self) -> bool {
+        //! Whether what is drawn depends on more than which cells are alive:
+        //! on their ages, colors or lineages, on the trails of dead cells,
+        //! on the activity of the heatmap or on the counts of the stats panel.
+        self.age_gradient
+            || self.trail > 0
+            || self.heatmap.is_enabled()
+            || self.immigration
+            || self.lineage
+            || self.stats.is_shown()
     }
 
     pub fn get_timeout(&self) -> i32 {
@@ -132,166 +1004,2591 @@ impl State {
     pub fn set_draw_char(&mut self, draw_char: char) {
         self.draw_char = draw_char;
     }
-}
 
-pub struct InputHandler {
-    input: InputType,
-}
+    pub fn get_dead_char(&self) -> char {
+        self.dead_char
+    }
 
-impl Default for InputHandler {
-    fn default() -> Self {
-        Self::new()
+    pub fn set_dead_char(&mut self, dead_char: char) {
+        self.dead_char = dead_char;
     }
-}
 
-impl InputHandler {
-    pub fn new() -> InputHandler {
-        InputHandler {
-            input: InputType::Continue,
-        }
+    pub fn cell_width(&self) -> usize {
+        //! Number of columns of the window each cell takes up: its character
+        //! and a space after it, so wide characters spread the cells out.
+        glyph_width(self.draw_char)
+            .max(glyph_width(self.dead_char))
+            .max(1)
+            + 1
     }
 
-    pub fn handle_input(&mut self, state: &mut State) -> Result<InputType> {
-        let c: i32 = getch();
-        self.input = if c == ArrowKeys::Down as i32 || c == 'j' as i32 {
-            InputType::Down
-        } else if c == ArrowKeys::Up as i32 || c == 'k' as i32 {
-            InputType::Up
-        } else {
-            match c as u8 as char {
-                'q' => InputType::Quit,
-                'a' => InputType::IncreaseTimeout,
-                's' => InputType::DecreaseTimeout,
-                _ => InputType::Continue,
-            }
-        };
+    pub fn get_theme(&self) -> &Theme {
+        &self.theme
+    }
 
-        match self.input {
-            InputType::Quit | InputType::Continue => (),
-            InputType::IncreaseTimeout => {
-                // Increase timeout
-                if state.timeout < 1000 {
-                    state.timeout += 10;
-                }
-                timeout(state.timeout);
-            }
-            InputType::DecreaseTimeout => {
-                // Decrease timeout
-                if state.timeout > 10 {
-                    state.timeout -= 10;
-                }
-                timeout(state.timeout);
-            }
-            _ => (),
-        }
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
 
-        Ok(self.input)
+    pub fn get_age_gradient(&self) -> bool {
+        self.age_gradient
     }
-}
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub enum InputType {
-    Quit,
-    Continue,
-    IncreaseTimeout,
-    DecreaseTimeout,
-    Up,
-    Down,
-}
+    pub fn set_age_gradient(&mut self, age_gradient: bool) {
+        self.age_gradient = age_gradient;
+    }
 
-pub fn initialize(
-    window: &mut Window,
-    num_alive: Option<usize>,
-    seed_file: &Option<String>,
-) -> Result<Vec<Vec<Cell>>> {
-    //! Initializes the grid with the given number of alive cells or seed file.
-    let mut grid: Vec<Vec<Cell>> = vec![];
-    let nrows: usize = window.get_rows() as usize - 1; // -1 to account for status bar at bottom
-    let ncols: usize = window.get_cols() as usize;
-    for i in 0..nrows {
-        grid.push(vec![]);
-        for j in 0..ncols / 2 {
-            // /2 to account for space between characters
-            grid[i].push(Cell::new(i, j, false));
-        }
+    pub fn set_inverse(&mut self, inverse: bool) {
+        self.inverse = inverse;
     }
 
-    if seed_file.is_some() && Path::new(&seed_file.clone().unwrap()).exists() {
-        // Read the seed file and set the cells to alive based on the seed file.
-        let seed: String = std::fs::read_to_string(seed_file.clone().unwrap()).unwrap();
-        for (rownum, line) in seed.lines().enumerate() {
-            if rownum >= nrows {
-                break;
-            }
-            let cells: Vec<char> = line.chars().collect::<Vec<char>>();
-            for (colnum, cell) in cells.iter().enumerate() {
-                if colnum >= ncols {
-                    break;
-                }
-                if *cell == '*' {
-                    grid[rownum][colnum].set_alive();
-                }
-            }
-        }
-    } else if (seed_file.is_some() && !Path::new(&seed_file.clone().unwrap()).exists())
-        || num_alive.is_some()
-    {
-        // Set the cells to alive randomly based on the number of alive cells.
-        if num_alive.unwrap() > grid.len() * grid[0].len() {
-            endwin();
-            return Err(anyhow::anyhow!(
-                "Number of alive cells cannot be greater than the number of cells in the grid."
-            ));
-        }
-        let mut rng: ThreadRng = rand::thread_rng();
-        let mut alive_cells: HashSet<(usize, usize)> = HashSet::new();
-        while alive_cells.len() < num_alive.unwrap() {
-            let i: usize = rng.gen::<usize>() % nrows;
-            let j: usize = rng.gen::<usize>() % (ncols / 2);
-            alive_cells.insert((i, j));
-        }
-        for (i, j) in alive_cells {
-            grid[i][j].set_alive();
-        }
-    } else {
-        endwin();
-        return Err(anyhow::anyhow!("Invalid arguments."));
+    pub fn get_neighbor_glyphs(&self) -> bool {
+        self.neighbor_glyphs
     }
 
-    Ok(grid)
-}
+    pub fn set_neighbor_glyphs(&mut self, neighbor_glyphs: bool) {
+        self.neighbor_glyphs = neighbor_glyphs;
+    }
 
-pub fn calc_next_frame(grid: &[Vec<Cell>]) -> Vec<Vec<Cell>> {
-    //! Calculates the next frame of the game, returning a new grid.
-    let mut next_frame: Vec<Vec<Cell>> = grid.to_vec();
+    pub fn get_immigration(&self) -> bool {
+        self.immigration
+    }
 
-    grid.iter().for_each(|row| {
-        row.iter().for_each(|cell| {
-            let count = cell.count_alive_neighbors(grid);
-            if cell.is_alive() {
-                if !(2..=3).contains(&count) {
-                    next_frame[cell.x][cell.y].set_dead();
-                }
-            } else if count == 3 {
-                next_frame[cell.x][cell.y].set_alive();
-            }
-        })
-    });
-    next_frame
-}
+    pub fn set_immigration(&mut self, immigration: bool) {
+        self.immigration = immigration;
+    }
 
-pub fn run_frame(
-    window: &mut Window,
-    grid: &[Vec<Cell>],
-    input_handler: &mut InputHandler,
-    state: &mut State,
-) -> Result<(InputType, Vec<Vec<Cell>>)> {
-    //! Runs a single loop of the game, drawing the grid, calculating the next
-    //! frame, and getting input from the user.
-    window.erase();
-    draw(window, grid, state)?;
-    window.refresh();
-    let next_grid = calc_next_frame(grid);
-    let input: InputType = input_handler.handle_input(state)?;
-    Ok((input, next_grid))
+    pub fn set_lineage(&mut self, lineage: bool) {
+        self.lineage = lineage;
+    }
+
+    pub fn set_stats_shown(&mut self, shown: bool) {
+        self.stats.set_shown(shown);
+    }
+
+    pub fn get_heatmap(&self) -> &Heatmap {
+        &self.heatmap
+    }
+
+    pub fn get_heatmap_mut(&mut self) -> &mut Heatmap {
+        &mut self.heatmap
+    }
+
+    pub fn set_heatmap(&mut self, heatmap: Heatmap) {
+        self.heatmap = heatmap;
+    }
+
+    pub fn get_trail(&self) -> u32 {
+        self.trail
+    }
+
+    pub fn set_trail(&mut self, trail: u32) {
+        self.trail = trail;
+    }
+
+    pub fn get_grid_lines(&self) -> bool {
+        self.grid_lines
+    }
+
+    pub fn set_grid_lines(&mut self, grid_lines: bool) {
+        self.grid_lines = grid_lines;
+    }
+
+    pub fn get_texture(&self) -> bool {
+        self.texture
+    }
+
+    pub fn set_texture(&mut self, texture: bool) {
+        self.texture = texture;
+    }
+
+    pub fn get_status_bar(&self) -> &StatusBar {
+        &self.status_bar
+    }
+
+    pub fn set_status_bar(&mut self, status_bar: StatusBar) {
+        self.status_bar = status_bar;
+    }
+
+    pub fn get_generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
+    pub fn get_topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn set_topology(&mut self, topology: Topology) {
+        if topology != Topology::Plane {
+            self.wrapped_topology = topology;
+        }
+        self.topology = topology;
+    }
+
+    pub fn set_seed_placement(&mut self, seed_placement: Placement) {
+        self.seed_placement = seed_placement;
+    }
+
+    pub fn set_seed_transform(&mut self, seed_transform: Transform) {
+        self.seed_transform = seed_transform;
+    }
+
+    pub fn set_seed_tiling(&mut self, seed_tiling: Option<usize>) {
+        self.seed_tiling = seed_tiling;
+    }
+
+    pub fn set_seed_crop(&mut self, seed_crop: bool) {
+        self.seed_crop = seed_crop;
+    }
+
+    pub fn get_metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: Metadata) {
+        self.metadata = metadata;
+    }
+
+    pub fn get_step(&self) -> usize {
+        self.step
+    }
+
+    pub fn set_step(&mut self, step: usize) {
+        self.step = step.max(1);
+    }
+
+    pub fn set_skip(&mut self, skip: u64) {
+        self.skip = skip;
+    }
+
+    pub fn generations_per_frame(&self) -> usize {
+        //! Number of generations advanced per frame: the step size, batched
+        //! further by the power profile.
+        self.step * self.power.batch()
+    }
+
+    pub fn is_inspecting(&self) -> bool {
+        self.cursor.is_some()
+    }
+
+    pub fn get_search(&self) -> &str {
+        &self.search
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.searching
+    }
+
+    pub fn set_searchable(&mut self, searchable: bool) {
+        self.searchable = searchable;
+    }
+
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        //! Returns the top-left and bottom-right corners of the selected region.
+        let (anchor, cursor) = (self.selection_anchor?, self.cursor?);
+        Some((
+            (anchor.0.min(cursor.0), anchor.1.min(cursor.1)),
+            (anchor.0.max(cursor.0), anchor.1.max(cursor.1)),
+        ))
+    }
+
+    pub fn set_export_format(&mut self, export_format: ExportFormat) {
+        self.export_format = export_format;
+    }
+
+    pub fn get_density(&self) -> f64 {
+        self.density
+    }
+
+    pub fn noise_count(&self) -> usize {
+        //! Number of random cells 'z' brings to life: the noise, scaled by
+        //! how the density of random fills compares to the default.
+        (self.noise as f64 * self.density / DEFAULT_FILL_DENSITY).round() as usize
+    }
+
+    pub fn set_density(&mut self, density: f64) {
+        self.density = density.clamp(0.0, 1.0);
+    }
+
+    pub fn set_noise(&mut self, noise: usize) {
+        self.noise = noise;
+    }
+
+    pub fn set_rain(&mut self, interval: Option<u64>) {
+        //! Makes it rain every `interval` generations, or stops the rain.
+        self.raining = interval.is_some();
+        if let Some(interval) = interval {
+            self.rain_interval = interval.max(1);
+        }
+    }
+
+    pub fn set_noise_soup(&mut self, soup: SoupOptions) {
+        //! Picks the cells brought to life by 'z' and by the rain as random
+        //! soups are, from a generator seeded with the soup's seed.
+        self.noise_soup = soup;
+        self.noise_rng = StdRng::seed_from_u64(soup.seed);
+    }
+
+    fn random_births(&mut self, grid: &[Vec<Cell>], count: usize) -> Vec<(usize, usize)> {
+        //! Picks up to `count` random cells as the soup options say, and
+        //! returns those of them that are dead.
+        let (nrows, ncols) = (grid.len(), grid[0].len());
+        let count: usize = count.min(self.noise_soup.capacity(nrows, ncols));
+        random_cells(&mut self.noise_rng, nrows, ncols, count, &self.noise_soup)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|&(i, j)| !grid[i][j].is_alive())
+            .collect()
+    }
+
+    pub fn get_history_mut(&mut self) -> &mut History {
+        &mut self.history
+    }
+
+    pub fn set_history(&mut self, history: History) {
+        self.history = history;
+    }
+
+    pub fn clear_origin(&mut self) {
+        //! Makes the next frame the one jumps backward are replayed from.
+        self.origin = None;
+    }
+
+    pub fn get_kiosk_mut(&mut self) -> Option<&mut Kiosk> {
+        self.kiosk.as_mut()
+    }
+
+    pub fn set_kiosk(&mut self, kiosk: Option<Kiosk>) {
+        self.kiosk = kiosk;
+    }
+
+    pub fn get_undo_mut(&mut self) -> &mut UndoHistory {
+        &mut self.undo
+    }
+
+    pub fn set_message(&mut self, message: Option<String>) {
+        self.message = message;
+        self.dirty = true;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.dirty = true;
+    }
+
+    pub fn get_alarms_mut(&mut self) -> &mut Alarms {
+        &mut self.alarms
+    }
+
+    pub fn set_alarms(&mut self, alarms: Alarms) {
+        self.alarms = alarms;
+    }
+
+    pub fn get_similarity(&self) -> Option<&SimilarityTracker> {
+        self.similarity.as_ref()
+    }
+
+    pub fn set_similarity(&mut self, similarity: Option<SimilarityTracker>) {
+        self.similarity = similarity;
+    }
+
+    fn move_cursor(&mut self, input: InputType, nrows: usize, ncols: usize) {
+        //! Moves the inspect cursor by one cell, wrapping around the edges.
+        let Some((i, j)) = self.cursor else {
+            return;
+        };
+        self.cursor = Some(match input {
+            InputType::Up => ((i + nrows - 1) % nrows, j),
+            InputType::Down => ((i + 1) % nrows, j),
+            InputType::Left => (i, (j + ncols - 1) % ncols),
+            InputType::Right => (i, (j + 1) % ncols),
+            _ => (i % nrows, j % ncols),
+        });
+    }
+
+    pub fn get_size(&self) -> (Option<usize>, Option<usize>) {
+        self.size
+    }
+
+    pub fn set_size(&mut self, size: (Option<usize>, Option<usize>)) {
+        self.size = size;
+    }
+
+    fn scroll_viewport(&mut self, visible: (usize, usize), nrows: usize, ncols: usize) {
+        //! Keeps the inspect cursor inside the viewport, and the viewport
+        //! inside the grid.
+        let visible: (usize, usize) = (visible.0.max(1), visible.1.max(1));
+        let (mut top, mut left) = self.viewport;
+        if let Some((i, j)) = self.cursor {
+            top = top.clamp((i + 1).saturating_sub(visible.0), i);
+            left = left.clamp((j + 1).saturating_sub(visible.1), j);
+        }
+        self.viewport = (top.min(nrows - visible.0), left.min(ncols - visible.1));
+    }
+
+    pub fn reset_run(&mut self) {
+        //! Starts the run over from generation 0, for a new grid.
+        self.heatmap.clear();
+        self.stats.clear();
+        self.generation = 0;
+        self.alarms.reset();
+        self.undo.clear();
+        self.history.clear();
+        self.origin = None;
+        self.dna = None;
+        self.dirty = true;
+    }
+
+    fn mark_edited(&mut self) {
+        //! An edited run can no longer be reproduced from its soup, or
+        //! replayed from before the edit.
+        self.dna = None;
+        self.origin = None;
+        self.show_code = false;
+    }
+
+    pub fn set_script(&mut self, script: Option<Script>) {
+        self.script = script;
+    }
+
+    pub fn set_stepper(&mut self, stepper: Stepper) {
+        self.stepper = stepper;
+    }
+
+    pub fn get_rule(&self) -> &Rule {
+        self.stepper.get_rule()
+    }
+
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.stepper.set_rule(rule);
+    }
+
+    pub fn get_rule_noise(&self) -> f64 {
+        self.stepper.get_noise()
+    }
+
+    pub fn reseed_rule_noise(&mut self) -> u64 {
+        self.stepper.reseed_noise()
+    }
+
+    pub fn is_hexagonal(&self) -> bool {
+        //! Whether the grid is hexagonal, which changes how it is drawn.
+        self.get_rule().get_neighborhood().shape == Shape::Hexagonal
+    }
+
+    pub fn is_lopsided(&self, nrows: usize) -> bool {
+        //! Whether a grid of `nrows` rows would join two rows shifted the same
+        //! way across its top and bottom edges, which a hexagonal grid only
+        //! avoids with an even number of rows.
+        self.is_hexagonal() && self.topology.joins_rows() && nrows % 2 == 1
+    }
+
+    pub fn set_cast(&mut self, cast: Option<Cast>) {
+        self.cast = cast;
+    }
+
+    pub fn set_csv_log(&mut self, csv_log: Option<CsvLog>) {
+        self.csv_log = csv_log;
+    }
+
+    pub fn set_events(&mut self, events: Events) {
+        self.events = events;
+    }
+
+    pub fn has_events(&self) -> bool {
+        self.events.is_enabled()
+    }
+
+    pub fn emit(&mut self, event: Event) {
+        //! Reports an event to the event stream, giving the stream up if it
+        //! can't be written to.
+        if let Err(e) = self.events.emit(event) {
+            self.events = Events::default();
+            self.set_message(Some(format!("{}; stopped the event stream", e)));
+        }
+    }
+
+    pub fn set_dna(&mut self, dna: Option<Dna>) {
+        self.dna = dna;
+    }
+
+    pub fn get_show_code(&self) -> bool {
+        self.show_code
+    }
+
+    pub fn code(&self) -> Option<String> {
+        //! Returns the DNA code reproducing the run up to the current generation.
+        self.dna.as_ref().map(|dna| {
+            Dna {
+                generation: self.generation,
+                ..dna.clone()
+            }
+            .encode()
+        })
+    }
+
+    pub fn get_power(&self) -> PowerProfile {
+        self.power
+    }
+
+    pub fn set_power(&mut self, power: PowerProfile) {
+        self.power = power;
+    }
+}
+
+pub struct InputHandler {
+    input: InputType,
+    /// Key pressed during the last frame, if it is passed on to scripts
+    key: Option<char>,
+    /// Records every input, to be saved as a macro
+    recorder: Option<Recorder>,
+    /// Macro whose inputs are played back as they fall due
+    playback: Option<Playback>,
+    /// Socket other programs send commands through
+    control: Option<ControlSocket>,
+    /// Commands received through the control socket, in the order they were
+    /// sent, with the client each came from
+    requests: VecDeque<(u64, Command)>,
+}
+
+impl Default for InputHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keys storing the grid in bookmark slots 1 to 9: the digits with Shift
+/// held, on a US keyboard.
+const BOOKMARK_KEYS: [char; 9] = ['!', '@', '#', '$', '%', '^', '&', '*', '('];
+
+impl InputHandler {
+    pub fn new() -> InputHandler {
+        InputHandler {
+            input: InputType::Continue,
+            key: None,
+            recorder: None,
+            playback: None,
+            control: None,
+            requests: VecDeque::new(),
+        }
+    }
+
+    pub fn get_key(&self) -> Option<char> {
+        self.key
+    }
+
+    pub fn get_recorder(&self) -> Option<&Recorder> {
+        self.recorder.as_ref()
+    }
+
+    pub fn set_recorder(&mut self, recorder: Option<Recorder>) {
+        self.recorder = recorder;
+    }
+
+    pub fn set_playback(&mut self, playback: Option<Playback>) {
+        self.playback = playback;
+    }
+
+    pub fn set_control(&mut self, control: Option<ControlSocket>) {
+        self.control = control;
+    }
+
+    fn poll_control(&mut self) {
+        //! Queues the commands received through the control socket.
+        if let Some(control) = &mut self.control {
+            self.requests.extend(control.poll());
+        }
+    }
+
+    fn next_request(&mut self) -> Option<(u64, Command)> {
+        //! Takes the next command, unless it is an input, which has to wait
+        //! for `handle_input`.
+        match self.requests.front() {
+            Some((_, Command::Input(_))) | None => None,
+            Some(_) => self.requests.pop_front(),
+        }
+    }
+
+    fn reply(&mut self, client: u64, result: &Result<String>) {
+        if let Some(control) = &mut self.control {
+            control.reply(client, result);
+        }
+    }
+
+    pub fn handle_input(&mut self, state: &mut State) -> Result<InputType> {
+        //! Reads a key and applies the input it stands for. When no key is
+        //! pressed, the next input of the macro being played back is applied
+        //! instead, once it falls due.
+        let key: i32 = read_key();
+        // with the mouse on, arrow keys are decoded by ncurses
+        let c: i32 = match key {
+            KEY_UP => ArrowKeys::Up as i32,
+            KEY_DOWN => ArrowKeys::Down as i32,
+            KEY_LEFT => ArrowKeys::Left as i32,
+            KEY_RIGHT => ArrowKeys::Right as i32,
+            c => c,
+        };
+        // scripts see the keys typed outside kiosk mode, the prompt, the help
+        // window and the settings menu
+        self.key = if !(0..256).contains(&c)
+            || state.kiosk.is_some()
+            || state.prompt.is_some()
+            || state.rule_editor.is_some()
+            || state.searching
+            || state.help
+            || state.menu.is_some()
+        {
+            None
+        } else {
+            char::from_u32(c as u32)
+        };
+        let mut input: InputType = if let Some(kiosk) = &mut state.kiosk {
+            // in kiosk mode, the only thing keys can do is quit
+            if c != ERR && kiosk.press(char::from_u32(c as u32).unwrap_or_default()) {
+                InputType::Quit
+            } else {
+                InputType::Continue
+            }
+        } else if state.help {
+            // any key closes the help window, and does nothing else
+            if c != ERR {
+                InputType::CloseHelp
+            } else {
+                InputType::Continue
+            }
+        } else if state.menu.is_some() {
+            menu_input(c)
+        } else if state.rule_editor.is_some() {
+            // letters typed into the editor mustn't be taken for the end of an
+            // arrow key, so keys decoded by ncurses are told apart from them
+            editor_input(key)
+        } else {
+            key_input(c, state.prompt.is_some(), state.searching)
+        };
+        if input == InputType::Continue {
+            if let Some(next) = self
+                .playback
+                .as_mut()
+                .and_then(|playback| playback.next_due())
+            {
+                input = next;
+            }
+        }
+        // inputs sent through the control socket are answered once applied
+        let mut client: Option<u64> = None;
+        if input == InputType::Continue {
+            if let Some(&(id, Command::Input(next))) = self.requests.front() {
+                self.requests.pop_front();
+                input = next;
+                client = Some(id);
+            }
+        }
+        if input != InputType::Continue {
+            if let Some(recorder) = &mut self.recorder {
+                recorder.record(input);
+            }
+        }
+        self.input = input;
+
+        match self.input {
+            InputType::Quit | InputType::Continue => (),
+            // Lower the frame rate
+            InputType::IncreaseTimeout if state.fps.is_some_and(|fps| fps > 1) => {
+                state.fps = state.fps.map(|fps| fps - 1)
+            }
+            // Raise the frame rate
+            InputType::DecreaseTimeout if state.fps.is_some_and(|fps| fps < MAX_FPS) => {
+                state.fps = state.fps.map(|fps| fps + 1)
+            }
+            // Increase timeout
+            InputType::IncreaseTimeout if state.fps.is_none() && state.timeout < 1000 => {
+                state.timeout += 10
+            }
+            // Decrease timeout
+            InputType::DecreaseTimeout if state.fps.is_none() && state.timeout > 10 => {
+                state.timeout -= 10
+            }
+            InputType::IncreaseStep => {
+                if let Some(&step) = STEP_SIZES.iter().find(|&&step| step > state.step) {
+                    state.step = step;
+                }
+            }
+            InputType::DecreaseStep => {
+                if let Some(&step) = STEP_SIZES.iter().rev().find(|&&step| step < state.step) {
+                    state.step = step;
+                }
+            }
+            InputType::IncreaseDensity | InputType::DecreaseDensity => {
+                // kept on the steps, however it started
+                let steps: f64 = (state.density / DENSITY_STEP).round()
+                    + if input == InputType::IncreaseDensity {
+                        1.0
+                    } else {
+                        -1.0
+                    };
+                state.set_density(steps * DENSITY_STEP);
+            }
+            InputType::PromptJump => state.prompt = Some(String::new()),
+            InputType::PromptDigit(digit) => {
+                if let Some(prompt) = &mut state.prompt {
+                    prompt.push(char::from(b'0' + digit));
+                }
+            }
+            InputType::PromptBackspace => {
+                if let Some(prompt) = &mut state.prompt {
+                    prompt.pop();
+                }
+                if state.searching {
+                    state.search.pop();
+                }
+            }
+            InputType::PromptCancel => state.prompt = None,
+            InputType::EditRule => {
+                let editor: LineEditor = LineEditor::new("Rule: ", &state.get_rule().to_string());
+                // the keys moving the cursor are only told apart from the
+                // letters of the rule once ncurses decodes them
+                state.rule_editor = Some((editor, is_keypad(stdscr())));
+                keypad(stdscr(), true);
+            }
+            InputType::EditChar(c) => {
+                if let Some((editor, _)) = &mut state.rule_editor {
+                    editor.insert(c);
+                }
+            }
+            InputType::EditBackspace
+            | InputType::EditDelete
+            | InputType::EditLeft
+            | InputType::EditRight
+            | InputType::EditHome
+            | InputType::EditEnd => {
+                if let Some((editor, _)) = &mut state.rule_editor {
+                    match self.input {
+                        InputType::EditBackspace => editor.backspace(),
+                        InputType::EditDelete => editor.delete(),
+                        InputType::EditLeft => editor.left(),
+                        InputType::EditRight => editor.right(),
+                        InputType::EditHome => editor.home(),
+                        _ => editor.end(),
+                    }
+                }
+            }
+            InputType::EditDone | InputType::EditCancel => {
+                if let Some((editor, decoded)) = state.rule_editor.take() {
+                    keypad(stdscr(), decoded);
+                    if self.input == InputType::EditDone {
+                        apply_rule(&editor.text(), state);
+                    }
+                }
+            }
+            InputType::StartSearch => state.searching = state.searchable,
+            InputType::SearchChar(c) => state.search.push(c),
+            InputType::SearchDone => state.searching = false,
+            InputType::SearchCancel => {
+                state.searching = false;
+                state.search.clear();
+            }
+            InputType::ShowHelp => state.help = true,
+            InputType::CloseHelp => state.help = false,
+            InputType::NextBrush => state.brush = state.brush.next(),
+            InputType::ToggleRain => state.raining = !state.raining,
+            InputType::ToggleWrap => {
+                if state.topology == Topology::Plane {
+                    state.topology = state.wrapped_topology;
+                } else {
+                    state.wrapped_topology = state.topology;
+                    state.topology = Topology::Plane;
+                }
+                state.mark_edited();
+            }
+            InputType::OpenMenu => state.menu = Some((settings_menu(state), state.theme.clone())),
+            InputType::CloseMenu => state.menu = None,
+            InputType::MenuUp | InputType::MenuDown => {
+                if let Some((menu, _)) = &mut state.menu {
+                    if self.input == InputType::MenuUp {
+                        menu.select_previous();
+                    } else {
+                        menu.select_next();
+                    }
+                }
+            }
+            InputType::MenuPrevious | InputType::MenuNext => {
+                if let Some((mut menu, theme)) = state.menu.take() {
+                    menu.change(self.input == InputType::MenuNext);
+                    apply_settings(&menu, &theme, state);
+                    state.menu = Some((menu, theme));
+                }
+            }
+            // the viewport is kept inside the grid when the frame is drawn
+            InputType::PanUp => state.viewport.0 = state.viewport.0.saturating_sub(PAN_STEP),
+            InputType::PanDown => state.viewport.0 += PAN_STEP,
+            InputType::PanLeft => state.viewport.1 = state.viewport.1.saturating_sub(PAN_STEP),
+            InputType::PanRight => state.viewport.1 += PAN_STEP,
+            InputType::ToggleHeatmap => state.heatmap.toggle(),
+            InputType::ToggleGridLines => state.grid_lines = !state.grid_lines,
+            InputType::ToggleTexture => state.texture = !state.texture,
+            InputType::ToggleMinimap => state.minimap.toggle(),
+            InputType::ToggleZoom => state.zoomed_out = !state.zoomed_out,
+            InputType::ToggleTurbo => {
+                state.turbo = !state.turbo;
+                // back to the timeout, the schedule starts over
+                state.next_frame = None;
+            }
+            InputType::ToggleStats => state.stats.toggle(),
+            InputType::ToggleCode => state.show_code = !state.show_code,
+            InputType::TogglePause => state.paused = !state.paused,
+            InputType::Pause | InputType::StepForward => state.paused = true,
+            InputType::Resume => state.paused = false,
+            InputType::ToggleInspect => {
+                state.cursor = match state.cursor {
+                    Some(_) => None,
+                    None => Some((0, 0)),
+                };
+                state.selection_anchor = None;
+            }
+            InputType::ToggleSelection => {
+                state.selection_anchor = match state.selection_anchor {
+                    Some(_) => None,
+                    None => state.cursor,
+                }
+            }
+            _ => (),
+        }
+
+        if self.input != InputType::Continue {
+            state.dirty = true;
+            state.message = None;
+        }
+        if let Some(client) = client {
+            self.reply(client, &Ok(String::new()));
+        }
+
+        Ok(self.input)
+    }
+}
+
+fn menu_input(c: i32) -> InputType {
+    //! Returns the input a key stands for while the settings menu is open.
+    if c == ArrowKeys::Up as i32 || c == 'k' as i32 {
+        InputType::MenuUp
+    } else if c == ArrowKeys::Down as i32 || c == 'j' as i32 {
+        InputType::MenuDown
+    } else if c == ArrowKeys::Left as i32 || c == 'h' as i32 {
+        InputType::MenuPrevious
+    } else if c == ArrowKeys::Right as i32 || c == 'l' as i32 {
+        InputType::MenuNext
+    } else if c == 10 || c == 13 || c == 'm' as i32 || c == 'q' as i32 {
+        // not Escape, which also starts the arrow keys
+        InputType::CloseMenu
+    } else {
+        InputType::Continue
+    }
+}
+
+/// Characters offered for drawing cells in the settings menu.
+const DRAW_CHARS: [char; 6] = ['*', '#', '@', 'o', '+', 'x'];
+
+fn settings_menu(state: &State) -> Menu {
+    //! Builds the settings menu, starting from the current settings.
+    Menu::new(
+        "Settings",
+        vec![
+            MenuItem::new(
+                "Character",
+                DRAW_CHARS.iter().map(char::to_string).collect(),
+                &state.draw_char.to_string(),
+            ),
+            MenuItem::new(
+                "Theme",
+                BUILTIN_THEMES.iter().map(|name| name.to_string()).collect(),
+                &state.theme.name,
+            ),
+            MenuItem::new(
+                "Rule",
+                RULES.iter().map(|rule| rule.to_string()).collect(),
+                &state.get_rule().to_string(),
+            ),
+            MenuItem::new(
+                "Topology",
+                Topology::value_variants()
+                    .iter()
+                    .map(|topology| topology.to_string())
+                    .collect(),
+                &state.topology.to_string(),
+            ),
+            MenuItem::new(
+                "Timeout",
+                (10..=1000)
+                    .step_by(10)
+                    .map(|ms| format!("{} ms", ms))
+                    .collect(),
+                &format!("{} ms", state.timeout),
+            ),
+            MenuItem::new(
+                "Step",
+                STEP_SIZES.iter().map(|step| step.to_string()).collect(),
+                &state.step.to_string(),
+            ),
+            MenuItem::new(
+                "Brush",
+                BRUSHES.iter().map(|brush| brush.to_string()).collect(),
+                &state.brush.to_string(),
+            ),
+            MenuItem::new(
+                "Spray density",
+                (1..=10).map(|tenths| format!("{}%", tenths * 10)).collect(),
+                &format!("{}%", (state.spray_density * 100.0).round()),
+            ),
+        ],
+    )
+}
+
+fn apply_settings(menu: &Menu, opening_theme: &Theme, state: &mut State) {
+    //! Changes the settings to what is chosen in the settings menu, which was
+    //! opened with the given theme.
+    if let Some(draw_char) = menu
+        .value("Character")
+        .and_then(|value| value.chars().next())
+    {
+        state.draw_char = draw_char;
+    }
+    if let Some(name) = menu.value("Theme").filter(|name| *name != state.theme.name) {
+        state.theme = match Theme::builtin(name) {
+            Some(theme) => theme,
+            None => opening_theme.clone(),
+        };
+    }
+    if let Some(topology) = menu
+        .value("Topology")
+        .and_then(|value| Topology::from_str(value, true).ok())
+        .filter(|topology| *topology != state.topology)
+    {
+        state.set_topology(topology);
+        state.mark_edited();
+    }
+    if let Some(rule) = menu
+        .value("Rule")
+        .filter(|rule| *rule != state.get_rule().to_string())
+        .and_then(|rule| rule.parse::<Rule>().ok())
+    {
+        // the rule carries the neighbourhood, so changing either is an edit
+        state.stepper.set_rule(rule);
+        state.mark_edited();
+    }
+    if let Some(timeout) = menu
+        .value("Timeout")
+        .and_then(|value| value.trim_end_matches(" ms").parse().ok())
+    {
+        // choosing a timeout in the menu stops aiming for a frame rate
+        if timeout != state.timeout {
+            state.fps = None;
+            state.timeout = timeout;
+        }
+    }
+    if let Some(step) = menu.value("Step").and_then(|value| value.parse().ok()) {
+        state.step = step;
+    }
+    if let Some(brush) = menu
+        .value("Brush")
+        .and_then(|value| BRUSHES.iter().find(|brush| brush.to_string() == value))
+    {
+        state.brush = *brush;
+    }
+    if let Some(percent) = menu
+        .value("Spray density")
+        .and_then(|value| value.trim_end_matches('%').parse::<f64>().ok())
+    {
+        state.spray_density = percent / 100.0;
+    }
+}
+
+/// Milliseconds to wait for the rest of an escape sequence after Escape.
+const ESCAPE_WAIT: i32 = 10;
+
+fn read_key() -> i32 {
+    //! Reads a key. Without ncurses decoding keys, arrow keys arrive as
+    //! `ESC [` or `ESC O` and a letter, which are read as a whole, so that
+    //! the `[` doesn't count as a key of its own: arrows come back as the
+    //! letter, which `ArrowKeys` stands for, and other sequences are dropped.
+    let c: i32 = getch();
+    if c != 27 {
+        return c;
+    }
+    timeout(ESCAPE_WAIT);
+    let next: i32 = getch();
+    if next != '[' as i32 && next != 'O' as i32 {
+        // Escape on its own, or held with another key
+        if next != ERR {
+            ungetch(next);
+        }
+        return 27;
+    }
+    // parameters, e.g. the 3 of Delete's `ESC [ 3 ~`, come before the last
+    // character
+    let mut last: i32 = getch();
+    while (0x20..0x40).contains(&last) {
+        last = getch();
+    }
+    match last {
+        65..=68 => last,
+        _ => ERR,
+    }
+}
+
+fn editor_input(c: i32) -> InputType {
+    //! Returns the input a key stands for while the rule is being typed.
+    match c {
+        // Enter
+        10 | 13 | KEY_ENTER => InputType::EditDone,
+        // Escape
+        27 => InputType::EditCancel,
+        // Backspace
+        8 | 127 | KEY_BACKSPACE => InputType::EditBackspace,
+        KEY_DC => InputType::EditDelete,
+        KEY_LEFT => InputType::EditLeft,
+        KEY_RIGHT => InputType::EditRight,
+        // Ctrl-A
+        1 | KEY_HOME => InputType::EditHome,
+        // Ctrl-E
+        5 | KEY_END => InputType::EditEnd,
+        32..=126 => InputType::EditChar(c as u8 as char),
+        _ => InputType::Continue,
+    }
+}
+
+fn apply_rule(text: &str, state: &mut State) {
+    //! Changes the rule of the running grid to the one typed, or says what is
+    //! wrong with it.
+    match text.parse::<Rule>() {
+        Ok(rule) => {
+            state.set_message(Some(format!("Rule: {}", rule)));
+            state.set_rule(rule);
+            state.mark_edited();
+            state.dirty = true;
+        }
+        Err(e) => state.set_message(Some(format!("Invalid rule '{}': {:#}", text.trim(), e))),
+    }
+}
+
+fn key_input(c: i32, prompting: bool, searching: bool) -> InputType {
+    //! Returns the input a key stands for, which depends on whether the
+    //! prompt or the search is open.
+    if searching {
+        // while the search is open, keys type into it
+        return match c {
+            // Enter
+            10 | 13 => InputType::SearchDone,
+            // Escape
+            27 => InputType::SearchCancel,
+            // Backspace
+            8 | 127 => InputType::PromptBackspace,
+            32..=126 => InputType::SearchChar(c as u8 as char),
+            _ => InputType::Continue,
+        };
+    }
+    if prompting {
+        // while the prompt is open, keys edit the generation number
+        return match c {
+            // Enter
+            10 | 13 => InputType::Jump,
+            // Escape
+            27 => InputType::PromptCancel,
+            // Backspace
+            8 | 127 => InputType::PromptBackspace,
+            _ if (48..=57).contains(&c) => InputType::PromptDigit((c - 48) as u8),
+            _ => InputType::Continue,
+        };
+    }
+    if c == ArrowKeys::Down as i32 || c == 'j' as i32 {
+        InputType::Down
+    } else if c == ArrowKeys::Up as i32 || c == 'k' as i32 {
+        InputType::Up
+    } else if c == ArrowKeys::Left as i32 {
+        InputType::Left
+    } else if c == ArrowKeys::Right as i32 {
+        InputType::Right
+    } else if c == KEY_MOUSE {
+        paint::mouse_input()
+    } else if !(0..256).contains(&c) {
+        // other special keys, like Page Down, aren't bound, and would be
+        // mistaken for characters if cut down to a byte
+        InputType::Continue
+    } else {
+        match c as u8 as char {
+            'q' => InputType::Quit,
+            'a' => InputType::IncreaseTimeout,
+            's' => InputType::DecreaseTimeout,
+            ']' => InputType::IncreaseStep,
+            '[' => InputType::DecreaseStep,
+            '+' | '=' => InputType::IncreaseDensity,
+            '-' => InputType::DecreaseDensity,
+            'h' => InputType::ToggleHeatmap,
+            'g' => InputType::ToggleGridLines,
+            't' => InputType::ToggleTexture,
+            'M' => InputType::ToggleMinimap,
+            'Z' => InputType::ToggleZoom,
+            'S' => InputType::ToggleStats,
+            'T' => InputType::ToggleTurbo,
+            'm' => InputType::OpenMenu,
+            'e' => InputType::NextBrush,
+            'z' => InputType::AddNoise,
+            'N' => InputType::NewSoup,
+            // Ctrl-L
+            '\x0c' => InputType::ClearGrid,
+            'R' => InputType::ToggleRain,
+            'W' => InputType::ToggleWrap,
+            ':' => InputType::EditRule,
+            'i' => InputType::ToggleInspect,
+            'c' => InputType::ToggleCode,
+            'p' => InputType::TogglePause,
+            'v' => InputType::ToggleSelection,
+            'w' => InputType::WriteSelection,
+            'x' => InputType::ClearSelection,
+            'f' => InputType::FillSelection,
+            'r' => InputType::RandomizeSelection,
+            ' ' => InputType::ToggleCell,
+            'u' => InputType::Undo,
+            'b' => InputType::StepBack,
+            '.' => InputType::StepForward,
+            'n' => InputType::FastForward,
+            'y' => InputType::CopySnapshot,
+            'G' => InputType::PromptJump,
+            '?' => InputType::ShowHelp,
+            '/' => InputType::StartSearch,
+            'H' => InputType::PanLeft,
+            'J' => InputType::PanDown,
+            'K' => InputType::PanUp,
+            'L' => InputType::PanRight,
+            '1'..='9' => InputType::RestoreBookmark(c as u8 - b'0'),
+            c if BOOKMARK_KEYS.contains(&c) => {
+                let slot: usize = BOOKMARK_KEYS.iter().position(|key| *key == c).unwrap_or(0);
+                InputType::StoreBookmark(slot as u8 + 1)
+            }
+            // Ctrl-R
+            '\x12' => InputType::Redo,
+            _ => InputType::Continue,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum InputType {
+    Quit,
+    Continue,
+    IncreaseTimeout,
+    DecreaseTimeout,
+    IncreaseStep,
+    DecreaseStep,
+    /// Raises the density of random fills by a step
+    IncreaseDensity,
+    /// Lowers the density of random fills by a step
+    DecreaseDensity,
+    Up,
+    Down,
+    ToggleHeatmap,
+    ToggleGridLines,
+    ToggleTexture,
+    ToggleMinimap,
+    /// Shows a grid larger than the window whole, shrunk to fit, or the part
+    /// in view again
+    ToggleZoom,
+    ToggleStats,
+    ToggleTurbo,
+    ToggleInspect,
+    ToggleCode,
+    TogglePause,
+    ToggleSelection,
+    WriteSelection,
+    ClearSelection,
+    FillSelection,
+    RandomizeSelection,
+    /// Brings random cells all over the grid to life
+    AddNoise,
+    /// Kills every cell and pauses, to draw on a blank grid
+    ClearGrid,
+    /// Replaces the grid with the next random soup
+    NewSoup,
+    /// Starts or stops random cells being born every few generations
+    ToggleRain,
+    /// Switches between wrapping edges, on a torus, and the bounded plane
+    ToggleWrap,
+    ToggleCell,
+    Undo,
+    Redo,
+    StepBack,
+    /// Pauses and advances one generation
+    StepForward,
+    Pause,
+    Resume,
+    FastForward,
+    CopySnapshot,
+    /// Stores the grid in a numbered bookmark slot, from 1 to 9
+    StoreBookmark(u8),
+    /// Restores the grid stored in a numbered bookmark slot
+    RestoreBookmark(u8),
+    PromptJump,
+    /// Types a digit into the prompt
+    PromptDigit(u8),
+    PromptBackspace,
+    PromptCancel,
+    /// Opens the line editor on the rule, to type a new one
+    EditRule,
+    /// Types a character into the line editor, before the cursor
+    EditChar(char),
+    EditBackspace,
+    EditDelete,
+    EditLeft,
+    EditRight,
+    EditHome,
+    EditEnd,
+    /// Closes the line editor, changing the rule to the one typed
+    EditDone,
+    EditCancel,
+    /// Opens the search for seeds, in the demo
+    StartSearch,
+    /// Types a character into the search
+    SearchChar(char),
+    /// Closes the search, keeping the seeds it filters out hidden
+    SearchDone,
+    /// Closes the search, showing every seed again
+    SearchCancel,
+    ShowHelp,
+    CloseHelp,
+    /// Starts painting live cells with the mouse, at a screen row and column
+    PaintStart(u16, u16),
+    /// Starts painting dead cells with the mouse, at a screen row and column
+    EraseStart(u16, u16),
+    /// Paints up to a screen row and column, while the mouse is dragged
+    PaintTo(u16, u16),
+    PaintEnd,
+    NextBrush,
+    OpenMenu,
+    CloseMenu,
+    /// Selects the setting above in the settings menu
+    MenuUp,
+    /// Selects the setting below in the settings menu
+    MenuDown,
+    /// Changes the selected setting to its previous value
+    MenuPrevious,
+    /// Changes the selected setting to its next value
+    MenuNext,
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    Jump,
+    Left,
+    Right,
+}
+
+/// Names inputs are saved under in macros, apart from `PromptDigit`, which
+/// is saved as `prompt-digit:N`, the bookmark inputs, saved as
+/// `store-bookmark:N` and `restore-bookmark:N`, `SearchChar` and `EditChar`,
+/// saved as `search-char:C` and `edit-char:C`, and
+/// the inputs painting at a screen position, which are saved with the row and
+/// column, as in `paint-to:Y,X`.
+const INPUT_NAMES: [(InputType, &str); 71] = [
+    (InputType::Quit, "quit"),
+    (InputType::Continue, "continue"),
+    (InputType::IncreaseTimeout, "increase-timeout"),
+    (InputType::DecreaseTimeout, "decrease-timeout"),
+    (InputType::IncreaseStep, "increase-step"),
+    (InputType::DecreaseStep, "decrease-step"),
+    (InputType::IncreaseDensity, "increase-density"),
+    (InputType::DecreaseDensity, "decrease-density"),
+    (InputType::Up, "up"),
+    (InputType::Down, "down"),
+    (InputType::ToggleHeatmap, "toggle-heatmap"),
+    (InputType::ToggleGridLines, "toggle-grid-lines"),
+    (InputType::ToggleTexture, "toggle-texture"),
+    (InputType::ToggleMinimap, "toggle-minimap"),
+    (InputType::ToggleZoom, "toggle-zoom"),
+    (InputType::ToggleStats, "toggle-stats"),
+    (InputType::ToggleTurbo, "toggle-turbo"),
+    (InputType::ToggleInspect, "toggle-inspect"),
+    (InputType::ToggleCode, "toggle-code"),
+    (InputType::TogglePause, "toggle-pause"),
+    (InputType::ToggleSelection, "toggle-selection"),
+    (InputType::WriteSelection, "write-selection"),
+    (InputType::ClearSelection, "clear-selection"),
+    (InputType::FillSelection, "fill-selection"),
+    (InputType::RandomizeSelection, "randomize-selection"),
+    (InputType::AddNoise, "add-noise"),
+    (InputType::ClearGrid, "clear-grid"),
+    (InputType::NewSoup, "new-soup"),
+    (InputType::ToggleRain, "toggle-rain"),
+    (InputType::ToggleWrap, "toggle-wrap"),
+    (InputType::ToggleCell, "toggle-cell"),
+    (InputType::Undo, "undo"),
+    (InputType::Redo, "redo"),
+    (InputType::StepBack, "step-back"),
+    (InputType::StepForward, "step-forward"),
+    (InputType::Pause, "pause"),
+    (InputType::Resume, "resume"),
+    (InputType::FastForward, "fast-forward"),
+    (InputType::CopySnapshot, "copy-snapshot"),
+    (InputType::PromptJump, "prompt-jump"),
+    (InputType::PromptBackspace, "prompt-backspace"),
+    (InputType::PromptCancel, "prompt-cancel"),
+    (InputType::EditRule, "edit-rule"),
+    (InputType::EditBackspace, "edit-backspace"),
+    (InputType::EditDelete, "edit-delete"),
+    (InputType::EditLeft, "edit-left"),
+    (InputType::EditRight, "edit-right"),
+    (InputType::EditHome, "edit-home"),
+    (InputType::EditEnd, "edit-end"),
+    (InputType::EditDone, "edit-done"),
+    (InputType::EditCancel, "edit-cancel"),
+    (InputType::StartSearch, "start-search"),
+    (InputType::SearchDone, "search-done"),
+    (InputType::SearchCancel, "search-cancel"),
+    (InputType::ShowHelp, "show-help"),
+    (InputType::CloseHelp, "close-help"),
+    (InputType::PaintEnd, "paint-end"),
+    (InputType::NextBrush, "next-brush"),
+    (InputType::OpenMenu, "open-menu"),
+    (InputType::CloseMenu, "close-menu"),
+    (InputType::MenuUp, "menu-up"),
+    (InputType::MenuDown, "menu-down"),
+    (InputType::MenuPrevious, "menu-previous"),
+    (InputType::MenuNext, "menu-next"),
+    (InputType::PanUp, "pan-up"),
+    (InputType::PanDown, "pan-down"),
+    (InputType::PanLeft, "pan-left"),
+    (InputType::PanRight, "pan-right"),
+    (InputType::Jump, "jump"),
+    (InputType::Left, "left"),
+    (InputType::Right, "right"),
+];
+
+impl fmt::Display for InputType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputType::PromptDigit(digit) => return write!(f, "prompt-digit:{}", digit),
+            InputType::StoreBookmark(slot) => return write!(f, "store-bookmark:{}", slot),
+            InputType::RestoreBookmark(slot) => return write!(f, "restore-bookmark:{}", slot),
+            InputType::SearchChar(c) => return write!(f, "search-char:{}", c),
+            InputType::EditChar(c) => return write!(f, "edit-char:{}", c),
+            InputType::PaintStart(y, x) => return write!(f, "paint-start:{},{}", y, x),
+            InputType::EraseStart(y, x) => return write!(f, "erase-start:{},{}", y, x),
+            InputType::PaintTo(y, x) => return write!(f, "paint-to:{},{}", y, x),
+            _ => (),
+        }
+        let name: &str = INPUT_NAMES
+            .iter()
+            .find(|(input, _)| input == self)
+            .map_or("continue", |(_, name)| name);
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for InputType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(digit) = s.strip_prefix("prompt-digit:") {
+            return match digit.parse::<u8>() {
+                Ok(digit) if digit < 10 => Ok(InputType::PromptDigit(digit)),
+                _ => Err(anyhow!("invalid digit '{}'", digit)),
+            };
+        }
+        for (prefix, input) in [
+            (
+                "store-bookmark:",
+                InputType::StoreBookmark as fn(u8) -> InputType,
+            ),
+            ("restore-bookmark:", InputType::RestoreBookmark),
+        ] {
+            if let Some(slot) = s.strip_prefix(prefix) {
+                return match slot.parse::<u8>() {
+                    Ok(slot) if (1..=9).contains(&slot) => Ok(input(slot)),
+                    _ => Err(anyhow!("invalid bookmark slot '{}'", slot)),
+                };
+            }
+        }
+        for (prefix, input) in [
+            (
+                "search-char:",
+                InputType::SearchChar as fn(char) -> InputType,
+            ),
+            ("edit-char:", InputType::EditChar),
+        ] {
+            if let Some(c) = s.strip_prefix(prefix) {
+                let mut chars = c.chars();
+                return match (chars.next(), chars.next()) {
+                    (Some(c), None) => Ok(input(c)),
+                    _ => Err(anyhow!("invalid character '{}'", c)),
+                };
+            }
+        }
+        if let Some((name, position)) = s.split_once(':') {
+            let input: fn(u16, u16) -> InputType = match name {
+                "paint-start" => InputType::PaintStart,
+                "erase-start" => InputType::EraseStart,
+                "paint-to" => InputType::PaintTo,
+                _ => return Err(anyhow!("unknown input '{}'", s)),
+            };
+            return match position
+                .split_once(',')
+                .map(|(y, x)| (y.parse(), x.parse()))
+            {
+                Some((Ok(y), Ok(x))) => Ok(input(y, x)),
+                _ => Err(anyhow!("invalid position '{}'", position)),
+            };
+        }
+        INPUT_NAMES
+            .iter()
+            .find(|(_, name)| *name == s)
+            .map(|(input, _)| *input)
+            .ok_or_else(|| anyhow!("unknown input '{}'", s))
+    }
+}
+
+fn empty_grid(window: &Window, state: &State) -> Result<Vec<Vec<Cell>>> {
+    //! Creates a grid of dead cells filling the window, apart from the status
+    //! bar, unless the state sets its size. Grids larger than the window are
+    //! shown through a viewport. A hexagonal grid whose rows wrap around
+    //! leaves out the last row of the window if it would have an odd number.
+    let window_rows: usize = window.get_rows() as usize - state.status_bar.height();
+    let window_cols: usize = window.get_cols() as usize / state.cell_width();
+    let nrows: usize = match state.size.0 {
+        Some(nrows) if state.is_lopsided(nrows) => {
+            return Err(anyhow::anyhow!(
+                "A hexagonal grid on a {} needs an even number of rows, not {}.",
+                state.topology,
+                nrows
+            ))
+        }
+        Some(nrows) => nrows,
+        None if state.is_lopsided(window_rows) => window_rows - 1,
+        None => window_rows,
+    };
+    let ncols: usize = state.size.1.unwrap_or(window_cols);
+    if nrows == 0 || ncols == 0 {
+        return Err(anyhow::anyhow!(
+            "The grid needs at least one row and column."
+        ));
+    }
+    Ok(new_grid(nrows, ncols))
+}
+
+pub fn new_grid(nrows: usize, ncols: usize) -> Vec<Vec<Cell>> {
+    //! Creates a grid of dead cells, without a window.
+    let mut grid: Vec<Vec<Cell>> = vec![];
+    for i in 0..nrows {
+        grid.push(vec![]);
+        for j in 0..ncols {
+            grid[i].push(Cell::new(i, j, false));
+        }
+    }
+    grid
+}
+
+pub fn initialize_from_pattern(
+    window: &mut Window,
+    state: &State,
+    pattern: &Pattern,
+) -> Result<Vec<Vec<Cell>>> {
+    //! Initializes the grid with the given pattern, turned and placed as the
+    //! state says seeds are.
+    let mut grid: Vec<Vec<Cell>> = empty_grid(window, state)?;
+    let pattern: Pattern = prepare_seed(pattern, &grid, state)?;
+    place_pattern(&mut grid, &pattern, state.seed_placement, state.seed_tiling);
+    Ok(grid)
+}
+
+fn prepare_seed(pattern: &Pattern, grid: &[Vec<Cell>], state: &State) -> Result<Pattern> {
+    //! Turns a loaded seed as the state says, and crops it to the grid if
+    //! the state says to. The seed is cropped or checked against the grid
+    //! before it is scaled, so a large scale is refused rather than blowing
+    //! up a pattern that couldn't fit anyway.
+    let (nrows, ncols) = (grid.len(), grid.first().map_or(0, Vec::len));
+    let scale: usize = state.seed_transform.scale.max(1);
+    let pattern: Pattern = pattern.transformed(Transform {
+        scale: 1,
+        ..state.seed_transform
+    });
+    let scaling: Transform = Transform {
+        scale,
+        ..Transform::default()
+    };
+    if state.seed_crop {
+        Ok(pattern
+            .cropped(nrows.div_ceil(scale), ncols.div_ceil(scale))
+            .transformed(scaling)
+            .cropped(nrows, ncols))
+    } else {
+        pattern.check_scaled_fits(scale, nrows, ncols)?;
+        Ok(pattern.transformed(scaling))
+    }
+}
+
+fn place_pattern(
+    grid: &mut [Vec<Cell>],
+    pattern: &Pattern,
+    placement: Placement,
+    tiling: Option<usize>,
+) {
+    //! Places a pattern on the grid, or tiles it across the grid with the
+    //! given spacing.
+    match tiling {
+        Some(spacing) => pattern.place_tiled(grid, placement, spacing),
+        None => pattern.place(grid, placement),
+    }
+}
+
+fn place_seed(grid: &mut [Vec<Cell>], seed: &str, state: &State) -> Result<Metadata> {
+    //! Reads a seed file, optionally followed by `@X,Y`, and places it on the
+    //! grid there, or with the given placement. Returns what the file says
+    //! about the pattern.
+    let (seed_file, at) = pattern::split_seed(seed);
+    let placement: Placement = at.unwrap_or(state.seed_placement);
+    if !Path::new(seed_file).exists() {
+        return Err(anyhow!("Seed file '{}' not found", seed_file));
+    }
+    let pattern: Pattern = prepare_seed(&Pattern::from_file(seed_file)?, grid, state)
+        .and_then(|pattern| {
+            pattern.check_fits(grid.len(), grid[0].len(), placement)?;
+            Ok(pattern)
+        })
+        .with_context(|| format!("Seed file '{}' doesn't fit", seed_file))?;
+    place_pattern(grid, &pattern, placement, state.seed_tiling);
+    Ok(pattern.metadata)
+}
+
+pub fn initialize(
+    window: &mut Window,
+    state: &mut State,
+    num_alive: Option<usize>,
+    seed_files: &[String],
+    placed: &[Placed],
+    soup: &SoupOptions,
+) -> Result<Vec<Vec<Cell>>> {
+    //! Initializes the grid with the given number of alive cells, or seed
+    //! files, each of which may end with `@X,Y` to say where it goes, and
+    //! library patterns. Random cells are generated according to the soup
+    //! options. The metadata of the first seed file or library pattern that
+    //! has any, or the seed of the soup, is kept in the state.
+    let mut metadata: Metadata = Metadata::default();
+    let mut soup_seed: Option<u64> = None;
+    let mut grid: Vec<Vec<Cell>> = empty_grid(window, state)?;
+    let any_exists: bool = seed_files
+        .iter()
+        .any(|seed| Path::new(pattern::split_seed(seed).0).exists());
+
+    match num_alive {
+        // Read the seed files and set the cells to alive based on them.
+        _ if any_exists || !placed.is_empty() => {
+            for seed in seed_files {
+                let seed_metadata: Metadata = place_seed(&mut grid, seed, state)?;
+                if metadata.is_empty() {
+                    metadata = seed_metadata;
+                }
+            }
+            for placed in placed {
+                let pattern: Pattern = placed
+                    .scaled(grid.len(), grid[0].len())
+                    .and_then(|pattern| {
+                        pattern.check_fits(grid.len(), grid[0].len(), placed.placement)?;
+                        Ok(pattern)
+                    })
+                    .with_context(|| {
+                        format!(
+                            "Pattern '{}' doesn't fit",
+                            placed.pattern.metadata.title().unwrap_or_default()
+                        )
+                    })?;
+                pattern.place(&mut grid, placed.placement);
+                if metadata.is_empty() {
+                    metadata = pattern.metadata;
+                }
+            }
+        }
+        // Set the cells to alive randomly based on the number of alive cells.
+        Some(num_alive) => {
+            soup_seed = Some(soup.seed);
+            let mut rng: StdRng = StdRng::seed_from_u64(soup.seed);
+            let alive_cells: HashSet<(usize, usize)> =
+                random_cells(&mut rng, grid.len(), grid[0].len(), num_alive, soup)?;
+            for (i, j) in alive_cells {
+                grid[i][j].set_alive();
+            }
+        }
+        None => return Err(anyhow::anyhow!("Invalid arguments.")),
+    }
+
+    state.set_metadata(metadata);
+    state.soup_seed = soup_seed;
+    Ok(grid)
+}
+
+/// Where the generations are worked out.
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum Engine {
+    /// On the CPU, on bit-packed rows
+    #[default]
+    Cpu,
+    /// On the GPU, with a compute shader; needs the `gpu` feature
+    Gpu,
+}
+
+/// Number of cells born and died in a generation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Delta {
+    /// Cells that came to life
+    pub births: usize,
+    /// Cells that died
+    pub deaths: usize,
+}
+
+/// Steps a grid generation after generation, keeping what it needs between
+/// them.
+#[derive(Debug, Default)]
+pub struct Stepper {
+    /// Grid the next generation is written into before it swaps places with
+    /// the current one
+    buffer: Vec<Vec<Cell>>,
+    /// Which cells changed in the last generation
+    activity: Activity,
+    /// GPU the generations are worked out on, if not the CPU
+    #[cfg(feature = "gpu")]
+    gpu: Option<GpuEngine>,
+    /// Why the GPU was given up on, until the user is told
+    gpu_failure: Option<String>,
+    /// Whether newborn cells take the color of their parents, for the
+    /// Immigration game
+    immigration: bool,
+    /// Whether newborn cells take the lineage of their parents
+    lineage: bool,
+    /// Rule the generations follow
+    rule: Rule,
+    /// Generation of the grid being stepped, which block rules need to know
+    /// where the blocks start
+    generation: u64,
+    /// Random flips of the outcomes of the rule, if it is noisy
+    noise: Option<Noise>,
+}
+
+/// Flips each outcome of a rule, whether a cell is born, survives or dies,
+/// with some probability.
+#[derive(Debug, Clone)]
+struct Noise {
+    /// Probability of each outcome being flipped
+    probability: f64,
+    /// Random number generator deciding which outcomes are flipped, seeded
+    /// so that noisy runs can be repeated
+    rng: StdRng,
+}
+
+impl Stepper {
+    pub fn new(engine: Engine) -> Result<Stepper> {
+        //! Creates a stepper working out the generations on the given engine.
+        let stepper: Stepper = Stepper::default();
+        match engine {
+            Engine::Cpu => Ok(stepper),
+            #[cfg(feature = "gpu")]
+            Engine::Gpu => Ok(Stepper {
+                gpu: Some(GpuEngine::new()?),
+                ..stepper
+            }),
+            #[cfg(not(feature = "gpu"))]
+            Engine::Gpu => Err(anyhow!(
+                "This build has no GPU engine; rebuild with `cargo build --features gpu`"
+            )),
+        }
+    }
+
+    pub fn set_immigration(&mut self, immigration: bool) {
+        self.immigration = immigration;
+    }
+
+    pub fn set_lineage(&mut self, lineage: bool) {
+        self.lineage = lineage;
+    }
+
+    pub fn get_rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+    }
+
+    pub fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
+    pub fn get_noise(&self) -> f64 {
+        self.noise.as_ref().map_or(0.0, |noise| noise.probability)
+    }
+
+    pub fn set_noise(&mut self, probability: f64, seed: u64) {
+        //! Makes the rule noisy, flipping each of its outcomes with the given
+        //! probability, or exact again with a probability of 0.
+        self.noise = (probability > 0.0).then(|| Noise {
+            probability,
+            rng: StdRng::seed_from_u64(seed),
+        });
+    }
+
+    pub fn reseed_noise(&mut self) -> u64 {
+        //! Reseeds the random flips of a noisy rule with a seed drawn from
+        //! them, and returns it, so that a run started over from that seed
+        //! flips the same outcomes as this one from now on.
+        let Some(noise) = &mut self.noise else {
+            return 0;
+        };
+        let seed: u64 = noise.rng.gen();
+        noise.rng = StdRng::seed_from_u64(seed);
+        seed
+    }
+
+    pub fn step(&mut self, grid: &mut Vec<Vec<Cell>>, topology: Topology) -> Delta {
+        //! Advances the grid by one generation, returning how many cells were
+        //! born and died. The next frame is written
+        //! into the buffer, which then swaps places with the grid, so that
+        //! stepping over and over reuses the same two grids instead of
+        //! allocating new ones. Which cells live is worked out on packed rows,
+        //! around the cells that changed last; the cells themselves only keep
+        //! track of their ages.
+        let gpu_alive: Option<BitGrid> = self.gpu_generations(grid, topology, 1);
+        let buffer: &mut Vec<Vec<Cell>> = &mut self.buffer;
+        if buffer.len() != grid.len() || buffer.first().map(Vec::len) != grid.first().map(Vec::len)
+        {
+            *buffer = grid.clone();
+        }
+
+        // rules reaching farther than one cell out count every cell's
+        // neighbors instead, and block rules replace whole blocks
+        let neighborhood: Neighborhood = self.rule.get_neighborhood();
+        let counted: Option<BitGrid> = match self.rule.get_blocks() {
+            Some(blocks) => Some(margolus::next_generation(
+                grid,
+                topology,
+                blocks,
+                self.generation,
+            )),
+            None => (!self.rule.has_table()).then(|| {
+                BitGrid::from_fn(grid.len(), grid[0].len(), |i, j| {
+                    let cell: &Cell = &grid[i][j];
+                    let neighbors: usize = cell.count_alive_neighbors(grid, topology, neighborhood);
+                    self.rule.is_alive_with(cell.is_alive(), neighbors)
+                })
+            }),
+        };
+        let alive: &BitGrid = match gpu_alive.as_ref().or(counted.as_ref()) {
+            Some(alive) => alive,
+            None => self.activity.next_generation(grid, topology, &self.rule),
+        };
+        let mut delta: Delta = Delta::default();
+        for row in grid.iter() {
+            for cell in row {
+                let mut next: Cell = *cell;
+                let mut alive_next: bool = alive.is_alive(cell.x, cell.y);
+                if let Some(noise) = self.noise.as_mut() {
+                    alive_next ^= noise.rng.gen_bool(noise.probability);
+                }
+                if cell.is_alive() {
+                    if !alive_next {
+                        next.set_dead();
+                        delta.deaths += 1;
+                    } else {
+                        next.age = cell.age.saturating_add(1);
+                    }
+                } else if alive_next {
+                    next.set_alive();
+                    delta.births += 1;
+                    if self.immigration {
+                        next.color = cell.parents_color(grid, topology, neighborhood);
+                    }
+                    if self.lineage {
+                        next.lineage = lineage::parents_lineage(cell, grid, topology, neighborhood);
+                    }
+                } else if let Some(since_death) = cell.since_death {
+                    next.since_death = Some(since_death.saturating_add(1));
+                }
+                buffer[cell.x][cell.y] = next;
+            }
+        }
+        std::mem::swap(grid, buffer);
+        self.generation += 1;
+        delta
+    }
+
+    pub fn step_many(
+        &mut self,
+        grid: &mut [Vec<Cell>],
+        topology: Topology,
+        generations: usize,
+    ) -> Option<Delta> {
+        //! Advances the grid by a number of generations at once on the GPU,
+        //! returning how many cells were born and died between the first and
+        //! the last, or `None` if they have to be stepped one at a time. The
+        //! cells only age by the number of generations, as the ones in between
+        //! are never seen.
+        if self.noise.is_some() || self.immigration || self.lineage {
+            return None;
+        }
+        let alive: BitGrid = self.gpu_generations(grid, topology, generations)?;
+        let mut delta: Delta = Delta::default();
+        for cell in grid.iter_mut().flatten() {
+            match (cell.is_alive(), alive.is_alive(cell.x, cell.y)) {
+                (true, true) => cell.age = cell.age.saturating_add(generations as u32),
+                (true, false) => {
+                    cell.set_dead();
+                    delta.deaths += 1;
+                }
+                (false, true) => {
+                    cell.set_alive();
+                    delta.births += 1;
+                }
+                (false, false) => {
+                    cell.since_death = cell
+                        .since_death
+                        .map(|since_death| since_death.saturating_add(generations as u32));
+                }
+            }
+        }
+        self.generation += generations as u64;
+        Some(delta)
+    }
+
+    #[cfg(feature = "gpu")]
+    fn gpu_generations(
+        &mut self,
+        grid: &[Vec<Cell>],
+        topology: Topology,
+        generations: usize,
+    ) -> Option<BitGrid> {
+        //! Works out which cells are alive the given number of generations
+        //! later on the GPU, if there is one and it knows the rule, which only
+        //! Life's is.
+        let gpu: &mut GpuEngine = self.gpu.as_mut().filter(|_| self.rule.is_life())?;
+        match gpu.next_generations(grid, topology, generations) {
+            Ok(alive) => Some(alive),
+            // a GPU that fails is given up on, and the CPU takes over
+            Err(e) => {
+                self.gpu = None;
+                self.gpu_failure = Some(e.to_string());
+                None
+            }
+        }
+    }
+
+    #[cfg(not(feature = "gpu"))]
+    fn gpu_generations(
+        &mut self,
+        _grid: &[Vec<Cell>],
+        _topology: Topology,
+        _generations: usize,
+    ) -> Option<BitGrid> {
+        None
+    }
+
+    pub fn take_gpu_failure(&mut self) -> Option<String> {
+        //! Returns why the GPU was given up on, once.
+        self.gpu_failure.take()
+    }
+}
+
+fn edit_region<F: FnMut() -> bool>(
+    grid: &mut [Vec<Cell>],
+    top_left: (usize, usize),
+    bottom_right: (usize, usize),
+    mut alive: F,
+) {
+    //! Sets every cell within a rectangle of the grid, given by its inclusive
+    //! corners, alive or dead as decided by `alive`.
+    let rows = grid.iter_mut().take(bottom_right.0 + 1).skip(top_left.0);
+    for row in rows {
+        for cell in row.iter_mut().take(bottom_right.1 + 1).skip(top_left.1) {
+            if alive() {
+                if !cell.is_alive() {
+                    cell.set_alive();
+                }
+            } else {
+                cell.set_dead();
+            }
+        }
+    }
+}
+
+fn apply_grid_input(grid: &mut [Vec<Cell>], input: InputType, state: &mut State) -> Result<()> {
+    //! Handles the input that needs to know about the grid, like moving the
+    //! inspect cursor, editing the grid and writing the selected region to a file.
+    state.move_cursor(input, grid.len(), grid[0].len());
+    match input {
+        InputType::StepBack => {
+            if !state.is_inspecting() && !state.paused {
+                state.set_message(Some("Pause with p to step back".to_string()));
+            } else if let Some((generation, previous)) = state.history.pop() {
+                grid.clone_from_slice(&previous);
+                state.generation = generation;
+                // the edits were made to a later frame
+                state.undo.clear();
+                if let Some(similarity) = &mut state.similarity {
+                    similarity.seek(state.generation);
+                }
+            } else {
+                state.set_message(Some("No earlier generations kept".to_string()));
+            }
+            return Ok(());
+        }
+        InputType::StoreBookmark(slot) => {
+            state
+                .bookmarks
+                .insert(slot, (state.generation, grid.to_vec()));
+            state.set_message(Some(format!(
+                "Stored generation {} in bookmark {}",
+                state.generation, slot
+            )));
+            return Ok(());
+        }
+        InputType::RestoreBookmark(slot) => {
+            match state.bookmarks.get(&slot) {
+                Some((generation, bookmark))
+                    if bookmark.len() == grid.len() && bookmark[0].len() == grid[0].len() =>
+                {
+                    grid.clone_from_slice(bookmark);
+                    state.generation = *generation;
+                    // the edits and earlier generations belong to the
+                    // run the bookmark replaced, whose soup may not be the
+                    // one the bookmark came from
+                    state.undo.clear();
+                    state.history.clear();
+                    state.mark_edited();
+                    state.dirty = true;
+                    if let Some(similarity) = &mut state.similarity {
+                        similarity.seek(state.generation);
+                    }
+                    state.set_message(Some(format!(
+                        "Restored generation {} from bookmark {}",
+                        state.generation, slot
+                    )));
+                }
+                Some(_) => state.set_message(Some(format!(
+                    "Bookmark {} was stored on a grid of another size",
+                    slot
+                ))),
+                None => state.set_message(Some(format!(
+                    "Nothing in bookmark {}; store the grid in it with Shift+{}",
+                    slot, slot
+                ))),
+            }
+            return Ok(());
+        }
+        InputType::CopySnapshot => {
+            let mut pattern: Pattern = Pattern {
+                metadata: state.metadata.clone(),
+                ..Pattern::from_region(grid, (0, 0), (grid.len() - 1, grid[0].len() - 1))
+            };
+            pattern
+                .metadata
+                .comments
+                .push(format!("Generation {}", state.generation));
+            let snapshot: String = pattern.to_rle(&state.get_rule().to_string());
+            let message: String = match clipboard::copy(&snapshot) {
+                Ok(tool) => format!("Copied generation {} with {}", state.generation, tool),
+                Err(e) => format!("{:#}", e),
+            };
+            state.set_message(Some(message));
+            return Ok(());
+        }
+        InputType::NextBrush => {
+            state.set_message(Some(format!("Brush: {}", state.brush)));
+            return Ok(());
+        }
+        InputType::ToggleRain => {
+            let message: String = if state.raining {
+                format!("Raining every {} generations", state.rain_interval)
+            } else {
+                "Rain stopped".to_string()
+            };
+            state.set_message(Some(message));
+            return Ok(());
+        }
+        InputType::ToggleWrap => {
+            let message: String = match state.topology {
+                Topology::Plane => "Edges are walls: nothing lies beyond them".to_string(),
+                topology => format!("Edges wrap around again, as on a {}", topology),
+            };
+            state.set_message(Some(message));
+            return Ok(());
+        }
+        InputType::Undo => {
+            if !state.undo.undo(grid) {
+                state.set_message(Some("Nothing to undo".to_string()));
+            }
+            state.origin = None;
+            return Ok(());
+        }
+        InputType::Redo => {
+            if !state.undo.redo(grid) {
+                state.set_message(Some("Nothing to redo".to_string()));
+            }
+            state.origin = None;
+            return Ok(());
+        }
+        InputType::ToggleCell => {
+            let Some(cursor) = state.cursor else {
+                state.set_message(Some(
+                    "No cursor: press i, then move to the cell to toggle".to_string(),
+                ));
+                return Ok(());
+            };
+            state.undo.edit(grid, cursor, cursor, |grid| {
+                let cell: &mut Cell = &mut grid[cursor.0][cursor.1];
+                if cell.is_alive() {
+                    cell.set_dead();
+                } else {
+                    cell.set_alive();
+                }
+            });
+        }
+        InputType::ClearGrid => {
+            let (nrows, ncols) = (grid.len(), grid[0].len());
+            state
+                .undo
+                .edit(grid, (0, 0), (nrows - 1, ncols - 1), |grid| {
+                    for cell in grid.iter_mut().flatten() {
+                        cell.set_dead();
+                    }
+                });
+            state.paused = true;
+            state.set_message(Some(
+                "Cleared the grid and paused; press i to draw, u to undo".to_string(),
+            ));
+        }
+        InputType::AddNoise => {
+            let (nrows, ncols) = (grid.len(), grid[0].len());
+            let born: Vec<(usize, usize)> = state.random_births(grid, state.noise_count());
+            let count: usize = born.len();
+            state
+                .undo
+                .edit(grid, (0, 0), (nrows - 1, ncols - 1), |grid| {
+                    for (i, j) in born {
+                        grid[i][j].set_alive();
+                    }
+                });
+            state.set_message(Some(format!("Added {} random cells", count)));
+        }
+        InputType::WriteSelection
+        | InputType::ClearSelection
+        | InputType::FillSelection
+        | InputType::RandomizeSelection => {
+            let Some((top_left, bottom_right)) = state.selection() else {
+                state.set_message(Some(
+                    "Nothing selected: press i, then v to select".to_string(),
+                ));
+                return Ok(());
+            };
+            let density: f64 = state.density;
+            let mut rng = rand::thread_rng();
+            let mut fill = |alive: &mut dyn FnMut() -> bool| {
+                state.undo.edit(grid, top_left, bottom_right, |grid| {
+                    edit_region(grid, top_left, bottom_right, alive)
+                })
+            };
+            match input {
+                InputType::ClearSelection => fill(&mut || false),
+                InputType::FillSelection => fill(&mut || true),
+                InputType::RandomizeSelection => fill(&mut || rng.gen_bool(density)),
+                _ => {
+                    write_selection(grid, top_left, bottom_right, state);
+                    return Ok(());
+                }
+            }
+        }
+        _ => return Ok(()),
+    }
+    state.mark_edited();
+    Ok(())
+}
+
+fn apply_paint_input(window: &Window, grid: &mut [Vec<Cell>], input: InputType, state: &mut State) {
+    //! Paints the cells the mouse was dragged over with the brush, from the
+    //! last cell painted.
+    let (alive, y, x) = match input {
+        InputType::PaintStart(y, x) => (true, y, x),
+        InputType::EraseStart(y, x) => (false, y, x),
+        InputType::PaintTo(y, x) => match state.painting {
+            Some(stroke) => (stroke.alive, y, x),
+            None => return,
+        },
+        InputType::PaintEnd => {
+            state.painting = None;
+            return;
+        }
+        _ => return,
+    };
+    // the cell under the pointer, if it is on the part of the grid in view,
+    // which cells shrunk to blocks are not
+    if is_zoomed_out(window, grid, state) {
+        return;
+    }
+    let (top, left) = window.position();
+    let row: i32 = y as i32 - top;
+    // undo the shift of odd rows of hexagonal grids
+    let width: i32 = state.cell_width() as i32;
+    let shift: i32 = if state.is_hexagonal() && (row + state.viewport.0 as i32) % 2 == 1 {
+        width / 2
+    } else {
+        0
+    };
+    let col: i32 = (x as i32 - left - shift).div_euclid(width);
+    let (nrows, ncols) = visible_size(window, grid, state);
+    if row < 0 || col < 0 || row as usize >= nrows || col as usize >= ncols {
+        return;
+    }
+    let cell: (usize, usize) = (
+        row as usize + state.viewport.0,
+        col as usize + state.viewport.1,
+    );
+    let continuing: bool = matches!(input, InputType::PaintTo(..));
+    let stroke: Stroke = match state.painting.filter(|_| continuing) {
+        Some(stroke) => stroke,
+        None => Stroke {
+            alive,
+            start: cell,
+            last: cell,
+        },
+    };
+    state.painting = Some(Stroke {
+        last: cell,
+        ..stroke
+    });
+
+    let path: Vec<(usize, usize)> = if state.brush == Brush::Line {
+        // the line is drawn again from where the stroke started
+        if continuing {
+            state.undo.undo(grid);
+        }
+        paint::line(stroke.start, cell)
+    } else {
+        paint::line(stroke.last, cell)
+    };
+    let size: (usize, usize) = (grid.len(), grid[0].len());
+    let cells: Vec<(usize, usize)> =
+        paint::brush_cells(state.brush, &path, size, state.spray_density);
+    let (Some(top), Some(bottom)) = (
+        cells.iter().map(|(i, _)| *i).min(),
+        cells.iter().map(|(i, _)| *i).max(),
+    ) else {
+        return;
+    };
+    let left: usize = cells.iter().map(|(_, j)| *j).min().unwrap_or(0);
+    let right: usize = cells.iter().map(|(_, j)| *j).max().unwrap_or(0);
+    let paint = |grid: &mut [Vec<Cell>]| {
+        for (i, j) in cells {
+            let cell: &mut Cell = &mut grid[i][j];
+            if !alive {
+                cell.set_dead();
+            } else if !cell.is_alive() {
+                cell.set_alive();
+            }
+        }
+    };
+    // a whole stroke is undone at once
+    if continuing && state.brush != Brush::Line {
+        state
+            .undo
+            .continue_edit(grid, (top, left), (bottom, right), paint);
+    } else {
+        state.undo.edit(grid, (top, left), (bottom, right), paint);
+    }
+    state.mark_edited();
+}
+
+fn write_selection(
+    grid: &[Vec<Cell>],
+    top_left: (usize, usize),
+    bottom_right: (usize, usize),
+    state: &mut State,
+) {
+    //! Writes the selected region to a new file in the current directory,
+    //! with what is known about the pattern the grid started from.
+    let pattern: Pattern = Pattern {
+        metadata: state.metadata.clone(),
+        ..Pattern::from_region(grid, top_left, bottom_right)
+    };
+    let timestamp: u64 = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path: String = format!(
+        "region-{}-{}.{}",
+        state.generation,
+        timestamp,
+        state.export_format.extension()
+    );
+    let rule: String = state.get_rule().to_string();
+    let message: String = match pattern.write(&path, state.export_format, &rule) {
+        Ok(()) => {
+            state.emit(Event::Saved {
+                what: "region",
+                path: path.clone(),
+            });
+            format!("Saved {}x{} region to {}", pattern.cols, pattern.rows, path)
+        }
+        Err(e) => format!("{:#}", e),
+    };
+    state.set_message(Some(message));
+}
+
+/// Number of generations between updates of the fast-forward progress indicator.
+const SKIP_PROGRESS_INTERVAL: u64 = 100;
+
+pub fn fast_forward(
+    window: &Window,
+    grid: &mut Vec<Vec<Cell>>,
+    state: &mut State,
+    generations: u64,
+) -> Result<()> {
+    //! Advances the grid many generations as fast as possible, only drawing a
+    //! progress indicator in place of the status bar along the way.
+    let nrows: usize = visible_size(window, grid, state).0;
+    let row: i32 = (nrows + state.status_bar.height()).saturating_sub(1) as i32;
+    let width: usize = (window.get_cols() as usize).saturating_sub(1);
+    for done in 0..generations {
+        if done % SKIP_PROGRESS_INTERVAL == 0 {
+            let progress: String = format!("Skipping: {}/{} generations", done, generations);
+            window.print(
+                0,
+                row,
+                &format!("{:width$}", fit_to_width(&progress, width), width = width),
+                state.theme.status.as_ref(),
+            )?;
+            window.refresh();
+        }
+        step_generation(grid, state);
+    }
+    state.undo.clear();
+    if let Some(similarity) = &mut state.similarity {
+        similarity.seek(state.generation);
+    }
+    state.dirty = true;
+    Ok(())
+}
+
+fn jump(window: &Window, grid: &mut Vec<Vec<Cell>>, state: &mut State) -> Result<()> {
+    //! Jumps to the generation typed into the prompt: forward by simulating,
+    //! backward by replaying from the origin.
+    let Some(target) = state
+        .prompt
+        .take()
+        .and_then(|prompt| prompt.parse::<u64>().ok())
+    else {
+        return Ok(());
+    };
+    state.dirty = true;
+    if target >= state.generation {
+        state.history.push(state.generation, grid);
+        return fast_forward(window, grid, state, target - state.generation);
+    }
+    let Some((origin_generation, origin)) = state.origin.clone() else {
+        return Ok(());
+    };
+    if target < origin_generation {
+        state.set_message(Some(format!(
+            "Can't jump back before generation {}, where the grid was last changed",
+            origin_generation
+        )));
+        return Ok(());
+    }
+    state.history.push(state.generation, grid);
+    state.generation = origin_generation;
+    *grid = origin;
+    fast_forward(window, grid, state, target - origin_generation)
+}
+
+fn step_generation(grid: &mut Vec<Vec<Cell>>, state: &mut State) {
+    //! Advances the grid one generation, letting it rain if it is due.
+    state.stepper.set_generation(state.generation);
+    let delta: Delta = state.stepper.step(grid, state.topology);
+    state.stats.record(delta);
+    state.generation += 1;
+    if let Some(log) = &mut state.csv_log {
+        let population: usize = grid.iter().flatten().filter(|cell| cell.is_alive()).count();
+        let cells: usize = grid.len() * grid[0].len();
+        if let Err(e) = log.record(state.generation, population, delta, cells) {
+            state.csv_log = None;
+            state.set_message(Some(format!("{}; stopped logging", e)));
+        }
+    }
+    if state.has_events() {
+        let population: usize = grid.iter().flatten().filter(|cell| cell.is_alive()).count();
+        state.emit(Event::Generation {
+            generation: state.generation,
+            population,
+        });
+    }
+    if state.raining && state.generation.is_multiple_of(state.rain_interval) {
+        for (i, j) in state.random_births(grid, RAIN_CELLS) {
+            grid[i][j].set_alive();
+        }
+        // the rain can't be replayed
+        state.mark_edited();
+    }
+}
+
+pub fn advance(grid: &mut Vec<Vec<Cell>>, state: &mut State, generations: usize) {
+    //! Advances the simulation by a number of generations.
+    state.history.push(state.generation, grid);
+    // unless something looks at every generation, the GPU works out all of
+    // them at once, and only the last one is read back
+    let mut batched: Option<Delta> = None;
+    if generations > 1 && !state.watches_generations() {
+        state.stepper.set_generation(state.generation);
+        batched = state.stepper.step_many(grid, state.topology, generations);
+    }
+    match batched {
+        Some(delta) => {
+            state.stats.record(delta);
+            state.generation += generations as u64;
+        }
+        None => {
+            for _ in 0..generations {
+                step_generation(grid, state);
+            }
+        }
+    }
+    if let Some(failure) = state.stepper.take_gpu_failure() {
+        state.set_message(Some(format!("{}; the CPU took over", failure)));
+    }
+    // edits can only be undone until the simulation moves on
+    state.undo.clear();
+    if let Some(similarity) = &mut state.similarity {
+        similarity.seek(state.generation);
+    }
+}
+
+fn run_command(grid: &mut Vec<Vec<Cell>>, state: &mut State, command: Command) -> Result<String> {
+    //! Runs a command from the control socket that isn't an input, returning
+    //! its output.
+    match command {
+        Command::Input(_) => Ok(String::new()),
+        Command::SetRule(rule) => {
+            state.stepper.set_rule(rule.parse()?);
+            state.mark_edited();
+            Ok(String::new())
+        }
+        Command::Load(path) => {
+            let pattern: Pattern = prepare_seed(&Pattern::from_file(&path)?, grid, state)?;
+            pattern.check_fits(grid.len(), grid[0].len(), state.seed_placement)?;
+            *grid = new_grid(grid.len(), grid[0].len());
+            place_pattern(grid, &pattern, state.seed_placement, state.seed_tiling);
+            state.set_metadata(pattern.metadata);
+            state.reset_run();
+            Ok(String::new())
+        }
+        Command::Dump => {
+            Ok(
+                Pattern::from_region(grid, (0, 0), (grid.len() - 1, grid[0].len() - 1))
+                    .to_seed_text(),
+            )
+        }
+    }
+}
+
+fn run_script<F>(grid: &mut [Vec<Cell>], state: &mut State, hook: F) -> bool
+where
+    F: FnOnce(&mut Script, &mut [Vec<Cell>]) -> Result<bool>,
+{
+    //! Runs a hook of the user's script, if there is one, and takes the
+    //! actions it asked for. Returns whether the script asked to stop. A
+    //! script that fails is disabled, with its error shown in the status bar.
+    let Some(script) = &mut state.script else {
+        return false;
+    };
+    let result = hook(script, grid).map(|changed| (changed, script.take_actions()));
+    match result {
+        Ok((changed, actions)) => {
+            if changed {
+                state.mark_edited();
+            }
+            if let Some(message) = actions.message {
+                state.set_message(Some(message));
+            }
+            if let Some(paused) = actions.paused {
+                state.set_paused(paused);
+            }
+            actions.stop
+        }
+        Err(e) => {
+            state.script = None;
+            state.set_message(Some(format!("{} (script disabled)", e)));
+            false
+        }
+    }
+}
+
+/// Longest keys are waited for at a time between frames, so that macros and
+/// commands from the control socket are served while waiting.
+const INPUT_POLL: Duration = Duration::from_millis(20);
+
+/// Number of late frames caught up on at once. A run further behind than
+/// that starts its schedule over, rather than racing to catch up.
+const MAX_FRAMES_BEHIND: u32 = 5;
+
+/// Number of frames worked out for every one drawn in turbo mode.
+const TURBO_FRAMES_PER_DRAW: u32 = 25;
+
+/// Highest frame rate that can be aimed for with `--fps`.
+pub const MAX_FPS: u32 = 1000;
+
+pub fn run_frame(
+    window: &mut Window,
+    grid: &mut Vec<Vec<Cell>>,
+    input_handler: &mut InputHandler,
+    state: &mut State,
+) -> Result<InputType> {
+    //! Runs a single loop of the game, drawing the grid, calculating the next
+    //! frame in place once it is due, and getting input from the user. Keys
+    //! are read as they are typed while waiting for the next frame; the loop
+    //! ends early for those that do something, without bringing the next
+    //! frame forward.
+    //!
+    //! Frames are due one frame duration after the last was due, however
+    //! long working it out took. Frames that are late are caught up on by
+    //! advancing as many at once.
+    let now: Instant = Instant::now();
+    let duration: Duration = state.frame_duration();
+    let frames_due: u32 = match state.next_frame {
+        _ if state.is_turbo() => 1,
+        None => 1,
+        Some(next) if now < next => 0,
+        Some(next) => 1 + ((now - next).as_nanos() / duration.as_nanos().max(1)) as u32,
+    };
+    let frame_due: bool = frames_due > 0;
+    if frame_due {
+        // too far behind, the schedule starts over instead
+        state.next_frame = match state.next_frame {
+            Some(next) if frames_due <= MAX_FRAMES_BEHIND => Some(next + duration * frames_due),
+            _ => Some(now + duration),
+        };
+        if state.origin.is_none() {
+            state.origin = Some((state.generation, grid.to_vec()));
+        }
+        state.heatmap.record(grid);
+        if let Some(similarity) = &mut state.similarity {
+            similarity.record(grid, state.generation);
+        }
+
+        let population: usize = grid.iter().flatten().filter(|cell| cell.is_alive()).count();
+        if let Some(alarm) = state.alarms.check(population) {
+            beep();
+            if state.alarms.action == AlarmAction::Pause {
+                // in kiosk mode, no one is there to resume the run, so it
+                // starts over instead
+                match &mut state.kiosk {
+                    Some(kiosk) => kiosk.raise_alarm(),
+                    None => state.paused = true,
+                }
+            }
+            state.set_message(Some(alarm));
+        }
+    }
+
+    let visible: (usize, usize) = visible_size(window, grid, state);
+    state.scroll_viewport(visible, grid.len(), grid[0].len());
+
+    // in turbo mode, only every few frames are drawn, unless an input changed
+    // what is shown
+    state.frames_undrawn += 1;
+    let drawing: bool =
+        !state.is_turbo() || state.dirty || state.frames_undrawn >= TURBO_FRAMES_PER_DRAW;
+
+    // in eco mode, skip redrawing a screen that would look exactly the same,
+    // apart from the status bar, which still counts the generations
+    let redrawing: bool = !state.power.is_eco()
+        || state.dirty
+        || state.shows_history()
+        || grid
+            .iter()
+            .flatten()
+            .map(|cell| cell.is_alive())
+            .ne(state.last_drawn.iter().copied());
+    if drawing && !redrawing {
+        let status_shown: bool = state.status_bar.is_visible()
+            && state.prompt.is_none()
+            && state.rule_editor.is_none()
+            && state.message.is_none()
+            && state.cursor.is_none()
+            && !state.show_code;
+        if status_shown {
+            draw_status_bar(window, grid, state)?;
+            window.refresh();
+        }
+    }
+    if drawing && redrawing {
+        state.frames_undrawn = 0;
+        // neighbors are counted for the frames drawn, not every generation
+        if state.neighbor_glyphs {
+            count_neighbors(grid, state.topology, state.get_rule().get_neighborhood());
+        }
+        window.erase();
+        draw(window, grid, state)?;
+        window.refresh();
+        // zoomed out, the whole grid is in view, and the minimap isn't needed
+        let in_view: (usize, usize) = if state.zoomed_out {
+            (grid.len(), grid[0].len())
+        } else {
+            visible
+        };
+        state
+            .minimap
+            .draw(window, grid, state.viewport, in_view, &state.theme)?;
+        state.stats.draw(window, &state.theme)?;
+        if state.help {
+            let settings: String = format!(
+                "Rule: {}, Topology: {}, {}, Step: {}",
+                state.get_rule(),
+                state.topology,
+                state.speed(),
+                state.step
+            );
+            help::draw(window, &settings, &state.theme)?;
+        }
+        if let Some((menu, _)) = &state.menu {
+            menu.draw(window, &state.theme)?;
+        }
+        if let Some(cast) = &mut state.cast {
+            if let Err(e) = cast.frame(window) {
+                state.cast = None;
+                state.set_message(Some(format!("{}; stopped recording", e)));
+            }
+        }
+        state.last_drawn.clear();
+        state
+            .last_drawn
+            .extend(grid.iter().flatten().map(|cell| cell.is_alive()));
+        state.dirty = false;
+    }
+
+    let advancing: bool = frame_due
+        && !(state.is_inspecting()
+            || state.paused
+            || state.prompt.is_some()
+            || state.rule_editor.is_some()
+            || state.help);
+    if advancing {
+        let frames: usize = frames_due.min(MAX_FRAMES_BEHIND) as usize;
+        advance(grid, state, state.generations_per_frame() * frames);
+    }
+
+    let mut stop: bool = false;
+    if advancing {
+        let generation: u64 = state.generation;
+        stop |= run_script(grid, state, |script, grid| {
+            script.on_generation(grid, generation)
+        });
+    }
+
+    // wait for the next frame, a little at a time, so that macros and
+    // commands from the control socket are served on time too
+    let mut input: InputType;
+    loop {
+        /* commands from the control socket that aren't inputs */
+        input_handler.poll_control();
+        while let Some((client, command)) = input_handler.next_request() {
+            let result: Result<String> = run_command(grid, state, command);
+            input_handler.reply(client, &result);
+        }
+
+        let wait: Duration = state.next_frame.map_or(Duration::ZERO, |next| {
+            next.saturating_duration_since(Instant::now())
+        });
+        timeout(wait.min(INPUT_POLL).as_millis() as i32);
+        input = input_handler.handle_input(state)?;
+        if let Some(key) = input_handler.get_key() {
+            stop |= run_script(grid, state, |script, grid| script.on_key(grid, key));
+        }
+        if input != InputType::Continue || stop || wait <= INPUT_POLL {
+            break;
+        }
+    }
+    if stop {
+        input = InputType::Quit;
+    }
+    if input != InputType::Continue {
+        state.emit(Event::Input {
+            name: input.to_string(),
+        });
+    }
+    apply_grid_input(grid, input, state)?;
+    apply_paint_input(window, grid, input, state);
+    if input == InputType::StepForward {
+        advance(grid, state, 1);
+    }
+    if input == InputType::FastForward {
+        state.history.push(state.generation, grid);
+        fast_forward(window, grid, state, state.skip)?;
+    }
+    if input == InputType::Jump {
+        jump(window, grid, state)?;
+    }
+    Ok(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_read_back_as_they_are_written() {
+        let mut inputs: Vec<InputType> = INPUT_NAMES.iter().map(|(input, _)| *input).collect();
+        inputs.extend([
+            InputType::PromptDigit(0),
+            InputType::PromptDigit(9),
+            InputType::PaintStart(3, 140),
+            InputType::EraseStart(0, 0),
+            InputType::PaintTo(65535, 12),
+            InputType::SearchChar('g'),
+            InputType::SearchChar(':'),
+            InputType::StoreBookmark(1),
+            InputType::RestoreBookmark(9),
+            InputType::EditChar(' '),
+            InputType::EditChar('/'),
+        ]);
+        for input in inputs {
+            let name: String = input.to_string();
+            assert_eq!(name.parse::<InputType>().unwrap(), input, "{}", name);
+        }
+    }
+
+    #[test]
+    fn input_names_are_unique() {
+        for (i, (_, name)) in INPUT_NAMES.iter().enumerate() {
+            assert!(
+                INPUT_NAMES[i + 1..].iter().all(|(_, other)| other != name),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_inputs_are_refused() {
+        for name in [
+            "",
+            "fly",
+            "prompt-digit:10",
+            "prompt-digit:x",
+            "paint-to:3",
+            "paint-to:-1,2",
+            "paint-sideways:1,2",
+            "search-char:ab",
+            "store-bookmark:0",
+            "restore-bookmark:10",
+            "edit-char:",
+        ] {
+            assert!(name.parse::<InputType>().is_err(), "{}", name);
+        }
+    }
 }
@@ -0,0 +1,58 @@
+//! This module contains the CSV log of a run, with a row of metrics for every
+//! generation worked out, for looking into how soups evolve in a
+//! spreadsheet afterwards.
+
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use super::conway::Delta;
+
+/// Names of the columns, in the order they are written.
+const HEADER: &str = "generation,population,births,deaths,activity";
+
+/// A CSV log being written.
+pub struct CsvLog {
+    /// File the log is written to
+    file: BufWriter<File>,
+    /// Path of the file, for errors
+    path: String,
+}
+
+impl CsvLog {
+    pub fn create(path: &str) -> Result<CsvLog> {
+        //! Creates the log file and writes the header.
+        let file: File = File::create(path)
+            .map_err(|e| anyhow!("Failed to create CSV log '{}': {}", path, e))?;
+        let mut log: CsvLog = CsvLog {
+            file: BufWriter::new(file),
+            path: path.to_string(),
+        };
+        log.write_line(HEADER)?;
+        Ok(log)
+    }
+
+    pub fn record(
+        &mut self,
+        generation: u64,
+        population: usize,
+        delta: Delta,
+        cells: usize,
+    ) -> Result<()> {
+        //! Writes the row of a generation that has just been worked out on a
+        //! grid of `cells` cells. The activity is the fraction of the cells
+        //! that changed.
+        let activity: f64 = (delta.births + delta.deaths) as f64 / cells.max(1) as f64;
+        let row: String = format!(
+            "{},{},{},{},{:.6}",
+            generation, population, delta.births, delta.deaths, activity
+        );
+        self.write_line(&row)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        writeln!(self.file, "{}", line)
+            .and_then(|_| self.file.flush())
+            .map_err(|e| anyhow!("Failed to write CSV log '{}': {}", self.path, e))
+    }
+}
@@ -1,38 +1,160 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use ncurses::*;
+use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
-use crate::conway::{initialize, run_frame};
+use crate::conway::{initialize, new_grid, run_frame};
 
-use super::conway::{Cell, InputHandler, InputType};
-use super::window::{Color, ColorPair, Window};
+use super::cast::Cast;
+use super::conway::{Cell, InputHandler, InputType, State, Stepper};
+use super::kiosk::Stagnation;
+use super::pattern::Metadata;
+use super::script::Script;
+use super::stability::{CycleDetector, Report};
+use super::status::fit_to_width;
+use super::terminal::Screen;
+use super::theme::Theme;
+use super::window::Window;
 use super::Cli;
 
-fn collect_seed_files(sidebar_width: usize) -> Result<Vec<String>> {
-    let files = fs::read_dir("seeds")?;
+/// Rows at the bottom of the sidebar showing the current sample's name,
+/// author and comments and how its run is going, with a blank row above them.
+const FOOTER_ROWS: usize = 7;
 
-    let mut samples: Vec<String> = files
-        .map(|file| {
-            let file: PathBuf = file.unwrap().path();
-            let mut name: String = file.file_name().unwrap().to_str().unwrap().to_string();
-            if name.len() > sidebar_width - 4 {
-                name = format!("{}...", &name[..sidebar_width - 7]);
-            }
-            name
-        })
-        .collect::<Vec<String>>();
-    samples.sort();
+/// A seed file, or a folder of them, under `seeds/`.
+#[derive(Debug, Clone)]
+struct Sample {
+    /// Path relative to `seeds/`, with `/` after each folder
+    path: String,
+    /// Number of folders the sample is in
+    depth: usize,
+    /// Whether the sample is a folder of samples
+    is_folder: bool,
+}
+
+impl Sample {
+    fn name(&self) -> &str {
+        //! The last part of the path.
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
 
-    Ok(samples)
+    fn folders(&self) -> impl Iterator<Item = &str> {
+        //! Paths of the folders the sample is in, outermost first.
+        self.path.match_indices('/').map(|(i, _)| &self.path[..i])
+    }
 }
 
-pub fn run(args: &Cli) -> Result<()> {
-    ncurses::setlocale(ncurses::LcCategory::all, "")?;
+fn collect_seed_files(
+    dir: &Path,
+    prefix: &str,
+    depth: usize,
+    samples: &mut Vec<Sample>,
+    skipped: &mut Vec<String>,
+) {
+    //! Lists the seed files and folders in a folder, each folder followed by
+    //! what is in it. Folders that can't be read are left out, and why is
+    //! added to `skipped`.
+    let listing = fs::read_dir(dir).and_then(|entries| {
+        entries
+            .map(|entry| {
+                let entry: fs::DirEntry = entry?;
+                Ok((
+                    entry.file_name().to_string_lossy().to_string(),
+                    entry.path().is_dir(),
+                ))
+            })
+            .collect::<std::io::Result<Vec<(String, bool)>>>()
+    });
+    let mut entries: Vec<(String, bool)> = match listing {
+        Ok(entries) => entries,
+        Err(e) => {
+            skipped.push(format!("{}: {}", dir.display(), e));
+            return;
+        }
+    };
+    entries.sort();
+    for (name, is_folder) in entries {
+        let path: String = format!("{}{}", prefix, name);
+        if is_folder {
+            samples.push(Sample {
+                path: path.clone(),
+                depth,
+                is_folder,
+            });
+            collect_seed_files(
+                &dir.join(&name),
+                &format!("{}/", path),
+                depth + 1,
+                samples,
+                skipped,
+            );
+        } else {
+            samples.push(Sample {
+                path,
+                depth,
+                is_folder,
+            });
+        }
+    }
+}
 
-    initscr();
+fn load_sample(
+    display: &mut Window,
+    state: &mut State,
+    args: &Cli,
+    sample: &Sample,
+) -> Result<Vec<Vec<Cell>>> {
+    //! Fills a new grid with a seed file under `seeds/`.
+    initialize(
+        display,
+        state,
+        args.alive,
+        &[format!("seeds/{}", sample.path)],
+        &[],
+        &args.soup_options(),
+    )
+}
 
-    curs_set(ncurses::CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+fn outcome(report: Option<&Report>) -> String {
+    //! Describes whether a run is still going, died out, or settled into a
+    //! cycle, and of which period.
+    match report {
+        None => String::from("running"),
+        Some(Report { period: None, .. }) => String::from("extinct"),
+        Some(Report {
+            period: Some(1), ..
+        }) => String::from("stable"),
+        Some(Report {
+            period: Some(period),
+            ..
+        }) => format!("stable (p{})", period),
+    }
+}
+
+pub fn run(
+    args: &Cli,
+    theme: Theme,
+    script: Option<Script>,
+    cast: Option<Cast>,
+    stepper: Stepper,
+    input_handler: &mut InputHandler,
+) -> Result<()> {
+    // collect the seed files for the sample display
+    let mut samples: Vec<Sample> = vec![];
+    let mut skipped: Vec<String> = vec![];
+    collect_seed_files(Path::new("seeds"), "", 0, &mut samples, &mut skipped);
+    if !samples.iter().any(|sample| !sample.is_folder) {
+        return Err(match skipped.first() {
+            Some(reason) => anyhow!("There are no seed files in seeds/: {}", reason),
+            None => anyhow!("There are no seed files in seeds/"),
+        });
+    }
+
+    ncurses::setlocale(ncurses::LcCategory::all, "")?;
+
+    let screen: Screen = Screen::start(false, None);
 
     start_color();
 
@@ -40,8 +162,6 @@ pub fn run(args: &Cli) -> Result<()> {
 
     refresh();
 
-    timeout(args.timeout);
-
     // Get the number of rows and columns for the entirety of the terminal
     let max_rows: i32 = LINES();
     let max_cols: i32 = COLS();
@@ -54,43 +174,158 @@ pub fn run(args: &Cli) -> Result<()> {
     let mut display: Window =
         Window::new(max_rows, max_cols - sidebar_width - 1, 0, sidebar_width + 1);
 
-    // collect the seed files for the sample display
-    let samples: Vec<String> = collect_seed_files(sidebar_width as usize)?;
+    // which sample is selected at the moment, which may be a folder, and
+    // which folders are folded away
+    let mut cur_sample: usize = 0;
+    let mut folded: HashSet<String> = HashSet::new();
 
-    // which sample is selected at the moment
-    let mut cur_sample: isize = 0;
-
-    // Initialize the grid with the first sample
+    // Initialize the grid with the first sample that loads. Those that
+    // don't are skipped, saying why
     let mut cur_input: InputType = InputType::Continue;
-    let mut input_handler: InputHandler = InputHandler::new();
-    let mut filename: String = format!("seeds/{}", &samples[cur_sample as usize]);
-    let mut grid: Vec<Vec<Cell>> = initialize(&mut display, args.alive, &Some(filename))?;
+    let mut state: State = args.build_state(theme);
+    state.set_script(script);
+    state.set_cast(cast);
+    state.set_stepper(stepper);
+    state.set_searchable(true);
+    let mut grid: Vec<Vec<Cell>> = vec![];
+    for (i, sample) in samples.iter().enumerate() {
+        if sample.is_folder {
+            continue;
+        }
+        match load_sample(&mut display, &mut state, args, sample) {
+            Ok(loaded) => {
+                cur_sample = i;
+                grid = loaded;
+                break;
+            }
+            Err(e) => skipped.push(format!("{}: {:#}", sample.path, e)),
+        }
+    }
+    if grid.is_empty() {
+        return Err(anyhow!(
+            "None of the seed files in seeds/ could be loaded: {}",
+            skipped.join("; ")
+        ));
+    }
+    if !skipped.is_empty() {
+        state.set_message(Some(format!("Skipped {}", skipped.join("; "))));
+    }
+
+    // in a slideshow, when the current sample started, and whether it has
+    // settled
+    let slide_time: Option<Duration> = args.slideshow.map(Duration::from_secs);
+    let mut slide_start: Instant = Instant::now();
+    let mut stagnation: Stagnation = Stagnation::default();
+    let mut slide_over: bool = false;
 
-    let mut state: super::conway::State = super::conway::State::new(args.timeout, args.character);
+    // what the samples were last filtered by
+    let mut last_search: String = String::new();
 
-    // color for the selected sample
-    let selected_color: ColorPair = ColorPair::new(Color::Black, Color::White);
+    // whether the current sample has died out or settled, as of the last
+    // generation checked
+    let mut cycle_detector: CycleDetector =
+        CycleDetector::new(state.get_topology(), state.get_rule().clone());
+    let mut report: Option<Report> = None;
+    let mut checked: Option<u64> = None;
 
     while cur_input != InputType::Quit {
-        // handle arrow keys
-        if cur_input == InputType::Down || cur_input == InputType::Up {
-            // update the sample based on the arrow key
-            if cur_input == InputType::Down {
-                cur_sample += 1;
-            } else if cur_input == InputType::Up {
-                cur_sample -= 1;
+        let search: String = state.get_search().to_lowercase();
+
+        // fold and unfold folders with the left and right arrow keys, unless
+        // they are moving the inspect cursor; left on anything else selects
+        // the folder it is in
+        let sample: &Sample = &samples[cur_sample];
+        if search.is_empty() && !state.is_inspecting() {
+            match cur_input {
+                InputType::Right if sample.is_folder => {
+                    folded.remove(&sample.path);
+                }
+                InputType::Left if sample.is_folder && !folded.contains(&sample.path) => {
+                    folded.insert(sample.path.clone());
+                }
+                InputType::Left => {
+                    if let Some(folder) = sample.folders().last() {
+                        cur_sample = samples
+                            .iter()
+                            .position(|sample| sample.path == folder)
+                            .unwrap_or(cur_sample);
+                    }
+                }
+                _ => (),
             }
+        }
+
+        // the samples in unfolded folders are listed, or while searching,
+        // the seed files matching the search
+        let visible: Vec<usize> = (0..samples.len())
+            .filter(|i| {
+                let sample: &Sample = &samples[*i];
+                if search.is_empty() {
+                    sample.folders().all(|folder| !folded.contains(folder))
+                } else {
+                    !sample.is_folder && sample.path.to_lowercase().contains(&search)
+                }
+            })
+            .collect();
+        let position: Option<usize> = visible.iter().position(|i| *i == cur_sample);
 
+        // handle arrow keys, unless they are moving the inspect cursor, move
+        // on to the next seed file once a slide is over, and move off the
+        // selected sample if the search hides it
+        let next_sample: Option<usize> = match (cur_input, position) {
+            _ if visible.is_empty() => None,
+            _ if slide_over => {
+                let files: Vec<usize> = if search.is_empty() {
+                    (0..samples.len())
+                        .filter(|i| !samples[*i].is_folder)
+                        .collect()
+                } else {
+                    visible.clone()
+                };
+                files
+                    .iter()
+                    .find(|i| **i > cur_sample)
+                    .or(files.first())
+                    .copied()
+            }
+            (InputType::Down | InputType::Up, _) if state.is_inspecting() => None,
             // wrap around the samples
-            if cur_sample >= samples.len() as isize {
-                cur_sample = 0;
-            } else if cur_sample < 0 {
-                cur_sample = samples.len() as isize - 1;
+            (InputType::Down, Some(position)) => Some(visible[(position + 1) % visible.len()]),
+            (InputType::Up, Some(position)) => {
+                Some(visible[(position + visible.len() - 1) % visible.len()])
+            }
+            (InputType::Down | InputType::Up, None) => Some(visible[0]),
+            (_, None) if search != last_search => Some(visible[0]),
+            _ => None,
+        };
+        last_search = search;
+        slide_over = false;
+
+        if let Some(next_sample) = next_sample {
+            cur_sample = next_sample;
+            let sample: &Sample = &samples[cur_sample];
+            for folder in sample.folders() {
+                folded.remove(folder);
             }
 
-            // populate the grid with the new sample
-            filename = format!("seeds/{}", &samples[cur_sample as usize]);
-            grid = initialize(&mut display, args.alive, &Some(filename))?;
+            // populate the grid with the new sample, or, if it doesn't load,
+            // leave it empty and say why
+            if !sample.is_folder {
+                grid = match load_sample(&mut display, &mut state, args, sample) {
+                    Ok(grid) => grid,
+                    Err(e) => {
+                        state.set_metadata(Metadata::default());
+                        state.set_message(Some(format!("Skipped {}: {:#}", sample.path, e)));
+                        new_grid(grid.len(), grid[0].len())
+                    }
+                };
+                state.reset_run();
+                slide_start = Instant::now();
+                stagnation.clear();
+                cycle_detector.clear();
+                report = None;
+                checked = None;
+            }
         }
 
         // clear the windows
@@ -100,28 +335,118 @@ pub fn run(args: &Cli) -> Result<()> {
         // draw the sidebar's border
         sidebar.draw_border()?;
 
-        // draw the sample names
-        for (i, sample) in samples.iter().enumerate() {
-            if cur_sample == i as isize {
-                sidebar.print(2, i as i32 + 1, sample, Some(&selected_color))?;
-            } else {
-                sidebar.print(2, i as i32 + 1, sample, None)?;
+        // draw the samples as a tree, or their paths while searching, with
+        // what the search matched in them underlined. The list scrolls to
+        // keep the selected sample in view, above the run's footer
+        let theme: &Theme = state.get_theme();
+        let width: usize = sidebar_width as usize - 3;
+        let list_rows: usize = (max_rows as usize).saturating_sub(2 + FOOTER_ROWS).max(1);
+        let position: usize = visible.iter().position(|i| *i == cur_sample).unwrap_or(0);
+        let first_row: usize = (position + 1).saturating_sub(list_rows);
+        for (row, i) in visible.iter().enumerate().skip(first_row).take(list_rows) {
+            let sample: &Sample = &samples[*i];
+            let label: String = match (last_search.is_empty(), sample.is_folder) {
+                (false, _) => sample.path.clone(),
+                (true, true) => format!(
+                    "{}{} {}/",
+                    "  ".repeat(sample.depth),
+                    if folded.contains(&sample.path) {
+                        '+'
+                    } else {
+                        '-'
+                    },
+                    sample.name()
+                ),
+                (true, false) => format!("{}{}", "  ".repeat(sample.depth), sample.name()),
+            };
+            let label: String = fit_to_width(&label, width);
+            let y: i32 = (row - first_row) as i32 + 1;
+            let color = theme.selection.as_ref().filter(|_| *i == cur_sample);
+            sidebar.print(2, y, &label, color)?;
+            let lower: String = label.to_lowercase();
+            if let Some(start) = lower
+                .find(&last_search)
+                .filter(|_| !last_search.is_empty() && lower.len() == label.len())
+            {
+                sidebar.print_attr(
+                    2 + label[..start].chars().count() as i32,
+                    y,
+                    &label[start..start + last_search.len()],
+                    color,
+                    A_UNDERLINE | A_BOLD,
+                )?;
             }
         }
+        if visible.is_empty() {
+            sidebar.print(2, 1, "no matches", None)?;
+        }
+
+        // and the search at the bottom, while it is typed or filters
+        if state.is_searching() || !last_search.is_empty() {
+            let cursor: &str = if state.is_searching() { "_" } else { "" };
+            sidebar.print(
+                1,
+                max_rows - 1,
+                &fit_to_width(&format!("/{}{}", state.get_search(), cursor), width + 1),
+                theme.status.as_ref(),
+            )?;
+        }
 
-        // run a single frame, collecting input and the updated grid.
-        let (input, new_grid) = run_frame(&mut display, &grid, &mut input_handler, &mut state)?;
+        // run a single frame, collecting input for the next iteration and
+        // updating the grid.
+        cur_input = run_frame(&mut display, &mut grid, input_handler, &mut state)?;
+
+        // check whether the run settled, once per generation until it has,
+        // and show how far along it is below the samples. Shapes crossing
+        // the edges of a torus look different until they are across, so a
+        // cycle found is kept until the run starts over
+        let generation: u64 = state.get_generation();
+        if checked.is_some_and(|checked| generation < checked) {
+            cycle_detector.clear();
+            report = None;
+        }
+        if report.is_none() && checked != Some(generation) {
+            report = cycle_detector.check(&grid, generation);
+        }
+        checked = Some(generation);
+        let population: usize = grid.iter().flatten().filter(|cell| cell.is_alive()).count();
+        let metadata: &Metadata = state.get_metadata();
+        let footer: [String; FOOTER_ROWS - 1] = [
+            metadata.name.clone().unwrap_or_default(),
+            metadata
+                .author
+                .as_ref()
+                .map_or(String::new(), |author| format!("by {}", author)),
+            metadata.comments.join(" "),
+            format!("Gen: {}", generation),
+            format!("Alive: {}", population),
+            format!("Status: {}", outcome(report.as_ref())),
+        ];
+        for (row, line) in footer.iter().enumerate() {
+            sidebar.print(
+                2,
+                max_rows - FOOTER_ROWS as i32 + row as i32,
+                &fit_to_width(line, width),
+                None,
+            )?;
+        }
+
+        // move on to the next seed file once the slide is over, unless
+        // paused or searching
+        if let Some(slide_time) = slide_time {
+            slide_over = !state.is_paused()
+                && !state.is_searching()
+                && cur_input == InputType::Continue
+                && (stagnation.is_stagnant(&grid, state.get_generation())
+                    || slide_start.elapsed() >= slide_time);
+        }
 
         // refresh just the sidebar. The display window will be refreshed as
         // part of the call to 'run_frame()'
         sidebar.refresh();
-
-        // update the input and grid for the next iteration
-        cur_input = input;
-        grid = new_grid;
     }
 
-    endwin();
+    drop(screen);
 
     Ok(())
 }
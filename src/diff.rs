@@ -0,0 +1,65 @@
+//! This module contains the `diff` subcommand, which compares two pattern
+//! files cell by cell, for checking exporters or the outputs of two engines
+//! against each other.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+
+use super::pattern::Pattern;
+
+/// Characters the compared cells are drawn with.
+const ADDED: char = '+';
+const REMOVED: char = '-';
+const UNCHANGED: char = 'O';
+const DEAD: char = '.';
+
+pub fn run(first: &str, second: &str, normalize: bool) -> Result<()> {
+    //! Reads both patterns and prints how many cells were added, removed and
+    //! left unchanged going from the first to the second, with a map of
+    //! where. With `normalize`, both patterns are moved to the top-left
+    //! corner first, so that patterns that only moved compare equal. Fails
+    //! if the patterns differ, like `diff`.
+    let before: HashSet<(usize, usize)> = cells(&Pattern::from_file(first)?, normalize);
+    let after: HashSet<(usize, usize)> = cells(&Pattern::from_file(second)?, normalize);
+    let added: usize = after.difference(&before).count();
+    let removed: usize = before.difference(&after).count();
+    let unchanged: usize = before.intersection(&after).count();
+
+    println!(
+        "Added: {}, removed: {}, unchanged: {}",
+        added, removed, unchanged
+    );
+    let rows: usize = before.union(&after).map(|(i, _)| i + 1).max().unwrap_or(0);
+    let cols: usize = before.union(&after).map(|(_, j)| j + 1).max().unwrap_or(0);
+    for i in 0..rows {
+        let line: String = (0..cols)
+            .map(
+                |j| match (before.contains(&(i, j)), after.contains(&(i, j))) {
+                    (false, true) => ADDED,
+                    (true, false) => REMOVED,
+                    (true, true) => UNCHANGED,
+                    (false, false) => DEAD,
+                },
+            )
+            .collect();
+        println!("{}", line);
+    }
+
+    if added + removed > 0 {
+        return Err(anyhow!("'{}' and '{}' differ", first, second));
+    }
+    Ok(())
+}
+
+fn cells(pattern: &Pattern, normalize: bool) -> HashSet<(usize, usize)> {
+    //! Returns the live cells of a pattern, moved so that the topmost and
+    //! leftmost of them are on the first row and column if `normalize`.
+    let top: usize = pattern.cells.iter().map(|(i, _)| *i).min().unwrap_or(0);
+    let left: usize = pattern.cells.iter().map(|(_, j)| *j).min().unwrap_or(0);
+    let (top, left) = if normalize { (top, left) } else { (0, 0) };
+    pattern
+        .cells
+        .iter()
+        .map(|(i, j)| (i - top, j - left))
+        .collect()
+}
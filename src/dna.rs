@@ -0,0 +1,225 @@
+//! This module contains the simulation "DNA": a compact, shareable string
+//! describing a random soup run, so that it can be reproduced exactly with
+//! `--from-code`.
+//!
+//! The code is the URL-safe base64 encoding of the rule, RNG seed, grid
+//! dimensions, generation, soup options and topology, separated by `|`.
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+
+use super::soup::{DistributionSpec, SoupOptions, Symmetry};
+use super::topology::Topology;
+
+/// Version of the code format, bumped whenever its fields change.
+const VERSION: &str = "2";
+
+/// Alphabet of URL-safe base64.
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Everything needed to reproduce a random soup run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dna {
+    /// Rule the simulation follows
+    pub rule: String,
+    /// Number of rows in the grid
+    pub rows: usize,
+    /// Number of columns in the grid
+    pub cols: usize,
+    /// Generation the run had reached
+    pub generation: u64,
+    /// Number of cells alive in the initial soup
+    pub alive: usize,
+    /// How the initial soup was generated, including the RNG seed
+    pub soup: SoupOptions,
+    /// How the edges of the grid are joined
+    pub topology: Topology,
+}
+
+impl Dna {
+    pub fn encode(&self) -> String {
+        //! Encodes the DNA into a shareable code.
+        let symmetry: String = match self.soup.symmetry {
+            Some(symmetry) => symmetry
+                .to_possible_value()
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_default(),
+            None => "-".to_string(),
+        };
+        let text: String = [
+            VERSION.to_string(),
+            self.rule.clone(),
+            self.soup.seed.to_string(),
+            format!("{}x{}", self.rows, self.cols),
+            self.generation.to_string(),
+            self.alive.to_string(),
+            self.soup.distribution.to_string(),
+            symmetry,
+            self.topology.to_string(),
+        ]
+        .join("|");
+        encode_base64(text.as_bytes())
+    }
+
+    pub fn decode(code: &str) -> Result<Dna> {
+        //! Decodes a code produced by `encode`.
+        let bytes: Vec<u8> = decode_base64(code.trim())?;
+        let text: String = String::from_utf8(bytes).map_err(|_| anyhow!("Invalid code"))?;
+        let fields: Vec<&str> = text.split('|').collect();
+        let topology: Topology = match (fields[0], fields.len()) {
+            // codes from before topologies were added are all on a torus
+            ("1", 8) => Topology::Torus,
+            (VERSION, 9) => Topology::from_str(fields[8], true).map_err(|e| anyhow!(e))?,
+            _ => return Err(anyhow!("Invalid code, or made by a different version")),
+        };
+        let (rows, cols) = fields[3]
+            .split_once('x')
+            .ok_or_else(|| anyhow!("Invalid dimensions in code"))?;
+        let symmetry: Option<Symmetry> = match fields[7] {
+            "-" => None,
+            name => Some(Symmetry::from_str(name, true).map_err(|e| anyhow!(e))?),
+        };
+        Ok(Dna {
+            rule: fields[1].to_string(),
+            rows: rows.parse().context("Invalid number of rows in code")?,
+            cols: cols.parse().context("Invalid number of columns in code")?,
+            generation: fields[4].parse().context("Invalid generation in code")?,
+            alive: fields[5].parse().context("Invalid alive count in code")?,
+            soup: SoupOptions {
+                distribution: fields[6].parse::<DistributionSpec>()?,
+                symmetry,
+                seed: fields[2].parse().context("Invalid RNG seed in code")?,
+            },
+            topology,
+        })
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    //! Encodes bytes as URL-safe base64 without padding.
+    let mut encoded: String = String::new();
+    for chunk in bytes.chunks(3) {
+        let mut group: u32 = 0;
+        for (i, byte) in chunk.iter().enumerate() {
+            group |= (*byte as u32) << (16 - 8 * i);
+        }
+        // every 3 bytes become 4 characters; shorter chunks need fewer
+        for i in 0..=chunk.len() {
+            encoded.push(BASE64[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    encoded
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>> {
+    //! Decodes URL-safe base64, with or without padding.
+    let mut bytes: Vec<u8> = vec![];
+    let mut group: u32 = 0;
+    let mut bits: u32 = 0;
+    for c in text.trim_end_matches('=').bytes() {
+        let value: usize = BASE64
+            .iter()
+            .position(|b| *b == c)
+            .ok_or_else(|| anyhow!("Invalid character '{}' in code", c as char))?;
+        group = (group << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((group >> bits) as u8);
+            group &= (1 << bits) - 1;
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dna(seed: u64, distribution: &str, symmetry: Option<Symmetry>, topology: Topology) -> Dna {
+        Dna {
+            rule: "B36/S23".to_string(),
+            rows: 30,
+            cols: 120,
+            generation: 0,
+            alive: 1000,
+            soup: SoupOptions {
+                distribution: distribution.parse().unwrap(),
+                symmetry,
+                seed,
+            },
+            topology,
+        }
+    }
+
+    #[test]
+    fn base64_round_trips_every_length() {
+        assert_eq!(encode_base64(b"f"), "Zg");
+        assert_eq!(encode_base64(b"fo"), "Zm8");
+        assert_eq!(encode_base64(b"foo"), "Zm9v");
+        assert_eq!(encode_base64(&[0xfb, 0xff]), "-_8");
+        assert_eq!(decode_base64("Zm8=").unwrap(), b"fo");
+        for length in 0..10 {
+            let bytes: Vec<u8> = (0..length).map(|i| (i * 97 + 13) as u8).collect();
+            assert_eq!(decode_base64(&encode_base64(&bytes)).unwrap(), bytes);
+        }
+        assert!(decode_base64("Zm9v!").is_err());
+    }
+
+    #[test]
+    fn codes_round_trip() {
+        let mut remainders: Vec<usize> = vec![];
+        for (seed, distribution, symmetry, topology) in [
+            (7, "uniform", None, Topology::Torus),
+            (
+                42,
+                "gaussian:0.2",
+                Some(Symmetry::FourFold),
+                Topology::Klein,
+            ),
+            (
+                1234,
+                "power-law:2.5",
+                Some(Symmetry::Diagonal),
+                Topology::Mirror,
+            ),
+            (
+                98765,
+                "uniform",
+                Some(Symmetry::Horizontal),
+                Topology::CrossSurface,
+            ),
+            (u64::MAX, "gaussian", None, Topology::Plane),
+        ] {
+            let dna: Dna = dna(seed, distribution, symmetry, topology);
+            let code: String = dna.encode();
+            remainders.push(decode_base64(&code).unwrap().len() % 3);
+            assert_eq!(Dna::decode(&code).unwrap(), dna, "{}", code);
+        }
+        // the text of the codes ends on every position in a group of 3 bytes
+        remainders.sort();
+        remainders.dedup();
+        assert_eq!(remainders, [0, 1, 2]);
+    }
+
+    #[test]
+    fn codes_from_before_topologies_are_on_a_torus() {
+        let code: String = encode_base64(b"1|B36/S23|42|30x120|0|1000|uniform|-");
+        assert_eq!(
+            Dna::decode(&code).unwrap(),
+            dna(42, "uniform", None, Topology::Torus)
+        );
+        // a version 1 code with a topology, or a version 2 code without one
+        for text in [
+            "1|B36/S23|42|30x120|0|1000|uniform|-|klein",
+            "2|B36/S23|42|30x120|0|1000|uniform|-",
+            "3|B36/S23|42|30x120|0|1000|uniform|-|torus",
+        ] {
+            assert!(
+                Dna::decode(&encode_base64(text.as_bytes())).is_err(),
+                "{}",
+                text
+            );
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! This module contains the line editor: a line of text typed in at the
+//! bottom of the window, after a label, with a cursor that can be moved
+//! along it to change the text anywhere.
+
+use anyhow::Result;
+use ncurses::*;
+
+use super::window::{ColorPair, Window};
+
+/// A line of text being edited, and where the cursor is in it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineEditor {
+    /// Text shown before the line, which can't be edited
+    label: String,
+    text: Vec<char>,
+    /// Index of the character the cursor is on, which is the length of the
+    /// text when it is past the end
+    cursor: usize,
+}
+
+impl LineEditor {
+    pub fn new(label: &str, text: &str) -> LineEditor {
+        //! Creates an editor for the given text, with the cursor past its end.
+        let text: Vec<char> = text.chars().collect();
+        LineEditor {
+            label: label.to_string(),
+            cursor: text.len(),
+            text,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    pub fn insert(&mut self, c: char) {
+        //! Types a character before the cursor.
+        self.text.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    pub fn backspace(&mut self) {
+        //! Deletes the character before the cursor.
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.text.remove(self.cursor);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        //! Deletes the character under the cursor.
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+        }
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.text.len());
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    pub fn draw(
+        &self,
+        window: &Window,
+        y: i32,
+        width: usize,
+        color: Option<&ColorPair>,
+    ) -> Result<()> {
+        //! Draws the label and the text on row `y` of the window, within
+        //! `width` columns, with the cursor in reverse video. A line too long
+        //! for them is scrolled to keep the cursor in sight.
+        if width == 0 {
+            return Ok(());
+        }
+        // a blank after the text for the cursor to sit on past its end
+        let line: Vec<char> = format!("{}{} ", self.label, self.text()).chars().collect();
+        let cursor: usize = self.label.chars().count() + self.cursor;
+        let start: usize = (cursor + 1).saturating_sub(width);
+        let visible: String = line.iter().skip(start).take(width).collect();
+        window.print(0, y, &format!("{:width$}", visible), color)?;
+        window.print_attr(
+            (cursor - start) as i32,
+            y,
+            &line[cursor].to_string(),
+            color,
+            A_REVERSE,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_happen_at_the_cursor() {
+        let mut editor: LineEditor = LineEditor::new("Rule: ", "B3/S23");
+        editor.insert('6');
+        assert_eq!(editor.text(), "B3/S236");
+        editor.home();
+        editor.right();
+        editor.right();
+        editor.insert('6');
+        assert_eq!(editor.text(), "B36/S236");
+        editor.backspace();
+        editor.backspace();
+        assert_eq!(editor.text(), "B/S236");
+        editor.delete();
+        assert_eq!(editor.text(), "BS236");
+        editor.end();
+        editor.left();
+        editor.delete();
+        editor.delete();
+        assert_eq!(editor.text(), "BS23");
+    }
+
+    #[test]
+    fn the_cursor_stays_within_the_text() {
+        let mut editor: LineEditor = LineEditor::new("Rule: ", "ab");
+        editor.right();
+        editor.insert('c');
+        assert_eq!(editor.text(), "abc");
+        editor.home();
+        editor.left();
+        editor.backspace();
+        editor.insert('_');
+        assert_eq!(editor.text(), "_abc");
+        editor.end();
+        editor.delete();
+        assert_eq!(editor.text(), "_abc");
+    }
+
+    #[test]
+    fn characters_are_not_bytes() {
+        let mut editor: LineEditor = LineEditor::new("Search: ", "héllo");
+        editor.left();
+        editor.left();
+        editor.left();
+        editor.backspace();
+        assert_eq!(editor.text(), "hllo");
+        editor.insert('e');
+        editor.delete();
+        editor.insert('i');
+        assert_eq!(editor.text(), "heilo");
+    }
+}
@@ -0,0 +1,118 @@
+//! This module contains the event stream, through which the run reports what
+//! happens in it as newline-delimited JSON, for dashboards and other tools
+//! to follow: generations advancing, the run stabilizing, inputs and saves.
+//!
+//! Every event is a JSON object on a line of its own, with the kind of event
+//! in `event` and the seconds since the run started in `time`, e.g.
+//! `{"event":"generation","time":1.25,"generation":12,"population":80}`.
+
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{BufWriter, IsTerminal, Write};
+use std::os::fd::FromRawFd;
+use std::time::Instant;
+
+use super::cast::escape;
+
+/// Something that happened in the run.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// The grid advanced to a generation
+    Generation { generation: u64, population: usize },
+    /// The run died out, or settled into a cycle of some period
+    Stabilized {
+        generation: u64,
+        period: Option<u64>,
+    },
+    /// An input was given, by a key, a macro or the control socket
+    Input { name: String },
+    /// Something was saved to a file: `session` or `region`
+    Saved { what: &'static str, path: String },
+}
+
+impl Event {
+    fn to_json(&self, time: f64) -> String {
+        //! Formats the event as a line of the stream, `time` seconds into it.
+        let (name, fields): (&str, String) = match self {
+            Event::Generation {
+                generation,
+                population,
+            } => (
+                "generation",
+                format!(r#""generation":{},"population":{}"#, generation, population),
+            ),
+            Event::Stabilized { generation, period } => (
+                "stabilized",
+                format!(
+                    r#""generation":{},"period":{}"#,
+                    generation,
+                    period.map_or("null".to_string(), |period| period.to_string())
+                ),
+            ),
+            Event::Input { name } => ("input", format!(r#""input":"{}""#, escape(name))),
+            Event::Saved { what, path } => (
+                "saved",
+                format!(r#""what":"{}","path":"{}""#, what, escape(path)),
+            ),
+        };
+        format!(r#"{{"event":"{}","time":{:.3},{}}}"#, name, time, fields)
+    }
+}
+
+/// The stream events are written to, if any.
+#[derive(Default)]
+pub struct Events {
+    /// Where the events go, and its name for errors
+    sink: Option<(BufWriter<File>, String)>,
+    /// When the stream was opened
+    start: Option<Instant>,
+}
+
+impl Events {
+    pub fn create(target: &str) -> Result<Events> {
+        //! Opens the event stream: a file, or the standard output for `-`.
+        //! The standard output has to be piped, as the game is drawn on the
+        //! terminal.
+        let file: File = if target == "-" {
+            if std::io::stdout().is_terminal() {
+                return Err(anyhow!(
+                    "Events can only be written to the standard output when it is piped, \
+                     e.g. `conway --events - | jq`"
+                ));
+            }
+            // a copy of the output, kept while the game draws on the terminal
+            let fd: i32 = unsafe { libc::dup(libc::STDOUT_FILENO) };
+            if fd < 0 {
+                return Err(anyhow!(
+                    "Failed to open the standard output for events: {}",
+                    std::io::Error::last_os_error()
+                ));
+            }
+            unsafe { File::from_raw_fd(fd) }
+        } else {
+            File::create(target)
+                .map_err(|e| anyhow!("Failed to create event stream '{}': {}", target, e))?
+        };
+        Ok(Events {
+            sink: Some((BufWriter::new(file), target.to_string())),
+            start: Some(Instant::now()),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    pub fn emit(&mut self, event: Event) -> Result<()> {
+        //! Writes an event to the stream, if there is one.
+        let Some((writer, target)) = &mut self.sink else {
+            return Ok(());
+        };
+        let time: f64 = self
+            .start
+            .map_or(0.0, |start| start.elapsed().as_secs_f64());
+        writeln!(writer, "{}", event.to_json(time))
+            .and_then(|_| writer.flush())
+            .map_err(|e| anyhow!("Failed to write event stream '{}': {}", target, e))
+    }
+}
@@ -0,0 +1,71 @@
+//! This module contains the `fetch` subcommand, which downloads a pattern
+//! by name from the LifeWiki pattern archive (https://conwaylife.com/patterns)
+//! into the seeds directory. Downloading needs a build with the `net`
+//! feature.
+
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+
+use super::pattern::Pattern;
+
+/// Base URL of the LifeWiki pattern archive
+const PATTERNS_URL: &str = "https://conwaylife.com/patterns";
+
+pub fn archive_name(name: &str) -> String {
+    //! Turns a pattern's name into the one its file has in the archive,
+    //! lowercase without spaces or punctuation, e.g. `Gosper glider gun`
+    //! into `gosperglidergun`.
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+pub fn run(name: &str, directory: &str) -> Result<()> {
+    //! Downloads the RLE of the named pattern into the directory, checking
+    //! that it is a pattern before saving it, and prints where it went.
+    let archive_name: String = archive_name(name);
+    if archive_name.is_empty() {
+        return Err(anyhow!("'{}' isn't the name of a pattern", name));
+    }
+    let path: PathBuf = Path::new(directory).join(format!("{}.rle", archive_name));
+    if path.exists() {
+        return Err(anyhow!(
+            "'{}' already exists; remove it to fetch it again",
+            path.display()
+        ));
+    }
+
+    let url: String = format!("{}/{}.rle", PATTERNS_URL, archive_name);
+    let text: String = download(&url)?;
+    Pattern::from_rle(&text).with_context(|| format!("'{}' isn't a valid RLE pattern", url))?;
+    std::fs::create_dir_all(directory)
+        .with_context(|| format!("Failed to create '{}'", directory))?;
+    std::fs::write(&path, text).with_context(|| format!("Failed to write '{}'", path.display()))?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+#[cfg(feature = "net")]
+fn download(url: &str) -> Result<String> {
+    //! Fetches a file from the archive as text.
+    match ureq::get(url).call() {
+        Ok(mut response) => response
+            .body_mut()
+            .read_to_string()
+            .map_err(|e| anyhow!("Failed to read '{}': {}", url, e)),
+        Err(ureq::Error::StatusCode(404)) => Err(anyhow!(
+            "Found no pattern at '{}'; names are as in the LifeWiki pattern \
+             archive, e.g. `gosperglidergun`",
+            url
+        )),
+        Err(e) => Err(anyhow!("Failed to download '{}': {}", url, e)),
+    }
+}
+
+#[cfg(not(feature = "net"))]
+fn download(_url: &str) -> Result<String> {
+    Err(anyhow!(
+        "This build can't download patterns; rebuild with `cargo build --features net`"
+    ))
+}
@@ -0,0 +1,403 @@
+//! This module contains the GPU engine, which works out which cells live in
+//! the next generation with a compute shader, for universes too big to step
+//! quickly on the CPU. It is only built with the `gpu` feature.
+//!
+//! The shader works on the same packed rows as the CPU engine, split into
+//! 32-bit words: each invocation adds up the neighbors of the 32 cells of one
+//! word. The border around the grid, with the cells across the edges, is
+//! filled by a second shader between generations, so that a frame of many
+//! generations is worked out without leaving the GPU, and read back once.
+
+use anyhow::{anyhow, Result};
+use std::sync::mpsc;
+
+use super::bitgrid::BitGrid;
+use super::conway::Cell;
+use super::topology::Topology;
+
+/// Compute shaders working out the next generation of one word of cells per
+/// invocation, `main`, and filling in one cell of the border per invocation,
+/// `border`.
+const SHADER: &str = r#"
+struct Size {
+    nrows: u32,
+    words: u32,
+    ncols: u32,
+    topology: u32,
+}
+
+@group(0) @binding(0) var<uniform> size: Size;
+@group(0) @binding(1) var<storage, read> cells: array<u32>;
+@group(0) @binding(2) var<storage, read_write> next: array<u32>;
+@group(0) @binding(3) var<storage, read_write> bordered: array<atomic<u32>>;
+
+fn word(i: u32, k: i32) -> u32 {
+    if (k < 0 || u32(k) >= size.words) {
+        return 0u;
+    }
+    return cells[i * size.words + u32(k)];
+}
+
+fn left(i: u32, k: i32) -> u32 {
+    return (word(i, k) << 1u) | (word(i, k - 1) >> 31u);
+}
+
+fn right(i: u32, k: i32) -> u32 {
+    return (word(i, k) >> 1u) | (word(i, k + 1) << 31u);
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.y + 1u;
+    let k = i32(id.x);
+    if (id.x >= size.words || i > size.nrows) {
+        return;
+    }
+    let above_left = left(i - 1u, k);
+    let above = word(i - 1u, k);
+    let above_right = right(i - 1u, k);
+    let beside_left = left(i, k);
+    let middle = word(i, k);
+    let beside_right = right(i, k);
+    let below_left = left(i + 1u, k);
+    let below = word(i + 1u, k);
+    let below_right = right(i + 1u, k);
+
+    // add up the eight neighbors, one bit of the count at a time
+    let sum_above = above_left ^ above ^ above_right;
+    let carry_above = (above_left & above) | ((above_left ^ above) & above_right);
+    let sum_beside = beside_left ^ beside_right ^ below_left;
+    let carry_beside = (beside_left & beside_right) | ((beside_left ^ beside_right) & below_left);
+    let sum_below = below ^ below_right;
+    let carry_below = below & below_right;
+    let ones = sum_above ^ sum_beside ^ sum_below;
+    let carry_ones = (sum_above & sum_beside) | ((sum_above ^ sum_beside) & sum_below);
+    let twos_partial = carry_above ^ carry_beside ^ carry_below;
+    let carry_twos = (carry_above & carry_beside) | ((carry_above ^ carry_beside) & carry_below);
+    let twos = twos_partial ^ carry_ones;
+    let fours_or_more = carry_twos | (twos_partial & carry_ones);
+
+    // alive with 3 neighbors, or with 2 if the cell already is
+    next[i * size.words + id.x] = ~fours_or_more & twos & (ones | middle);
+}
+
+fn mirror(i: i32, n: i32) -> i32 {
+    var mirrored = i;
+    if (i < 0) {
+        mirrored = -1 - i;
+    } else if (i >= n) {
+        mirrored = 2 * n - 1 - i;
+    }
+    return clamp(mirrored, 0, n - 1);
+}
+
+// a position of a row or column of n cells, at most n beyond either end of
+// it, wrapped around into it
+fn wrap(i: i32, n: i32) -> i32 {
+    if (i < 0) {
+        return i + n;
+    }
+    if (i >= n) {
+        return i - n;
+    }
+    return i;
+}
+
+// the cell at a position beyond the edges, as `Topology::locate` finds it,
+// or -1 if there is none
+fn locate(i: i32, j: i32) -> vec2<i32> {
+    let rows = i32(size.nrows);
+    let cols = i32(size.ncols);
+    let beyond_rows = i < 0 || i >= rows;
+    let beyond_cols = j < 0 || j >= cols;
+    var x = i;
+    var y = j;
+    switch size.topology {
+        case 1u: {
+            if (beyond_rows || beyond_cols) {
+                return vec2<i32>(-1, -1);
+            }
+        }
+        case 2u: {
+            if (beyond_rows) {
+                y = cols - 1 - j;
+            }
+        }
+        case 3u: {
+            if (beyond_cols) {
+                x = rows - 1 - i;
+            }
+            if (beyond_rows) {
+                y = cols - 1 - j;
+            }
+        }
+        case 4u: {
+            x = mirror(i, rows);
+            y = mirror(j, cols);
+        }
+        default: {}
+    }
+    return vec2<i32>(wrap(x, rows), wrap(y, cols));
+}
+
+fn is_alive(i: u32, j: u32) -> bool {
+    return (atomicLoad(&bordered[i * size.words + j / 32u]) & (1u << (j % 32u))) != 0u;
+}
+
+@compute @workgroup_size(64)
+fn border(@builtin(global_invocation_id) id: vec3<u32>) {
+    // the top row, then the bottom row, then the left and right ends of the
+    // rows in between
+    let width = size.ncols + 2u;
+    let n = id.x;
+    if (n >= 2u * width + 2u * size.nrows) {
+        return;
+    }
+    var i = 0u;
+    var j = n;
+    if (n >= 2u * width) {
+        i = (n - 2u * width) / 2u + 1u;
+        j = select(0u, size.ncols + 1u, (n - 2u * width) % 2u == 1u);
+    } else if (n >= width) {
+        i = size.nrows + 1u;
+        j = n - width;
+    }
+    let across = locate(i32(i) - 1, i32(j) - 1);
+    let word = i * size.words + j / 32u;
+    let bit = 1u << (j % 32u);
+    if (across.x >= 0 && is_alive(u32(across.x) + 1u, u32(across.y) + 1u)) {
+        atomicOr(&bordered[word], bit);
+    } else {
+        atomicAnd(&bordered[word], ~bit);
+    }
+}
+"#;
+
+/// Number of invocations in a workgroup, as declared in the shader.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Buffers for a grid of one size, reused until the size changes.
+struct Buffers {
+    /// Number of rows and columns of the grid
+    size: (usize, usize),
+    /// Size of the grid and its topology, as the shader sees them
+    params: wgpu::Buffer,
+    /// Two generations of cells, which take turns at being the current one
+    /// and the next one
+    grids: [wgpu::Buffer; 2],
+    /// Copy of the last generation that can be read back
+    staging: wgpu::Buffer,
+    /// Bind groups working out the next generation from each of the grids
+    steps: [wgpu::BindGroup; 2],
+    /// Bind groups filling in the border of each of the grids
+    borders: [wgpu::BindGroup; 2],
+}
+
+/// A GPU the next generations are worked out on.
+pub struct GpuEngine {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    border_pipeline: wgpu::ComputePipeline,
+    buffers: Option<Buffers>,
+}
+
+impl std::fmt::Debug for GpuEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("GpuEngine").finish_non_exhaustive()
+    }
+}
+
+impl GpuEngine {
+    pub fn new() -> Result<GpuEngine> {
+        //! Finds a GPU and compiles the shaders on it.
+        let instance: wgpu::Instance = wgpu::Instance::default();
+        let adapter: wgpu::Adapter =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                ..Default::default()
+            }))
+            .ok_or_else(|| anyhow!("No GPU found for the GPU engine"))?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None))
+                .map_err(|e| anyhow!("Failed to open the GPU: {}", e))?;
+        let module: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("conway"),
+                source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+            });
+        let pipeline = |entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("conway"),
+                layout: None,
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let (pipeline, border_pipeline) = (pipeline("main"), pipeline("border"));
+        Ok(GpuEngine {
+            device,
+            queue,
+            pipeline,
+            border_pipeline,
+            buffers: None,
+        })
+    }
+
+    fn prepare_buffers(&mut self, nrows: usize, ncols: usize, words: usize) {
+        //! Creates the buffers for a grid of the given size, unless the ones
+        //! it has are for that size already.
+        if self
+            .buffers
+            .as_ref()
+            .is_some_and(|buffers| buffers.size == (nrows, ncols))
+        {
+            return;
+        }
+        let bytes: u64 = (words * 4) as u64;
+        let buffer = |usage: wgpu::BufferUsages, size: u64| {
+            self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("conway"),
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        let params: wgpu::Buffer = buffer(
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            16,
+        );
+        let grids: [wgpu::Buffer; 2] = [(); 2].map(|_| {
+            buffer(
+                wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_DST
+                    | wgpu::BufferUsages::COPY_SRC,
+                bytes,
+            )
+        });
+        let staging: wgpu::Buffer = buffer(
+            wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            bytes,
+        );
+        let bind_group = |pipeline: &wgpu::ComputePipeline, grids: &[(u32, &wgpu::Buffer)]| {
+            let entries: Vec<wgpu::BindGroupEntry> = [(0, &params)]
+                .iter()
+                .chain(grids)
+                .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                    binding: *binding,
+                    resource: buffer.as_entire_binding(),
+                })
+                .collect();
+            self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("conway"),
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &entries,
+            })
+        };
+        let steps: [wgpu::BindGroup; 2] = [0, 1]
+            .map(|from| bind_group(&self.pipeline, &[(1, &grids[from]), (2, &grids[1 - from])]));
+        let borders: [wgpu::BindGroup; 2] =
+            [0, 1].map(|grid| bind_group(&self.border_pipeline, &[(3, &grids[grid])]));
+        self.buffers = Some(Buffers {
+            size: (nrows, ncols),
+            params,
+            grids,
+            staging,
+            steps,
+            borders,
+        });
+    }
+
+    pub fn next_generations(
+        &mut self,
+        grid: &[Vec<Cell>],
+        topology: Topology,
+        generations: usize,
+    ) -> Result<BitGrid> {
+        //! Calculates which cells of the grid are alive the given number of
+        //! generations later. The generations in between stay on the GPU;
+        //! only the last one is read back.
+        let current: BitGrid = BitGrid::pack(grid, topology);
+        let (nrows, ncols) = current.size();
+        let words: Vec<u32> = current.to_words32();
+        let row_words: usize = words.len() / (nrows + 2);
+
+        self.prepare_buffers(nrows, ncols, words.len());
+        let Some(buffers) = &self.buffers else {
+            return Err(anyhow!("GPU buffers missing"));
+        };
+        let params: [u32; 4] = [
+            nrows as u32,
+            row_words as u32,
+            ncols as u32,
+            topology_code(topology),
+        ];
+        self.queue
+            .write_buffer(&buffers.params, 0, &to_bytes(&params));
+        self.queue
+            .write_buffer(&buffers.grids[0], 0, &to_bytes(&words));
+
+        let mut encoder: wgpu::CommandEncoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let border_cells: u32 = (2 * (ncols + 2) + 2 * nrows) as u32;
+        for generation in 0..generations {
+            let from: usize = generation % 2;
+            // the first generation was packed with its border
+            if generation > 0 {
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+                pass.set_pipeline(&self.border_pipeline);
+                pass.set_bind_group(0, &buffers.borders[from], &[]);
+                pass.dispatch_workgroups(border_cells.div_ceil(WORKGROUP_SIZE), 1, 1);
+            }
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &buffers.steps[from], &[]);
+            pass.dispatch_workgroups((row_words as u32).div_ceil(WORKGROUP_SIZE), nrows as u32, 1);
+        }
+        encoder.copy_buffer_to_buffer(
+            &buffers.grids[generations % 2],
+            0,
+            &buffers.staging,
+            0,
+            (words.len() * 4) as u64,
+        );
+        self.queue.submit([encoder.finish()]);
+
+        // wait for the result, and read it back
+        let slice: wgpu::BufferSlice = buffers.staging.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|e| anyhow!("GPU engine stopped: {}", e))?
+            .map_err(|e| anyhow!("Failed to read from the GPU: {}", e))?;
+        let next: Vec<u32> = slice
+            .get_mapped_range()
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        buffers.staging.unmap();
+        Ok(BitGrid::from_words32(nrows, ncols, &next))
+    }
+}
+
+fn topology_code(topology: Topology) -> u32 {
+    //! Number the shader knows the topology by.
+    match topology {
+        Topology::Torus => 0,
+        Topology::Plane => 1,
+        Topology::Klein => 2,
+        Topology::CrossSurface => 3,
+        Topology::Mirror => 4,
+    }
+}
+
+fn to_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
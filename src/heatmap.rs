@@ -0,0 +1,104 @@
+//! This module contains the Heatmap struct, which keeps track of how often
+//! each position of the grid has been alive over the last few generations.
+
+use std::collections::VecDeque;
+
+use super::conway::Cell;
+use super::window::{Color, ColorPair};
+
+/// Colors used for increasing amounts of activity, from rarely to always alive.
+const HEAT_COLORS: [Color; 5] = [
+    Color::Blue,
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Red,
+];
+
+/// Per-cell accumulation buffer counting how many of the last `generations`
+/// generations each position was alive in.
+pub struct Heatmap {
+    /// Number of generations the counts are taken over
+    generations: usize,
+    /// Alive state of every position, for each of the recorded generations
+    history: VecDeque<Vec<bool>>,
+    /// Number of recorded generations each position was alive in
+    counts: Vec<u32>,
+    /// Number of columns in the recorded grids
+    cols: usize,
+    /// Whether the heatmap is shown instead of the normal colors
+    enabled: bool,
+}
+
+impl Heatmap {
+    pub fn new(generations: usize) -> Heatmap {
+        Heatmap {
+            generations: generations.max(1),
+            history: VecDeque::new(),
+            counts: vec![],
+            cols: 0,
+            enabled: false,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    pub fn clear(&mut self) {
+        //! Forgets all recorded generations.
+        self.history.clear();
+        self.counts.clear();
+        self.cols = 0;
+    }
+
+    pub fn record(&mut self, grid: &[Vec<Cell>]) {
+        //! Adds a generation to the heatmap, dropping the oldest one if
+        //! `generations` generations have already been recorded.
+        let cols: usize = grid.first().map_or(0, |row| row.len());
+        let alive: Vec<bool> = grid.iter().flatten().map(|cell| cell.is_alive()).collect();
+        if alive.len() != self.counts.len() || cols != self.cols {
+            // the grid changed size, so the old counts no longer line up
+            self.clear();
+            self.counts = vec![0; alive.len()];
+            self.cols = cols;
+        }
+
+        for (count, alive) in self.counts.iter_mut().zip(&alive) {
+            *count += *alive as u32;
+        }
+        self.history.push_back(alive);
+
+        if self.history.len() > self.generations {
+            if let Some(oldest) = self.history.pop_front() {
+                for (count, alive) in self.counts.iter_mut().zip(&oldest) {
+                    *count -= *alive as u32;
+                }
+            }
+        }
+    }
+
+    pub fn heat(&self, i: usize, j: usize) -> f32 {
+        //! Returns the fraction of recorded generations in which the position
+        //! was alive, from 0 to 1.
+        match self.counts.get(i * self.cols + j) {
+            Some(count) if !self.history.is_empty() => *count as f32 / self.history.len() as f32,
+            _ => 0.0,
+        }
+    }
+
+    pub fn color(&self, i: usize, j: usize, foreground: Color) -> Option<ColorPair> {
+        //! Returns the color a position is drawn with, or `None` if it has
+        //! not been alive in any of the recorded generations.
+        let heat: f32 = self.heat(i, j);
+        if heat <= 0.0 {
+            return None;
+        }
+        let index: usize = ((heat * HEAT_COLORS.len() as f32) as usize).min(HEAT_COLORS.len() - 1);
+        Some(ColorPair::new(foreground, HEAT_COLORS[index]))
+    }
+}
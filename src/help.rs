@@ -0,0 +1,111 @@
+//! This module contains the help window, opened with '?', which lists every
+//! key and the current settings over the grid until a key is pressed.
+
+use anyhow::Result;
+
+use super::status::fit_to_width;
+use super::theme::Theme;
+use super::window::Window;
+
+/// Keys listed in the help window, with what they do.
+const KEYS: [(&str, &str); 44] = [
+    ("q", "quit"),
+    ("p", "pause or resume"),
+    ("b", "step back while paused"),
+    (".", "advance one generation"),
+    ("n", "fast-forward"),
+    ("G", "jump to a generation"),
+    ("a / s", "longer / shorter timeout"),
+    ("] / [", "more / fewer steps per frame"),
+    ("T", "turbo mode"),
+    ("j / k", "next / previous demo seed"),
+    ("/", "search the demo seeds"),
+    ("Left/Right", "fold / unfold a demo folder"),
+    ("h", "activity heatmap"),
+    ("g", "grid lines"),
+    ("t", "dim dead cells"),
+    ("m", "settings menu"),
+    ("M", "minimap"),
+    ("Z", "zoom out to the whole grid"),
+    ("S", "births and deaths"),
+    ("i", "inspect mode"),
+    ("Arrows", "move the cursor"),
+    ("H J K L", "move the view"),
+    ("v", "start or clear a selection"),
+    ("w", "save the selection"),
+    ("x", "clear the selected cells"),
+    ("f", "fill the selected cells"),
+    ("r", "randomize the selected cells"),
+    ("z", "add random cells"),
+    ("Ctrl-L", "clear the grid"),
+    ("N", "new random soup"),
+    ("+ / -", "denser / sparser random fills"),
+    ("R", "rain"),
+    ("W", "wrap edges or not"),
+    (":", "type a new rule"),
+    ("Space", "toggle the cell at the cursor"),
+    ("Drag", "paint cells, with --mouse"),
+    ("e", "next brush"),
+    ("u", "undo"),
+    ("Ctrl-R", "redo"),
+    ("c", "show the code of the soup"),
+    ("y", "copy the grid as RLE"),
+    ("Shift+N", "store the grid in bookmark N"),
+    ("1-9", "restore bookmark N"),
+    ("?", "this help"),
+];
+
+/// Line at the bottom of the help window.
+const FOOTER: &str = "Press any key to close";
+
+pub fn draw(parent: &Window, settings: &str, theme: &Theme) -> Result<()> {
+    //! Draws the help window in the middle of the window the grid was just
+    //! drawn and refreshed in. The keys are split into as many columns as
+    //! needed to fit its height.
+    let keys: Vec<String> = KEYS
+        .iter()
+        .map(|(key, effect)| format!("{:7} {}", key, effect))
+        .collect();
+    // box, settings and footer lines, and a blank line after the settings
+    // and before the footer
+    let rows_available: usize = (parent.get_rows() as usize).saturating_sub(6).max(1);
+    let columns: usize = keys.len().div_ceil(rows_available);
+    let rows_per_column: usize = keys.len().div_ceil(columns);
+    // where each column starts, two spaces after the longest key before it
+    let starts: Vec<usize> = keys
+        .chunks(rows_per_column)
+        .scan(0, |start, column| {
+            let this: usize = *start;
+            *start += column.iter().map(|key| key.len()).max().unwrap_or(0) + 2;
+            Some(this)
+        })
+        .collect();
+    let content_width: usize = keys
+        .chunks(rows_per_column)
+        .zip(&starts)
+        .map(|(column, start)| start + column.iter().map(|key| key.len()).max().unwrap_or(0))
+        .max()
+        .unwrap_or(0)
+        .max(settings.len())
+        .max(FOOTER.len());
+    // one column of padding on each side, inside the box
+    let cols: i32 = (content_width as i32 + 4).min(parent.get_cols());
+    let rows: i32 = (rows_per_column as i32 + 6).min(parent.get_rows());
+    let width: usize = (cols as usize).saturating_sub(4);
+    let window: Window = Window::centered(parent, rows, cols);
+
+    window.erase();
+    window.draw_box();
+    let status = theme.status.as_ref();
+    window.print(2, 1, &fit_to_width(settings, width), status)?;
+    for (n, key) in keys.iter().enumerate() {
+        let x: usize = starts[n / rows_per_column];
+        let y: i32 = (n % rows_per_column) as i32 + 3;
+        if x < width && y < rows - 3 {
+            window.print(x as i32 + 2, y, &fit_to_width(key, width - x), None)?;
+        }
+    }
+    window.print(2, rows - 2, &fit_to_width(FOOTER, width), status)?;
+    window.refresh();
+    Ok(())
+}
@@ -0,0 +1,99 @@
+//! This module contains the History struct, which keeps the most recent
+//! frames so the simulation can be stepped backward. Frames are kept whole,
+//! with the ages and colors of their cells, so large grids keep fewer of
+//! them.
+
+use std::collections::VecDeque;
+
+use super::conway::Cell;
+
+/// Default number of frames that can be stepped back through.
+pub const DEFAULT_HISTORY_FRAMES: usize = 100;
+
+/// Most memory the kept frames may take up. Grids whose frames are larger
+/// than this keep no history at all.
+const MAX_HISTORY_BYTES: usize = 256 << 20;
+
+/// Ring buffer of the most recently displayed frames, oldest first.
+pub struct History {
+    /// Generation and grid of each of the recorded frames
+    frames: VecDeque<(u64, Vec<Vec<Cell>>)>,
+    /// Maximum number of frames kept
+    capacity: usize,
+    /// Maximum memory the kept frames may take up, in bytes
+    max_bytes: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new(DEFAULT_HISTORY_FRAMES)
+    }
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        History {
+            frames: VecDeque::new(),
+            capacity,
+            max_bytes: MAX_HISTORY_BYTES,
+        }
+    }
+
+    pub fn push(&mut self, generation: u64, grid: &[Vec<Cell>]) {
+        //! Records a frame, forgetting the oldest ones if the history is full,
+        //! either by number of frames or by the memory they take up.
+        let frame_bytes: usize =
+            grid.len() * grid.first().map_or(0, Vec::len) * std::mem::size_of::<Cell>();
+        let capacity: usize = self.capacity.min(self.max_bytes / frame_bytes.max(1));
+        if capacity == 0 {
+            self.frames.clear();
+            return;
+        }
+        while self.frames.len() >= capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back((generation, grid.to_vec()));
+    }
+
+    pub fn pop(&mut self) -> Option<(u64, Vec<Vec<Cell>>)> {
+        //! Removes and returns the most recent frame.
+        self.frames.pop_back()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conway::new_grid;
+
+    #[test]
+    fn frames_are_kept_up_to_the_capacity() {
+        let mut history: History = History::new(3);
+        let grid: Vec<Vec<Cell>> = new_grid(4, 4);
+        for generation in 0..5 {
+            history.push(generation, &grid);
+        }
+        assert_eq!(history.pop().map(|(generation, _)| generation), Some(4));
+        assert_eq!(history.pop().map(|(generation, _)| generation), Some(3));
+        assert_eq!(history.pop().map(|(generation, _)| generation), Some(2));
+        assert!(history.pop().is_none());
+    }
+
+    #[test]
+    fn large_frames_are_kept_up_to_the_memory_limit() {
+        let mut history: History = History::new(10);
+        // room for four frames of 8x8 cells
+        history.max_bytes = 4 * 64 * std::mem::size_of::<Cell>();
+        for generation in 0..10 {
+            history.push(generation, &new_grid(8, 8));
+        }
+        assert_eq!(history.frames.len(), 4);
+        // a frame larger than the limit leaves nothing to step back to
+        history.push(10, &new_grid(20, 20));
+        assert!(history.pop().is_none());
+    }
+}
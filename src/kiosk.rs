@@ -0,0 +1,117 @@
+//! This module contains kiosk mode, a read-only mode for public displays.
+//! Every key is ignored except the quit sequence, and the grid is reseeded
+//! whenever the run stops being interesting.
+//!
+//! Runs stop being interesting when they stagnate, which the demo's
+//! slideshow also watches for.
+
+use std::collections::VecDeque;
+
+use super::conway::Cell;
+
+/// Number of recent frames a frame is compared against. A run that repeats
+/// itself within this many frames has settled into still lifes and
+/// oscillators of a period this long or shorter, like the pentadecathlon.
+const STAGNATION_PERIOD: usize = 15;
+
+/// Number of generations after which the grid is reseeded anyway, for runs
+/// that never settle, like gliders flying around the wrapping edges.
+pub const MAX_GENERATIONS: u64 = 5000;
+
+/// Read-only kiosk mode.
+#[derive(Debug, Clone)]
+pub struct Kiosk {
+    /// Keys that have to be typed in order to quit
+    quit_sequence: Vec<char>,
+    /// Number of keys of the quit sequence typed so far
+    typed: usize,
+    /// Recent frames, to tell when the run has stagnated
+    stagnation: Stagnation,
+    /// Whether an alarm that would pause the run went off, which reseeds
+    /// the grid instead
+    alarmed: bool,
+}
+
+/// Watches a run for frames that repeat, to tell when it has settled.
+#[derive(Debug, Clone, Default)]
+pub struct Stagnation {
+    /// Alive state of every position, for each of the most recent frames
+    recent: VecDeque<Vec<bool>>,
+    /// Generation of the last frame recorded, as the same frame is looked at
+    /// again until the next one is due
+    generation: Option<u64>,
+}
+
+impl Kiosk {
+    pub fn new(quit_sequence: &str) -> Kiosk {
+        let mut quit_sequence: Vec<char> = quit_sequence.chars().collect();
+        if quit_sequence.is_empty() {
+            quit_sequence.push('q');
+        }
+        Kiosk {
+            quit_sequence,
+            typed: 0,
+            stagnation: Stagnation::default(),
+            alarmed: false,
+        }
+    }
+
+    pub fn press(&mut self, key: char) -> bool {
+        //! Records a keypress. Returns true once the whole quit sequence has
+        //! been typed; a wrong key starts the sequence over.
+        if key == self.quit_sequence[self.typed] {
+            self.typed += 1;
+        } else {
+            self.typed = (key == self.quit_sequence[0]) as usize;
+        }
+        if self.typed == self.quit_sequence.len() {
+            self.typed = 0;
+            return true;
+        }
+        false
+    }
+
+    pub fn is_stagnant(&mut self, grid: &[Vec<Cell>], generation: u64) -> bool {
+        //! Records a frame, returning true if the run has stagnated.
+        self.stagnation.is_stagnant(grid, generation)
+    }
+
+    pub fn raise_alarm(&mut self) {
+        self.alarmed = true;
+    }
+
+    pub fn is_alarmed(&self) -> bool {
+        self.alarmed
+    }
+
+    pub fn clear(&mut self) {
+        //! Forgets the recent frames and the alarm, after the grid was
+        //! reseeded.
+        self.stagnation.clear();
+        self.alarmed = false;
+    }
+}
+
+impl Stagnation {
+    pub fn is_stagnant(&mut self, grid: &[Vec<Cell>], generation: u64) -> bool {
+        //! Records the frame of a generation, returning true if the grid is
+        //! empty or the frame repeats one of the recent frames. A generation
+        //! that was just recorded isn't recorded again.
+        if self.generation.replace(generation) == Some(generation) {
+            return false;
+        }
+        let alive: Vec<bool> = grid.iter().flatten().map(|cell| cell.is_alive()).collect();
+        let stagnant: bool = !alive.contains(&true) || self.recent.contains(&alive);
+        self.recent.push_back(alive);
+        if self.recent.len() > STAGNATION_PERIOD {
+            self.recent.pop_front();
+        }
+        stagnant
+    }
+
+    pub fn clear(&mut self) {
+        //! Forgets the recent frames, after the grid changed.
+        self.recent.clear();
+        self.generation = None;
+    }
+}
@@ -0,0 +1,325 @@
+//! This module contains the built-in library of well-known patterns, which
+//! `--place` puts on the grid by name, and the small expressions it takes:
+//! a name, optionally followed by where the pattern goes and how it is
+//! turned, e.g. `glider@5,5` or `blinker@20,10:rot90`.
+
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+
+use super::fetch::archive_name;
+use super::pattern::{Pattern, Placement, Rotation, Transform};
+
+/// Where a library pattern is offered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shelf {
+    /// Put on the grid with `--place`
+    Place,
+    /// Only named by the census
+    Census,
+    /// Both placed and named by the census
+    Both,
+}
+
+/// Patterns in the library, under their names in the LifeWiki pattern
+/// archive, with the name they are known by, their cells in the seed file
+/// format and where they are offered. Census objects are given in one of
+/// their phases.
+const PATTERNS: [(&str, &str, &str, Shelf); 23] = [
+    ("block", "Block", "**\n**", Shelf::Both),
+    ("beehive", "Beehive", ".**.\n*..*\n.**.", Shelf::Both),
+    ("loaf", "Loaf", ".**.\n*..*\n.*.*\n..*.", Shelf::Both),
+    ("boat", "Boat", "**.\n*.*\n.*.", Shelf::Both),
+    ("ship", "Ship", "**.\n*.*\n.**", Shelf::Census),
+    ("tub", "Tub", ".*.\n*.*\n.*.", Shelf::Census),
+    ("pond", "Pond", ".**.\n*..*\n*..*\n.**.", Shelf::Census),
+    (
+        "longboat",
+        "Long boat",
+        "**..\n*.*.\n.*.*\n..*.",
+        Shelf::Census,
+    ),
+    ("barge", "Barge", ".*..\n*.*.\n.*.*\n..*.", Shelf::Census),
+    (
+        "mango",
+        "Mango",
+        ".**..\n*..*.\n.*..*\n..**.",
+        Shelf::Census,
+    ),
+    ("blinker", "Blinker", "***", Shelf::Both),
+    ("toad", "Toad", ".***\n***.", Shelf::Both),
+    ("beacon", "Beacon", "**..\n**..\n..**\n..**", Shelf::Both),
+    (
+        "pulsar",
+        "Pulsar",
+        "..***...***..\n\
+         .............\n\
+         *....*.*....*\n\
+         *....*.*....*\n\
+         *....*.*....*\n\
+         ..***...***..\n\
+         .............\n\
+         ..***...***..\n\
+         *....*.*....*\n\
+         *....*.*....*\n\
+         *....*.*....*\n\
+         .............\n\
+         ..***...***..",
+        Shelf::Place,
+    ),
+    (
+        "pentadecathlon",
+        "Pentadecathlon",
+        "..*....*..\n**.****.**\n..*....*..",
+        Shelf::Place,
+    ),
+    ("glider", "Glider", ".*.\n..*\n***", Shelf::Both),
+    (
+        "lwss",
+        "Lightweight spaceship",
+        ".*..*\n*....\n*...*\n****.",
+        Shelf::Both,
+    ),
+    (
+        "mwss",
+        "Middleweight spaceship",
+        "...*..\n.*...*\n*.....\n*....*\n*****.",
+        Shelf::Place,
+    ),
+    (
+        "hwss",
+        "Heavyweight spaceship",
+        "...**..\n.*....*\n*......\n*.....*\n******.",
+        Shelf::Place,
+    ),
+    ("rpentomino", "R-pentomino", ".**\n**.\n.*.", Shelf::Place),
+    ("acorn", "Acorn", ".*.....\n...*...\n**..***", Shelf::Place),
+    (
+        "diehard",
+        "Diehard",
+        "......*.\n**......\n.*...***",
+        Shelf::Place,
+    ),
+    (
+        "gosperglidergun",
+        "Gosper glider gun",
+        "........................*...........\n\
+         ......................*.*...........\n\
+         ............**......**............**\n\
+         ...........*...*....**............**\n\
+         **........*.....*...**..............\n\
+         **........*...*.**....*.*...........\n\
+         ..........*.....*.......*...........\n\
+         ...........*...*....................\n\
+         ............**......................",
+        Shelf::Place,
+    ),
+];
+
+pub fn names() -> Vec<&'static str> {
+    PATTERNS
+        .iter()
+        .filter(|(_, _, _, shelf)| *shelf != Shelf::Census)
+        .map(|(name, _, _, _)| *name)
+        .collect()
+}
+
+pub fn census_objects() -> Vec<(String, Pattern)> {
+    //! Returns the patterns the census names objects after, under their
+    //! names in lowercase, as the census reports them.
+    PATTERNS
+        .iter()
+        .filter(|(_, _, _, shelf)| *shelf != Shelf::Place)
+        // the library's own seeds always parse
+        .filter_map(|(_, title, seed, _)| {
+            Some((title.to_lowercase(), Pattern::from_seed_text(seed).ok()?))
+        })
+        .collect()
+}
+
+pub fn find(name: &str) -> Result<Pattern> {
+    //! Returns the library pattern with the given name, which is matched as
+    //! in the pattern archive, ignoring case, spaces and punctuation, so
+    //! that `R-pentomino` finds `rpentomino`.
+    let wanted: String = archive_name(name);
+    let (_, title, seed, _) = PATTERNS
+        .iter()
+        .filter(|(_, _, _, shelf)| *shelf != Shelf::Census)
+        .find(|(name, _, _, _)| *name == wanted)
+        .ok_or_else(|| {
+            anyhow!(
+                "no pattern '{}' in the library, which has {}",
+                name,
+                names().join(", ")
+            )
+        })?;
+    let mut pattern: Pattern = Pattern::from_seed_text(seed)?;
+    pattern.metadata.name = Some(title.to_string());
+    Ok(pattern)
+}
+
+/// A library pattern placed on the grid from the command line, written
+/// `NAME[@X,Y][:TRANSFORM]...`, where each transform is `rot90`, `rot180`,
+/// `rot270`, `flip-h`, `flip-v` or `scaleN`.
+#[derive(Debug, Clone)]
+pub struct Placed {
+    /// Pattern, already turned as the expression says, but not yet scaled
+    pub pattern: Pattern,
+    /// Size of the square block each cell is blown up into, from all the
+    /// `scaleN` transforms together
+    pub scale: usize,
+    /// Where it goes, in the middle of the grid unless `@X,Y` is given
+    pub placement: Placement,
+}
+
+impl FromStr for Placed {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        //! Parses a pattern expression. Transforms are applied one after the
+        //! other, in the order they are written, so `rot90:rot90` turns the
+        //! pattern by a half and `scale2:scale3` blows each cell up sixfold.
+        //! Scaling commutes with turning, so it is kept aside until the size
+        //! of the grid is known, and `scaled` does it.
+        let mut parts = s.split(':');
+        let head: &str = parts.next().unwrap_or_default();
+        let (name, placement) = match head.split_once('@') {
+            Some((name, at)) => (name, at.parse::<Placement>()?),
+            None => (head, Placement::Center),
+        };
+        let mut pattern: Pattern = find(name.trim())?;
+        let mut scale: usize = 1;
+        for suffix in parts {
+            let mut transform: Transform = Transform::default();
+            match suffix.trim() {
+                "rot90" => transform.rotation = Some(Rotation::Quarter),
+                "rot180" => transform.rotation = Some(Rotation::Half),
+                "rot270" => transform.rotation = Some(Rotation::ThreeQuarters),
+                "flip-h" => transform.flip_horizontal = true,
+                "flip-v" => transform.flip_vertical = true,
+                suffix => {
+                    let factor: usize = suffix
+                        .strip_prefix("scale")
+                        .and_then(|scale| scale.parse::<usize>().ok())
+                        .filter(|scale| *scale > 0)
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "unknown transform '{}', expected rot90, rot180, rot270, \
+                                 flip-h, flip-v or scaleN",
+                                suffix
+                            )
+                        })?;
+                    scale = scale
+                        .checked_mul(factor)
+                        .ok_or_else(|| anyhow!("the pattern is scaled too much: '{}'", s))?;
+                }
+            }
+            pattern = pattern.transformed(transform);
+        }
+        Ok(Placed {
+            pattern,
+            scale,
+            placement,
+        })
+    }
+}
+
+impl Placed {
+    pub fn scaled(&self, nrows: usize, ncols: usize) -> Result<Pattern> {
+        //! Returns the pattern blown up by its scale, after checking that it
+        //! fits a grid of `nrows` by `ncols` cells, so that a huge scale is
+        //! refused before any cell is expanded.
+        self.pattern.check_scaled_fits(self.scale, nrows, ncols)?;
+        Ok(self.pattern.transformed(Transform {
+            scale: self.scale,
+            ..Transform::default()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(expression: &str) -> Vec<(usize, usize)> {
+        let placed: Placed = expression.parse().unwrap();
+        let mut cells: Vec<(usize, usize)> = placed.scaled(usize::MAX, usize::MAX).unwrap().cells;
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn every_pattern_is_found_by_name() {
+        for name in names() {
+            let pattern: Pattern = find(name).unwrap();
+            assert!(!pattern.cells.is_empty(), "{}", name);
+            assert!(pattern.metadata.name.is_some(), "{}", name);
+        }
+        assert_eq!(find("R-Pentomino").unwrap().cells.len(), 5);
+        assert!(find("unicorn").is_err());
+    }
+
+    #[test]
+    fn census_only_patterns_are_not_placed() {
+        assert!(find("ship").is_err());
+        let objects: Vec<String> = census_objects().into_iter().map(|(name, _)| name).collect();
+        assert!(objects.contains(&"long boat".to_string()));
+        assert!(objects.contains(&"lightweight spaceship".to_string()));
+        assert!(!objects.contains(&"gosper glider gun".to_string()));
+    }
+
+    #[test]
+    fn transforms_apply_in_the_order_written() {
+        assert_eq!(cells("glider:rot90:rot90"), cells("glider:rot180"));
+        assert_eq!(cells("glider:rot90:rot180"), cells("glider:rot270"));
+        assert_eq!(cells("glider:flip-h:flip-h"), cells("glider"));
+        assert_eq!(cells("glider:scale2:scale3"), cells("glider:scale6"));
+        assert_ne!(cells("glider:rot90:flip-h"), cells("glider:flip-h:rot90"));
+
+        let turned: Pattern = find("glider")
+            .unwrap()
+            .transformed(Transform {
+                rotation: Some(Rotation::Quarter),
+                ..Transform::default()
+            })
+            .transformed(Transform {
+                flip_horizontal: true,
+                ..Transform::default()
+            });
+        let mut turned: Vec<(usize, usize)> = turned.cells;
+        turned.sort();
+        assert_eq!(cells("glider:rot90:flip-h"), turned);
+    }
+
+    #[test]
+    fn placements_are_read_from_the_name() {
+        let placed: Placed = "blinker@20,10:rot90".parse().unwrap();
+        assert_eq!(placed.placement, Placement::At { x: 20, y: 10 });
+        assert_eq!((placed.pattern.rows, placed.pattern.cols), (3, 1));
+        let placed: Placed = "blinker".parse().unwrap();
+        assert_eq!(placed.placement, Placement::Center);
+    }
+
+    #[test]
+    fn scaled_patterns_are_checked_before_they_are_expanded() {
+        let placed: Placed = "glider:scale100000".parse().unwrap();
+        assert_eq!(placed.pattern.cells.len(), 5);
+        assert!(placed.scaled(1000, 1000).is_err());
+        let placed: Placed = "glider:scale2".parse().unwrap();
+        assert_eq!(placed.scaled(6, 6).unwrap().cells.len(), 20);
+        assert!(placed.scaled(5, 6).is_err());
+    }
+
+    #[test]
+    fn invalid_expressions_are_refused() {
+        for expression in [
+            "unicorn",
+            "glider:rot45",
+            "glider:scale0",
+            "glider:scale",
+            "glider:scale4294967296:scale4294967296",
+            "glider@x,y",
+        ] {
+            assert!(expression.parse::<Placed>().is_err(), "{}", expression);
+        }
+    }
+}
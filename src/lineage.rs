@@ -0,0 +1,68 @@
+//! This module contains lineages, which color each alive cell by the region
+//! of the starting grid its ancestors came from. The grid is split into a few
+//! regions when the run starts, and newborn cells take the lineage most of
+//! their parents have, so it shows how the areas of a soup spread and mix.
+
+use super::conway::Cell;
+use super::neighborhood::Neighborhood;
+use super::topology::Topology;
+use super::window::{Color, ColorPair};
+
+/// Number of rows and columns of regions the starting grid is split into.
+const REGIONS: (usize, usize) = (2, 3);
+
+/// Colors the lineages are drawn in, one per region, row by row.
+const LINEAGE_COLORS: [Color; REGIONS.0 * REGIONS.1] = [
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+];
+
+pub fn tag_regions(grid: &mut [Vec<Cell>]) {
+    //! Starts a lineage in each region of the grid, giving every alive cell
+    //! the lineage of the region it is in.
+    let nrows: usize = grid.len();
+    let ncols: usize = grid[0].len();
+    for cell in grid.iter_mut().flatten() {
+        cell.lineage = cell.is_alive().then(|| {
+            let region: usize = cell.x * REGIONS.0 / nrows * REGIONS.1 + cell.y * REGIONS.1 / ncols;
+            region as u8
+        });
+    }
+}
+
+pub fn parents_lineage(
+    cell: &Cell,
+    grid: &[Vec<Cell>],
+    topology: Topology,
+    neighborhood: Neighborhood,
+) -> Option<u8> {
+    //! Returns the lineage most of the cell's alive neighbors have, which the
+    //! cell takes when it is born. Ties go to the lineage of the earlier
+    //! region; cells whose parents have no lineage, such as cells brought to
+    //! life by hand, have none either.
+    let nrows: usize = grid.len();
+    let ncols: usize = grid[0].len();
+    let mut counts: [usize; LINEAGE_COLORS.len()] = [0; LINEAGE_COLORS.len()];
+    for (i, j) in neighborhood.offsets(cell.x) {
+        let Some((x, y)) = topology.locate(cell.x as isize + i, cell.y as isize + j, nrows, ncols)
+        else {
+            continue;
+        };
+        if let Some(lineage) = grid[x][y].lineage.filter(|_| grid[x][y].is_alive()) {
+            counts[lineage as usize] += 1;
+        }
+    }
+    let most: usize = *counts.iter().max()?;
+    (most > 0).then(|| counts.iter().position(|count| *count == most).unwrap_or(0) as u8)
+}
+
+pub fn color(lineage: u8, alive: Option<&ColorPair>) -> ColorPair {
+    //! Returns the color a cell of the lineage is drawn in, keeping the
+    //! background of the theme's alive color.
+    let background: Color = alive.map(|c| c.background()).unwrap_or(Color::Black);
+    ColorPair::new(LINEAGE_COLORS[lineage as usize], background)
+}
@@ -5,17 +5,104 @@
  * - Any live cell with more than three live neighbours dies, as if by overpopulation.
  * - Any dead cell with exactly three live neighbours becomes a live cell, as if by reproduction.
  *
- * Due to the "infinite" nature of the game, this implementation simply uses wrapping edges.
+ * Other rules can be chosen with --rule.
+ *
+ * Due to the "infinite" nature of the game, this implementation uses wrapping edges by default;
+ * other topologies can be chosen with --topology.
  * */
 
+pub mod alarm;
+pub mod attract;
+pub mod bitgrid;
+pub mod cast;
+pub mod catagolue;
+pub mod census;
+pub mod clipboard;
+pub mod compare;
+pub mod control;
+pub mod convert;
 pub mod conway;
+pub mod csvlog;
 pub mod demo;
+pub mod diff;
+pub mod dna;
+pub mod editor;
+pub mod events;
+pub mod fetch;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod heatmap;
+pub mod help;
+pub mod history;
+pub mod kiosk;
+pub mod library;
+pub mod lineage;
+pub mod margolus;
+pub mod menu;
+pub mod metrics;
+pub mod minimap;
+pub mod neighborhood;
+pub mod paint;
+pub mod pattern;
+pub mod power;
+pub mod recording;
+pub mod rule;
+pub mod script;
+pub mod search;
+pub mod serve;
+pub mod session;
+pub mod soup;
+pub mod soupstats;
+pub mod stability;
+pub mod stats;
+pub mod status;
+pub mod svg;
+pub mod terminal;
+pub mod theme;
+pub mod topology;
+pub mod undo;
+pub mod verify;
 pub mod window;
 
+use alarm::{AlarmAction, Alarms};
 use anyhow::Result;
-use clap::Parser;
-use conway::{initialize, run_frame, Cell, InputHandler, InputType};
+use attract::{Attract, Scene};
+use cast::Cast;
+use census::Census;
+use clap::{Parser, Subcommand};
+use control::ControlSocket;
+use conway::{
+    fast_forward, initialize, initialize_from_pattern, run_frame, Cell, Engine, InputHandler,
+    InputType, Stepper,
+};
+use csvlog::CsvLog;
+use dna::Dna;
+use events::{Event, Events};
+use heatmap::Heatmap;
+use history::History;
+use kiosk::Kiosk;
+use library::Placed;
+use metrics::SimilarityTracker;
 use ncurses::*;
+use pattern::{ExportFormat, Pattern, Placement, Rotation, Transform};
+use power::PowerProfile;
+use recording::{Playback, Recorder};
+use rule::Rule;
+use script::Script;
+use search::SearchOptions;
+use serve::ServeOptions;
+use session::Session;
+use soup::{DistributionSpec, SoupOptions, Symmetry};
+use soupstats::SoupStatsOptions;
+use stability::{CycleDetector, Report};
+use status::{StatusBar, StatusField, DEFAULT_STATUS_FIELDS};
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use svg::SvgOptions;
+use terminal::Screen;
+use theme::Theme;
+use topology::Topology;
 use window::Window;
 
 /// Conway's Game of Life
@@ -23,38 +110,771 @@ use window::Window;
 /// A simple implementation of Conway's Game of Life using ncurses.
 #[derive(Parser)]
 pub struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
     /// Number of alive cells to start with
     #[clap(short = 'a', long = "alive", default_value = "1000")]
     alive: Option<usize>,
-    /// Seed file to start with
-    #[clap(short = 's', long = "seed", default_value = "None")]
-    seed_file: Option<String>,
+    /// Seed file to start with, or - to read a pattern from stdin. Repeat it, adding @X,Y to each file, to put several patterns on the grid
+    #[clap(short = 's', long = "seed")]
+    seed_files: Vec<String>,
+    /// Pattern from the built-in library to put on the grid, as NAME[@X,Y][:TRANSFORM]..., e.g. glider@5,5 or blinker@20,10:rot90; transforms are rot90, rot180, rot270, flip-h, flip-v and scaleN. Repeat it for several patterns
+    #[clap(long = "place")]
+    place: Vec<Placed>,
     /// Timeout in milliseconds
     #[clap(short = 't', long = "timeout", default_value = "100")]
     timeout: i32,
+    /// Aim for this many frames per second instead of waiting a timeout between frames
+    #[clap(long = "fps", conflicts_with = "timeout", value_parser = clap::value_parser!(u32).range(1..=conway::MAX_FPS as i64))]
+    fps: Option<u32>,
     /// What character to use to draw each cell
-    #[clap(short = 'c', long = "character", default_value = "*")]
+    #[clap(short = 'c', long = "character", default_value = "*", value_parser = parse_character)]
     character: char,
+    /// What character to draw dead cells with, e.g. '.' or '·' to show the extent of the grid
+    #[clap(long = "dead-character", default_value = " ", value_parser = parse_character)]
+    dead_character: char,
+    /// Start in turbo mode, running as fast as the machine allows and drawing only some of the frames; toggled with T
+    #[clap(long = "turbo")]
+    turbo: bool,
     /// Run a demo program to see the various seeds
     #[clap(short = 'd', long = "demo")]
     demo: bool,
+    /// Move on to the next seed of the demo after this many seconds, or once the run settles
+    #[clap(long = "slideshow", requires = "demo", value_parser = clap::value_parser!(u64).range(1..))]
+    slideshow: Option<u64>,
+    /// Number of columns in the grid; defaults to what fits in the terminal
+    #[clap(long = "width")]
+    width: Option<usize>,
+    /// Number of rows in the grid; defaults to what fits in the terminal
+    #[clap(long = "height")]
+    height: Option<usize>,
+    /// How the edges of the grid are joined
+    #[clap(long = "topology", value_enum, default_value = "torus")]
+    topology: Topology,
+    /// Rule the simulation follows: B3/S23 and other B/S rules, isotropic non-totalistic rules like B2-a/S12, or MAP strings
+    #[clap(long = "rule", default_value = "B3/S23", value_parser = parse_rule)]
+    rule: Rule,
+    /// Run the same grid side by side under this rule, stepped in lockstep with the one under --rule
+    #[clap(long = "compare-rule", value_parser = parse_rule, conflicts_with = "demo")]
+    compare_rule: Option<Rule>,
+    /// Run the same grid side by side on this topology, stepped in lockstep with the one on --topology
+    #[clap(long = "compare-topology", value_enum, conflicts_with = "demo")]
+    compare_topology: Option<Topology>,
+    /// Probability with which each birth and survival decision of the rule is flipped
+    #[clap(long = "rule-noise", default_value_t = 0.0, value_parser = parse_density)]
+    rule_noise: f64,
+    /// Mirror randomly generated soups
+    #[clap(long = "symmetry", value_enum)]
+    symmetry: Option<Symmetry>,
+    /// Distribution of randomly generated cells: uniform, gaussian[:SIGMA] or power-law[:ALPHA]
+    #[clap(long = "distribution", default_value = "uniform")]
+    distribution: DistributionSpec,
+    /// apgcode to start with, e.g. xq4_153. Overrides -s/--seed
+    #[clap(long = "seed-apgcode")]
+    seed_apgcode: Option<String>,
+    /// Seed of the random number generator used for random soups
+    #[clap(long = "rng-seed")]
+    rng_seed: Option<u64>,
+    /// Reproduce a run shared with the 'c' key
+    #[clap(long = "from-code")]
+    from_code: Option<String>,
+    /// Save the run to this session file every few seconds and when quitting
+    #[clap(long = "session")]
+    session: Option<String>,
+    /// Pick up the run saved in a session file, and keep saving it there unless --session says otherwise
+    #[clap(long = "resume", conflicts_with_all = ["from_code", "demo"])]
+    resume: Option<String>,
+    /// Color theme: one of the built-in themes, or a path to a theme file
+    #[clap(long = "theme", default_value = "default")]
+    theme: String,
+    /// Color cells by age, using a true-color gradient when the terminal supports it
+    #[clap(long = "age-gradient")]
+    age_gradient: bool,
+    /// Draw alive cells as solid blocks in reverse video instead of characters
+    #[clap(long = "inverse")]
+    inverse: bool,
+    /// Draw alive cells by their number of neighbors, from lonely to crowded: . o O @
+    #[clap(long = "neighbor-glyphs")]
+    neighbor_glyphs: bool,
+    /// Start with dead cells drawn dimmed, to show the extent of the grid; toggled with t
+    #[clap(long = "texture")]
+    texture: bool,
+    /// Play the Immigration game: cells have one of two colors, and newborn cells take the color most of their parents have
+    #[clap(long = "immigration")]
+    immigration: bool,
+    /// Color each cell by the region of the starting grid its ancestors came from; newborn cells take the lineage most of their parents have
+    #[clap(long = "lineage", conflicts_with = "immigration")]
+    lineage: bool,
+    /// Start with the panel of births and deaths per generation shown; toggled with S
+    #[clap(long = "stats")]
+    stats: bool,
+    /// Number of generations the activity heatmap ('h') is taken over
+    #[clap(long = "heatmap-generations", default_value_t = conway::DEFAULT_HEATMAP_GENERATIONS)]
+    heatmap_generations: usize,
+    /// Number of frames kept for stepping backward with 'b'; large grids keep fewer, so that the frames take up at most 256 MB
+    #[clap(long = "history", default_value_t = history::DEFAULT_HISTORY_FRAMES)]
+    history: usize,
+    /// Save energy by redrawing less often; enabled automatically on battery
+    #[clap(long = "eco")]
+    eco: bool,
+    /// Number of generations dead cells leave a fading ghost trail for
+    #[clap(long = "trail", default_value = "0")]
+    trail: u32,
+    /// Comma-separated fields shown in the status bar: alive, generation, step, timeout, rule, rule-noise, topology, power, density, seed, pattern, similarity, hints
+    #[clap(long = "status", value_enum, value_delimiter = ',', default_values_t = DEFAULT_STATUS_FIELDS)]
+    status: Vec<StatusField>,
+    /// Hide the status bar, giving its row to the grid
+    #[clap(long = "no-status")]
+    no_status: bool,
+    /// Keep loaded seeds at the top-left corner of the grid instead of centering them
+    #[clap(long = "no-center")]
+    no_center: bool,
+    /// Place loaded seeds with their top-left corner at column X and row Y of the grid
+    #[clap(long = "seed-at", value_name = "X,Y")]
+    seed_at: Option<Placement>,
+    /// Rotate loaded seeds clockwise by 90, 180 or 270 degrees before placing them
+    #[clap(long = "rotate", value_enum, value_name = "DEGREES")]
+    rotate: Option<Rotation>,
+    /// Mirror loaded seeds left to right before placing them
+    #[clap(long = "flip-h")]
+    flip_h: bool,
+    /// Mirror loaded seeds top to bottom before placing them
+    #[clap(long = "flip-v")]
+    flip_v: bool,
+    /// Expand each cell of loaded seeds into an NxN block before placing them
+    #[clap(long = "scale", value_name = "N", default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    scale: u32,
+    /// Repeat loaded seeds across the whole grid, with GAP dead cells between copies; only whole copies are placed
+    #[clap(long = "tile", value_name = "GAP", num_args = 0..=1, default_missing_value = "2")]
+    tile: Option<usize>,
+    /// Cut loaded seeds larger than the grid down to its size, keeping their middle, instead of refusing them
+    #[clap(long = "crop")]
+    crop: bool,
+    /// Format selected regions are written in with 'w'
+    #[clap(long = "export-format", value_enum, default_value = "seed")]
+    export_format: ExportFormat,
+    /// Number of generations advanced per frame; changed with '[' and ']'
+    #[clap(long = "step", default_value = "1")]
+    step: usize,
+    /// Fast-forward this many generations at startup; 'n' skips the same number
+    #[clap(long = "skip")]
+    skip: Option<u64>,
+    /// Fraction of cells brought to life in new soups started with 'N', when randomizing the selected region with 'r', and, relative to 0.5, by 'z'; defaults to the density of the starting soup; changed with '+' and '-'
+    #[clap(long = "fill-density", value_parser = parse_density)]
+    fill_density: Option<f64>,
+    /// Number of random cells brought to life at once with 'z' at a fill density of 0.5, scaled by the fill density
+    #[clap(long = "noise", default_value_t = conway::DEFAULT_NOISE)]
+    noise: usize,
+    /// Bring a few random cells to life every this many generations; 'R' turns it on and off
+    #[clap(long = "rain", value_parser = clap::value_parser!(u64).range(1..))]
+    rain: Option<u64>,
+    /// Raise an alarm when the population rises above this
+    #[clap(long = "alarm-above")]
+    alarm_above: Option<usize>,
+    /// Raise an alarm when the population falls below this
+    #[clap(long = "alarm-below")]
+    alarm_below: Option<usize>,
+    /// What an alarm does: pause the simulation, or only notify
+    #[clap(long = "alarm-action", value_enum, default_value = "pause")]
+    alarm_action: AlarmAction,
+    /// Lock the controls for public displays, and reseed the grid when the run settles
+    #[clap(long = "kiosk")]
+    kiosk: bool,
+    /// Keys that have to be typed in order to quit kiosk mode
+    #[clap(long = "kiosk-quit", default_value = "q")]
+    kiosk_quit: String,
+    /// Switch to a new soup under another rule, density and color theme every this many seconds, or sooner once the run settles
+    #[clap(long = "attract", conflicts_with = "demo", value_parser = clap::value_parser!(u64).range(1..))]
+    attract: Option<u64>,
+    /// Paint cells by dragging the mouse: left button to bring them to life, right to kill them
+    #[clap(long = "mouse")]
+    mouse: bool,
+    /// Run until the grid dies out or repeats itself, then exit and print a report
+    #[clap(long = "until-stable")]
+    until_stable: bool,
+    /// Seed file of a reference run to compare the current run against
+    #[clap(long = "reference")]
+    reference: Option<String>,
+    /// Record the keys pressed during the run to a macro file
+    #[clap(long = "record")]
+    record: Option<String>,
+    /// Play back a macro file recorded with --record
+    #[clap(long = "play")]
+    play: Option<String>,
+    /// Unix socket other programs can send commands to, like pause, step or dump
+    #[clap(long = "control-socket")]
+    control_socket: Option<String>,
+    /// Print the final grid to stdout on exit, as RLE or in the given format
+    #[clap(long = "dump-final", value_enum, num_args = 0..=1, default_missing_value = "rle")]
+    dump_final: Option<ExportFormat>,
+    /// Engine the generations are worked out on
+    #[clap(long = "engine", value_enum, default_value = "cpu")]
+    engine: Engine,
+    /// Record the run to an asciinema v2 cast file
+    #[clap(long = "record-cast")]
+    record_cast: Option<String>,
+    /// Log the generation, population, births, deaths and activity of every generation to a CSV file
+    #[clap(long = "log-csv", conflicts_with = "demo")]
+    log_csv: Option<String>,
+    /// Report generations, the run stabilizing, inputs and saves as newline-delimited JSON to a file, or to the piped output with -
+    #[clap(long = "events", conflicts_with = "demo")]
+    events: Option<String>,
+    /// Rhai script adding behaviors through on_generation and on_key hooks
+    #[clap(long = "script")]
+    script: Option<String>,
+}
+
+impl Cli {
+    pub fn stepper(&self, rule: Rule) -> Result<Stepper> {
+        //! Creates the engine working out the generations under the given
+        //! rule, set up from the command line options.
+        let mut stepper: Stepper = Stepper::new(self.engine)?;
+        stepper.set_immigration(self.immigration);
+        stepper.set_lineage(self.lineage);
+        stepper.set_rule(rule);
+        stepper.set_noise(self.rule_noise, self.rng_seed.unwrap_or(0));
+        Ok(stepper)
+    }
+
+    pub fn build_state(&self, theme: Theme) -> conway::State {
+        //! Creates the game state from the command line options.
+        let mut state: conway::State = conway::State::new(self.timeout, self.character, theme);
+        state.set_dead_char(self.dead_character);
+        state.set_age_gradient(self.age_gradient);
+        state.set_inverse(self.inverse);
+        state.set_neighbor_glyphs(self.neighbor_glyphs);
+        state.set_texture(self.texture);
+        state.set_immigration(self.immigration);
+        state.set_lineage(self.lineage);
+        state.set_heatmap(Heatmap::new(self.heatmap_generations));
+        state.set_stats_shown(self.stats);
+        state.set_trail(self.trail);
+        state.set_topology(self.topology);
+        state.set_seed_placement(match (self.seed_at, self.no_center) {
+            (Some(placement), _) => placement,
+            (None, true) => Placement::TopLeft,
+            (None, false) => Placement::Center,
+        });
+        state.set_seed_transform(Transform {
+            rotation: self.rotate,
+            flip_horizontal: self.flip_h,
+            flip_vertical: self.flip_v,
+            scale: self.scale as usize,
+        });
+        state.set_seed_tiling(self.tile);
+        state.set_seed_crop(self.crop);
+        state.set_size((self.height, self.width));
+        state.set_history(History::new(self.history));
+        state.set_power(PowerProfile::detect(self.eco));
+        state.set_export_format(self.export_format);
+        state.set_step(self.step);
+        state.set_fps(self.fps);
+        state.set_turbo(self.turbo);
+        state.set_skip(self.skip.unwrap_or(conway::DEFAULT_SKIP));
+        state.set_density(self.fill_density.unwrap_or(conway::DEFAULT_FILL_DENSITY));
+        state.set_noise(self.noise);
+        state.set_rain(self.rain);
+        state.set_noise_soup(self.soup_options());
+        state.set_alarms(Alarms::new(
+            self.alarm_above,
+            self.alarm_below,
+            self.alarm_action,
+        ));
+        if self.kiosk {
+            state.set_kiosk(Some(Kiosk::new(&self.kiosk_quit)));
+        }
+        state.set_status_bar(if self.no_status {
+            StatusBar::hidden()
+        } else {
+            StatusBar::new(self.status.clone())
+        });
+        state
+    }
+
+    pub fn input_handler(&self) -> Result<InputHandler> {
+        //! Creates the input handler, recording or playing back macros and
+        //! listening on the control socket.
+        let mut input_handler: InputHandler = InputHandler::new();
+        input_handler.set_recorder(self.record.as_deref().map(Recorder::new));
+        input_handler.set_playback(self.play.as_deref().map(Playback::load).transpose()?);
+        input_handler.set_control(
+            self.control_socket
+                .as_deref()
+                .map(ControlSocket::bind)
+                .transpose()?,
+        );
+        Ok(input_handler)
+    }
+
+    pub fn soup_dna(&self, grid: &[Vec<Cell>]) -> Dna {
+        //! Describes a random soup generated on the grid from these options.
+        Dna {
+            rule: self.rule.to_string(),
+            rows: grid.len(),
+            cols: grid[0].len(),
+            generation: 0,
+            alive: self.alive.unwrap_or(0),
+            soup: self.soup_options(),
+            topology: self.topology,
+        }
+    }
+
+    pub fn soup_options(&self) -> SoupOptions {
+        //! Collects the options controlling how random soups are generated.
+        SoupOptions {
+            distribution: self.distribution,
+            symmetry: self.symmetry,
+            seed: self.rng_seed.unwrap_or(0),
+        }
+    }
+
+    pub fn output_taken(&self) -> bool {
+        //! Whether standard output is kept for the final grid or the event
+        //! stream, so messages have to go to stderr.
+        self.dump_final.is_some() || self.events.as_deref() == Some("-")
+    }
+}
+
+fn parse_rule(value: &str) -> Result<Rule, String> {
+    //! Parses a rulestring.
+    value.parse::<Rule>().map_err(|e| e.to_string())
+}
+
+fn parse_character(value: &str) -> Result<char, String> {
+    //! Parses a single character cells can be drawn with, one or two columns
+    //! wide.
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if (1..=2).contains(&window::glyph_width(c)) => Ok(c),
+        (Some(_), None) => Err(format!(
+            "'{}' can't be drawn in one or two columns of the terminal",
+            value
+        )),
+        _ => Err(format!("expected a single character, got '{}'", value)),
+    }
+}
+
+fn parse_density(value: &str) -> Result<f64, String> {
+    //! Parses a fraction between 0 and 1.
+    match value.parse::<f64>() {
+        Ok(density) if (0.0..=1.0).contains(&density) => Ok(density),
+        _ => Err(format!(
+            "expected a number between 0 and 1, got '{}'",
+            value
+        )),
+    }
+}
+
+/// Number of generations of similarity to the reference run that are kept.
+const REFERENCE_HISTORY: usize = 1000;
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print (and optionally open) the Catagolue census page of an object
+    Catagolue {
+        /// apgcode of the object, e.g. xq4_153
+        apgcode: String,
+        /// Rule the object was found in
+        #[clap(short = 'r', long = "rule", default_value = "b3s23")]
+        rule: String,
+        /// Open the page in the default browser
+        #[clap(short = 'o', long = "open")]
+        open: bool,
+    },
+    /// Convert a pattern file between the seed, RLE, plaintext, Life 1.06 and macrocell formats
+    Convert {
+        /// Pattern file to read
+        input: String,
+        /// File to write; the pattern is printed if this is left out
+        output: Option<String>,
+        /// Format of the input, if it can't be told from the file name
+        #[clap(long = "from", value_enum)]
+        from: Option<ExportFormat>,
+        /// Format of the output, if it can't be told from the file name
+        #[clap(long = "to", value_enum)]
+        to: Option<ExportFormat>,
+        /// Rule written in RLE headers
+        #[clap(short = 'r', long = "rule", default_value = "B3/S23")]
+        rule: String,
+    },
+    /// Compare two pattern files cell by cell
+    Diff {
+        /// Pattern file to compare from
+        first: String,
+        /// Pattern file to compare to
+        second: String,
+        /// Move both patterns to the top-left corner before comparing them
+        #[clap(short = 'n', long = "normalize")]
+        normalize: bool,
+    },
+    /// Download a pattern by name from the LifeWiki pattern archive
+    Fetch {
+        /// Name of the pattern, as on LifeWiki, e.g. gosperglidergun
+        name: String,
+        /// Directory the pattern is saved in
+        #[clap(short = 'o', long = "output", default_value = "seeds")]
+        output: String,
+    },
+    /// Restore a terminal left broken by a crashed run
+    ResetTerminal,
+    /// Write an animated SVG of a pattern's first generations
+    Svg {
+        /// Pattern file to animate
+        input: String,
+        /// SVG file to write; the SVG is printed if this is left out
+        output: Option<String>,
+        /// Number of generations in the animation
+        #[clap(short = 'g', long = "generations", default_value = "100")]
+        generations: usize,
+        /// Number of dead cells added around the pattern on each side
+        #[clap(long = "padding", default_value = "10")]
+        padding: usize,
+        /// How the edges of the padded grid are joined
+        #[clap(long = "topology", value_enum, default_value = "torus")]
+        topology: Topology,
+        /// Width and height of each cell, in pixels
+        #[clap(long = "cell-size", default_value = "10")]
+        cell_size: usize,
+        /// Milliseconds each generation is shown for
+        #[clap(long = "frame-ms", default_value = "100")]
+        frame_ms: u64,
+    },
+    /// Run the simulation without a terminal and stream it to web browsers
+    Serve {
+        /// Address to serve the page on; 0.0.0.0 lets other machines watch
+        #[clap(long = "bind", default_value = "127.0.0.1")]
+        bind: IpAddr,
+        /// Port to serve the page on
+        #[clap(short = 'p', long = "port", default_value = "8080")]
+        port: u16,
+        /// Number of alive cells to start with
+        #[clap(short = 'a', long = "alive", default_value = "1000")]
+        alive: usize,
+        /// Seed file to start with instead of a random soup
+        #[clap(short = 's', long = "seed")]
+        seed_file: Option<String>,
+        /// Number of columns in the grid
+        #[clap(long = "width", default_value = "80")]
+        width: usize,
+        /// Number of rows in the grid
+        #[clap(long = "height", default_value = "60")]
+        height: usize,
+        /// Seed of the random number generator used for the random soup
+        #[clap(long = "rng-seed")]
+        rng_seed: Option<u64>,
+        /// How the edges of the grid are joined
+        #[clap(long = "topology", value_enum, default_value = "torus")]
+        topology: Topology,
+        /// Milliseconds between generations
+        #[clap(short = 't', long = "timeout", default_value = "100")]
+        timeout: u64,
+    },
+    /// Run random soups without a terminal, census what they leave and log rare objects
+    Search {
+        /// Number of soups to run
+        #[clap(short = 'n', long = "soups", default_value = "1000")]
+        soups: u64,
+        /// Number of alive cells in each soup
+        #[clap(short = 'a', long = "alive", default_value = "1000")]
+        alive: usize,
+        /// Number of columns in each soup's grid
+        #[clap(long = "width", default_value = "64")]
+        width: usize,
+        /// Number of rows in each soup's grid
+        #[clap(long = "height", default_value = "64")]
+        height: usize,
+        /// Seed of the first soup; each soup after it uses the next seed
+        #[clap(long = "rng-seed")]
+        rng_seed: Option<u64>,
+        /// How the edges of the grid are joined
+        #[clap(long = "topology", value_enum, default_value = "torus")]
+        topology: Topology,
+        /// Mirror the soups
+        #[clap(long = "symmetry", value_enum)]
+        symmetry: Option<Symmetry>,
+        /// Distribution of the soups' cells: uniform, gaussian[:SIGMA] or power-law[:ALPHA]
+        #[clap(long = "distribution", default_value = "uniform")]
+        distribution: DistributionSpec,
+        /// Number of generations after which a soup is censused even if it hasn't stabilized
+        #[clap(long = "max-generations", default_value = "10000")]
+        max_generations: u64,
+    },
+    /// Run random soups without a terminal and report statistics of how they turn out
+    SoupStats {
+        /// Number of soups to run
+        #[clap(short = 'n', long = "runs", default_value = "1000")]
+        runs: u64,
+        /// Fraction of each soup's grid brought to life
+        #[clap(long = "density", default_value_t = 0.3, value_parser = parse_density)]
+        density: f64,
+        /// Number of columns in each soup's grid
+        #[clap(long = "width", default_value = "64")]
+        width: usize,
+        /// Number of rows in each soup's grid
+        #[clap(long = "height", default_value = "64")]
+        height: usize,
+        /// Seed of the first soup; each soup after it uses the next seed
+        #[clap(long = "rng-seed")]
+        rng_seed: Option<u64>,
+        /// How the edges of the grid are joined
+        #[clap(long = "topology", value_enum, default_value = "torus")]
+        topology: Topology,
+        /// Rule the soups follow
+        #[clap(long = "rule", default_value = "B3/S23", value_parser = parse_rule)]
+        rule: Rule,
+        /// Mirror the soups
+        #[clap(long = "symmetry", value_enum)]
+        symmetry: Option<Symmetry>,
+        /// Distribution of the soups' cells: uniform, gaussian[:SIGMA] or power-law[:ALPHA]
+        #[clap(long = "distribution", default_value = "uniform")]
+        distribution: DistributionSpec,
+        /// Number of generations after which a soup that hasn't stabilized is given up on
+        #[clap(long = "max-generations", default_value = "10000")]
+        max_generations: u64,
+    },
+    /// Check that the engine reproduces known evolutions of patterns
+    Verify {
+        /// Fixture files, or directories searched for .fixture files
+        #[clap(default_value = "fixtures")]
+        paths: Vec<String>,
+        /// Engine the generations are worked out on
+        #[clap(long = "engine", value_enum, default_value = "cpu")]
+        engine: Engine,
+    },
 }
 
 fn main() -> Result<()> {
     let mut args = Cli::parse();
 
+    match &args.command {
+        Some(Command::Catagolue {
+            apgcode,
+            rule,
+            open,
+        }) => return catagolue::run(apgcode, rule, *open),
+        Some(Command::Convert {
+            input,
+            output,
+            from,
+            to,
+            rule,
+        }) => return convert::run(input, output.as_deref(), *from, *to, rule),
+        Some(Command::Diff {
+            first,
+            second,
+            normalize,
+        }) => return diff::run(first, second, *normalize),
+        Some(Command::Fetch { name, output }) => return fetch::run(name, output),
+        Some(Command::ResetTerminal) => return terminal::reset(),
+        Some(Command::Svg {
+            input,
+            output,
+            generations,
+            padding,
+            topology,
+            cell_size,
+            frame_ms,
+        }) => {
+            return svg::run(
+                input,
+                output.as_deref(),
+                &SvgOptions {
+                    generations: *generations,
+                    padding: *padding,
+                    topology: *topology,
+                    cell_size: *cell_size,
+                    frame_ms: *frame_ms,
+                },
+            )
+        }
+        Some(Command::Serve {
+            bind,
+            port,
+            alive,
+            seed_file,
+            width,
+            height,
+            rng_seed,
+            topology,
+            timeout,
+        }) => {
+            return serve::run(&ServeOptions {
+                bind: *bind,
+                port: *port,
+                rows: *height,
+                cols: *width,
+                alive: *alive,
+                seed_file: seed_file.clone(),
+                soup: SoupOptions {
+                    seed: rng_seed.unwrap_or_else(rand::random),
+                    ..args.soup_options()
+                },
+                topology: *topology,
+                timeout: *timeout,
+            })
+        }
+        Some(Command::Search {
+            soups,
+            alive,
+            width,
+            height,
+            rng_seed,
+            topology,
+            symmetry,
+            distribution,
+            max_generations,
+        }) => {
+            return search::run(&SearchOptions {
+                soups: *soups,
+                rows: *height,
+                cols: *width,
+                alive: *alive,
+                soup: SoupOptions {
+                    distribution: *distribution,
+                    symmetry: *symmetry,
+                    seed: rng_seed.unwrap_or_else(rand::random),
+                },
+                topology: *topology,
+                max_generations: *max_generations,
+            })
+        }
+        Some(Command::SoupStats {
+            runs,
+            density,
+            width,
+            height,
+            rng_seed,
+            topology,
+            rule,
+            symmetry,
+            distribution,
+            max_generations,
+        }) => {
+            return soupstats::run(&SoupStatsOptions {
+                runs: *runs,
+                rows: *height,
+                cols: *width,
+                density: *density,
+                soup: SoupOptions {
+                    distribution: *distribution,
+                    symmetry: *symmetry,
+                    seed: rng_seed.unwrap_or_else(rand::random),
+                },
+                topology: *topology,
+                rule: rule.clone(),
+                max_generations: *max_generations,
+            })
+        }
+        Some(Command::Verify { paths, engine }) => return verify::run(paths, *engine),
+        None => (),
+    }
+
+    /* clean up after a previous run that crashed, and after this one if it
+     * panics */
+    terminal::recover_stale_session()?;
+    terminal::install_panic_hook();
+
+    /* load the theme before touching the terminal, so errors are readable */
+    let theme: Theme = Theme::load(&args.theme)?;
+
+    /* and read the reference run's seed */
+    let reference_pattern: Option<Pattern> = args
+        .reference
+        .as_deref()
+        .map(Pattern::from_file)
+        .transpose()?;
+
+    /* decode the apgcode up front for the same reason */
+    let apgcode_pattern: Option<Pattern> = args
+        .seed_apgcode
+        .as_deref()
+        .map(Pattern::from_apgcode)
+        .transpose()?;
+
+    /* a seed piped to stdin has to be read before ncurses takes over, after
+     * which keys are read from the terminal instead */
+    let stdin_pattern: Option<Pattern> = match args.seed_files.as_slice() {
+        [seed] if seed == "-" && apgcode_pattern.is_none() => {
+            let pattern: Pattern = Pattern::from_stdin()?;
+            terminal::reopen_tty()?;
+            Some(pattern)
+        }
+        _ => None,
+    };
+    let start_pattern: Option<Pattern> = apgcode_pattern.or(stdin_pattern);
+
+    /* a shared code overrides the options describing the soup */
+    let dna: Option<Dna> = args.from_code.as_deref().map(Dna::decode).transpose()?;
+    if let Some(dna) = &dna {
+        args.alive = Some(dna.alive);
+        args.distribution = dna.soup.distribution;
+        args.symmetry = dna.soup.symmetry;
+        args.rng_seed = Some(dna.soup.seed);
+        args.topology = dna.topology;
+        args.rule = dna.rule.parse()?;
+    }
+    /* a resumed session overrides everything it keeps track of */
+    let session: Option<Session> = args.resume.as_deref().map(Session::load).transpose()?;
+    if let Some(session) = &session {
+        args.rule = session.rule.parse()?;
+        args.topology = session.topology;
+        args.timeout = session.timeout;
+        args.fps = session.fps;
+        args.step = session.step;
+        args.rule_noise = session.rule_noise;
+        args.rng_seed = Some(session.rng_seed);
+        args.immigration = session.immigration;
+    }
+    let session_path: Option<String> = args.session.clone().or(args.resume.clone());
+
+    /* pick the soup's seed now, so that the run can be shared */
+    if args.rng_seed.is_none() {
+        args.rng_seed = Some(rand::random());
+    }
+
+    /* compile the script before touching the terminal, too */
+    let script: Option<Script> = args.script.as_deref().map(Script::load).transpose()?;
+
+    /* and create the cast file */
+    let cast: Option<Cast> = args.record_cast.as_deref().map(Cast::create).transpose()?;
+    let csv_log: Option<CsvLog> = args.log_csv.as_deref().map(CsvLog::create).transpose()?;
+    /* before the output is taken over for drawing, in case the events go
+     * down the pipe */
+    let events: Option<Events> = args.events.as_deref().map(Events::create).transpose()?;
+
+    /* and set up the engine */
+    let stepper: Stepper = args.stepper(args.rule.clone())?;
+
+    /* and read the macro */
+    let mut input_handler: InputHandler = args.input_handler()?;
+
     if args.demo {
-        demo::run(&args)?;
-        return Ok(());
+        demo::run(&args, theme, script, cast, stepper, &mut input_handler)?;
+        return save_recording(&input_handler, args.output_taken());
+    }
+
+    /* the comparison runs a second engine, under the other rule */
+    if args.compare_rule.is_some() || args.compare_topology.is_some() {
+        let compared: Stepper =
+            args.stepper(args.compare_rule.clone().unwrap_or(args.rule.clone()))?;
+        let topology: Topology = args.compare_topology.unwrap_or(args.topology);
+        return compare::run(
+            &args,
+            theme,
+            start_pattern.as_ref(),
+            stepper,
+            compared,
+            topology,
+        );
     }
 
     ncurses::setlocale(LcCategory::all, "")?;
 
-    /* initialize screen */
-    initscr();
+    /* draw on the terminal even if the output is piped */
+    let piped_stdout: Option<i32> = terminal::redirect_stdout()?;
 
-    /* hide cursor */
-    curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+    /* initialize screen, which is handed back however we exit */
+    let screen: Screen = Screen::start(args.mouse, piped_stdout);
 
     /* enables colors */
     start_color();
@@ -65,6 +885,11 @@ fn main() -> Result<()> {
     /* keypresses will not be displayed on screen */
     noecho();
 
+    /* report mouse drags, for painting cells */
+    if args.mouse {
+        paint::enable_mouse();
+    }
+
     /*
      * Set minimum timeout to 10ms, maximum timeout to 1000ms, and makes
      * timeout is in increment of 10.
@@ -77,31 +902,315 @@ fn main() -> Result<()> {
         args.timeout = (args.timeout / 10) * 10;
     }
 
-    /* Applies timeout value */
-    timeout(args.timeout);
-
     /* get the number of rows and columns */
     let nrows: usize = LINES() as usize - 1;
     let ncols: usize = COLS() as usize - 1;
 
-    let mut input_handler: InputHandler = InputHandler::new();
-
     let mut win: Window = Window::new(nrows as i32, ncols as i32, 0, 0);
 
+    let mut state: conway::State = args.build_state(theme);
+    state.set_script(script);
+    state.set_cast(cast);
+    state.set_csv_log(csv_log);
+    state.set_events(events.unwrap_or_default());
+    state.set_stepper(stepper);
+    if let Some(dna) = &dna {
+        state.set_size((Some(dna.rows), Some(dna.cols)));
+    }
+    if let Some(session) = &session {
+        state.set_size((Some(session.grid.len()), Some(session.grid[0].len())));
+        state.set_generation(session.generation);
+    }
+
     /* initialize the grid */
-    let mut grid: Vec<Vec<Cell>> = initialize(&mut win, args.alive, &args.seed_file)?;
+    let mut grid: Vec<Vec<Cell>> = match (&session, &start_pattern) {
+        (Some(session), _) => session.grid.clone(),
+        (None, Some(pattern)) => {
+            state.set_metadata(pattern.metadata.clone());
+            initialize_from_pattern(&mut win, &state, pattern)?
+        }
+        (None, None) => initialize(
+            &mut win,
+            &mut state,
+            args.alive,
+            &args.seed_files,
+            &args.place,
+            &args.soup_options(),
+        )?,
+    };
+    if args.immigration && session.is_none() {
+        conway::color_randomly(&mut grid, args.soup_options().seed);
+    }
+    /* sessions don't keep lineages, a resumed run starts them over */
+    if args.lineage {
+        lineage::tag_regions(&mut grid);
+    }
 
-    let mut state: conway::State = conway::State::new(args.timeout, args.character);
+    /* the reference run starts on a grid the same size as ours */
+    if let Some(pattern) = &reference_pattern {
+        let reference: Vec<Vec<Cell>> = initialize_from_pattern(&mut win, &state, pattern)?;
+        state.set_similarity(Some(SimilarityTracker::new(
+            reference,
+            REFERENCE_HISTORY,
+            args.topology,
+            args.rule.clone(),
+        )));
+    }
 
+    /* random soups can be shared, seed files, patterns, sessions and noisy
+     * runs can't */
+    let is_soup: bool = start_pattern.is_none()
+        && session.is_none()
+        && args.rule_noise == 0.0
+        && args.place.is_empty()
+        && !args
+            .seed_files
+            .iter()
+            .any(|seed| Path::new(pattern::split_seed(seed).0).exists());
+    if is_soup {
+        state.set_dna(Some(args.soup_dna(&grid)));
+        if args.fill_density.is_none() {
+            let cells: usize = grid.len() * grid[0].len();
+            state.set_density(args.alive.unwrap_or(0) as f64 / cells.max(1) as f64);
+        }
+    }
+
+    /* catch up with the shared run */
+    if let Some(dna) = &dna {
+        let mut stepper: Stepper = Stepper::default();
+        stepper.set_immigration(args.immigration);
+        stepper.set_lineage(args.lineage);
+        stepper.set_rule(args.rule.clone());
+        for _ in 0..dna.generation {
+            stepper.step(&mut grid, args.topology);
+        }
+        state.set_generation(dna.generation);
+    }
+
+    if let Some(skip) = args.skip {
+        fast_forward(&win, &mut grid, &mut state, skip)?;
+    }
+
+    let mut cycle_detector: CycleDetector = CycleDetector::new(args.topology, args.rule.clone());
+    let mut report: Option<Report> = None;
+    let mut stabilized: bool = false;
+    let mut last_checked: Option<u64> = None;
+    let mut last_save: Instant = Instant::now();
+    let mut attract: Option<Attract> = args
+        .attract
+        .map(|interval| Attract::new(interval, args.rng_seed.unwrap_or(0)));
     loop {
-        let (input, new_grid) = run_frame(&mut win, &grid, &mut input_handler, &mut state)?;
-        grid = new_grid;
+        let input: InputType = run_frame(&mut win, &mut grid, &mut input_handler, &mut state)?;
         if input == InputType::Quit {
             break;
         }
+
+        /* throw the grid away for the next soup, with the same options and
+         * the density set */
+        if input == InputType::NewSoup {
+            let cells: usize = grid.len() * grid[0].len();
+            args.alive = Some((state.get_density() * cells as f64).round() as usize);
+            args.rng_seed = Some(args.rng_seed.unwrap_or(0).wrapping_add(1));
+            grid = start_soup(&args, &mut win, &mut state)?;
+            state.set_message(Some(format!(
+                "New soup with seed {}",
+                args.soup_options().seed
+            )));
+            cycle_detector.clear();
+            report = None;
+            stabilized = false;
+            last_checked = None;
+            continue;
+        }
+
+        /* the edges were joined another way with 'W' or the menu, or the
+         * rule was changed with ':' or the menu: the run goes on from here
+         * under the new ones, and so do the next soups */
+        if (state.get_topology() != args.topology || *state.get_rule() != args.rule)
+            && state.is_lopsided(grid.len())
+        {
+            state.set_topology(args.topology);
+            state.set_rule(args.rule.clone());
+            state.set_message(Some(format!(
+                "A hexagonal grid can't wrap around {} rows; the edges and rule stay as they were",
+                grid.len()
+            )));
+        } else if state.get_topology() != args.topology || *state.get_rule() != args.rule {
+            args.topology = state.get_topology();
+            args.rule = state.get_rule().clone();
+            cycle_detector = CycleDetector::new(args.topology, args.rule.clone());
+            report = None;
+            stabilized = false;
+            last_checked = None;
+        }
+
+        /* save the session every so often, in case the terminal goes away */
+        if let Some(path) = &session_path {
+            if last_save.elapsed() >= Duration::from_secs(session::AUTOSAVE_SECONDS) {
+                match Session::capture(&grid, &mut state).save(path) {
+                    Ok(()) => state.emit(Event::Saved {
+                        what: "session",
+                        path: path.clone(),
+                    }),
+                    Err(e) => state.set_message(Some(e.to_string())),
+                }
+                last_save = Instant::now();
+            }
+        }
+
+        /* keep track of whether the run has stabilized, for the report at
+         * the end. Frames showing the same generation again, while paused or
+         * cut short by an input, aren't repeats. The event stream hears once
+         * about the run stabilizing, until it starts over */
+        if last_checked != Some(state.get_generation()) {
+            last_checked = Some(state.get_generation());
+            report = cycle_detector.check(&grid, state.get_generation());
+            if let Some(found) = report.as_ref().filter(|_| !stabilized) {
+                stabilized = true;
+                state.emit(Event::Stabilized {
+                    generation: found.generation,
+                    period: found.period,
+                });
+            }
+            if args.until_stable && report.is_some() {
+                break;
+            }
+        }
+
+        /* in attract mode, take the caption down after a while, and move on
+         * to the next scene once this one is over. Frames cut short by an
+         * input show the same generation again, which isn't stagnation */
+        if let Some(attract) = &mut attract {
+            if attract.caption_expired() {
+                state.set_message(None);
+            }
+            if !state.is_paused()
+                && input == InputType::Continue
+                && attract.is_over(&grid, state.get_generation())
+            {
+                let scene: Scene = attract.next_scene(state.get_rule());
+                args.rng_seed = Some(rand::random());
+                args.alive = Some(scene.alive(&grid));
+                args.rule = scene.rule.clone();
+                state.set_rule(scene.rule.clone());
+                state.set_theme(scene.theme.clone());
+                grid = start_soup(&args, &mut win, &mut state)?;
+                state.set_message(Some(scene.caption()));
+                cycle_detector = CycleDetector::new(args.topology, args.rule.clone());
+                report = None;
+                stabilized = false;
+                last_checked = None;
+                continue;
+            }
+        }
+
+        /* in kiosk mode, start over with a new soup once the run settles, or
+         * an alarm goes off */
+        let paused: bool = state.is_paused();
+        let generation: u64 = state.get_generation();
+        let Some(kiosk) = state.get_kiosk_mut() else {
+            continue;
+        };
+        if paused
+            || !(kiosk.is_alarmed()
+                || kiosk.is_stagnant(&grid, generation)
+                || generation >= kiosk::MAX_GENERATIONS)
+        {
+            continue;
+        }
+        kiosk.clear();
+        args.rng_seed = Some(rand::random());
+        grid = start_soup(&args, &mut win, &mut state)?;
+        cycle_detector.clear();
+        report = None;
+        stabilized = false;
+        last_checked = None;
     }
 
-    endwin();
+    drop(screen);
+
+    /* messages go to stderr while the output is kept for the final grid or
+     * the event stream */
+    let output_taken: bool = args.output_taken();
 
+    if let Some(path) = &session_path {
+        Session::capture(&grid, &mut state).save(path)?;
+        state.emit(Event::Saved {
+            what: "session",
+            path: path.clone(),
+        });
+        if output_taken {
+            eprintln!("Session saved to {}", path);
+        } else {
+            println!("Session saved to {}", path);
+        }
+    }
+
+    /* report what the run came to */
+    let summary: String = match &report {
+        Some(report) => report.to_string(),
+        None => format!(
+            "Generations: {}\nPopulation: {}\nOutcome: still running",
+            state.get_generation(),
+            grid.iter().flatten().filter(|cell| cell.is_alive()).count()
+        ),
+    };
+    let census: Census = Census::take(&grid, args.topology);
+    if output_taken {
+        eprintln!("{}\n{}", summary, census);
+    } else {
+        println!("{}\n{}", summary, census);
+    }
+
+    /* print the code that was on screen, so it can be copied */
+    if state.get_show_code() {
+        if let Some(code) = state.code() {
+            if output_taken {
+                eprintln!("conway --from-code {}", code);
+            } else {
+                println!("conway --from-code {}", code);
+            }
+        }
+    }
+
+    /* print the final grid, for other programs to pick up */
+    if let Some(format) = args.dump_final {
+        let pattern: Pattern = Pattern {
+            metadata: state.get_metadata().clone(),
+            ..Pattern::from_region(&grid, (0, 0), (grid.len() - 1, grid[0].len() - 1))
+        };
+        print!("{}", pattern.to_text(format, &state.get_rule().to_string()));
+    }
+
+    save_recording(&input_handler, output_taken)
+}
+
+fn start_soup(args: &Cli, win: &mut Window, state: &mut conway::State) -> Result<Vec<Vec<Cell>>> {
+    //! Generates a random soup from the options and starts the run over on
+    //! it.
+    let mut grid: Vec<Vec<Cell>> =
+        initialize(win, state, args.alive, &[], &[], &args.soup_options())?;
+    if args.immigration {
+        conway::color_randomly(&mut grid, args.soup_options().seed);
+    }
+    if args.lineage {
+        lineage::tag_regions(&mut grid);
+    }
+    state.reset_run();
+    state.set_dna(Some(args.soup_dna(&grid)));
+    Ok(grid)
+}
+
+fn save_recording(input_handler: &InputHandler, output_taken: bool) -> Result<()> {
+    //! Saves the macro recorded with --record, once the terminal is restored,
+    //! saying so on stderr if standard output is taken.
+    if let Some(recorder) = input_handler.get_recorder() {
+        recorder.save()?;
+        if output_taken {
+            eprintln!("Macro saved to {}", recorder.get_path());
+        } else {
+            println!("Macro saved to {}", recorder.get_path());
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,77 @@
+//! This module contains stepping block rules, like Critters and falling sand,
+//! which don't look at the neighbors of each cell but split the grid into
+//! 2x2 blocks and replace each block as a whole.
+//!
+//! The blocks follow the Margolus partition: on even generations they start
+//! at even rows and columns, and on odd generations one cell further down
+//! and right, so that what happens in one block spreads to the next.
+//!
+//! Blocks are numbered by their live cells: 1 for the top-left one, 2 for
+//! the top-right one, 4 for the bottom-left one and 8 for the bottom-right
+//! one, as MCell's `MS,D...` rules do.
+
+use super::bitgrid::BitGrid;
+use super::conway::Cell;
+use super::topology::Topology;
+
+pub fn next_generation(
+    grid: &[Vec<Cell>],
+    topology: Topology,
+    blocks: &[u8; 16],
+    generation: u64,
+) -> BitGrid {
+    //! Works out which cells are alive in the next generation, given the
+    //! block each block of the current generation becomes.
+    let nrows: usize = grid.len();
+    let ncols: usize = grid[0].len();
+    let phase: usize = (generation % 2) as usize;
+    BitGrid::from_fn(nrows, ncols, |i, j| {
+        let (top, left) = (corner(i, phase), corner(j, phase));
+        let (Some(rows), Some(cols)) = (
+            block_lines(top, nrows, topology),
+            block_lines(left, ncols, topology),
+        ) else {
+            // blocks sticking out of the grid are left as they are
+            return grid[i][j].is_alive();
+        };
+        let mut block: usize = 0;
+        for (bit, (row, col)) in [
+            (rows[0], cols[0]),
+            (rows[0], cols[1]),
+            (rows[1], cols[0]),
+            (rows[1], cols[1]),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            if grid[row][col].is_alive() {
+                block |= 1 << bit;
+            }
+        }
+        let bit: usize = 2 * usize::from(i != rows[0]) + usize::from(j != cols[0]);
+        blocks[block] & (1 << bit) != 0
+    })
+}
+
+fn corner(index: usize, phase: usize) -> isize {
+    //! Finds the first row or column of the block a row or column is in.
+    index as isize - ((index + phase) % 2) as isize
+}
+
+fn block_lines(first: isize, size: usize, topology: Topology) -> Option<[usize; 2]> {
+    //! Finds the two rows or columns of a block starting at `first`, which
+    //! may lie one past either edge of the grid. Only a torus of even size
+    //! splits evenly into blocks across its edges; elsewhere, blocks across
+    //! the edges don't exist.
+    let size: isize = size as isize;
+    if (0..size - 1).contains(&first) {
+        return Some([first as usize, first as usize + 1]);
+    }
+    match topology {
+        Topology::Torus if size % 2 == 0 => Some([
+            first.rem_euclid(size) as usize,
+            (first + 1).rem_euclid(size) as usize,
+        ]),
+        _ => None,
+    }
+}
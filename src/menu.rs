@@ -0,0 +1,154 @@
+//! This module contains menus: windows over the grid listing settings, each
+//! with a few values to choose from. One setting is selected at a time, and
+//! its value is changed in place.
+
+use anyhow::Result;
+use ncurses::*;
+
+use super::status::fit_to_width;
+use super::theme::Theme;
+use super::window::Window;
+
+/// Line at the bottom of menus.
+const FOOTER: &str = "j/k: select, h/l: change, Enter: close";
+
+/// A setting in a menu, and the values it can take.
+#[derive(Debug, Clone)]
+pub struct MenuItem {
+    /// Name the setting is listed under
+    label: String,
+    /// Values the setting can take, in the order they are cycled through
+    choices: Vec<String>,
+    /// Index of the current value in `choices`
+    chosen: usize,
+}
+
+impl MenuItem {
+    pub fn new(label: &str, choices: Vec<String>, current: &str) -> MenuItem {
+        //! Creates a setting whose value is `current`, which is added to the
+        //! choices if it isn't one of them.
+        let mut choices: Vec<String> = choices;
+        let chosen: usize = match choices.iter().position(|choice| choice == current) {
+            Some(chosen) => chosen,
+            None => {
+                choices.insert(0, current.to_string());
+                0
+            }
+        };
+        MenuItem {
+            label: label.to_string(),
+            choices,
+            chosen,
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.choices[self.chosen]
+    }
+}
+
+/// A menu of settings.
+#[derive(Debug, Clone)]
+pub struct Menu {
+    title: String,
+    items: Vec<MenuItem>,
+    /// Index of the selected setting
+    selected: usize,
+}
+
+impl Menu {
+    pub fn new(title: &str, items: Vec<MenuItem>) -> Menu {
+        Menu {
+            title: title.to_string(),
+            items,
+            selected: 0,
+        }
+    }
+
+    pub fn value(&self, label: &str) -> Option<&str> {
+        //! Returns the current value of the setting with the given label.
+        self.items
+            .iter()
+            .find(|item| item.label == label)
+            .map(|item| item.value())
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.items.len();
+    }
+
+    pub fn change(&mut self, forward: bool) {
+        //! Changes the selected setting to its next or previous value,
+        //! wrapping around at either end.
+        let item: &mut MenuItem = &mut self.items[self.selected];
+        let len: usize = item.choices.len();
+        item.chosen = if forward {
+            (item.chosen + 1) % len
+        } else {
+            (item.chosen + len - 1) % len
+        };
+    }
+
+    pub fn draw(&self, parent: &Window, theme: &Theme) -> Result<()> {
+        //! Draws the menu in the middle of the window the grid was just drawn
+        //! and refreshed in, with the selected setting highlighted.
+        let label_width: usize = self
+            .items
+            .iter()
+            .map(|item| item.label.len())
+            .max()
+            .unwrap_or(0);
+        let lines: Vec<String> = self
+            .items
+            .iter()
+            .map(|item| {
+                // settings that can't be changed aren't shown as a choice
+                let value: String = if item.choices.len() > 1 {
+                    format!("< {} >", item.value())
+                } else {
+                    format!("  {}", item.value())
+                };
+                format!("{:label_width$}  {}", item.label, value)
+            })
+            .collect();
+        let content_width: usize = lines
+            .iter()
+            .map(|line| line.len())
+            .chain([self.title.len(), FOOTER.len()])
+            .max()
+            .unwrap_or(0);
+        // box with the title on it, footer line and a blank line before it
+        let window: Window =
+            Window::centered(parent, lines.len() as i32 + 4, content_width as i32 + 4);
+        let width: usize = (window.get_cols() as usize).saturating_sub(4);
+
+        window.erase();
+        window.draw_box();
+        let status = theme.status.as_ref();
+        window.print_attr(2, 0, &fit_to_width(&self.title, width), status, A_BOLD)?;
+        for (n, line) in lines.iter().enumerate() {
+            let y: i32 = n as i32 + 1;
+            if y >= window.get_rows() - 3 {
+                break;
+            }
+            let attr: attr_t = if n == self.selected {
+                A_REVERSE
+            } else {
+                A_NORMAL
+            };
+            window.print_attr(2, y, &fit_to_width(line, width), None, attr)?;
+        }
+        window.print(
+            2,
+            window.get_rows() - 2,
+            &fit_to_width(FOOTER, width),
+            status,
+        )?;
+        window.refresh();
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+//! This module contains similarity metrics between grids, and a tracker
+//! comparing the current run against a reference run generation by generation.
+
+use std::collections::VecDeque;
+
+use super::conway::{Cell, Stepper};
+use super::rule::Rule;
+use super::topology::Topology;
+
+/// Characters of the similarity sparkline, from least to most similar.
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+pub fn hamming(a: &[Vec<Cell>], b: &[Vec<Cell>]) -> usize {
+    //! Number of positions where one grid has a live cell and the other
+    //! doesn't. Positions outside either grid count as dead.
+    let rows: usize = a.len().max(b.len());
+    let mut distance: usize = 0;
+    for i in 0..rows {
+        let (row_a, row_b) = (a.get(i), b.get(i));
+        let cols: usize = row_a
+            .map_or(0, |r| r.len())
+            .max(row_b.map_or(0, |r| r.len()));
+        for j in 0..cols {
+            let alive_a: bool = row_a.and_then(|r| r.get(j)).is_some_and(|c| c.is_alive());
+            let alive_b: bool = row_b.and_then(|r| r.get(j)).is_some_and(|c| c.is_alive());
+            distance += (alive_a != alive_b) as usize;
+        }
+    }
+    distance
+}
+
+pub fn jaccard(a: &[Vec<Cell>], b: &[Vec<Cell>]) -> f64 {
+    //! Jaccard index of the sets of live cells of two grids: the number of
+    //! positions alive in both, divided by the number alive in either. Two
+    //! empty grids are identical, with an index of 1.
+    let alive = |grid: &[Vec<Cell>]| grid.iter().flatten().filter(|c| c.is_alive()).count();
+    let union_minus_intersection: usize = hamming(a, b);
+    let total: usize = alive(a) + alive(b);
+    // |A ∪ B| = (|A| + |B| + |A △ B|) / 2 and |A ∩ B| = (|A| + |B| - |A △ B|) / 2
+    let union: usize = (total + union_minus_intersection) / 2;
+    if union == 0 {
+        return 1.0;
+    }
+    let intersection: usize = (total - union_minus_intersection) / 2;
+    intersection as f64 / union as f64
+}
+
+/// Similarity of the run at one generation to the reference run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Similarity {
+    pub generation: u64,
+    pub hamming: usize,
+    pub jaccard: f64,
+}
+
+/// Steps a reference run alongside the current one, recording how similar
+/// they are at every recorded generation.
+pub struct SimilarityTracker {
+    /// Grid of the reference run at generation 0, for seeking backward
+    initial: Vec<Vec<Cell>>,
+    /// Grid of the reference run, at the generation of the next record
+    reference: Vec<Vec<Cell>>,
+    /// Steps the reference run
+    stepper: Stepper,
+    /// Generation of the reference grid
+    generation: u64,
+    /// Topology the reference run is simulated on
+    topology: Topology,
+    /// Most recent records, oldest first
+    history: VecDeque<Similarity>,
+    /// Maximum number of records kept
+    capacity: usize,
+}
+
+impl SimilarityTracker {
+    pub fn new(
+        reference: Vec<Vec<Cell>>,
+        capacity: usize,
+        topology: Topology,
+        rule: Rule,
+    ) -> SimilarityTracker {
+        let mut stepper: Stepper = Stepper::default();
+        stepper.set_rule(rule);
+        SimilarityTracker {
+            initial: reference.clone(),
+            reference,
+            stepper,
+            generation: 0,
+            topology,
+            history: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn record(&mut self, grid: &[Vec<Cell>], generation: u64) -> Similarity {
+        //! Compares the grid with the reference run at the same generation.
+        let similarity: Similarity = Similarity {
+            generation,
+            hamming: hamming(grid, &self.reference),
+            jaccard: jaccard(grid, &self.reference),
+        };
+        if self.history.back().map(|s| s.generation) != Some(generation) {
+            self.history.push_back(similarity);
+            if self.history.len() > self.capacity {
+                self.history.pop_front();
+            }
+        }
+        similarity
+    }
+
+    pub fn seek(&mut self, generation: u64) {
+        //! Moves the reference run to the given generation, keeping it in
+        //! lockstep with the current run. Seeking backward replays it from
+        //! generation 0.
+        if generation < self.generation {
+            self.reference = self.initial.clone();
+            self.generation = 0;
+        }
+        self.stepper.set_generation(self.generation);
+        for _ in self.generation..generation {
+            self.stepper.step(&mut self.reference, self.topology);
+        }
+        self.generation = generation;
+    }
+
+    pub fn latest(&self) -> Option<Similarity> {
+        self.history.back().copied()
+    }
+
+    pub fn history(&self) -> impl Iterator<Item = &Similarity> {
+        self.history.iter()
+    }
+
+    pub fn sparkline(&self, width: usize) -> String {
+        //! Plots the Jaccard index of the last `width` records.
+        let skip: usize = self.history.len().saturating_sub(width);
+        self.history
+            .iter()
+            .skip(skip)
+            .map(|s| SPARKS[((s.jaccard * (SPARKS.len() - 1) as f64).round() as usize).min(7)])
+            .collect()
+    }
+}
@@ -0,0 +1,155 @@
+//! This module contains the minimap, shown in the top-right corner when the
+//! grid is larger than the window: a downsampled view of the whole grid,
+//! where each character shows how crowded a block of cells is, and the
+//! blocks in view are highlighted.
+
+use anyhow::Result;
+use ncurses::*;
+
+use super::conway::Cell;
+use super::theme::Theme;
+use super::window::Window;
+
+/// Most rows and columns of characters the map itself takes, inside its box.
+const MAX_ROWS: usize = 8;
+const MAX_COLS: usize = 24;
+
+/// Characters blocks are drawn with, from sparse to crowded. Empty blocks
+/// are left blank.
+const DENSITY_CHARS: [char; 5] = ['.', ':', '+', '*', '#'];
+
+/// Fraction of alive cells drawn with the last of `DENSITY_CHARS`. Soups
+/// rarely get more crowded than this, so the characters are spread over the
+/// densities actually seen.
+const FULL_DENSITY: f64 = 0.5;
+
+pub fn downsample(grid: &[Vec<Cell>], block: (usize, usize)) -> Vec<Vec<f64>> {
+    //! Shrinks the grid to the fraction of alive cells in each block of
+    //! `block.0` rows by `block.1` columns. Blocks along the bottom and
+    //! right edges may be cut short.
+    let (block_rows, block_cols) = (block.0.max(1), block.1.max(1));
+    let ncols: usize = grid.first().map_or(0, |row| row.len());
+    let mut alive: Vec<Vec<usize>> =
+        vec![vec![0; ncols.div_ceil(block_cols)]; grid.len().div_ceil(block_rows)];
+    for (i, row) in grid.iter().enumerate() {
+        for (j, cell) in row.iter().enumerate() {
+            if cell.is_alive() {
+                alive[i / block_rows][j / block_cols] += 1;
+            }
+        }
+    }
+    alive
+        .iter()
+        .enumerate()
+        .map(|(bi, row)| {
+            let rows: usize = block_rows.min(grid.len() - bi * block_rows);
+            row.iter()
+                .enumerate()
+                .map(|(bj, count)| {
+                    let cols: usize = block_cols.min(ncols - bj * block_cols);
+                    *count as f64 / (rows * cols) as f64
+                })
+                .collect()
+        })
+        .collect()
+}
+
+pub fn block_size(nrows: usize, ncols: usize, max_rows: usize, max_cols: usize) -> (usize, usize) {
+    //! Finds the smallest blocks that fit the grid into `max_rows` by
+    //! `max_cols` characters. Cells are drawn two characters wide, so each
+    //! block is twice as tall as it is wide to keep the grid's shape.
+    let mut width: usize = 1;
+    while nrows.div_ceil(2 * width) > max_rows.max(1) || ncols.div_ceil(width) > max_cols.max(1) {
+        width += 1;
+    }
+    (2 * width, width)
+}
+
+pub fn density_char(density: f64) -> char {
+    //! Character a block with the given fraction of alive cells is drawn
+    //! with, blank if it is empty.
+    if density <= 0.0 {
+        return ' ';
+    }
+    let index: usize = (density / FULL_DENSITY * DENSITY_CHARS.len() as f64).ceil() as usize;
+    DENSITY_CHARS[index.clamp(1, DENSITY_CHARS.len()) - 1]
+}
+
+/// The minimap, and the window it is drawn in while it is shown.
+#[derive(Default)]
+pub struct Minimap {
+    /// Whether the minimap was turned off
+    hidden: bool,
+    /// Window the minimap is drawn in, while the grid doesn't fit
+    window: Option<Window>,
+}
+
+impl Minimap {
+    pub fn toggle(&mut self) {
+        self.hidden = !self.hidden;
+    }
+
+    pub fn draw(
+        &mut self,
+        parent: &Window,
+        grid: &[Vec<Cell>],
+        viewport: (usize, usize),
+        visible: (usize, usize),
+        theme: &Theme,
+    ) -> Result<()> {
+        //! Draws the minimap over the top-right corner of the window the grid
+        //! was just drawn and refreshed in, if the grid doesn't fit in it.
+        let (nrows, ncols) = (grid.len(), grid.first().map_or(0, |row| row.len()));
+        let fits: bool = nrows <= visible.0 && ncols <= visible.1;
+        let block: (usize, usize) = block_size(nrows, ncols, MAX_ROWS, MAX_COLS);
+        let map: Vec<Vec<f64>> = downsample(grid, block);
+        let (rows, cols) = (
+            map.len() as i32 + 2,
+            map.first().map_or(0, Vec::len) as i32 + 2,
+        );
+        if self.hidden || fits || rows > parent.get_rows() || cols > parent.get_cols() {
+            if self.window.take().is_some() {
+                // uncover what was under the minimap
+                parent.touch();
+                parent.refresh();
+            }
+            return Ok(());
+        }
+
+        let (top, left) = parent.position();
+        let position: (i32, i32) = (top, left + parent.get_cols() - cols);
+        let window: &Window = match &self.window {
+            Some(window)
+                if window.position() == position
+                    && (window.get_rows(), window.get_cols()) == (rows, cols) =>
+            {
+                window
+            }
+            _ => self
+                .window
+                .insert(Window::new(rows, cols, position.0, position.1)),
+        };
+
+        window.erase();
+        window.draw_box();
+        for (bi, row) in map.iter().enumerate() {
+            for (bj, density) in row.iter().enumerate() {
+                let output: char = density_char(*density);
+                // blocks overlapping the part of the grid in view
+                let in_view: bool = bi * block.0 < viewport.0 + visible.0
+                    && (bi + 1) * block.0 > viewport.0
+                    && bj * block.1 < viewport.1 + visible.1
+                    && (bj + 1) * block.1 > viewport.1;
+                window.print_attr(
+                    bj as i32 + 1,
+                    bi as i32 + 1,
+                    &output.to_string(),
+                    theme.alive.as_ref(),
+                    if in_view { A_REVERSE } else { A_NORMAL },
+                )?;
+            }
+        }
+        window.refresh();
+        Ok(())
+    }
+}
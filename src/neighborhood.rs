@@ -0,0 +1,100 @@
+//! This module contains the neighborhoods rules count the neighbors of a
+//! cell in: the cells around it within some radius, in the shape of a
+//! square (Moore) or of a diamond (von Neumann), or the six cells around it
+//! on a hexagonal grid.
+//!
+//! Hexagonal grids are kept as rows of cells where every other row is
+//! shifted half a cell right, as they are drawn, so the neighbors above and
+//! below a cell depend on which row it is in.
+
+use std::fmt;
+
+/// Shape of the cells around a cell that are its neighbors.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Shape {
+    /// Every cell in the square around the cell
+    #[default]
+    Moore,
+    /// Every cell within as many orthogonal steps of the cell
+    VonNeumann,
+    /// The six cells around the cell on a hexagonal grid, whatever the radius
+    Hexagonal,
+}
+
+/// Which cells around a cell are its neighbors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighborhood {
+    /// Shape of the neighborhood
+    pub shape: Shape,
+    /// Distance, in cells, the neighborhood reaches out to
+    pub radius: usize,
+}
+
+impl Default for Neighborhood {
+    fn default() -> Neighborhood {
+        //! The eight cells around a cell, which Life counts.
+        Neighborhood {
+            shape: Shape::Moore,
+            radius: 1,
+        }
+    }
+}
+
+impl fmt::Display for Neighborhood {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.shape {
+            Shape::Moore => write!(f, "Moore, range {}", self.radius),
+            Shape::VonNeumann => write!(f, "von Neumann, range {}", self.radius),
+            Shape::Hexagonal => write!(f, "hexagonal"),
+        }
+    }
+}
+
+impl Neighborhood {
+    pub fn suffix(&self) -> &'static str {
+        //! Letter ending the rulestrings of outer totalistic rules in the
+        //! neighborhood, as in `B2/S34H`.
+        match self.shape {
+            Shape::Moore => "",
+            Shape::VonNeumann => "V",
+            Shape::Hexagonal => "H",
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        //! Number of neighbors every cell has.
+        let radius: usize = self.radius;
+        match self.shape {
+            Shape::Moore => (2 * radius + 1).pow(2) - 1,
+            Shape::VonNeumann => 2 * radius * (radius + 1),
+            Shape::Hexagonal => 6,
+        }
+    }
+
+    pub fn offsets(&self, row: usize) -> Vec<(isize, isize)> {
+        //! Lists the offsets of the neighbors of a cell in the given row, row
+        //! by row.
+        if self.shape == Shape::Hexagonal {
+            // the rows above and below an odd row reach half a cell further
+            // right, those of an even row half a cell further left
+            let shift: isize = (row % 2) as isize;
+            return vec![
+                (-1, shift - 1),
+                (-1, shift),
+                (0, -1),
+                (0, 1),
+                (1, shift - 1),
+                (1, shift),
+            ];
+        }
+        let radius: isize = self.radius as isize;
+        (-radius..=radius)
+            .flat_map(|di| (-radius..=radius).map(move |dj| (di, dj)))
+            .filter(|(di, dj)| match self.shape {
+                Shape::VonNeumann => di.abs() + dj.abs() <= radius,
+                _ => true,
+            })
+            .filter(|offset| *offset != (0, 0))
+            .collect()
+    }
+}
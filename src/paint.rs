@@ -0,0 +1,201 @@
+//! This module contains painting cells with the mouse, enabled with --mouse:
+//! dragging with the left button brings the cells under the pointer to life,
+//! and dragging with the right button kills them.
+//!
+//! The terminal only reports where the pointer is while a button is held,
+//! and quick drags skip over cells, so the cells between two reported
+//! positions are painted too.
+//!
+//! Cells are painted with a brush, which decides which cells around the
+//! pointer are painted: a single cell, a 3x3 square, a circle, a random spray
+//! of cells, or a straight line from where the stroke started.
+
+use ncurses::*;
+use rand::Rng;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Write;
+
+use super::conway::InputType;
+
+/// Asks the terminal to report the pointer moving while a button is held,
+/// which ncurses doesn't ask for itself.
+const DRAG_REPORTING_ON: &str = "\x1b[?1002h";
+const DRAG_REPORTING_OFF: &str = "\x1b[?1002l";
+
+/// Radius, in cells, of the circle and spray brushes.
+const BRUSH_RADIUS: isize = 3;
+
+/// Default fraction of the cells under the spray brush that are painted.
+pub const DEFAULT_SPRAY_DENSITY: f64 = 0.2;
+
+/// Which cells around the pointer are painted.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Brush {
+    /// Only the cell under the pointer
+    #[default]
+    Dot,
+    /// The 3x3 square around the pointer
+    Square,
+    /// A circle around the pointer
+    Circle,
+    /// A straight line from where the stroke started to the pointer, which
+    /// follows the pointer until the button is released
+    Line,
+    /// Random cells in a circle around the pointer
+    Spray,
+}
+
+/// Every brush, in the order they are cycled through.
+pub const BRUSHES: [Brush; 5] = [
+    Brush::Dot,
+    Brush::Square,
+    Brush::Circle,
+    Brush::Line,
+    Brush::Spray,
+];
+
+impl fmt::Display for Brush {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Brush::Dot => write!(f, "1x1"),
+            Brush::Square => write!(f, "3x3"),
+            Brush::Circle => write!(f, "circle"),
+            Brush::Line => write!(f, "line"),
+            Brush::Spray => write!(f, "spray"),
+        }
+    }
+}
+
+impl Brush {
+    pub fn next(self) -> Brush {
+        let index: usize = BRUSHES.iter().position(|brush| *brush == self).unwrap_or(0);
+        BRUSHES[(index + 1) % BRUSHES.len()]
+    }
+
+    fn footprint(self) -> Vec<(isize, isize)> {
+        //! Offsets from the pointer of the cells the brush covers.
+        let radius: isize = match self {
+            Brush::Dot | Brush::Line => 0,
+            Brush::Square => 1,
+            Brush::Circle | Brush::Spray => BRUSH_RADIUS,
+        };
+        let square = (-radius..=radius).flat_map(|i| (-radius..=radius).map(move |j| (i, j)));
+        match self {
+            // a little over the radius, which rounds the circle off
+            Brush::Circle | Brush::Spray => square
+                .filter(|(i, j)| i * i + j * j <= radius * radius + radius)
+                .collect(),
+            _ => square.collect(),
+        }
+    }
+}
+
+/// A stroke of the brush, while a mouse button is held.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stroke {
+    /// Whether cells are painted alive or dead
+    pub alive: bool,
+    /// Cell the stroke started at
+    pub start: (usize, usize),
+    /// Last cell the pointer was dragged to
+    pub last: (usize, usize),
+}
+
+pub fn brush_cells(
+    brush: Brush,
+    path: &[(usize, usize)],
+    size: (usize, usize),
+    spray_density: f64,
+) -> Vec<(usize, usize)> {
+    //! Lists the cells a brush paints when moved along a path, within a grid
+    //! of the given number of rows and columns. The spray brush only paints
+    //! some of them, at random.
+    let footprint: Vec<(isize, isize)> = brush.footprint();
+    let mut cells: HashSet<(usize, usize)> = HashSet::new();
+    for (i, j) in path {
+        for (di, dj) in &footprint {
+            let (i, j) = (*i as isize + di, *j as isize + dj);
+            if (0..size.0 as isize).contains(&i) && (0..size.1 as isize).contains(&j) {
+                cells.insert((i as usize, j as usize));
+            }
+        }
+    }
+    let mut rng = rand::thread_rng();
+    cells
+        .into_iter()
+        .filter(|_| brush != Brush::Spray || rng.gen_bool(spray_density.clamp(0.0, 1.0)))
+        .collect()
+}
+
+pub fn enable_mouse() {
+    //! Starts reading mouse events, after ncurses is initialized.
+    keypad(stdscr(), true);
+    mousemask((ALL_MOUSE_EVENTS | REPORT_MOUSE_POSITION) as mmask_t, None);
+    // report presses and releases right away, rather than waiting to see
+    // whether they make a click
+    mouseinterval(0);
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(DRAG_REPORTING_ON.as_bytes());
+    let _ = stdout.flush();
+}
+
+pub fn disable_mouse() {
+    //! Stops the terminal from reporting drags, after ncurses has ended.
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(DRAG_REPORTING_OFF.as_bytes());
+    let _ = stdout.flush();
+}
+
+pub fn mouse_input() -> InputType {
+    //! Reads the mouse event ncurses has just reported, returning the input
+    //! it stands for at its screen position.
+    let mut event: MEVENT = MEVENT {
+        id: 0,
+        x: 0,
+        y: 0,
+        z: 0,
+        bstate: 0,
+    };
+    if getmouse(&mut event) != OK {
+        return InputType::Continue;
+    }
+    let (y, x) = (event.y.max(0) as u16, event.x.max(0) as u16);
+    let pressed = |button: i32| event.bstate & button as mmask_t != 0;
+    if pressed(BUTTON1_PRESSED) {
+        InputType::PaintStart(y, x)
+    } else if pressed(BUTTON3_PRESSED) {
+        InputType::EraseStart(y, x)
+    } else if pressed(BUTTON1_RELEASED) || pressed(BUTTON3_RELEASED) {
+        InputType::PaintEnd
+    } else if pressed(REPORT_MOUSE_POSITION) {
+        InputType::PaintTo(y, x)
+    } else {
+        InputType::Continue
+    }
+}
+
+pub fn line(from: (usize, usize), to: (usize, usize)) -> Vec<(usize, usize)> {
+    //! Lists the cells on a straight line between two cells, both included.
+    let (mut i, mut j) = (from.0 as isize, from.1 as isize);
+    let (end_i, end_j) = (to.0 as isize, to.1 as isize);
+    let (di, dj) = ((end_i - i).abs(), -(end_j - j).abs());
+    let (step_i, step_j) = ((end_i - i).signum(), (end_j - j).signum());
+    let mut error: isize = di + dj;
+    let mut cells: Vec<(usize, usize)> = vec![];
+    loop {
+        cells.push((i as usize, j as usize));
+        if (i, j) == (end_i, end_j) {
+            return cells;
+        }
+        let twice: isize = 2 * error;
+        if twice >= dj {
+            error += dj;
+            i += step_i;
+        }
+        if twice <= di {
+            error += di;
+            j += step_j;
+        }
+    }
+}
@@ -0,0 +1,1283 @@
+//! This module contains the Pattern struct, a set of live cells that can be
+//! placed onto the grid, and the decoders that produce patterns.
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::io::Read;
+use std::str::FromStr;
+
+use super::conway::Cell;
+
+/// File formats patterns can be read and written in.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// The seed file format, with `*` (or `O`) for live cells, `.` for dead
+    /// cells and `#` comments
+    Seed,
+    /// Run-length encoded, as used by most Life software
+    Rle,
+    /// The plaintext `.cells` format, with `O` for live cells and `!` comments
+    Plaintext,
+    /// Life 1.06, listing the `x y` coordinates of each live cell
+    Life106,
+    /// Golly's macrocell format, a quadtree of 8x8 leaves in which repeated
+    /// parts are written once, for very large patterns
+    Macrocell,
+}
+
+/// Most live cells a macrocell file is expanded into, beyond which it is
+/// refused rather than filling the memory.
+const MAX_MACROCELL_CELLS: u64 = 10_000_000;
+
+/// Level of the 8x8 leaves of a macrocell quadtree.
+const MACROCELL_LEAF_LEVEL: u32 = 3;
+
+/// A node of a macrocell quadtree, as read from a file.
+enum MacrocellNode {
+    /// The empty node, numbered 0
+    Empty,
+    /// An 8x8 block of cells, with the (row, column) of its live ones
+    Leaf(Vec<(u64, u64)>),
+    /// A square of 2^level cells, split into its northwest, northeast,
+    /// southwest and southeast quarters
+    Branch { level: u32, children: [usize; 4] },
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Seed => "txt",
+            ExportFormat::Rle => "rle",
+            ExportFormat::Plaintext => "cells",
+            ExportFormat::Life106 => "lif",
+            ExportFormat::Macrocell => "mc",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        //! Name of the format, for messages.
+        match self {
+            ExportFormat::Seed => "seed",
+            ExportFormat::Rle => "RLE",
+            ExportFormat::Plaintext => "plaintext",
+            ExportFormat::Life106 => "Life 1.06",
+            ExportFormat::Macrocell => "macrocell",
+        }
+    }
+
+    pub fn detect(path: &str, text: &str) -> ExportFormat {
+        //! Guesses the format of a pattern file from its extension, or failing
+        //! that from its contents, taking it for a seed file if they don't
+        //! tell.
+        ExportFormat::recognize(path, text).unwrap_or(ExportFormat::Seed)
+    }
+
+    pub fn recognize(path: &str, text: &str) -> Option<ExportFormat> {
+        //! Recognizes the format of a pattern file from its extension, or
+        //! failing that from its contents: the `[M2]` macrocell header, the
+        //! `#Life 1.06` header, RLE's
+        //! `x = ..` header or `$` and `!` tags, plaintext's `!` comments, or
+        //! rows of `*`, `O` and `.`. Returns `None` if nothing gives it away.
+        let extension: Option<&str> = std::path::Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str());
+        match extension {
+            Some("rle") => return Some(ExportFormat::Rle),
+            Some("cells") => return Some(ExportFormat::Plaintext),
+            Some("lif" | "life") => return Some(ExportFormat::Life106),
+            Some("mc") => return Some(ExportFormat::Macrocell),
+            _ => (),
+        }
+        let lines: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let first_line: &str = lines.first().copied().unwrap_or("");
+        // seed files and RLE both have `#` comments
+        let body: Vec<&str> = lines
+            .into_iter()
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+        let is_rle_header: bool = body.first().is_some_and(|line| {
+            line.strip_prefix('x')
+                .is_some_and(|rest| rest.trim_start().starts_with('='))
+        });
+        let is_seed: bool = body
+            .iter()
+            .all(|line| line.chars().all(|c| ".*O".contains(c) || c.is_whitespace()));
+        let is_rle_body: bool = body.iter().any(|line| line.contains(['$', '!']));
+        // rows of cells with a typo in them are still seed files, so that
+        // the typo is what gets reported
+        let has_rows: bool = body
+            .first()
+            .is_some_and(|line| line.starts_with(['.', '*']));
+        if first_line.starts_with("[M2]") {
+            Some(ExportFormat::Macrocell)
+        } else if first_line.starts_with("#Life 1.06") {
+            Some(ExportFormat::Life106)
+        } else if is_rle_header {
+            Some(ExportFormat::Rle)
+        } else if first_line.starts_with('!') {
+            Some(ExportFormat::Plaintext)
+        } else if is_seed {
+            Some(ExportFormat::Seed)
+        } else if is_rle_body {
+            Some(ExportFormat::Rle)
+        } else if has_rows {
+            Some(ExportFormat::Seed)
+        } else {
+            None
+        }
+    }
+}
+
+/// Where a pattern is placed on the grid.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Placement {
+    /// Aligned with the top-left corner of the grid, blank rows and columns
+    /// at its top and left included
+    TopLeft,
+    /// With the box around its live cells in the middle of the grid
+    #[default]
+    Center,
+    /// With the top-left corner of the box around its live cells at column
+    /// `x` and row `y` of the grid
+    At { x: usize, y: usize },
+}
+
+impl FromStr for Placement {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        //! Parses the `X,Y` position of the pattern's top-left corner.
+        let (x, y) = s
+            .split_once(',')
+            .ok_or_else(|| anyhow!("expected X,Y, got '{}'", s))?;
+        match (x.trim().parse::<usize>(), y.trim().parse::<usize>()) {
+            (Ok(x), Ok(y)) => Ok(Placement::At { x, y }),
+            _ => Err(anyhow!("expected two whole numbers X,Y, got '{}'", s)),
+        }
+    }
+}
+
+/// Clockwise rotation applied to a pattern.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Rotation {
+    /// A quarter turn
+    #[value(name = "90")]
+    Quarter,
+    /// A half turn
+    #[value(name = "180")]
+    Half,
+    /// Three quarter turns
+    #[value(name = "270")]
+    ThreeQuarters,
+}
+
+/// How a pattern is turned around before being placed: mirrored first, then
+/// rotated, then scaled up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    /// Clockwise rotation, if any
+    pub rotation: Option<Rotation>,
+    /// Whether the pattern is mirrored left to right
+    pub flip_horizontal: bool,
+    /// Whether the pattern is mirrored top to bottom
+    pub flip_vertical: bool,
+    /// Size of the square block each cell is expanded into
+    pub scale: usize,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            rotation: None,
+            flip_horizontal: false,
+            flip_vertical: false,
+            scale: 1,
+        }
+    }
+}
+
+pub fn split_seed(seed: &str) -> (&str, Option<Placement>) {
+    //! Splits a seed given on the command line into the path of its file
+    //! and, if it ends with `@X,Y`, where it is placed.
+    match seed.rsplit_once('@') {
+        Some((path, at)) => match at.parse::<Placement>() {
+            Ok(placement) => (path, Some(placement)),
+            Err(_) => (seed, None),
+        },
+        None => (seed, None),
+    }
+}
+
+/// What a pattern file says about the pattern: the `#N`, `#O` and `#C`
+/// lines of RLE, or the `!Name:`, `!Author:` and other `!` lines of
+/// plaintext.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    /// Name of the pattern
+    pub name: Option<String>,
+    /// Who found or wrote the pattern
+    pub author: Option<String>,
+    /// Lines of comments, in order
+    pub comments: Vec<String>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.author.is_none() && self.comments.is_empty()
+    }
+
+    fn from_tagged(text: &str) -> Metadata {
+        //! Reads the `#N`, `#O` and `#C` lines of a file, giving the
+        //! pattern's name, author and comments, as in RLE.
+        let mut metadata: Metadata = Metadata::default();
+        for line in text.lines().map(str::trim) {
+            let (Some(tag), Some(value)) = (line.get(..2), line.get(2..)) else {
+                continue;
+            };
+            let value: String = value.trim().to_string();
+            match tag {
+                "#N" => metadata.name = Some(value),
+                "#O" => metadata.author = Some(value),
+                "#C" | "#c" => metadata.comments.push(value),
+                _ => (),
+            }
+        }
+        metadata
+    }
+
+    fn to_tagged(&self) -> String {
+        //! Writes the name, author and comments as `#N`, `#O` and `#C` lines.
+        let mut text: String = String::new();
+        if let Some(name) = &self.name {
+            text.push_str(&format!("#N {}\n", name));
+        }
+        if let Some(author) = &self.author {
+            text.push_str(&format!("#O {}\n", author));
+        }
+        for comment in &self.comments {
+            text.push_str(&format!("#C {}\n", comment));
+        }
+        text
+    }
+
+    pub fn title(&self) -> Option<String> {
+        //! Describes the pattern by its name and author, e.g. `Gosper glider
+        //! gun by Bill Gosper`, if the file gives either.
+        match (&self.name, &self.author) {
+            (Some(name), Some(author)) => Some(format!("{} by {}", name, author)),
+            (Some(name), None) => Some(name.clone()),
+            (None, Some(author)) => Some(format!("by {}", author)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// A pattern of live cells, independent of any grid.
+/// Coordinates are relative to the top-left corner of the pattern.
+#[derive(Debug, Clone, Default)]
+pub struct Pattern {
+    /// (row, column) coordinates of the live cells
+    pub cells: Vec<(usize, usize)>,
+    /// Number of rows spanned by the pattern
+    pub rows: usize,
+    /// Number of columns spanned by the pattern
+    pub cols: usize,
+    /// Name, author and comments from the file the pattern was read from
+    pub metadata: Metadata,
+}
+
+impl Pattern {
+    pub fn from_cells(cells: Vec<(usize, usize)>) -> Pattern {
+        //! Creates a pattern from a list of live cells, computing its size.
+        let rows: usize = cells.iter().map(|(i, _)| i + 1).max().unwrap_or(0);
+        let cols: usize = cells.iter().map(|(_, j)| j + 1).max().unwrap_or(0);
+        Pattern {
+            cells,
+            rows,
+            cols,
+            metadata: Metadata::default(),
+        }
+    }
+
+    pub fn from_seed_text(text: &str) -> Result<Pattern> {
+        //! Parses the seed file format, where each `*` or `O` is a live cell,
+        //! each `.` or space a dead cell, and lines starting with `#` are
+        //! comments. Any other character is reported with its line and
+        //! column.
+        let mut cells: Vec<(usize, usize)> = vec![];
+        let rows = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim_start().starts_with('#'));
+        for (rownum, (lineno, line)) in rows.enumerate() {
+            for (colnum, cell) in line.chars().enumerate() {
+                match cell {
+                    '*' | 'O' => cells.push((rownum, colnum)),
+                    '.' => (),
+                    c if c.is_whitespace() => (),
+                    c => {
+                        return Err(anyhow!(
+                            "unexpected character '{}' at line {}, column {}",
+                            c,
+                            lineno + 1,
+                            colnum + 1
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(Pattern::from_cells(cells))
+    }
+
+    pub fn from_file(path: &str) -> Result<Pattern> {
+        //! Reads a seed file into a pattern, in whichever of the supported
+        //! formats it is in.
+        let text: String = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read seed file '{}'", path))?;
+        let format: ExportFormat = ExportFormat::recognize(path, &text).ok_or_else(|| {
+            anyhow!(
+                "Unrecognized seed file '{}': expected rows of '*' and '.' (seed), \
+                 RLE with an 'x = ..' header, plaintext with 'O' and '.', a \
+                 '#Life 1.06' header or a '[M2]' macrocell header",
+                path
+            )
+        })?;
+        Pattern::parse(&text, format).with_context(|| {
+            format!(
+                "Invalid seed file '{}' in the {} format",
+                path,
+                format.name()
+            )
+        })
+    }
+
+    pub fn from_stdin() -> Result<Pattern> {
+        //! Reads a pattern piped to stdin, in any of the supported formats.
+        let mut text: String = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .context("Failed to read the seed from stdin")?;
+        Pattern::parse(&text, ExportFormat::detect("-", &text)).context("Invalid seed on stdin")
+    }
+
+    pub fn from_plaintext(text: &str) -> Pattern {
+        //! Parses the plaintext format, where each `O` (or `*`) is a live cell
+        //! and lines starting with `!` are comments, `!Name:` and `!Author:`
+        //! giving the pattern's name and author.
+        let mut metadata: Metadata = Metadata::default();
+        for line in text.lines().filter_map(|line| line.strip_prefix('!')) {
+            let line: &str = line.trim();
+            if let Some(name) = line.strip_prefix("Name:") {
+                metadata.name = Some(name.trim().to_string());
+            } else if let Some(author) = line.strip_prefix("Author:") {
+                metadata.author = Some(author.trim().to_string());
+            } else if !line.is_empty() {
+                metadata.comments.push(line.to_string());
+            }
+        }
+        let rows: Vec<&str> = text.lines().filter(|line| !line.starts_with('!')).collect();
+        let mut cells: Vec<(usize, usize)> = vec![];
+        for (rownum, line) in rows.iter().enumerate() {
+            for (colnum, cell) in line.chars().enumerate() {
+                if cell == 'O' || cell == '*' {
+                    cells.push((rownum, colnum));
+                }
+            }
+        }
+        Pattern {
+            metadata,
+            ..Pattern::from_cells(cells)
+        }
+    }
+
+    pub fn from_rle(text: &str) -> Result<Pattern> {
+        //! Parses the RLE format. The `x = .., y = ..` header is optional, and
+        //! any state other than `b` or `.` counts as alive. `#N`, `#O` and
+        //! `#C` lines give the pattern's name, author and comments.
+        let metadata: Metadata = Metadata::from_tagged(text);
+        let mut cells: Vec<(usize, usize)> = vec![];
+        let (mut row, mut col): (usize, usize) = (0, 0);
+        let mut count: Option<usize> = None;
+        let body = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('#') && !line.starts_with('x'));
+        'lines: for line in body {
+            for c in line.chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    count = Some(count.unwrap_or(0) * 10 + digit as usize);
+                    continue;
+                }
+                let run: usize = count.take().unwrap_or(1);
+                match c {
+                    'b' | '.' => col += run,
+                    '$' => {
+                        row += run;
+                        col = 0;
+                    }
+                    '!' => break 'lines,
+                    c if c.is_ascii_alphabetic() => {
+                        cells.extend((col..col + run).map(|j| (row, j)));
+                        col += run;
+                    }
+                    c if c.is_whitespace() => (),
+                    c => return Err(anyhow!("Invalid character '{}' in RLE", c)),
+                }
+            }
+        }
+        Ok(Pattern {
+            metadata,
+            ..Pattern::from_cells(cells)
+        })
+    }
+
+    pub fn from_life106(text: &str) -> Result<Pattern> {
+        //! Parses the Life 1.06 format, with one `x y` coordinate pair of a
+        //! live cell per line. Coordinates may be negative, so the pattern is
+        //! moved to start at the top-left corner. `#N`, `#O` and `#C` lines
+        //! give the pattern's name, author and comments, as in RLE.
+        let mut coordinates: Vec<(i64, i64)> = vec![];
+        for (lineno, line) in text.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parsed: Option<(i64, i64)> = line
+                .split_once(char::is_whitespace)
+                .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)));
+            let (x, y) = parsed.ok_or_else(|| {
+                anyhow!("line {}: expected '<x> <y>', got '{}'", lineno + 1, line)
+            })?;
+            coordinates.push((y, x));
+        }
+        let top: i64 = coordinates.iter().map(|(i, _)| *i).min().unwrap_or(0);
+        let left: i64 = coordinates.iter().map(|(_, j)| *j).min().unwrap_or(0);
+        Ok(Pattern {
+            metadata: Metadata::from_tagged(text),
+            ..Pattern::from_cells(
+                coordinates
+                    .iter()
+                    .map(|(i, j)| ((i - top) as usize, (j - left) as usize))
+                    .collect(),
+            )
+        })
+    }
+
+    pub fn from_macrocell(text: &str) -> Result<Pattern> {
+        //! Parses Golly's macrocell format. After the `[M2]` header and `#`
+        //! lines, each line is a node of a quadtree, numbered from 1: either
+        //! an 8x8 leaf, written as rows of `.` and `*` ending in `$`, or
+        //! `level nw ne sw se`, a square of 2^level cells made of the nodes
+        //! numbered, 0 being empty. The last node is the whole pattern.
+        //! `#N`, `#O` and `#C` lines give the pattern's name, author and
+        //! comments, as in RLE.
+        let mut nodes: Vec<MacrocellNode> = vec![MacrocellNode::Empty];
+        for (lineno, line) in text.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with(['[', '#']) {
+                continue;
+            }
+            let node: MacrocellNode = if line.starts_with(['.', '*', '$']) {
+                Pattern::macrocell_leaf(line)
+            } else {
+                Pattern::macrocell_branch(line, &nodes)
+            }
+            .with_context(|| format!("line {}", lineno + 1))?;
+            nodes.push(node);
+        }
+
+        // count the cells first, as repeated nodes can stand for more than
+        // fit in memory
+        let mut populations: Vec<u64> = Vec::with_capacity(nodes.len());
+        for node in &nodes {
+            let population: u64 = match node {
+                MacrocellNode::Empty => 0,
+                MacrocellNode::Leaf(cells) => cells.len() as u64,
+                MacrocellNode::Branch { children, .. } => children
+                    .iter()
+                    .fold(0u64, |sum, &child| sum.saturating_add(populations[child])),
+            };
+            populations.push(population);
+        }
+        let root: usize = nodes.len() - 1;
+        if populations[root] > MAX_MACROCELL_CELLS {
+            return Err(anyhow!(
+                "the pattern has {} live cells, more than the {} that can be loaded",
+                populations[root],
+                MAX_MACROCELL_CELLS
+            ));
+        }
+
+        let mut coordinates: Vec<(u64, u64)> = vec![];
+        expand_macrocell(&nodes, root, (0, 0), &mut coordinates);
+        let top: u64 = coordinates.iter().map(|(i, _)| *i).min().unwrap_or(0);
+        let left: u64 = coordinates.iter().map(|(_, j)| *j).min().unwrap_or(0);
+        Ok(Pattern {
+            metadata: Metadata::from_tagged(text),
+            ..Pattern::from_cells(
+                coordinates
+                    .iter()
+                    .map(|(i, j)| ((i - top) as usize, (j - left) as usize))
+                    .collect(),
+            )
+        })
+    }
+
+    fn macrocell_leaf(line: &str) -> Result<MacrocellNode> {
+        //! Parses an 8x8 leaf of a macrocell file, whose rows end in `$` and
+        //! leave out their trailing dead cells.
+        let size: u64 = 1 << MACROCELL_LEAF_LEVEL;
+        let mut cells: Vec<(u64, u64)> = vec![];
+        let (mut row, mut col): (u64, u64) = (0, 0);
+        for c in line.chars() {
+            match c {
+                '$' => (row, col) = (row + 1, 0),
+                '.' | '*' if row < size && col < size => {
+                    if c == '*' {
+                        cells.push((row, col));
+                    }
+                    col += 1;
+                }
+                '.' | '*' => return Err(anyhow!("leaf '{}' is larger than 8x8", line)),
+                _ => return Err(anyhow!("unexpected '{}' in leaf '{}'", c, line)),
+            }
+        }
+        Ok(MacrocellNode::Leaf(cells))
+    }
+
+    fn macrocell_branch(line: &str, nodes: &[MacrocellNode]) -> Result<MacrocellNode> {
+        //! Parses a `level nw ne sw se` node of a macrocell file, whose
+        //! children have to come before it and be one level lower.
+        let numbers: Vec<u64> = line
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| anyhow!("expected 'level nw ne sw se', got '{}'", line))?;
+        let [level, nw, ne, sw, se] = numbers[..] else {
+            return Err(anyhow!("expected 'level nw ne sw se', got '{}'", line));
+        };
+        if level == 1 {
+            return Err(anyhow!(
+                "only two-state patterns are supported, not multi-state ones"
+            ));
+        }
+        if level <= MACROCELL_LEAF_LEVEL as u64 || level > 62 {
+            return Err(anyhow!("unsupported level {} in '{}'", level, line));
+        }
+        let children: [usize; 4] = [nw, ne, sw, se].map(|child| child as usize);
+        for &child in &children {
+            let child_level: Option<u32> = match nodes.get(child) {
+                None => return Err(anyhow!("node {} isn't defined yet in '{}'", child, line)),
+                Some(MacrocellNode::Empty) => None,
+                Some(MacrocellNode::Leaf(_)) => Some(MACROCELL_LEAF_LEVEL),
+                Some(MacrocellNode::Branch { level, .. }) => Some(*level),
+            };
+            if child_level.is_some_and(|child_level| child_level as u64 != level - 1) {
+                return Err(anyhow!(
+                    "node {} in '{}' isn't of level {}",
+                    child,
+                    line,
+                    level - 1
+                ));
+            }
+        }
+        Ok(MacrocellNode::Branch {
+            level: level as u32,
+            children,
+        })
+    }
+
+    pub fn parse(text: &str, format: ExportFormat) -> Result<Pattern> {
+        //! Parses a pattern in the given format.
+        match format {
+            ExportFormat::Seed => Pattern::from_seed_text(text),
+            ExportFormat::Rle => Pattern::from_rle(text),
+            ExportFormat::Plaintext => Ok(Pattern::from_plaintext(text)),
+            ExportFormat::Life106 => Pattern::from_life106(text),
+            ExportFormat::Macrocell => Pattern::from_macrocell(text),
+        }
+    }
+
+    pub fn from_apgcode(apgcode: &str) -> Result<Pattern> {
+        //! Decodes an apgcode such as `xq4_153` or `xs4_33`.
+        //!
+        //! The part after the underscore is in extended Wechsler format: each
+        //! character from `0-9a-v` encodes a column of five cells (least
+        //! significant bit on top), `w` and `x` stand for two and three empty
+        //! columns, `y` followed by a character stands for 4 to 39 empty
+        //! columns, and `z` starts the next strip of five rows.
+        let wechsler: &str = match apgcode.split_once('_') {
+            Some((prefix, wechsler)) => {
+                let mut chars = prefix.chars();
+                let valid_prefix: bool = chars.next() == Some('x')
+                    && matches!(chars.next(), Some('s' | 'p' | 'q'))
+                    && chars.as_str().parse::<usize>().is_ok();
+                if !valid_prefix {
+                    return Err(anyhow!(
+                        "Unsupported apgcode prefix '{}': expected xs<N>, xp<N> or xq<N>",
+                        prefix
+                    ));
+                }
+                wechsler
+            }
+            None => apgcode,
+        };
+
+        let mut cells: Vec<(usize, usize)> = vec![];
+        let mut strip: usize = 0;
+        let mut col: usize = 0;
+        let mut chars = wechsler.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                'w' => col += 2,
+                'x' => col += 3,
+                'y' => {
+                    let count: char = chars
+                        .next()
+                        .ok_or_else(|| anyhow!("apgcode '{}' ends after 'y'", apgcode))?;
+                    let count: u32 = count.to_digit(36).ok_or_else(|| {
+                        anyhow!("Invalid character '{}' after 'y' in apgcode", count)
+                    })?;
+                    col += 4 + count as usize;
+                }
+                'z' => {
+                    strip += 1;
+                    col = 0;
+                }
+                _ => {
+                    let bits: u32 = c
+                        .to_digit(32)
+                        .ok_or_else(|| anyhow!("Invalid character '{}' in apgcode", c))?;
+                    for bit in 0..5 {
+                        if bits & (1 << bit) != 0 {
+                            cells.push((strip * 5 + bit, col));
+                        }
+                    }
+                    col += 1;
+                }
+            }
+        }
+
+        if cells.is_empty() {
+            return Err(anyhow!("apgcode '{}' contains no live cells", apgcode));
+        }
+        Ok(Pattern::from_cells(cells))
+    }
+
+    pub fn from_region(
+        grid: &[Vec<Cell>],
+        top_left: (usize, usize),
+        bottom_right: (usize, usize),
+    ) -> Pattern {
+        //! Copies the live cells within a rectangle of the grid, given by its
+        //! inclusive corners, into a pattern the size of the rectangle.
+        let mut cells: Vec<(usize, usize)> = vec![];
+        let rows = grid
+            .iter()
+            .enumerate()
+            .take(bottom_right.0 + 1)
+            .skip(top_left.0);
+        for (i, row) in rows {
+            let row = row
+                .iter()
+                .enumerate()
+                .take(bottom_right.1 + 1)
+                .skip(top_left.1);
+            for (j, cell) in row {
+                if cell.is_alive() {
+                    cells.push((i - top_left.0, j - top_left.1));
+                }
+            }
+        }
+        Pattern {
+            cells,
+            rows: bottom_right.0 - top_left.0 + 1,
+            cols: bottom_right.1 - top_left.1 + 1,
+            metadata: Metadata::default(),
+        }
+    }
+
+    pub fn transformed(&self, transform: Transform) -> Pattern {
+        //! Returns the pattern mirrored, rotated and scaled as the transform
+        //! says, within the box of its rows and columns, so that blank rows
+        //! and columns move with it.
+        let (rows, cols) = (self.rows, self.cols);
+        let cells: Vec<(usize, usize)> = self
+            .cells
+            .iter()
+            .map(|&(i, j)| {
+                let i: usize = if transform.flip_vertical {
+                    rows - 1 - i
+                } else {
+                    i
+                };
+                let j: usize = if transform.flip_horizontal {
+                    cols - 1 - j
+                } else {
+                    j
+                };
+                match transform.rotation {
+                    None => (i, j),
+                    Some(Rotation::Quarter) => (j, rows - 1 - i),
+                    Some(Rotation::Half) => (rows - 1 - i, cols - 1 - j),
+                    Some(Rotation::ThreeQuarters) => (cols - 1 - j, i),
+                }
+            })
+            .collect();
+        let (rows, cols) = match transform.rotation {
+            Some(Rotation::Quarter | Rotation::ThreeQuarters) => (cols, rows),
+            _ => (rows, cols),
+        };
+        let scale: usize = transform.scale.max(1);
+        let cells: Vec<(usize, usize)> = cells
+            .into_iter()
+            .flat_map(|(i, j)| {
+                (0..scale)
+                    .flat_map(move |di| (0..scale).map(move |dj| (i * scale + di, j * scale + dj)))
+            })
+            .collect();
+        Pattern {
+            cells,
+            rows: rows * scale,
+            cols: cols * scale,
+            metadata: self.metadata.clone(),
+        }
+    }
+
+    pub fn cropped(&self, nrows: usize, ncols: usize) -> Pattern {
+        //! Cuts the pattern down to fit a grid of `nrows` by `ncols` cells,
+        //! keeping the middle of the box around its live cells along each
+        //! side that is too long.
+        let (first_row, first_col) = self.first_cell();
+        let last_row: usize = self.cells.iter().map(|(i, _)| *i).max().unwrap_or(0);
+        let last_col: usize = self.cells.iter().map(|(_, j)| *j).max().unwrap_or(0);
+        // first row or column kept along a side, and how many are
+        let window = |size: usize, first: usize, last: usize, limit: usize| {
+            if size <= limit {
+                (0, size)
+            } else {
+                (first + (last + 1 - first).saturating_sub(limit) / 2, limit)
+            }
+        };
+        let (top, rows) = window(self.rows, first_row, last_row, nrows);
+        let (left, cols) = window(self.cols, first_col, last_col, ncols);
+        Pattern {
+            cells: self
+                .cells
+                .iter()
+                .filter(|(i, j)| (top..top + rows).contains(i) && (left..left + cols).contains(j))
+                .map(|(i, j)| (i - top, j - left))
+                .collect(),
+            rows,
+            cols,
+            metadata: self.metadata.clone(),
+        }
+    }
+
+    fn to_rows(&self) -> Vec<Vec<bool>> {
+        //! Expands the pattern into rows of alive/dead cells.
+        let mut rows: Vec<Vec<bool>> = vec![vec![false; self.cols]; self.rows];
+        for &(i, j) in &self.cells {
+            rows[i][j] = true;
+        }
+        rows
+    }
+
+    pub fn to_seed_text(&self) -> String {
+        //! Writes the pattern in the seed file format, with `*` for live
+        //! cells and `.` for dead cells.
+        let mut text: String = String::new();
+        for row in self.to_rows() {
+            text.extend(row.iter().map(|alive| if *alive { '*' } else { '.' }));
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn to_plaintext(&self) -> String {
+        //! Writes the pattern in the plaintext format, with `O` for live cells
+        //! and `.` for dead cells, after its name, author and comments.
+        let mut text: String = String::new();
+        if let Some(name) = &self.metadata.name {
+            text.push_str(&format!("!Name: {}\n", name));
+        }
+        if let Some(author) = &self.metadata.author {
+            text.push_str(&format!("!Author: {}\n", author));
+        }
+        for comment in &self.metadata.comments {
+            text.push_str(&format!("!{}\n", comment));
+        }
+        text.push_str(&self.to_seed_text().replace('*', "O"));
+        text
+    }
+
+    pub fn to_life106(&self) -> String {
+        //! Writes the pattern in the Life 1.06 format, with its name, author
+        //! and comments as in RLE.
+        let mut text: String = "#Life 1.06\n".to_string();
+        text.push_str(&self.metadata.to_tagged());
+        for (i, j) in &self.cells {
+            text.push_str(&format!("{} {}\n", j, i));
+        }
+        text
+    }
+
+    pub fn to_rle(&self, rule: &str) -> String {
+        //! Writes the pattern in the RLE format, wrapping lines at 70 characters.
+        let mut items: Vec<String> = vec![];
+        let mut last_row: usize = 0;
+        for (i, row) in self.to_rows().iter().enumerate() {
+            // trailing dead cells of a row are implied
+            let len: usize = row.iter().rposition(|alive| *alive).map_or(0, |j| j + 1);
+            if len == 0 {
+                continue;
+            }
+            if i > last_row {
+                items.push(run(i - last_row, '$'));
+            }
+            last_row = i;
+            let mut j: usize = 0;
+            while j < len {
+                let count: usize = row[j..len].iter().take_while(|a| **a == row[j]).count();
+                items.push(run(count, if row[j] { 'o' } else { 'b' }));
+                j += count;
+            }
+        }
+        items.push("!".to_string());
+
+        let mut text: String = self.metadata.to_tagged();
+        text.push_str(&format!(
+            "x = {}, y = {}, rule = {}\n",
+            self.cols, self.rows, rule
+        ));
+        let mut line_len: usize = 0;
+        for item in items {
+            // only break lines between runs
+            if line_len + item.len() > 70 {
+                text.push('\n');
+                line_len = 0;
+            }
+            line_len += item.len();
+            text.push_str(&item);
+        }
+        text.push('\n');
+        text
+    }
+
+    pub fn to_macrocell(&self, rule: &str) -> String {
+        //! Writes the pattern in Golly's macrocell format, writing each
+        //! distinct node of the quadtree once, after its name, author and
+        //! comments as in RLE.
+        let mut level: u32 = MACROCELL_LEAF_LEVEL;
+        while (1usize << level) < self.rows.max(self.cols) {
+            level += 1;
+        }
+        let mut lines: Vec<String> = vec![];
+        let mut numbers: HashMap<String, usize> = HashMap::new();
+        build_macrocell(self.cells.clone(), level, &mut lines, &mut numbers);
+        let mut text: String = format!("[M2] (conway)\n#R {}\n", rule);
+        text.push_str(&self.metadata.to_tagged());
+        for line in lines {
+            text.push_str(&line);
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn to_text(&self, format: ExportFormat, rule: &str) -> String {
+        //! Writes the pattern in the given format.
+        match format {
+            ExportFormat::Seed => self.to_seed_text(),
+            ExportFormat::Rle => self.to_rle(rule),
+            ExportFormat::Plaintext => self.to_plaintext(),
+            ExportFormat::Life106 => self.to_life106(),
+            ExportFormat::Macrocell => self.to_macrocell(rule),
+        }
+    }
+
+    pub fn write(&self, path: &str, format: ExportFormat, rule: &str) -> Result<()> {
+        //! Writes the pattern to a file in the given format.
+        let text: String = self.to_text(format, rule);
+        std::fs::write(path, text).with_context(|| format!("Failed to write '{}'", path))
+    }
+
+    pub fn check_fits(&self, nrows: usize, ncols: usize, placement: Placement) -> Result<()> {
+        //! Fails if the pattern is larger than a grid of `nrows` by `ncols`
+        //! cells, or runs off it where it is placed, rather than have `place`
+        //! cut it off.
+        if let Placement::At { x, y } = placement {
+            let (top, left) = self.origin(nrows, ncols, placement);
+            if top + self.rows as isize > nrows as isize
+                || left + self.cols as isize > ncols as isize
+            {
+                let (first_row, first_col) = self.first_cell();
+                return Err(anyhow!(
+                    "the pattern is {} rows by {} columns, which runs off the {} by {} grid \
+                     at {},{}",
+                    self.rows - first_row,
+                    self.cols - first_col,
+                    nrows,
+                    ncols,
+                    x,
+                    y
+                ));
+            }
+        }
+        if self.rows > nrows || self.cols > ncols {
+            return Err(anyhow!(
+                "the pattern is {} rows by {} columns, larger than the {} by {} grid; \
+                 enlarge the terminal, or set --height and --width",
+                self.rows,
+                self.cols,
+                nrows,
+                ncols
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn check_scaled_fits(&self, scale: usize, nrows: usize, ncols: usize) -> Result<()> {
+        //! Fails if blowing the pattern up by `scale` would make it larger
+        //! than a grid of `nrows` by `ncols` cells, before any cell is
+        //! expanded, so that a huge scale can't run out of memory.
+        let rows: Option<usize> = self.rows.checked_mul(scale);
+        let cols: Option<usize> = self.cols.checked_mul(scale);
+        match (rows, cols) {
+            (Some(rows), Some(cols)) if rows <= nrows && cols <= ncols => Ok(()),
+            _ => Err(anyhow!(
+                "the pattern is {} rows by {} columns, which scaled by {} is larger than the \
+                 {} by {} grid; enlarge the terminal, or set --height and --width",
+                self.rows,
+                self.cols,
+                scale,
+                nrows,
+                ncols
+            )),
+        }
+    }
+
+    pub fn place(&self, grid: &mut [Vec<Cell>], placement: Placement) {
+        //! Sets the pattern's cells alive on the grid, where the placement
+        //! puts them. Cells that don't fit on the grid are truncated.
+        let (nrows, ncols) = (grid.len(), grid.first().map_or(0, Vec::len));
+        let (top, left) = self.origin(nrows, ncols, placement);
+        self.place_at(grid, top, left);
+    }
+
+    pub fn place_tiled(&self, grid: &mut [Vec<Cell>], placement: Placement, spacing: usize) {
+        //! Repeats the pattern across the whole grid in a lattice, with
+        //! `spacing` dead cells between the boxes around the live cells of
+        //! neighboring copies and one copy where the placement puts it.
+        //! Only copies that fit on the grid whole are placed, so that cut-off
+        //! ones don't meet their neighbors across the edges of a torus.
+        if self.cells.is_empty() {
+            return;
+        }
+        let (nrows, ncols) = (grid.len(), grid.first().map_or(0, Vec::len));
+        let (first_row, first_col) = self.first_cell();
+        let last_row: usize = self.cells.iter().map(|(i, _)| *i).max().unwrap_or(0);
+        let last_col: usize = self.cells.iter().map(|(_, j)| *j).max().unwrap_or(0);
+        let (row_period, col_period) = (
+            (last_row - first_row + 1 + spacing) as isize,
+            (last_col - first_col + 1 + spacing) as isize,
+        );
+        let (top, left) = self.origin(nrows, ncols, placement);
+        // start from the copy whose live cells begin just above and left of
+        // the grid, so that the lattice covers all of it
+        let top: isize =
+            (top + first_row as isize).rem_euclid(row_period) - row_period - first_row as isize;
+        let left: isize =
+            (left + first_col as isize).rem_euclid(col_period) - col_period - first_col as isize;
+        let fits = |start: isize, first: usize, last: usize, size: usize| -> bool {
+            start + first as isize >= 0 && start + (last as isize) < size as isize
+        };
+        for copy_top in (top..nrows as isize).step_by(row_period as usize) {
+            if !fits(copy_top, first_row, last_row, nrows) {
+                continue;
+            }
+            for copy_left in (left..ncols as isize).step_by(col_period as usize) {
+                if fits(copy_left, first_col, last_col, ncols) {
+                    self.place_at(grid, copy_top, copy_left);
+                }
+            }
+        }
+    }
+
+    fn place_at(&self, grid: &mut [Vec<Cell>], top: isize, left: isize) {
+        //! Sets the pattern's cells alive on the grid with its row and column
+        //! 0 at `top` and `left`, truncating cells that don't fit.
+        for &(i, j) in &self.cells {
+            let (i, j) = (i as isize + top, j as isize + left);
+            if i < 0 || j < 0 {
+                continue;
+            }
+            if let Some(cell) = grid
+                .get_mut(i as usize)
+                .and_then(|row| row.get_mut(j as usize))
+            {
+                cell.set_alive();
+            }
+        }
+    }
+
+    fn first_cell(&self) -> (usize, usize) {
+        //! Finds the top-left corner of the box around the live cells.
+        (
+            self.cells.iter().map(|(i, _)| *i).min().unwrap_or(0),
+            self.cells.iter().map(|(_, j)| *j).min().unwrap_or(0),
+        )
+    }
+
+    fn origin(&self, nrows: usize, ncols: usize, placement: Placement) -> (isize, isize) {
+        //! Finds where the pattern's row and column 0 go on a grid of
+        //! `nrows` by `ncols` cells.
+        let (first_row, first_col) = self.first_cell();
+        let (top, left): (usize, usize) = match placement {
+            Placement::TopLeft => return (0, 0),
+            Placement::Center => (
+                nrows.saturating_sub(self.rows - first_row) / 2,
+                ncols.saturating_sub(self.cols - first_col) / 2,
+            ),
+            Placement::At { x, y } => (y, x),
+        };
+        (
+            top as isize - first_row as isize,
+            left as isize - first_col as isize,
+        )
+    }
+}
+
+fn expand_macrocell(
+    nodes: &[MacrocellNode],
+    node: usize,
+    (top, left): (u64, u64),
+    cells: &mut Vec<(u64, u64)>,
+) {
+    //! Adds the live cells of a node of a macrocell quadtree, whose top-left
+    //! corner is at `top` and `left`.
+    match &nodes[node] {
+        MacrocellNode::Empty => (),
+        MacrocellNode::Leaf(leaf) => {
+            cells.extend(leaf.iter().map(|(i, j)| (top + i, left + j)));
+        }
+        MacrocellNode::Branch { level, children } => {
+            let half: u64 = 1 << (level - 1);
+            let corners: [(u64, u64); 4] = [
+                (top, left),
+                (top, left + half),
+                (top + half, left),
+                (top + half, left + half),
+            ];
+            for (&child, corner) in children.iter().zip(corners) {
+                expand_macrocell(nodes, child, corner, cells);
+            }
+        }
+    }
+}
+
+fn build_macrocell(
+    cells: Vec<(usize, usize)>,
+    level: u32,
+    lines: &mut Vec<String>,
+    numbers: &mut HashMap<String, usize>,
+) -> usize {
+    //! Adds the node of a macrocell quadtree for a square of 2^level cells,
+    //! with the given live cells relative to its top-left corner, and the
+    //! nodes under it, returning its number. Nodes already written are
+    //! reused.
+    if cells.is_empty() {
+        return 0;
+    }
+    let line: String = if level == MACROCELL_LEAF_LEVEL {
+        let size: usize = 1 << MACROCELL_LEAF_LEVEL;
+        let mut rows: Vec<Vec<char>> = vec![vec!['.'; size]; size];
+        for (i, j) in cells {
+            rows[i][j] = '*';
+        }
+        let last: usize = rows.iter().rposition(|row| row.contains(&'*')).unwrap_or(0);
+        rows[..=last]
+            .iter()
+            .map(|row| {
+                // trailing dead cells of a row are implied
+                let len: usize = row.iter().rposition(|c| *c == '*').map_or(0, |j| j + 1);
+                format!("{}$", row[..len].iter().collect::<String>())
+            })
+            .collect()
+    } else {
+        let half: usize = 1 << (level - 1);
+        let mut quarters: [Vec<(usize, usize)>; 4] = Default::default();
+        for (i, j) in cells {
+            let quarter: usize = 2 * usize::from(i >= half) + usize::from(j >= half);
+            quarters[quarter].push((i % half, j % half));
+        }
+        let children: Vec<String> = quarters
+            .into_iter()
+            .map(|quarter| build_macrocell(quarter, level - 1, lines, numbers).to_string())
+            .collect();
+        format!("{} {}", level, children.join(" "))
+    };
+    if let Some(&number) = numbers.get(&line) {
+        return number;
+    }
+    lines.push(line.clone());
+    numbers.insert(line, lines.len());
+    lines.len()
+}
+
+fn run(count: usize, tag: char) -> String {
+    //! Formats a run of `count` of the tag for an RLE body.
+    if count > 1 {
+        format!("{}{}", count, tag)
+    } else {
+        tag.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER: &str = ".*.\n..*\n***\n";
+
+    fn glider() -> Pattern {
+        Pattern {
+            metadata: Metadata {
+                name: Some("Glider".to_string()),
+                author: Some("Richard K. Guy".to_string()),
+                comments: vec!["The smallest spaceship".to_string()],
+            },
+            ..Pattern::from_seed_text(GLIDER).unwrap()
+        }
+    }
+
+    fn sorted_cells(pattern: &Pattern) -> Vec<(usize, usize)> {
+        let mut cells: Vec<(usize, usize)> = pattern.cells.clone();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn every_format_reads_back_what_it_writes() {
+        let glider: Pattern = glider();
+        for format in [
+            ExportFormat::Seed,
+            ExportFormat::Rle,
+            ExportFormat::Plaintext,
+            ExportFormat::Life106,
+            ExportFormat::Macrocell,
+        ] {
+            let text: String = glider.to_text(format, "B3/S23");
+            assert_eq!(ExportFormat::detect("-", &text), format, "{}", text);
+            let read: Pattern = Pattern::parse(&text, format).unwrap();
+            assert_eq!(sorted_cells(&read), sorted_cells(&glider), "{}", text);
+            assert_eq!((read.rows, read.cols), (3, 3), "{}", text);
+            if format != ExportFormat::Seed {
+                assert_eq!(read.metadata, glider.metadata, "{}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn rle_runs_span_cells_and_rows() {
+        let rle: Pattern = Pattern::from_rle("#N Glider\nx = 3, y = 3\nbo$2bo$3o!").unwrap();
+        assert_eq!(sorted_cells(&rle), sorted_cells(&glider()));
+        assert_eq!(rle.metadata.name.as_deref(), Some("Glider"));
+
+        let gap: Pattern = Pattern::from_rle("o2$o!").unwrap();
+        assert_eq!(gap.cells, vec![(0, 0), (2, 0)]);
+        assert!(Pattern::from_rle("3o?!").is_err());
+    }
+
+    #[test]
+    fn life106_moves_negative_coordinates_to_the_corner() {
+        let pattern: Pattern = Pattern::from_life106("#Life 1.06\n-1 -1\n0 -1\n1 -1\n").unwrap();
+        assert_eq!(pattern.cells, vec![(0, 0), (0, 1), (0, 2)]);
+        assert!(Pattern::from_life106("#Life 1.06\n1\n").is_err());
+    }
+
+    #[test]
+    fn macrocell_keeps_patterns_far_apart() {
+        let mut cells: Vec<(usize, usize)> = glider().cells;
+        cells.extend(glider().cells.iter().map(|(i, j)| (i + 96, j + 200)));
+        let pattern: Pattern = Pattern::from_cells(cells);
+        let text: String = pattern.to_macrocell("B3/S23");
+        let read: Pattern = Pattern::from_macrocell(&text).unwrap();
+        assert_eq!(sorted_cells(&read), sorted_cells(&pattern));
+        // both gliders are the same leaf, at whole leaves apart, written once
+        assert_eq!(text.lines().filter(|line| line.ends_with('$')).count(), 1);
+    }
+
+    #[test]
+    fn transforms_undo_themselves() {
+        let glider: Pattern = glider();
+        let quarter: Transform = Transform {
+            rotation: Some(Rotation::Quarter),
+            ..Transform::default()
+        };
+        let mut turned: Pattern = glider.clone();
+        for _ in 0..4 {
+            turned = turned.transformed(quarter);
+        }
+        assert_eq!(sorted_cells(&turned), sorted_cells(&glider));
+
+        let flip: Transform = Transform {
+            flip_horizontal: true,
+            ..Transform::default()
+        };
+        let flipped: Pattern = glider.transformed(flip);
+        assert_ne!(sorted_cells(&flipped), sorted_cells(&glider));
+        assert_eq!(
+            sorted_cells(&flipped.transformed(flip)),
+            sorted_cells(&glider)
+        );
+    }
+
+    #[test]
+    fn apgcodes_are_decoded() {
+        for (apgcode, seed) in [
+            ("xs4_33", "**\n**"),
+            ("xq4_153", "***\n..*\n.*."),
+            // two blinkers two columns apart, then four columns apart
+            ("xp2_7w7", "*..*\n*..*\n*..*"),
+            ("xp2_7y07", "*....*\n*....*\n*....*"),
+            // a pentadecathlon, whose ten rows take two strips
+            (
+                "xp15_4r4z4r4",
+                ".*.\n.*.\n*.*\n.*.\n.*.\n.*.\n.*.\n*.*\n.*.\n.*.",
+            ),
+        ] {
+            let pattern: Pattern = Pattern::from_apgcode(apgcode).unwrap();
+            let expected: Pattern = Pattern::from_seed_text(seed).unwrap();
+            assert_eq!(
+                sorted_cells(&pattern),
+                sorted_cells(&expected),
+                "{}",
+                apgcode
+            );
+            assert_eq!(
+                (pattern.rows, pattern.cols),
+                (expected.rows, expected.cols),
+                "{}",
+                apgcode
+            );
+        }
+        // the prefix is optional
+        assert_eq!(
+            sorted_cells(&Pattern::from_apgcode("33").unwrap()),
+            sorted_cells(&Pattern::from_apgcode("xs4_33").unwrap())
+        );
+    }
+
+    #[test]
+    fn invalid_apgcodes_are_refused() {
+        for apgcode in [
+            "ys4_33", "xr4_33", "xs_33", "xsN_33", "xs4_", "xs4_0w0", "xs4_3y", "xs4_3y!", "xs4_3!",
+        ] {
+            assert!(Pattern::from_apgcode(apgcode).is_err(), "{}", apgcode);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! This module contains the power profile, which lets the game use less
+//! energy when running on battery.
+
+use std::fmt;
+use std::fs;
+
+/// Number of generations simulated per displayed frame in eco mode. The frame
+/// timeout is multiplied by the same amount, so the simulation runs at the
+/// same speed while the screen is redrawn less often.
+pub const ECO_BATCH: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerProfile {
+    /// Redraw every generation
+    Normal,
+    /// Eco mode, requested with `--eco`
+    Eco,
+    /// Eco mode, enabled automatically because the machine is on battery
+    Battery,
+}
+
+impl PowerProfile {
+    pub fn detect(eco: bool) -> PowerProfile {
+        //! Picks the power profile based on the `--eco` flag and the power supply.
+        if eco {
+            PowerProfile::Eco
+        } else if on_battery() {
+            PowerProfile::Battery
+        } else {
+            PowerProfile::Normal
+        }
+    }
+
+    pub fn is_eco(&self) -> bool {
+        *self != PowerProfile::Normal
+    }
+
+    pub fn batch(&self) -> usize {
+        //! Number of generations simulated per displayed frame.
+        if self.is_eco() {
+            ECO_BATCH
+        } else {
+            1
+        }
+    }
+}
+
+impl fmt::Display for PowerProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PowerProfile::Normal => write!(f, "normal"),
+            PowerProfile::Eco => write!(f, "eco"),
+            PowerProfile::Battery => write!(f, "eco (battery)"),
+        }
+    }
+}
+
+pub fn on_battery() -> bool {
+    //! Whether the machine is running on battery. Only detected on Linux, via
+    //! `/sys/class/power_supply`; everywhere else this is always false.
+    let Ok(supplies) = fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    let mut has_battery: bool = false;
+    for supply in supplies.flatten() {
+        let path = supply.path();
+        let read = |name: &str| {
+            fs::read_to_string(path.join(name))
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default()
+        };
+        match read("type").as_str() {
+            // any connected charger means we're not on battery
+            "Mains" | "USB" if read("online") == "1" => return false,
+            "Battery" if read("status") == "Discharging" => has_battery = true,
+            _ => (),
+        }
+    }
+    has_battery
+}
@@ -0,0 +1,195 @@
+//! This module contains keyboard macros: recording the inputs of a run with
+//! the time each was made, and playing them back so demos can be scripted.
+//!
+//! Macros are text files with one input per line, made of the number of
+//! milliseconds since the start of the run and the name of the input, like
+//! `1500 toggle-pause`. Blank lines and lines starting with `#` are ignored.
+
+use anyhow::{anyhow, Result};
+use std::collections::VecDeque;
+use std::fs;
+use std::time::Instant;
+
+use super::conway::InputType;
+
+/// Records inputs as they are made.
+pub struct Recorder {
+    /// File the macro is saved to
+    path: String,
+    /// When recording started
+    start: Instant,
+    /// Milliseconds since the start at which each input was made
+    events: Vec<(u64, InputType)>,
+}
+
+impl Recorder {
+    pub fn new(path: &str) -> Recorder {
+        Recorder {
+            path: path.to_string(),
+            start: Instant::now(),
+            events: vec![],
+        }
+    }
+
+    pub fn record(&mut self, input: InputType) {
+        self.events
+            .push((self.start.elapsed().as_millis() as u64, input));
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn save(&self) -> Result<()> {
+        //! Writes the recorded inputs to the macro file.
+        let mut text: String = String::from("# conway macro: milliseconds input\n");
+        for (time, input) in &self.events {
+            text.push_str(&format!("{} {}\n", time, input));
+        }
+        fs::write(&self.path, text)
+            .map_err(|e| anyhow!("Failed to write macro '{}': {}", self.path, e))
+    }
+}
+
+/// Plays back the inputs of a macro.
+pub struct Playback {
+    /// When playback started
+    start: Instant,
+    /// Inputs that haven't been played yet, in order
+    events: VecDeque<(u64, InputType)>,
+}
+
+impl Playback {
+    pub fn load(path: &str) -> Result<Playback> {
+        //! Reads a macro file, starting the clock for its inputs.
+        let text: String = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read macro '{}': {}", path, e))?;
+        let mut events: Vec<(u64, InputType)> = vec![];
+        for (number, line) in text.lines().enumerate() {
+            // only leading blanks are dropped, as trailing ones can be the
+            // character typed, like in `edit-char: `
+            let line: &str = line.trim_start();
+            if line.trim_end().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let event = line
+                .split_once(char::is_whitespace)
+                .and_then(|(time, input)| {
+                    let input: &str = input.trim_start();
+                    let input: InputType =
+                        input.parse().or_else(|_| input.trim_end().parse()).ok()?;
+                    Some((time.parse().ok()?, input))
+                });
+            match event {
+                Some(event) => events.push(event),
+                None => {
+                    return Err(anyhow!(
+                        "Invalid line {} in macro '{}': expected 'MILLISECONDS INPUT', got '{}'",
+                        number + 1,
+                        path,
+                        line
+                    ))
+                }
+            }
+        }
+        // inputs made at the same time keep their order
+        events.sort_by_key(|(time, _)| *time);
+        Ok(Playback {
+            start: Instant::now(),
+            events: events.into(),
+        })
+    }
+
+    pub fn next_due(&mut self) -> Option<InputType> {
+        //! Returns the next input, if it is time for it.
+        let now: u64 = self.start.elapsed().as_millis() as u64;
+        match self.events.front() {
+            Some((time, _)) if *time <= now => self.events.pop_front().map(|(_, input)| input),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("conway-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn play(path: &str) -> Vec<InputType> {
+        let mut playback: Playback = Playback::load(path).unwrap();
+        let _ = fs::remove_file(path);
+        // gives inputs recorded a few milliseconds in time to fall due
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        std::iter::from_fn(|| playback.next_due()).collect()
+    }
+
+    #[test]
+    fn macros_play_back_what_was_recorded() {
+        let inputs: Vec<InputType> = vec![
+            InputType::EditRule,
+            InputType::EditChar(' '),
+            InputType::EditChar('#'),
+            InputType::EditDone,
+            InputType::RestoreBookmark(2),
+            InputType::PaintTo(4, 7),
+        ];
+        let mut recorder: Recorder = Recorder::new(&temp_path("round-trip.macro"));
+        for input in &inputs {
+            recorder.record(*input);
+        }
+        recorder.save().unwrap();
+        assert_eq!(play(recorder.get_path()), inputs);
+    }
+
+    #[test]
+    fn blank_lines_and_comments_are_skipped() {
+        let path: String = temp_path("comments.macro");
+        fs::write(
+            &path,
+            "# a comment\n\n   \n  0 edit-char: \n0 edit-char:#\n  # indented\n0\ttoggle-pause\n",
+        )
+        .unwrap();
+        assert_eq!(
+            play(&path),
+            vec![
+                InputType::EditChar(' '),
+                InputType::EditChar('#'),
+                InputType::TogglePause,
+            ]
+        );
+    }
+
+    #[test]
+    fn inputs_are_played_in_time_order() {
+        let path: String = temp_path("order.macro");
+        fs::write(&path, "0 quit\n0 up\n0 down\n").unwrap();
+        assert_eq!(
+            play(&path),
+            vec![InputType::Quit, InputType::Up, InputType::Down]
+        );
+        fs::write(&path, "60000 quit\n20 down\n0 up\n").unwrap();
+        assert_eq!(play(&path), vec![InputType::Up, InputType::Down]);
+    }
+
+    #[test]
+    fn invalid_lines_are_refused() {
+        let path: String = temp_path("invalid.macro");
+        for text in [
+            "quit\n",
+            "soon quit\n",
+            "0 fly\n",
+            "0 prompt-digit:12\n",
+            "0 edit-char:ab\n",
+        ] {
+            fs::write(&path, text).unwrap();
+            assert!(Playback::load(&path).is_err(), "{}", text);
+        }
+        let _ = fs::remove_file(&path);
+    }
+}
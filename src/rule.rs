@@ -0,0 +1,596 @@
+//! This module contains the rules the grid can be simulated with.
+//!
+//! A rule decides whether a cell is alive in the next generation from the
+//! arrangement of the cell and its eight neighbors. Outer totalistic rules,
+//! like Life's `B3/S23`, only look at how many neighbors are alive; isotropic
+//! non-totalistic rules, written in Hensel notation like `B2-a/S12`, also
+//! look at where they are, up to rotations and reflections; and `MAP` rules
+//! list the outcome of each of the 512 arrangements.
+//!
+//! Rules may also count the neighbors in other neighborhoods: the four
+//! orthogonal neighbors of von Neumann rules like `B2/S013V`, the six
+//! neighbors of hexagonal rules like `B2/S34H`, and the larger neighborhoods
+//! of Larger than Life rules like Bugs, `R5,C0,M1,S34..58,B34..45,NM`.
+//! Block rules, written `MS,D` and the block each of the 16 blocks of 2x2
+//! cells becomes, step the grid a block at a time instead.
+//!
+//! Rules that only look one cell out are kept as a table of the 512
+//! outcomes, indexed by the arrangement: the cells are read row by row from
+//! the top-left, the first one giving the most significant bit. Larger and
+//! hexagonal neighborhoods are kept as the numbers of alive neighbors at
+//! which cells are born and survive, and block rules as their 16 blocks.
+
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use super::neighborhood::{Neighborhood, Shape};
+
+/// Bit of each neighbor in the index of an arrangement, clockwise from the
+/// top: N, NE, E, SE, S, SW, W, NW.
+const NEIGHBOR_BITS: [usize; 8] = [1 << 7, 1 << 6, 1 << 3, 1, 1 << 1, 1 << 2, 1 << 5, 1 << 8];
+
+/// Bit of the cell itself in the index of an arrangement.
+const CENTER_BIT: usize = 1 << 4;
+
+/// Letters of Hensel notation for each number of alive neighbors from 0 to
+/// 4, with an example of the arrangement each letter stands for: bit `n` is
+/// set when neighbor `n` of `NEIGHBOR_BITS` is alive. Every rotation and
+/// reflection of the example is included. Arrangements of 5 to 8 neighbors
+/// take the letter of the opposite arrangement of 8 minus as many.
+const HENSEL_LETTERS: [&[(char, u8)]; 5] = [
+    &[],
+    &[('c', 0b0000_0010), ('e', 0b0000_0001)],
+    &[
+        ('c', 0b0000_1010),
+        ('e', 0b0000_0101),
+        ('k', 0b0000_1001),
+        ('a', 0b0000_0011),
+        ('i', 0b0001_0001),
+        ('n', 0b0010_0010),
+    ],
+    &[
+        ('c', 0b0010_1010),
+        ('e', 0b0001_0101),
+        ('k', 0b0010_0101),
+        ('a', 0b0000_0111),
+        ('i', 0b1000_0011),
+        ('n', 0b0000_1011),
+        ('y', 0b0010_1001),
+        ('q', 0b0010_0011),
+        ('j', 0b0100_0011),
+        ('r', 0b0001_0011),
+    ],
+    &[
+        ('c', 0b1010_1010),
+        ('e', 0b0101_0101),
+        ('k', 0b0100_1011),
+        ('a', 0b0000_1111),
+        ('i', 0b0001_1011),
+        ('n', 0b1000_1011),
+        ('y', 0b0010_1011),
+        ('q', 0b0010_0111),
+        ('j', 0b0101_0011),
+        ('r', 0b0001_0111),
+        ('t', 0b1001_0011),
+        ('w', 0b0110_0011),
+        ('z', 0b0011_0011),
+    ],
+];
+
+/// Well-known rules, offered in the settings menu: Life, HighLife, Day &
+/// Night, Just Friends, tlife, and the block rules Critters and falling sand.
+pub const RULES: [&str; 7] = [
+    "B3/S23",
+    "B36/S23",
+    "B3678/S34678",
+    "B2-a/S12",
+    "B3/S2-i34q",
+    "MS,D15;14;13;3;11;5;6;1;7;9;10;2;12;4;8;0",
+    "MS,D0;4;8;12;4;12;12;13;8;12;12;14;12;13;14;15",
+];
+
+/// Largest radius of the neighborhoods of Larger than Life rules.
+pub const MAX_RADIUS: usize = 10;
+
+/// Alphabet of the base64 encoding used by `MAP` rules.
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A rule, with the outcome of every arrangement of a cell and its neighbors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    /// Name of the rule, as it is written in rulestrings
+    name: String,
+    /// Cells counted as the neighbors of a cell
+    neighborhood: Neighborhood,
+    /// Whether a cell is alive in the next generation
+    outcomes: Outcomes,
+}
+
+/// How a rule decides whether a cell is alive in the next generation.
+#[derive(Debug, Clone, PartialEq)]
+enum Outcomes {
+    /// For neighborhoods reaching one cell out: bit `index % 64` of word
+    /// `index / 64` is set when the arrangement with that index leaves the
+    /// cell alive
+    Table([u64; 8]),
+    /// For larger and hexagonal neighborhoods: the numbers of alive cells at
+    /// which a dead cell is born and a live cell survives, the cell itself
+    /// counted if `middle` is set
+    Counts {
+        birth: Vec<RangeInclusive<usize>>,
+        survival: Vec<RangeInclusive<usize>>,
+        middle: bool,
+    },
+    /// For block rules: the block each block of 2x2 cells becomes, as
+    /// numbered in the `margolus` module
+    Blocks([u8; 16]),
+}
+
+impl Default for Rule {
+    fn default() -> Rule {
+        //! Conway's Game of Life, `B3/S23`.
+        Rule::from_str("B3/S23").unwrap()
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        //! Parses a rulestring: `B3/S23` (or `b3s23`), an isotropic
+        //! non-totalistic rule in Hensel notation like `B2-a/S12`, `MAP`
+        //! followed by the 512 outcomes in base64, a von Neumann rule like
+        //! `B2/S013V`, a hexagonal rule like `B2/S34H`, or a Larger than Life
+        //! rule like `R5,C0,M1,S34..58,B34..45,NM`, or a block rule like
+        //! `MS,D0;4;8;12;4;12;12;13;8;12;12;14;12;13;14;15`.
+        let s: &str = s.trim();
+        if let Some(encoded) = s.strip_prefix("MAP") {
+            return Ok(Rule {
+                name: format!("MAP{}", encoded.trim_end_matches('=')),
+                neighborhood: Neighborhood::default(),
+                outcomes: Outcomes::Table(decode_map(encoded)?),
+            });
+        }
+        let lower: String = s.to_lowercase();
+        if let Some(blocks) = lower.strip_prefix("ms,d") {
+            return parse_blocks(blocks).map_err(|e| anyhow!("invalid rule '{}': {}", s, e));
+        }
+        if lower.starts_with('r') {
+            return parse_larger_than_life(&lower)
+                .map_err(|e| anyhow!("invalid rule '{}': {}", s, e));
+        }
+        let (birth, survival) = lower
+            .strip_prefix('b')
+            .and_then(|rest| rest.split_once('s'))
+            .ok_or_else(|| {
+                anyhow!(
+                    "invalid rule '{}': expected B.../S..., MAP..., R... or MS,D...",
+                    s
+                )
+            })?;
+        let birth: &str = birth.strip_suffix('/').unwrap_or(birth);
+        for (suffix, shape) in [('v', Shape::VonNeumann), ('h', Shape::Hexagonal)] {
+            if let Some(survival) = survival.strip_suffix(suffix) {
+                return parse_outer_totalistic(birth, survival, shape)
+                    .map_err(|e| anyhow!("invalid rule '{}': {}", s, e));
+            }
+        }
+        let mut table: [u64; 8] = [0; 8];
+        for (conditions, center) in [(birth, 0), (survival, CENTER_BIT)] {
+            for mask in
+                parse_conditions(conditions).map_err(|e| anyhow!("invalid rule '{}': {}", s, e))?
+            {
+                let neighbors: usize = NEIGHBOR_BITS
+                    .iter()
+                    .enumerate()
+                    .filter(|(n, _)| mask & (1 << n) != 0)
+                    .map(|(_, bit)| bit)
+                    .sum();
+                let index: usize = center | neighbors;
+                table[index / 64] |= 1 << (index % 64);
+            }
+        }
+        Ok(Rule {
+            name: format!("B{}/S{}", birth, survival),
+            neighborhood: Neighborhood::default(),
+            outcomes: Outcomes::Table(table),
+        })
+    }
+}
+
+impl Rule {
+    pub fn is_life(&self) -> bool {
+        //! Whether the rule is Conway's Game of Life, however it is written.
+        self.outcomes == Rule::default().outcomes
+    }
+
+    pub fn get_neighborhood(&self) -> Neighborhood {
+        self.neighborhood
+    }
+
+    pub fn has_table(&self) -> bool {
+        //! Whether the rule only looks one cell out, and is worked out with
+        //! `is_alive_next` rather than `is_alive_with`.
+        matches!(self.outcomes, Outcomes::Table(_))
+    }
+
+    pub fn get_blocks(&self) -> Option<&[u8; 16]> {
+        //! The block each block of 2x2 cells becomes, for block rules, which
+        //! are stepped by the `margolus` module rather than cell by cell.
+        match &self.outcomes {
+            Outcomes::Blocks(blocks) => Some(blocks),
+            _ => None,
+        }
+    }
+
+    pub fn is_alive_next(&self, index: usize) -> bool {
+        //! Whether a cell is alive in the next generation, given the index of
+        //! the arrangement of the cell and its neighbors.
+        match &self.outcomes {
+            Outcomes::Table(table) => table[index / 64] & (1 << (index % 64)) != 0,
+            Outcomes::Counts { .. } | Outcomes::Blocks(_) => false,
+        }
+    }
+
+    pub fn is_alive_with(&self, alive: bool, neighbors: usize) -> bool {
+        //! Whether a cell is alive in the next generation, given whether it
+        //! is alive now and how many of its neighbors are, for rules with
+        //! larger neighborhoods.
+        match &self.outcomes {
+            Outcomes::Table(_) | Outcomes::Blocks(_) => false,
+            Outcomes::Counts {
+                birth,
+                survival,
+                middle,
+            } => {
+                let count: usize = neighbors + usize::from(alive && *middle);
+                let ranges: &[RangeInclusive<usize>] = if alive { survival } else { birth };
+                ranges.iter().any(|range| range.contains(&count))
+            }
+        }
+    }
+}
+
+fn counts_table(neighborhood: Neighborhood, outcome: impl Fn(bool, usize) -> bool) -> [u64; 8] {
+    //! Builds the table of a rule whose neighborhood reaches one cell out,
+    //! given whether a cell is alive in the next generation from whether it
+    //! is alive now and how many of its neighbors are.
+    let offsets: Vec<(isize, isize)> = neighborhood.offsets(0);
+    let mut table: [u64; 8] = [0; 8];
+    for index in 0..512 {
+        let neighbors: usize = offsets
+            .iter()
+            .filter(|(di, dj)| index & (1 << (4 - 3 * di - dj)) != 0)
+            .count();
+        if outcome(index & CENTER_BIT != 0, neighbors) {
+            table[index / 64] |= 1 << (index % 64);
+        }
+    }
+    table
+}
+
+fn parse_outer_totalistic(birth: &str, survival: &str, shape: Shape) -> Result<Rule> {
+    //! Parses the birth and survival parts of a rule counting the four
+    //! orthogonal neighbors, or the six neighbors on a hexagonal grid.
+    let neighborhood: Neighborhood = Neighborhood { shape, radius: 1 };
+    let size: usize = neighborhood.offsets(0).len();
+    let counts = |conditions: &str| -> Result<Vec<usize>> {
+        conditions
+            .chars()
+            .map(|c| {
+                c.to_digit(10)
+                    .map(|count| count as usize)
+                    .filter(|count| *count <= size)
+                    .ok_or_else(|| anyhow!("unexpected '{}' with {} neighbors", c, size))
+            })
+            .collect()
+    };
+    let (births, survivals) = (counts(birth)?, counts(survival)?);
+    let name: String = format!("B{}/S{}{}", birth, survival, neighborhood.suffix());
+    let outcomes: Outcomes = match shape {
+        // which cells are neighbors on a hexagonal grid depends on the row,
+        // which the table can't tell
+        Shape::Hexagonal => Outcomes::Counts {
+            birth: births.iter().map(|count| *count..=*count).collect(),
+            survival: survivals.iter().map(|count| *count..=*count).collect(),
+            middle: false,
+        },
+        _ => Outcomes::Table(counts_table(neighborhood, |alive, neighbors| {
+            if alive {
+                survivals.contains(&neighbors)
+            } else {
+                births.contains(&neighbors)
+            }
+        })),
+    };
+    Ok(Rule {
+        name,
+        neighborhood,
+        outcomes,
+    })
+}
+
+fn parse_larger_than_life(rule: &str) -> Result<Rule> {
+    //! Parses a Larger than Life rule, written as comma-separated fields:
+    //! `R` and the radius, `C0` or `C2` for two states, `M1` if the cell
+    //! counts itself (`M0` otherwise), `S` and `B` and the ranges of alive
+    //! cells at which cells survive and are born, like `2..3`, and `NM` for
+    //! a square neighborhood or `NN` for a diamond.
+    let mut radius: Option<usize> = None;
+    let (mut survival, mut birth) = (None, None);
+    let mut middle: bool = false;
+    let mut shape: Shape = Shape::Moore;
+    let range = |value: &str| -> Result<RangeInclusive<usize>> {
+        let (start, end) = value.split_once("..").unwrap_or((value, value));
+        match (start.parse::<usize>(), end.parse::<usize>()) {
+            (Ok(start), Ok(end)) if start <= end => Ok(start..=end),
+            _ => Err(anyhow!("invalid range '{}'", value)),
+        }
+    };
+    for field in rule.split(',').map(str::trim) {
+        let (key, value) = field.split_at(field.len().min(1));
+        match (key, value) {
+            ("r", value) => match value.parse::<usize>() {
+                Ok(r) if (1..=MAX_RADIUS).contains(&r) => radius = Some(r),
+                _ => return Err(anyhow!("the radius must be 1 to {}", MAX_RADIUS)),
+            },
+            ("c", "0" | "2") => (),
+            ("c", _) => return Err(anyhow!("only rules with two states are supported")),
+            ("m", "0") => middle = false,
+            ("m", "1") => middle = true,
+            ("s", value) => survival = Some(range(value)?),
+            ("b", value) => birth = Some(range(value)?),
+            ("n", "m") => shape = Shape::Moore,
+            ("n", "n") => shape = Shape::VonNeumann,
+            _ => return Err(anyhow!("unexpected '{}'", field)),
+        }
+    }
+    let (Some(radius), Some(survival), Some(birth)) = (radius, survival, birth) else {
+        return Err(anyhow!("expected R, S and B fields"));
+    };
+    let neighborhood: Neighborhood = Neighborhood { shape, radius };
+    let name: String = format!(
+        "R{},C0,M{},S{}..{},B{}..{},N{}",
+        radius,
+        u8::from(middle),
+        survival.start(),
+        survival.end(),
+        birth.start(),
+        birth.end(),
+        match shape {
+            Shape::VonNeumann => 'N',
+            _ => 'M',
+        }
+    );
+    let outcomes: Outcomes = Outcomes::Counts {
+        birth: vec![birth],
+        survival: vec![survival],
+        middle,
+    };
+    let outcomes: Outcomes = match radius {
+        // small enough for the table, which is much faster
+        1 => {
+            let rule: Rule = Rule {
+                name: String::new(),
+                neighborhood,
+                outcomes,
+            };
+            Outcomes::Table(counts_table(neighborhood, |alive, neighbors| {
+                rule.is_alive_with(alive, neighbors)
+            }))
+        }
+        _ => outcomes,
+    };
+    Ok(Rule {
+        name,
+        neighborhood,
+        outcomes,
+    })
+}
+
+fn parse_blocks(blocks: &str) -> Result<Rule> {
+    //! Parses the 16 blocks, separated by semicolons, that the blocks of a
+    //! block rule become.
+    let blocks: Vec<u8> = blocks
+        .split(';')
+        .map(|block| match block.trim().parse::<u8>() {
+            Ok(block) if block < 16 => Ok(block),
+            _ => Err(anyhow!("invalid block '{}', expected 0 to 15", block)),
+        })
+        .collect::<Result<_>>()?;
+    let blocks: [u8; 16] = blocks
+        .try_into()
+        .map_err(|blocks: Vec<u8>| anyhow!("expected 16 blocks, got {}", blocks.len()))?;
+    let name: Vec<String> = blocks.iter().map(u8::to_string).collect();
+    Ok(Rule {
+        name: format!("MS,D{}", name.join(";")),
+        neighborhood: Neighborhood::default(),
+        outcomes: Outcomes::Blocks(blocks),
+    })
+}
+
+fn parse_conditions(conditions: &str) -> Result<Vec<u8>> {
+    //! Lists the arrangements of neighbors, as masks of `NEIGHBOR_BITS`, that
+    //! the birth or survival part of a rulestring stands for: each number of
+    //! neighbors, optionally followed by the letters of the arrangements
+    //! meant, or by `-` and the letters of the arrangements left out.
+    let mut masks: Vec<u8> = vec![];
+    let mut chars = conditions.chars().peekable();
+    while let Some(digit) = chars.next() {
+        let count: u32 = digit
+            .to_digit(10)
+            .filter(|count| *count <= 8)
+            .ok_or_else(|| anyhow!("unexpected '{}'", digit))?;
+        let negated: bool = chars.next_if_eq(&'-').is_some();
+        let mut letters: Vec<char> = vec![];
+        while let Some(letter) = chars.next_if(char::is_ascii_lowercase) {
+            letters.push(letter);
+        }
+        if negated && letters.is_empty() {
+            return Err(anyhow!("'-' after {} must be followed by letters", count));
+        }
+        let mut arrangements: Vec<(char, u8)> = hensel_arrangements(count);
+        if let Some(letter) = letters
+            .iter()
+            .find(|letter| !arrangements.iter().any(|(l, _)| l == *letter))
+        {
+            return Err(anyhow!("no arrangement '{}{}'", count, letter));
+        }
+        if !letters.is_empty() {
+            arrangements.retain(|(letter, _)| letters.contains(letter) != negated);
+        }
+        masks.extend(arrangements.into_iter().map(|(_, mask)| mask));
+    }
+    Ok(masks)
+}
+
+fn hensel_arrangements(count: u32) -> Vec<(char, u8)> {
+    //! Lists every arrangement of the given number of alive neighbors, with
+    //! its letter in Hensel notation.
+    let (letters, invert): (&[(char, u8)], bool) = match count {
+        0..=4 => (HENSEL_LETTERS[count as usize], false),
+        _ => (HENSEL_LETTERS[8 - count as usize], true),
+    };
+    if letters.is_empty() {
+        // no neighbors, or all of them
+        return vec![(' ', if invert { u8::MAX } else { 0 })];
+    }
+    let mut arrangements: Vec<(char, u8)> = vec![];
+    for (letter, example) in letters {
+        let example: u8 = if invert { !example } else { *example };
+        for mask in symmetries(example) {
+            if !arrangements.iter().any(|(_, m)| *m == mask) {
+                arrangements.push((*letter, mask));
+            }
+        }
+    }
+    arrangements
+}
+
+fn symmetries(mask: u8) -> [u8; 8] {
+    //! Rotates and reflects an arrangement of neighbors in every way. The
+    //! neighbors go clockwise, so a quarter turn moves each two places, and
+    //! a reflection across the vertical axis reverses their order.
+    let reflected: u8 = (0..8)
+        .filter(|n| mask & (1 << n) != 0)
+        .map(|n| 1 << ((8 - n) % 8))
+        .sum();
+    let mut all: [u8; 8] = [0; 8];
+    for turns in 0..4 {
+        all[2 * turns] = mask.rotate_left(2 * turns as u32);
+        all[2 * turns + 1] = reflected.rotate_left(2 * turns as u32);
+    }
+    all
+}
+
+fn decode_map(encoded: &str) -> Result<[u64; 8]> {
+    //! Decodes the base64 outcomes of a `MAP` rule, the outcome of
+    //! arrangement 0 being the most significant bit of the first byte.
+    let encoded: &str = encoded.trim_end_matches('=');
+    let mut bits: Vec<bool> = vec![];
+    for c in encoded.bytes() {
+        let value: usize = BASE64
+            .iter()
+            .position(|b| *b == c)
+            .ok_or_else(|| anyhow!("invalid character '{}' in MAP rule", c as char))?;
+        bits.extend((0..6).rev().map(|bit| value & (1 << bit) != 0));
+    }
+    if bits.len() < 512 {
+        return Err(anyhow!(
+            "MAP rule too short: expected 86 base64 characters, got {}",
+            encoded.len()
+        ));
+    }
+    let mut table: [u64; 8] = [0; 8];
+    for (index, alive) in bits.iter().take(512).enumerate() {
+        if *alive {
+            table[index / 64] |= 1 << (index % 64);
+        }
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn life_is_recognized_however_it_is_written() {
+        for rule in ["B3/S23", "b3s23", "B3S23", " B3/S23 "] {
+            assert!(rule.parse::<Rule>().unwrap().is_life(), "{}", rule);
+        }
+        assert!(!"B36/S23".parse::<Rule>().unwrap().is_life());
+    }
+
+    #[test]
+    fn map_rules_match_their_rulestrings() {
+        let life: Rule = "MAPARYXfhZofugWaH7oaIDogBZofuhogOiAaIDogIAAgAAWaH7oaIDogGiA6ICAAIAAaIDogIAAgACAAIAAAAAAAA"
+            .parse()
+            .unwrap();
+        assert!(life.is_life());
+        assert!("MAPARYX".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn rules_read_back_as_they_are_written() {
+        for rule in [
+            "B3/S23",
+            "B36/S23",
+            "B2-a/S12",
+            "B3/S2-i34q",
+            "B2/S013V",
+            "B2/S34H",
+            "R5,C0,M1,S34..58,B34..45,NM",
+            "MS,D15;14;13;3;11;5;6;1;7;9;10;2;12;4;8;0",
+        ] {
+            let parsed: Rule = rule.parse().unwrap();
+            let reparsed: Rule = parsed.to_string().parse().unwrap();
+            assert_eq!(parsed, reparsed, "{}", rule);
+        }
+    }
+
+    #[test]
+    fn suffixes_pick_the_neighborhood() {
+        let shape = |rule: &str| rule.parse::<Rule>().unwrap().get_neighborhood().shape;
+        assert_eq!(shape("B3/S23"), Shape::Moore);
+        assert_eq!(shape("B2/S013V"), Shape::VonNeumann);
+        assert_eq!(shape("B2/S34H"), Shape::Hexagonal);
+    }
+
+    #[test]
+    fn hensel_letters_pick_out_arrangements() {
+        // arrangements are indexed row by row from the top-left, so this is
+        // the cell's north and north-east neighbors, which make up 2a, and
+        // this its north and south neighbors, which make up 2i
+        let (two_a, two_i): (usize, usize) = (0b011_000_000, 0b010_000_010);
+        let rule: Rule = "B2-a/S12".parse().unwrap();
+        assert!(!rule.is_alive_next(two_a));
+        assert!(rule.is_alive_next(two_i));
+        let both: Rule = "B2/S12".parse().unwrap();
+        assert!(both.is_alive_next(two_a));
+        assert!(both.is_alive_next(two_i));
+        assert!("B2z/S23".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn invalid_rules_are_refused() {
+        for rule in [
+            "",
+            "life",
+            "B9/S23",
+            "B2-/S23",
+            "R11,C0,M1,S1..2,B1..2,NM",
+            "MS,D1;2;3",
+        ] {
+            assert!(rule.parse::<Rule>().is_err(), "{}", rule);
+        }
+    }
+}
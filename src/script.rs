@@ -0,0 +1,193 @@
+//! This module contains user scripts, written in Rhai, which add behaviors
+//! to a run without recompiling through hooks called by the game:
+//!
+//! - `on_generation(grid, gen)`, after each frame the simulation advances
+//! - `on_key(key)`, when a key is pressed, with the key as a string
+//!
+//! Scripts reach the grid through a `Grid` with `rows()`, `cols()`,
+//! `get(row, col)`, `set(row, col, alive)` and `population()`, or through
+//! `grid()` from any hook. Positions wrap around the edges, so scripts can't
+//! reach outside the grid. They can also call `pause()`, `resume()` and
+//! `stop()`, and `print` shows a message in the status bar.
+
+use anyhow::{anyhow, Result};
+use rhai::{Dynamic, Engine, Scope, AST, INT};
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+use super::conway::Cell;
+
+/// Maximum number of operations a hook may run, so a runaway script can't
+/// freeze the game.
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+/// The grid as scripts see it: whether each cell is alive. Clones share the
+/// same cells.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptGrid(Rc<RefCell<Vec<Vec<bool>>>>);
+
+impl ScriptGrid {
+    fn locate(&self, i: INT, j: INT) -> Option<(usize, usize)> {
+        //! Wraps a position around the edges of the grid.
+        let cells = self.0.borrow();
+        let (nrows, ncols) = (cells.len() as INT, cells.first()?.len() as INT);
+        Some((i.rem_euclid(nrows) as usize, j.rem_euclid(ncols) as usize))
+    }
+
+    fn rows(&mut self) -> INT {
+        self.0.borrow().len() as INT
+    }
+
+    fn cols(&mut self) -> INT {
+        self.0.borrow().first().map_or(0, |row| row.len()) as INT
+    }
+
+    fn get(&mut self, i: INT, j: INT) -> bool {
+        self.locate(i, j)
+            .is_some_and(|(i, j)| self.0.borrow()[i][j])
+    }
+
+    fn set(&mut self, i: INT, j: INT, alive: bool) {
+        if let Some((i, j)) = self.locate(i, j) {
+            self.0.borrow_mut()[i][j] = alive;
+        }
+    }
+
+    fn population(&mut self) -> INT {
+        self.0
+            .borrow()
+            .iter()
+            .flatten()
+            .filter(|alive| **alive)
+            .count() as INT
+    }
+}
+
+/// What scripts asked the game to do.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptActions {
+    /// Message to show in the status bar
+    pub message: Option<String>,
+    /// Whether to pause or resume the simulation
+    pub paused: Option<bool>,
+    /// Whether to end the run
+    pub stop: bool,
+}
+
+/// A loaded script and the engine that runs it.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    /// Grid shared with the script while a hook runs
+    grid: ScriptGrid,
+    /// Actions requested by the script since they were last taken
+    actions: Rc<RefCell<ScriptActions>>,
+}
+
+impl Script {
+    pub fn load(path: &str) -> Result<Script> {
+        //! Reads and compiles a script file.
+        let text: String = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read script '{}': {}", path, e))?;
+        let grid: ScriptGrid = ScriptGrid::default();
+        let actions: Rc<RefCell<ScriptActions>> = Rc::new(RefCell::new(ScriptActions::default()));
+
+        let mut engine: Engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine
+            .register_type_with_name::<ScriptGrid>("Grid")
+            .register_fn("rows", ScriptGrid::rows)
+            .register_fn("cols", ScriptGrid::cols)
+            .register_fn("get", ScriptGrid::get)
+            .register_fn("set", ScriptGrid::set)
+            .register_fn("population", ScriptGrid::population);
+        let shared: ScriptGrid = grid.clone();
+        engine.register_fn("grid", move || shared.clone());
+        let shared = actions.clone();
+        engine.register_fn("pause", move || shared.borrow_mut().paused = Some(true));
+        let shared = actions.clone();
+        engine.register_fn("resume", move || shared.borrow_mut().paused = Some(false));
+        let shared = actions.clone();
+        engine.register_fn("stop", move || shared.borrow_mut().stop = true);
+        let shared = actions.clone();
+        engine.on_print(move |text| shared.borrow_mut().message = Some(text.to_string()));
+
+        let ast: AST = engine
+            .compile(text)
+            .map_err(|e| anyhow!("Failed to compile script '{}': {}", path, e))?;
+        Ok(Script {
+            engine,
+            ast,
+            grid,
+            actions,
+        })
+    }
+
+    pub fn on_generation(&mut self, grid: &mut [Vec<Cell>], generation: u64) -> Result<bool> {
+        //! Runs the `on_generation` hook, if the script has one. Returns
+        //! whether the script changed the grid.
+        if !self.has_hook("on_generation", 2) {
+            return Ok(false);
+        }
+        self.share(grid);
+        self.call("on_generation", (self.grid.clone(), generation as INT))?;
+        Ok(self.apply(grid))
+    }
+
+    pub fn on_key(&mut self, grid: &mut [Vec<Cell>], key: char) -> Result<bool> {
+        //! Runs the `on_key` hook, if the script has one. Returns whether the
+        //! script changed the grid.
+        if !self.has_hook("on_key", 1) {
+            return Ok(false);
+        }
+        self.share(grid);
+        self.call("on_key", (key.to_string(),))?;
+        Ok(self.apply(grid))
+    }
+
+    pub fn take_actions(&mut self) -> ScriptActions {
+        //! Returns the actions requested since this was last called.
+        self.actions.take()
+    }
+
+    fn has_hook(&self, name: &str, params: usize) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|hook| hook.name == name && hook.params.len() == params)
+    }
+
+    fn call(&self, name: &str, args: impl rhai::FuncArgs) -> Result<()> {
+        self.engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, name, args)
+            .map(|_| ())
+            .map_err(|e| anyhow!("Script error in {}: {}", name, e))
+    }
+
+    fn share(&self, grid: &[Vec<Cell>]) {
+        //! Copies the grid for the script to see.
+        *self.grid.0.borrow_mut() = grid
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.is_alive()).collect())
+            .collect();
+    }
+
+    fn apply(&self, grid: &mut [Vec<Cell>]) -> bool {
+        //! Copies the script's changes back to the grid, returning whether
+        //! there were any.
+        let cells = self.grid.0.borrow();
+        let mut changed: bool = false;
+        for cell in grid.iter_mut().flatten() {
+            let alive: bool = cells[cell.x][cell.y];
+            if alive != cell.is_alive() {
+                if alive {
+                    cell.set_alive();
+                } else {
+                    cell.set_dead();
+                }
+                changed = true;
+            }
+        }
+        changed
+    }
+}
@@ -0,0 +1,139 @@
+//! This module contains the `search` subcommand, which runs many random soups
+//! without a terminal, takes a census of what each of them leaves behind, and
+//! reports the rare objects along with the seed of the soup they came from.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use rand::{rngs::StdRng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+
+use super::census::{canonical, is_rare, library, objects, Census};
+use super::conway::{new_grid, Cell, Stepper};
+use super::pattern::Pattern;
+use super::rule::Rule;
+use super::soup::{random_cells, SoupOptions};
+use super::stability::CycleDetector;
+use super::topology::Topology;
+
+/// What to search.
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Number of soups to run
+    pub soups: u64,
+    /// Number of rows in each soup's grid
+    pub rows: usize,
+    /// Number of columns in each soup's grid
+    pub cols: usize,
+    /// Number of alive cells in each soup
+    pub alive: usize,
+    /// How soups are generated. Each soup's seed is one more than the last.
+    pub soup: SoupOptions,
+    /// How the edges of the grid are joined
+    pub topology: Topology,
+    /// Number of generations after which a soup is censused even if it
+    /// hasn't stabilized
+    pub max_generations: u64,
+}
+
+pub fn run(options: &SearchOptions) -> Result<()> {
+    //! Runs the soups, printing each rare object as it is found and the
+    //! totals of every kind of object at the end.
+    let library: HashMap<Vec<(isize, isize)>, String> = library();
+    let mut totals: HashMap<String, usize> = HashMap::new();
+    for n in 0..options.soups {
+        let soup: SoupOptions = SoupOptions {
+            seed: options.soup.seed.wrapping_add(n),
+            ..options.soup
+        };
+        let grid: Vec<Vec<Cell>> = run_soup(options, &soup)?;
+        let census: Census = Census::take(&grid, options.topology);
+
+        let rare: Vec<&String> = census
+            .counts
+            .iter()
+            .map(|(name, _)| name)
+            .filter(|name| is_rare(name))
+            .collect();
+        if !rare.is_empty() {
+            report_rare(options, &soup, &grid, &rare, &library);
+        }
+        for (name, count) in census.counts {
+            *totals.entry(name).or_insert(0) += count;
+        }
+    }
+
+    let mut totals: Vec<(String, usize)> = totals.into_iter().collect();
+    totals.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    println!("Soups: {}", options.soups);
+    println!("{}", Census { counts: totals });
+    Ok(())
+}
+
+fn run_soup(options: &SearchOptions, soup: &SoupOptions) -> Result<Vec<Vec<Cell>>> {
+    //! Generates a soup and runs it until it stabilizes, or for the maximum
+    //! number of generations.
+    let mut grid: Vec<Vec<Cell>> = new_grid(options.rows, options.cols);
+    let mut rng: StdRng = StdRng::seed_from_u64(soup.seed);
+    for (i, j) in random_cells(&mut rng, options.rows, options.cols, options.alive, soup)? {
+        grid[i][j].set_alive();
+    }
+    let mut stepper: Stepper = Stepper::default();
+    let mut cycle_detector: CycleDetector = CycleDetector::new(options.topology, Rule::default());
+    for generation in 0..options.max_generations {
+        if cycle_detector.check(&grid, generation).is_some() {
+            break;
+        }
+        stepper.step(&mut grid, options.topology);
+    }
+    Ok(grid)
+}
+
+fn report_rare(
+    options: &SearchOptions,
+    soup: &SoupOptions,
+    grid: &[Vec<Cell>],
+    rare: &[&String],
+    library: &HashMap<Vec<(isize, isize)>, String>,
+) {
+    //! Prints the rare objects a soup left behind, with the command that
+    //! reruns the soup and the RLE of each unrecognized object.
+    println!(
+        "Seed {}: {}",
+        soup.seed,
+        rare.iter()
+            .map(|name| name.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ")
+    );
+    let symmetry: String = match soup.symmetry.and_then(|s| s.to_possible_value()) {
+        Some(value) => format!(" --symmetry {}", value.get_name()),
+        None => String::new(),
+    };
+    println!(
+        "  conway --rng-seed {} --alive {} --height {} --width {} --topology {} --distribution {}{}",
+        soup.seed,
+        options.alive,
+        options.rows,
+        options.cols,
+        options.topology,
+        soup.distribution,
+        symmetry
+    );
+    // print each kind of unrecognized object once
+    let mut printed: HashSet<Vec<(isize, isize)>> = HashSet::new();
+    for object in objects(grid, options.topology) {
+        let object: Vec<(isize, isize)> = canonical(&object);
+        if library.contains_key(&object) || !printed.insert(object.clone()) {
+            continue;
+        }
+        let pattern: Pattern = Pattern::from_cells(
+            object
+                .iter()
+                .map(|(i, j)| (*i as usize, *j as usize))
+                .collect(),
+        );
+        for line in pattern.to_rle("B3/S23").lines() {
+            println!("  {}", line);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+//! This module contains the `serve` subcommand, which runs the simulation
+//! without a terminal and streams it to web browsers: the page served at `/`
+//! draws the grid on a canvas, updated over a WebSocket at `/ws`.
+
+use anyhow::{anyhow, Result};
+use rand::{rngs::StdRng, SeedableRng};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::{Message, WebSocket};
+
+use super::conway::{new_grid, Cell, Stepper};
+use super::pattern::{Pattern, Placement};
+use super::soup::{random_cells, SoupOptions};
+use super::topology::Topology;
+
+/// Page served to browsers, which draws the frames it is sent.
+const PAGE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Conway's Game of Life</title>
+<style>
+  html, body { margin: 0; height: 100%; background: #000; color: #888; font-family: monospace; }
+  body { display: flex; flex-direction: column; align-items: center; justify-content: center; }
+  canvas { image-rendering: pixelated; }
+</style>
+</head>
+<body>
+<canvas id="grid"></canvas>
+<div id="status">Connecting...</div>
+<script>
+  const canvas = document.getElementById("grid");
+  const context = canvas.getContext("2d");
+  const status = document.getElementById("status");
+  const socket = new WebSocket(`ws://${location.host}/ws`);
+  socket.onmessage = (event) => {
+    const frame = JSON.parse(event.data);
+    const size = Math.max(1, Math.floor(Math.min(
+      window.innerWidth / frame.cols,
+      (window.innerHeight - 30) / frame.rows,
+    )));
+    canvas.width = frame.cols * size;
+    canvas.height = frame.rows * size;
+    context.fillStyle = "#000";
+    context.fillRect(0, 0, canvas.width, canvas.height);
+    context.fillStyle = "#fff";
+    for (let i = 0; i < frame.rows; i++) {
+      for (let j = 0; j < frame.cols; j++) {
+        if (frame.cells[i * frame.cols + j] === "1") {
+          context.fillRect(j * size, i * size, size, size);
+        }
+      }
+    }
+    status.textContent = `Generation ${frame.generation}, ${frame.alive} alive`;
+  };
+  socket.onclose = () => { status.textContent = "Disconnected"; };
+</script>
+</body>
+</html>
+"##;
+
+/// How long a browser has to send its request, or take a frame, before it
+/// is dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Frames waiting to be sent to a browser. One that falls further behind
+/// misses frames rather than holding up the others.
+const QUEUED_FRAMES: usize = 2;
+
+/// What to simulate and where to serve it.
+#[derive(Debug, Clone)]
+pub struct ServeOptions {
+    /// Address to listen on
+    pub bind: IpAddr,
+    /// Port to listen on
+    pub port: u16,
+    /// Number of rows in the grid
+    pub rows: usize,
+    /// Number of columns in the grid
+    pub cols: usize,
+    /// Number of alive cells in a random soup
+    pub alive: usize,
+    /// Seed file to start with instead of a random soup
+    pub seed_file: Option<String>,
+    /// How the random soup is generated
+    pub soup: SoupOptions,
+    /// How the edges of the grid are joined
+    pub topology: Topology,
+    /// Milliseconds between generations
+    pub timeout: u64,
+}
+
+pub fn run(options: &ServeOptions) -> Result<()> {
+    //! Serves the simulation until the process is killed. Each browser is
+    //! answered on a thread of its own.
+    let address: SocketAddr = SocketAddr::new(options.bind, options.port);
+    let listener: TcpListener = TcpListener::bind(address)
+        .map_err(|e| anyhow!("Failed to listen on {}: {}", address, e))?;
+    listener.set_nonblocking(true)?;
+    println!("Serving on http://{}", address);
+
+    let mut grid: Vec<Vec<Cell>> = initial_grid(options)?;
+    let mut generation: u64 = 0;
+    let mut stepper: Stepper = Stepper::default();
+    let mut viewers: Vec<SyncSender<Message>> = vec![];
+    loop {
+        let start: Instant = Instant::now();
+        while let Ok((stream, _)) = listener.accept() {
+            let (sender, frames) = mpsc::sync_channel(QUEUED_FRAMES);
+            thread::spawn(move || answer(stream, frames));
+            viewers.push(sender);
+        }
+
+        let frame: Message = Message::text(frame_json(&grid, generation));
+        // viewers that have left are dropped, and those that can't keep up
+        // miss the frame
+        viewers.retain(|viewer| {
+            !matches!(
+                viewer.try_send(frame.clone()),
+                Err(TrySendError::Disconnected(_))
+            )
+        });
+
+        stepper.step(&mut grid, options.topology);
+        generation += 1;
+        thread::sleep(Duration::from_millis(options.timeout).saturating_sub(start.elapsed()));
+    }
+}
+
+fn initial_grid(options: &ServeOptions) -> Result<Vec<Vec<Cell>>> {
+    //! Creates the grid from the seed file, or a random soup.
+    let mut grid: Vec<Vec<Cell>> = new_grid(options.rows, options.cols);
+    match &options.seed_file {
+        Some(seed_file) => Pattern::from_file(seed_file)?.place(&mut grid, Placement::default()),
+        None => {
+            let mut rng: StdRng = StdRng::seed_from_u64(options.soup.seed);
+            let cells = random_cells(
+                &mut rng,
+                options.rows,
+                options.cols,
+                options.alive,
+                &options.soup,
+            )?;
+            for (i, j) in cells {
+                grid[i][j].set_alive();
+            }
+        }
+    }
+    Ok(grid)
+}
+
+fn answer(stream: TcpStream, frames: Receiver<Message>) {
+    //! Answers a connection, sending the frames to a WebSocket until it is
+    //! closed or stops taking them.
+    let Some(mut viewer) = connect(stream) else {
+        return;
+    };
+    for frame in frames {
+        if viewer.send(frame).is_err() {
+            return;
+        }
+    }
+}
+
+fn connect(stream: TcpStream) -> Option<WebSocket<TcpStream>> {
+    //! Answers a new connection: upgrades requests for `/ws` to a WebSocket,
+    //! and serves the page for anything else.
+    stream.set_nonblocking(false).ok()?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT)).ok()?;
+
+    // look at the request line without taking it, so the WebSocket
+    // handshake can read the whole request
+    let mut buffer: [u8; 512] = [0; 512];
+    let n: usize = stream.peek(&mut buffer).ok()?;
+    let request: String = String::from_utf8_lossy(&buffer[..n]).to_string();
+    if request.starts_with("GET /ws ") {
+        return tungstenite::accept(stream).ok();
+    }
+    serve_page(stream);
+    None
+}
+
+fn serve_page(mut stream: TcpStream) {
+    //! Reads the rest of an HTTP request and answers it with the page.
+    let mut buffer: [u8; 4096] = [0; 4096];
+    let mut request: Vec<u8> = vec![];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut buffer) {
+            Ok(0) => return,
+            Ok(n) => request.extend_from_slice(&buffer[..n]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => return,
+        }
+    }
+    let response: String = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        PAGE.len(),
+        PAGE
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn frame_json(grid: &[Vec<Cell>], generation: u64) -> String {
+    //! Encodes a frame for the page: its size, generation, number of alive
+    //! cells, and a string of `0` and `1` for the cells, row by row.
+    let cells: String = grid
+        .iter()
+        .flatten()
+        .map(|cell| if cell.is_alive() { '1' } else { '0' })
+        .collect();
+    format!(
+        r#"{{"generation":{},"rows":{},"cols":{},"alive":{},"cells":"{}"}}"#,
+        generation,
+        grid.len(),
+        grid.first().map_or(0, |row| row.len()),
+        cells.chars().filter(|c| *c == '1').count(),
+        cells
+    )
+}
@@ -0,0 +1,298 @@
+//! This module contains session files, which keep everything about a run
+//! needed to pick it up again later with `--resume`: the grid, the
+//! generation, the rule, the topology, the speed and the state of the random
+//! flips of a noisy rule.
+//!
+//! Sessions are text files of `key: value` lines followed by the grid, one
+//! line per row, with `.` for dead cells and `O` for alive ones, or `X` for
+//! alive cells of the second color of the Immigration game. Lines starting
+//! with `#` are ignored.
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use std::fs;
+
+use super::conway::{new_grid, Cell, State};
+use super::topology::Topology;
+
+/// Version of the session format, bumped whenever its fields change.
+const VERSION: &str = "1";
+
+/// Number of seconds between saves of the session while the run goes on.
+pub const AUTOSAVE_SECONDS: u64 = 10;
+
+/// A run, as it was when it was saved.
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// Rule the run follows
+    pub rule: String,
+    /// How the edges of the grid are joined
+    pub topology: Topology,
+    /// Generation the run had reached
+    pub generation: u64,
+    /// Milliseconds between frames
+    pub timeout: i32,
+    /// Frames per second aimed for instead of the timeout, if set
+    pub fps: Option<u32>,
+    /// Number of generations advanced per frame
+    pub step: usize,
+    /// Probability of each outcome of the rule being flipped
+    pub rule_noise: f64,
+    /// Seed the random flips of the rule go on from
+    pub rng_seed: u64,
+    /// Whether the run plays the Immigration game
+    pub immigration: bool,
+    /// Cells of the grid
+    pub grid: Vec<Vec<Cell>>,
+}
+
+impl Session {
+    pub fn capture(grid: &[Vec<Cell>], state: &mut State) -> Session {
+        //! Takes down where a run is. The random flips of a noisy rule are
+        //! reseeded, so that the run and any resumed from it go on alike.
+        Session {
+            rule: state.get_rule().to_string(),
+            topology: state.get_topology(),
+            generation: state.get_generation(),
+            timeout: state.get_timeout(),
+            fps: state.get_fps(),
+            step: state.get_step(),
+            rule_noise: state.get_rule_noise(),
+            rng_seed: state.reseed_rule_noise(),
+            immigration: state.get_immigration(),
+            grid: grid.to_vec(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        //! Writes the session file. It is written next to the file first and
+        //! then moved over it, so that an interrupted save doesn't lose the
+        //! session saved before.
+        let mut text: String = format!(
+            "# conway session\nversion: {}\nrule: {}\ntopology: {}\ngeneration: {}\n\
+             timeout: {}\nfps: {}\nstep: {}\nrule-noise: {}\nrng-seed: {}\nimmigration: {}\n\
+             size: {}x{}\n",
+            VERSION,
+            self.rule,
+            self.topology,
+            self.generation,
+            self.timeout,
+            self.fps.map_or("-".to_string(), |fps| fps.to_string()),
+            self.step,
+            self.rule_noise,
+            self.rng_seed,
+            self.immigration,
+            self.grid.len(),
+            self.grid[0].len()
+        );
+        for row in &self.grid {
+            text.extend(row.iter().map(|cell| match (cell.is_alive(), cell.color) {
+                (false, _) => '.',
+                (true, 0) => 'O',
+                (true, _) => 'X',
+            }));
+            text.push('\n');
+        }
+        let partial: String = format!("{}.partial", path);
+        fs::write(&partial, text)
+            .and_then(|_| fs::rename(&partial, path))
+            .map_err(|e| anyhow!("Failed to write session '{}': {}", path, e))
+    }
+
+    pub fn load(path: &str) -> Result<Session> {
+        //! Reads a session file written by `save`.
+        let text: String = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read session '{}': {}", path, e))?;
+        let mut lines = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        let mut field = |key: &str| -> Result<String> {
+            match lines.next().and_then(|line| line.split_once(':')) {
+                Some((name, value)) if name.trim() == key => Ok(value.trim().to_string()),
+                _ => Err(anyhow!(
+                    "Invalid session '{}': expected '{}: ...'",
+                    path,
+                    key
+                )),
+            }
+        };
+        if field("version")? != VERSION {
+            return Err(anyhow!(
+                "Session '{}' was made by a different version",
+                path
+            ));
+        }
+        let rule: String = field("rule")?;
+        let topology: Topology =
+            Topology::from_str(&field("topology")?, true).map_err(|e| anyhow!(e))?;
+        let generation: u64 = field("generation")?
+            .parse()
+            .context("Invalid generation in session")?;
+        let timeout: i32 = field("timeout")?
+            .parse()
+            .context("Invalid timeout in session")?;
+        let fps: Option<u32> = match field("fps")?.as_str() {
+            "-" => None,
+            fps => Some(fps.parse().context("Invalid frame rate in session")?),
+        };
+        let step: usize = field("step")?.parse().context("Invalid step in session")?;
+        let rule_noise: f64 = field("rule-noise")?
+            .parse()
+            .context("Invalid rule noise in session")?;
+        let rng_seed: u64 = field("rng-seed")?
+            .parse()
+            .context("Invalid RNG seed in session")?;
+        let immigration: bool = field("immigration")?
+            .parse()
+            .context("Invalid immigration flag in session")?;
+        let size: String = field("size")?;
+        let (rows, cols) = size
+            .split_once('x')
+            .ok_or_else(|| anyhow!("Invalid size in session"))?;
+        let rows: usize = rows.parse().context("Invalid number of rows in session")?;
+        let cols: usize = cols
+            .parse()
+            .context("Invalid number of columns in session")?;
+        if rows == 0 || cols == 0 {
+            return Err(anyhow!(
+                "Session '{}' has no cells: its size is {}",
+                path,
+                size
+            ));
+        }
+
+        let mut grid: Vec<Vec<Cell>> = new_grid(rows, cols);
+        for (i, row) in grid.iter_mut().enumerate() {
+            let line: &str = lines
+                .next()
+                .ok_or_else(|| anyhow!("Session '{}' has fewer than {} rows", path, rows))?;
+            if line.chars().count() != cols {
+                return Err(anyhow!(
+                    "Row {} of session '{}' isn't {} cells wide",
+                    i + 1,
+                    path,
+                    cols
+                ));
+            }
+            for (cell, c) in row.iter_mut().zip(line.chars()) {
+                match c {
+                    '.' => (),
+                    'O' | 'X' => {
+                        cell.set_alive();
+                        cell.color = (c == 'X') as u8;
+                    }
+                    _ => {
+                        return Err(anyhow!(
+                            "Unexpected character '{}' in row {} of session '{}'",
+                            c,
+                            i + 1,
+                            path
+                        ))
+                    }
+                }
+            }
+        }
+
+        Ok(Session {
+            rule,
+            topology,
+            generation,
+            timeout,
+            fps,
+            step,
+            rule_noise,
+            rng_seed,
+            immigration,
+            grid,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("conway-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn cells(grid: &[Vec<Cell>]) -> Vec<Vec<(bool, u8)>> {
+        grid.iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| (cell.is_alive(), cell.color))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn header(size: &str) -> String {
+        format!(
+            "version: {}\nrule: B3/S23\ntopology: torus\ngeneration: 0\ntimeout: 100\n\
+             fps: -\nstep: 1\nrule-noise: 0\nrng-seed: 0\nimmigration: false\nsize: {}\n",
+            VERSION, size
+        )
+    }
+
+    #[test]
+    fn sessions_load_as_they_were_saved() {
+        let mut grid: Vec<Vec<Cell>> = new_grid(4, 6);
+        grid[1][2].set_alive();
+        grid[3][5].set_alive();
+        grid[3][5].color = 1;
+        let session: Session = Session {
+            rule: "B36/S23".to_string(),
+            topology: Topology::Klein,
+            generation: 42,
+            timeout: 80,
+            fps: Some(30),
+            step: 3,
+            rule_noise: 0.25,
+            rng_seed: 7,
+            immigration: true,
+            grid,
+        };
+        let path: String = temp_path("round-trip.session");
+        session.save(&path).unwrap();
+        let loaded: Session = Session::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(loaded.rule, session.rule);
+        assert_eq!(loaded.topology, session.topology);
+        assert_eq!(loaded.generation, session.generation);
+        assert_eq!(loaded.timeout, session.timeout);
+        assert_eq!(loaded.fps, session.fps);
+        assert_eq!(loaded.step, session.step);
+        assert_eq!(loaded.rule_noise, session.rule_noise);
+        assert_eq!(loaded.rng_seed, session.rng_seed);
+        assert_eq!(loaded.immigration, session.immigration);
+        assert_eq!(cells(&loaded.grid), cells(&session.grid));
+    }
+
+    #[test]
+    fn sessions_without_cells_are_refused() {
+        let path: String = temp_path("empty.session");
+        for size in ["0x5", "5x0"] {
+            fs::write(&path, header(size)).unwrap();
+            assert!(Session::load(&path).is_err(), "{}", size);
+        }
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn rows_must_match_the_size() {
+        let path: String = temp_path("short.session");
+        for rows in ["...\n..\n", "...\n", "....\n...\n", "...\n.?.\n"] {
+            fs::write(&path, header("2x3") + rows).unwrap();
+            assert!(Session::load(&path).is_err(), "{}", rows);
+        }
+        fs::write(&path, header("2x3") + "...\n.OX\n").unwrap();
+        let loaded: Result<Session> = Session::load(&path);
+        let _ = fs::remove_file(&path);
+        assert!(loaded.is_ok());
+    }
+}
@@ -0,0 +1,315 @@
+//! This module contains the random-initialization subsystem, which generates
+//! random soups of live cells.
+//!
+//! Where cells are placed is decided by a `Distribution`, selected by name
+//! with `--distribution NAME[:PARAM]`:
+//!
+//! * `uniform`: every cell is equally likely
+//! * `gaussian[:SIGMA]`: a blob around the center of the grid, with a standard
+//!   deviation of `SIGMA` times the grid size (default 0.15)
+//! * `power-law[:ALPHA]`: clusters whose sizes follow a power law with
+//!   exponent `ALPHA` (default 2.0), so most clusters are small but a few are large
+//!
+//! The result can then be mirrored according to a `Symmetry`.
+
+use anyhow::{anyhow, Result};
+use rand::{Rng, RngCore};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// Symmetry applied to randomly generated soups.
+#[derive(Debug, PartialEq, Clone, Copy, clap::ValueEnum)]
+pub enum Symmetry {
+    /// Mirror the left half of the grid onto the right half
+    Horizontal,
+    /// Mirror the top half of the grid onto the bottom half
+    Vertical,
+    /// Mirror both horizontally and vertically
+    #[value(alias = "4-fold")]
+    FourFold,
+    /// Mirror across the main diagonal of the top-left square
+    Diagonal,
+}
+
+impl Symmetry {
+    fn domain(&self, nrows: usize, ncols: usize) -> (usize, usize) {
+        //! Returns the size of the region random cells are picked from before
+        //! being mirrored.
+        match self {
+            Symmetry::Horizontal => (nrows, ncols.div_ceil(2)),
+            Symmetry::Vertical => (nrows.div_ceil(2), ncols),
+            Symmetry::FourFold => (nrows.div_ceil(2), ncols.div_ceil(2)),
+            Symmetry::Diagonal => (nrows.min(ncols), nrows.min(ncols)),
+        }
+    }
+
+    fn capacity(&self, nrows: usize, ncols: usize) -> usize {
+        //! Returns the number of cells that can be reached by mirroring the domain.
+        match self {
+            Symmetry::Diagonal => nrows.min(ncols).pow(2),
+            _ => nrows * ncols,
+        }
+    }
+
+    fn images(&self, i: usize, j: usize, nrows: usize, ncols: usize) -> Vec<(usize, usize)> {
+        //! Returns the cell at (i, j) along with all of its mirror images.
+        match self {
+            Symmetry::Horizontal => vec![(i, j), (i, ncols - 1 - j)],
+            Symmetry::Vertical => vec![(i, j), (nrows - 1 - i, j)],
+            Symmetry::FourFold => vec![
+                (i, j),
+                (i, ncols - 1 - j),
+                (nrows - 1 - i, j),
+                (nrows - 1 - i, ncols - 1 - j),
+            ],
+            Symmetry::Diagonal => vec![(i, j), (j, i)],
+        }
+    }
+}
+
+/// A way of picking random cells within a region.
+pub trait Distribution {
+    /// Picks one or more cells within a region of `nrows` by `ncols` cells.
+    fn sample(&self, rng: &mut dyn RngCore, nrows: usize, ncols: usize) -> Vec<(usize, usize)>;
+}
+
+/// Every cell is equally likely to be picked.
+pub struct Uniform;
+
+impl Distribution for Uniform {
+    fn sample(&self, rng: &mut dyn RngCore, nrows: usize, ncols: usize) -> Vec<(usize, usize)> {
+        vec![(rng.gen_range(0..nrows), rng.gen_range(0..ncols))]
+    }
+}
+
+/// Cells are picked from a normal distribution around the center of the region.
+pub struct Gaussian {
+    /// Standard deviation, as a fraction of the size of the region
+    pub sigma: f64,
+}
+
+impl Distribution for Gaussian {
+    fn sample(&self, rng: &mut dyn RngCore, nrows: usize, ncols: usize) -> Vec<(usize, usize)> {
+        // Box-Muller transform, giving two independent standard normal samples
+        let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+        let u2: f64 = rng.gen::<f64>();
+        let radius: f64 = (-2.0 * u1.ln()).sqrt();
+        let angle: f64 = 2.0 * std::f64::consts::PI * u2;
+        let coordinate = |normal: f64, size: usize| {
+            let center: f64 = size as f64 / 2.0;
+            let value: f64 = center + normal * self.sigma * size as f64;
+            (value.max(0.0) as usize).min(size - 1)
+        };
+        vec![(
+            coordinate(radius * angle.cos(), nrows),
+            coordinate(radius * angle.sin(), ncols),
+        )]
+    }
+}
+
+/// Cells are picked in clusters around uniformly random centers, with cluster
+/// sizes following a power law.
+pub struct PowerLaw {
+    /// Exponent of the power law; larger values make large clusters rarer
+    pub alpha: f64,
+}
+
+/// Largest cluster a `PowerLaw` distribution produces.
+const MAX_CLUSTER_SIZE: usize = 64;
+
+impl Distribution for PowerLaw {
+    fn sample(&self, rng: &mut dyn RngCore, nrows: usize, ncols: usize) -> Vec<(usize, usize)> {
+        // inverse transform sampling of a Pareto distribution with minimum 1
+        let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+        let size: usize = (u.powf(-1.0 / self.alpha) as usize).clamp(1, MAX_CLUSTER_SIZE);
+
+        // grow the cluster as a random walk from a uniformly random center
+        let mut i: usize = rng.gen_range(0..nrows);
+        let mut j: usize = rng.gen_range(0..ncols);
+        let mut cells: Vec<(usize, usize)> = vec![(i, j)];
+        while cells.len() < size {
+            i = (i as isize + rng.gen_range(-1..=1)).clamp(0, nrows as isize - 1) as usize;
+            j = (j as isize + rng.gen_range(-1..=1)).clamp(0, ncols as isize - 1) as usize;
+            cells.push((i, j));
+        }
+        cells
+    }
+}
+
+/// A distribution selected by name, with its optional parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DistributionSpec {
+    #[default]
+    Uniform,
+    Gaussian(f64),
+    PowerLaw(f64),
+}
+
+impl DistributionSpec {
+    pub fn build(&self) -> Box<dyn Distribution> {
+        //! Creates the distribution described by this spec.
+        match *self {
+            DistributionSpec::Uniform => Box::new(Uniform),
+            DistributionSpec::Gaussian(sigma) => Box::new(Gaussian { sigma }),
+            DistributionSpec::PowerLaw(alpha) => Box::new(PowerLaw { alpha }),
+        }
+    }
+}
+
+impl FromStr for DistributionSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, param) = match s.split_once(':') {
+            Some((name, param)) => {
+                let param: f64 = param
+                    .parse()
+                    .map_err(|_| anyhow!("invalid parameter '{}' for '{}'", param, name))?;
+                if param <= 0.0 {
+                    return Err(anyhow!("the parameter of '{}' must be positive", name));
+                }
+                (name, Some(param))
+            }
+            None => (s, None),
+        };
+        match name {
+            "uniform" => Ok(DistributionSpec::Uniform),
+            "gaussian" => Ok(DistributionSpec::Gaussian(param.unwrap_or(0.15))),
+            "power-law" => Ok(DistributionSpec::PowerLaw(param.unwrap_or(2.0))),
+            _ => Err(anyhow!(
+                "unknown distribution '{}': expected uniform, gaussian or power-law",
+                name
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DistributionSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DistributionSpec::Uniform => write!(f, "uniform"),
+            DistributionSpec::Gaussian(sigma) => write!(f, "gaussian:{}", sigma),
+            DistributionSpec::PowerLaw(alpha) => write!(f, "power-law:{}", alpha),
+        }
+    }
+}
+
+/// How random soups are generated.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SoupOptions {
+    /// Distribution the cells are picked from
+    pub distribution: DistributionSpec,
+    /// Symmetry the cells are mirrored with, if any
+    pub symmetry: Option<Symmetry>,
+    /// Seed of the random number generator, so soups can be reproduced
+    pub seed: u64,
+}
+
+impl SoupOptions {
+    pub fn capacity(&self, nrows: usize, ncols: usize) -> usize {
+        //! Returns the largest number of cells a soup can have within a grid
+        //! of `nrows` by `ncols` cells.
+        match self.symmetry {
+            Some(symmetry) => symmetry.capacity(nrows, ncols),
+            None => nrows * ncols,
+        }
+    }
+}
+
+pub fn random_cells(
+    rng: &mut dyn RngCore,
+    nrows: usize,
+    ncols: usize,
+    count: usize,
+    options: &SoupOptions,
+) -> Result<HashSet<(usize, usize)>> {
+    //! Picks `count` distinct random cells within a grid of `nrows` by `ncols`
+    //! cells, according to the soup options. With a symmetry, every picked
+    //! cell comes with its mirror images, and the last set of images is kept
+    //! whole, so the soup stays symmetric: it can have up to one cell more
+    //! than `count` with two images, or three more with four.
+    let capacity: usize = options.capacity(nrows, ncols);
+    if count > capacity {
+        return Err(anyhow!(
+            "Number of alive cells cannot be greater than the number of cells in the grid."
+        ));
+    }
+
+    let (domain_rows, domain_cols) = match options.symmetry {
+        Some(symmetry) => symmetry.domain(nrows, ncols),
+        None => (nrows, ncols),
+    };
+    let distribution: Box<dyn Distribution> = options.distribution.build();
+    // concentrated distributions take very long to reach the last few free
+    // cells of a nearly full grid, so switch to uniform after a while
+    let max_samples: usize = capacity.saturating_mul(16);
+    let mut samples: usize = 0;
+
+    let mut cells: HashSet<(usize, usize)> = HashSet::new();
+    while cells.len() < count {
+        let picked: Vec<(usize, usize)> = if samples < max_samples {
+            distribution.sample(rng, domain_rows, domain_cols)
+        } else {
+            Uniform.sample(rng, domain_rows, domain_cols)
+        };
+        samples += 1;
+        for (i, j) in picked {
+            // clusters can pick more cells than are still wanted
+            if cells.len() >= count {
+                break;
+            }
+            match options.symmetry {
+                // add the mirror images of every picked cell
+                Some(symmetry) => cells.extend(symmetry.images(i, j, nrows, ncols)),
+                None => {
+                    cells.insert((i, j));
+                }
+            }
+        }
+    }
+    Ok(cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn soups_stop_once_the_count_is_reached() {
+        for distribution in ["uniform", "gaussian", "power-law"] {
+            for symmetry in [
+                None,
+                Some(Symmetry::Horizontal),
+                Some(Symmetry::FourFold),
+                Some(Symmetry::Diagonal),
+            ] {
+                let options: SoupOptions = SoupOptions {
+                    distribution: distribution.parse().unwrap(),
+                    symmetry,
+                    seed: 0,
+                };
+                let images: usize = match symmetry {
+                    None => 1,
+                    Some(Symmetry::FourFold) => 4,
+                    Some(_) => 2,
+                };
+                for count in [1, 2, 3, 5, 30, 97] {
+                    let mut rng: StdRng = StdRng::seed_from_u64(count as u64);
+                    let cells: HashSet<(usize, usize)> =
+                        random_cells(&mut rng, 21, 30, count, &options).unwrap();
+                    assert!(
+                        (count..count + images).contains(&cells.len()),
+                        "{} {:?} {}: {}",
+                        distribution,
+                        symmetry,
+                        count,
+                        cells.len()
+                    );
+                }
+            }
+        }
+    }
+}
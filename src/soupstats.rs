@@ -0,0 +1,136 @@
+//! This module contains the `soup-stats` subcommand, which runs many random
+//! soups without a terminal and reports how they turn out as a whole: how
+//! long they take to stabilize, how many cells they leave and how often they
+//! die out, for seeing how the density and the rule shape the outcomes.
+
+use anyhow::Result;
+use rand::{rngs::StdRng, SeedableRng};
+
+use super::conway::{new_grid, Cell, Stepper};
+use super::rule::Rule;
+use super::soup::{random_cells, SoupOptions};
+use super::stability::{CycleDetector, Report};
+use super::topology::Topology;
+
+/// What to run.
+#[derive(Debug, Clone)]
+pub struct SoupStatsOptions {
+    /// Number of soups to run
+    pub runs: u64,
+    /// Number of rows in each soup's grid
+    pub rows: usize,
+    /// Number of columns in each soup's grid
+    pub cols: usize,
+    /// Fraction of each soup's grid brought to life
+    pub density: f64,
+    /// How soups are generated. Each soup's seed is one more than the last.
+    pub soup: SoupOptions,
+    /// How the edges of the grid are joined
+    pub topology: Topology,
+    /// Rule the soups follow
+    pub rule: Rule,
+    /// Number of generations after which a soup that hasn't stabilized is
+    /// given up on
+    pub max_generations: u64,
+}
+
+/// How a soup turned out.
+struct Outcome {
+    /// Report on how the soup stabilized, if it did in time
+    report: Option<Report>,
+    /// Number of alive cells left at the end
+    population: usize,
+}
+
+pub fn run(options: &SoupStatsOptions) -> Result<()> {
+    //! Runs the soups and prints the statistics of their outcomes.
+    let alive: usize = (options.density * (options.rows * options.cols) as f64).round() as usize;
+    let mut outcomes: Vec<Outcome> = Vec::new();
+    for n in 0..options.runs {
+        let soup: SoupOptions = SoupOptions {
+            seed: options.soup.seed.wrapping_add(n),
+            ..options.soup
+        };
+        outcomes.push(run_soup(options, &soup, alive)?);
+    }
+
+    let runs: usize = outcomes.len();
+    let mut lifetimes: Vec<u64> = outcomes
+        .iter()
+        .filter_map(|outcome| outcome.report.as_ref())
+        .map(|report| report.generation)
+        .collect();
+    let mut populations: Vec<u64> = outcomes
+        .iter()
+        .map(|outcome| outcome.population as u64)
+        .collect();
+    let extinct: usize = outcomes
+        .iter()
+        .filter(|outcome| matches!(outcome.report, Some(Report { period: None, .. })))
+        .count();
+
+    println!(
+        "Soups: {} of {}x{} at density {}, rule {}, {} topology",
+        runs, options.rows, options.cols, options.density, options.rule, options.topology
+    );
+    println!(
+        "Stabilized: {} ({}), {} still running after {} generations",
+        lifetimes.len(),
+        percent(lifetimes.len(), runs),
+        runs - lifetimes.len(),
+        options.max_generations
+    );
+    println!("Extinct: {} ({})", extinct, percent(extinct, runs));
+    println!("Lifetime: {}", summary(&mut lifetimes));
+    println!("Final population: {}", summary(&mut populations));
+    Ok(())
+}
+
+fn run_soup(options: &SoupStatsOptions, soup: &SoupOptions, alive: usize) -> Result<Outcome> {
+    //! Generates a soup and runs it until it stabilizes, or for the maximum
+    //! number of generations.
+    let mut grid: Vec<Vec<Cell>> = new_grid(options.rows, options.cols);
+    let mut rng: StdRng = StdRng::seed_from_u64(soup.seed);
+    for (i, j) in random_cells(&mut rng, options.rows, options.cols, alive, soup)? {
+        grid[i][j].set_alive();
+    }
+    let mut stepper: Stepper = Stepper::default();
+    stepper.set_rule(options.rule.clone());
+    let mut cycle_detector: CycleDetector =
+        CycleDetector::new(options.topology, options.rule.clone());
+    let mut report: Option<Report> = None;
+    for generation in 0..options.max_generations {
+        report = cycle_detector.check(&grid, generation);
+        if report.is_some() {
+            break;
+        }
+        stepper.step(&mut grid, options.topology);
+    }
+    let population: usize = grid.iter().flatten().filter(|cell| cell.is_alive()).count();
+    Ok(Outcome { report, population })
+}
+
+fn percent(count: usize, total: usize) -> String {
+    //! Formats a count as a percentage of a total.
+    format!("{:.1}%", count as f64 * 100.0 / total.max(1) as f64)
+}
+
+fn summary(values: &mut [u64]) -> String {
+    //! Describes the spread of some values: their mean, 10th, 50th and 90th
+    //! percentiles and their maximum.
+    if values.is_empty() {
+        return "-".to_string();
+    }
+    values.sort_unstable();
+    let mean: f64 = values.iter().sum::<u64>() as f64 / values.len() as f64;
+    // nearest-rank percentiles
+    let percentile = |p: usize| values[(values.len() * p).div_ceil(100).max(1) - 1];
+    format!(
+        "mean {:.1}, p10 {}, p50 {}, p90 {}, max {}",
+        mean,
+        percentile(10),
+        percentile(50),
+        percentile(90),
+        values[values.len() - 1]
+    )
+}
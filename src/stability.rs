@@ -0,0 +1,255 @@
+//! This module contains the detection of runs that have stabilized, either
+//! by dying out or by settling into a cycle, and the report printed for them.
+//! A cycle may move the pattern, as spaceships like the glider do, so frames
+//! are compared by the shape of their live cells, wherever it is.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use super::conway::{Cell, Stepper};
+use super::rule::Rule;
+use super::topology::Topology;
+
+/// How a run ended up once it stabilized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Report {
+    /// Generation at which the run was found to be stable
+    pub generation: u64,
+    /// Number of alive cells at that generation
+    pub population: usize,
+    /// Period of the cycle the run settled into, or `None` if it died out
+    pub period: Option<u64>,
+    /// Rows and columns the pattern moves by each period
+    pub displacement: (isize, isize),
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Generations: {}", self.generation)?;
+        writeln!(f, "Population: {}", self.population)?;
+        match (self.period, self.displacement) {
+            (None, _) => write!(f, "Outcome: extinct"),
+            (Some(1), (0, 0)) => write!(f, "Outcome: still life (period 1)"),
+            (Some(period), (0, 0)) => write!(f, "Outcome: oscillating (period {})", period),
+            (Some(period), displacement) => write!(
+                f,
+                "Outcome: spaceship, {} (period {})",
+                velocity(period, displacement),
+                period
+            ),
+        }
+    }
+}
+
+/// Remembers every frame seen so far, by hash, to notice when one repeats.
+#[derive(Debug, Clone, Default)]
+pub struct CycleDetector {
+    /// Generation each frame was first seen at, by the hash of its shape
+    seen: HashMap<u64, u64>,
+    /// Topology the run is simulated on
+    topology: Topology,
+    /// Rule the run follows
+    rule: Rule,
+}
+
+impl CycleDetector {
+    pub fn new(topology: Topology, rule: Rule) -> CycleDetector {
+        CycleDetector {
+            seen: HashMap::new(),
+            topology,
+            rule,
+        }
+    }
+
+    pub fn check(&mut self, grid: &[Vec<Cell>], generation: u64) -> Option<Report> {
+        //! Records a frame, returning a report if the run has died out or
+        //! repeats an earlier frame, possibly moved.
+        let (shape, _) = shape(grid);
+        let population: usize = shape.len();
+        if population == 0 {
+            return Some(Report {
+                generation,
+                population,
+                period: None,
+                displacement: (0, 0),
+            });
+        }
+        let mut hasher = DefaultHasher::new();
+        shape.hash(&mut hasher);
+        // the blocks of block rules alternate, so frames only repeat in the
+        // same phase
+        if self.rule.get_blocks().is_some() {
+            (generation % 2).hash(&mut hasher);
+        }
+        match self.seen.insert(hasher.finish(), generation) {
+            // frames may be more than one generation apart, so the gap is a
+            // multiple of the period
+            Some(first) if first < generation => {
+                let (period, displacement) =
+                    motion(grid, generation, first, self.topology, &self.rule);
+                Some(Report {
+                    generation,
+                    population,
+                    period: Some(period),
+                    displacement,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.seen.clear();
+    }
+}
+
+fn shape(grid: &[Vec<Cell>]) -> (Vec<(usize, usize)>, (usize, usize)) {
+    //! Returns the live cells relative to the top-left corner of their
+    //! bounding box, and that corner.
+    let cells: Vec<(usize, usize)> = grid
+        .iter()
+        .flatten()
+        .filter(|cell| cell.is_alive())
+        .map(|cell| (cell.x, cell.y))
+        .collect();
+    let top: usize = cells.iter().map(|(i, _)| *i).min().unwrap_or(0);
+    let left: usize = cells.iter().map(|(_, j)| *j).min().unwrap_or(0);
+    let shape: Vec<(usize, usize)> = cells.iter().map(|(i, j)| (i - top, j - left)).collect();
+    (shape, (top, left))
+}
+
+fn motion(
+    grid: &[Vec<Cell>],
+    generation: u64,
+    first: u64,
+    topology: Topology,
+    rule: &Rule,
+) -> (u64, (isize, isize)) {
+    //! Finds the number of generations it takes the grid, at the given
+    //! generation, to return to its current shape, knowing it did since the
+    //! `first` generation, and how far the shape moved in that time.
+    let max: u64 = generation - first;
+    let (start, (top, left)) = shape(grid);
+    let mut next: Vec<Vec<Cell>> = grid.to_vec();
+    let mut stepper: Stepper = Stepper::default();
+    stepper.set_rule(rule.clone());
+    stepper.set_generation(generation);
+    for period in 1..=max {
+        stepper.step(&mut next, topology);
+        let (cells, (next_top, next_left)) = shape(&next);
+        if cells == start {
+            let displacement: (isize, isize) = (
+                next_top as isize - top as isize,
+                next_left as isize - left as isize,
+            );
+            return (period, displacement);
+        }
+    }
+    (max, (0, 0))
+}
+
+pub fn velocity(period: u64, displacement: (isize, isize)) -> String {
+    //! Describes the speed and direction of a spaceship in the usual notation,
+    //! like `c/4 diagonal` for the glider or `c/2 orthogonal` for the LWSS.
+    let (rows, cols) = (
+        displacement.0.unsigned_abs() as u64,
+        displacement.1.unsigned_abs() as u64,
+    );
+    let speed = |distance: u64| -> String {
+        let divisor: u64 = gcd(distance, period);
+        match (distance / divisor, period / divisor) {
+            (1, 1) => "c".to_string(),
+            (1, period) => format!("c/{}", period),
+            (distance, 1) => format!("{}c", distance),
+            (distance, period) => format!("{}c/{}", distance, period),
+        }
+    };
+    if rows == 0 || cols == 0 {
+        format!("{} orthogonal", speed(rows.max(cols)))
+    } else if rows == cols {
+        format!("{} diagonal", speed(rows))
+    } else {
+        format!(
+            "({},{})c/{} oblique",
+            rows.max(cols),
+            rows.min(cols),
+            period
+        )
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conway::new_grid;
+    use crate::pattern::Pattern;
+
+    fn settle(seed: &str) -> Report {
+        let pattern: Pattern = Pattern::from_seed_text(seed).unwrap();
+        let mut grid: Vec<Vec<Cell>> = new_grid(30, 30);
+        for (i, j) in &pattern.cells {
+            grid[i + 10][j + 10].set_alive();
+        }
+        let mut detector: CycleDetector = CycleDetector::new(Topology::Plane, Rule::default());
+        let mut stepper: Stepper = Stepper::default();
+        for generation in 0..20 {
+            if let Some(report) = detector.check(&grid, generation) {
+                return report;
+            }
+            stepper.set_generation(generation);
+            stepper.step(&mut grid, Topology::Plane);
+        }
+        panic!("{} never settled", seed);
+    }
+
+    #[test]
+    fn spaceships_are_reported_with_their_velocity() {
+        let glider: Report = settle(".*.\n..*\n***");
+        assert_eq!((glider.period, glider.displacement), (Some(4), (1, 1)));
+        assert_eq!(velocity(4, glider.displacement), "c/4 diagonal");
+        assert!(glider
+            .to_string()
+            .ends_with("Outcome: spaceship, c/4 diagonal (period 4)"));
+
+        let lwss: Report = settle(".*..*\n*....\n*...*\n****.");
+        assert_eq!((lwss.period, lwss.displacement), (Some(4), (0, -2)));
+        assert_eq!(velocity(4, lwss.displacement), "c/2 orthogonal");
+    }
+
+    #[test]
+    fn oscillators_and_still_lifes_stay_in_place() {
+        let blinker: Report = settle("***");
+        assert_eq!((blinker.period, blinker.displacement), (Some(2), (0, 0)));
+        assert!(blinker.to_string().ends_with("oscillating (period 2)"));
+        let block: Report = settle("**\n**");
+        assert_eq!((block.period, block.displacement), (Some(1), (0, 0)));
+        let dead: Report = settle("*");
+        assert_eq!(dead.period, None);
+    }
+
+    #[test]
+    fn velocities_are_written_in_the_usual_notation() {
+        for (period, displacement, expected) in [
+            (4, (1, 1), "c/4 diagonal"),
+            (4, (-1, 1), "c/4 diagonal"),
+            (4, (0, 2), "c/2 orthogonal"),
+            (4, (-2, 0), "c/2 orthogonal"),
+            (1, (0, 1), "c orthogonal"),
+            (3, (0, 2), "2c/3 orthogonal"),
+            (6, (3, 3), "c/2 diagonal"),
+            (5, (2, 1), "(2,1)c/5 oblique"),
+        ] {
+            assert_eq!(velocity(period, displacement), expected);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! This module contains the stats panel, shown in the top-left corner: the
+//! cells born and died in the last generation, the totals since the run
+//! started and the average over the last few generations.
+
+use std::collections::VecDeque;
+
+use anyhow::Result;
+
+use super::conway::Delta;
+use super::theme::Theme;
+use super::window::Window;
+
+/// Number of generations the average births and deaths are taken over.
+const ROLLING_GENERATIONS: usize = 20;
+
+/// Births and deaths of the run, and the window they are drawn in while the
+/// panel is shown.
+#[derive(Default)]
+pub struct Stats {
+    /// Whether the panel is shown
+    shown: bool,
+    /// Births and deaths of the last `ROLLING_GENERATIONS` generations,
+    /// oldest first
+    recent: VecDeque<Delta>,
+    /// Cells born since the run started
+    total_births: u64,
+    /// Cells died since the run started
+    total_deaths: u64,
+    /// Window the panel is drawn in, while it is shown
+    window: Option<Window>,
+}
+
+impl Stats {
+    pub fn toggle(&mut self) {
+        self.shown = !self.shown;
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.shown
+    }
+
+    pub fn set_shown(&mut self, shown: bool) {
+        self.shown = shown;
+    }
+
+    pub fn record(&mut self, delta: Delta) {
+        //! Counts the births and deaths of a generation.
+        if self.recent.len() == ROLLING_GENERATIONS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(delta);
+        self.total_births += delta.births as u64;
+        self.total_deaths += delta.deaths as u64;
+    }
+
+    pub fn clear(&mut self) {
+        //! Forgets the births and deaths counted so far, for a new run.
+        self.recent.clear();
+        self.total_births = 0;
+        self.total_deaths = 0;
+    }
+
+    fn lines(&self) -> [String; 3] {
+        //! Text of the panel, one line per row.
+        let last: Delta = self.recent.back().copied().unwrap_or_default();
+        let generations: f64 = self.recent.len().max(1) as f64;
+        let (births, deaths) = self.recent.iter().fold((0, 0), |(births, deaths), delta| {
+            (births + delta.births, deaths + delta.deaths)
+        });
+        [
+            format!("Last:    +{} -{}", last.births, last.deaths),
+            format!("Total:   +{} -{}", self.total_births, self.total_deaths),
+            format!(
+                "Avg/{}: +{:.1} -{:.1}",
+                ROLLING_GENERATIONS,
+                births as f64 / generations,
+                deaths as f64 / generations
+            ),
+        ]
+    }
+
+    pub fn draw(&mut self, parent: &Window, theme: &Theme) -> Result<()> {
+        //! Draws the panel over the top-left corner of the window the grid
+        //! was just drawn and refreshed in, if it is shown and fits.
+        let lines: [String; 3] = self.lines();
+        let (rows, cols) = (
+            lines.len() as i32 + 2,
+            lines
+                .iter()
+                .map(|line| line.chars().count())
+                .max()
+                .unwrap_or(0) as i32
+                + 4,
+        );
+        if !self.shown || rows > parent.get_rows() || cols > parent.get_cols() {
+            if self.window.take().is_some() {
+                // uncover what was under the panel
+                parent.touch();
+                parent.refresh();
+            }
+            return Ok(());
+        }
+
+        let position: (i32, i32) = parent.position();
+        let window: &Window = match &self.window {
+            Some(window)
+                if window.position() == position
+                    && (window.get_rows(), window.get_cols()) == (rows, cols) =>
+            {
+                window
+            }
+            _ => self
+                .window
+                .insert(Window::new(rows, cols, position.0, position.1)),
+        };
+
+        window.erase();
+        window.draw_box();
+        for (i, line) in lines.iter().enumerate() {
+            window.print(2, i as i32 + 1, line, theme.status.as_ref())?;
+        }
+        window.refresh();
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+//! This module contains the configuration of the status bar shown below the grid.
+
+/// A piece of information that can be shown in the status bar.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum StatusField {
+    /// Number of alive cells
+    Alive,
+    /// Number of generations simulated so far
+    Generation,
+    /// Number of generations advanced per frame
+    Step,
+    /// Frame timeout in milliseconds
+    Timeout,
+    /// Rule the simulation follows
+    Rule,
+    /// Probability of each outcome of the rule being flipped, if it is noisy
+    RuleNoise,
+    /// How the edges of the grid are joined
+    Topology,
+    /// Current power profile
+    Power,
+    /// Fraction of cells brought to life in new soups and random fills
+    Density,
+    /// Seed of the random soup, if the grid was generated from one
+    Seed,
+    /// Name and author of the loaded pattern, if its file gives them
+    Pattern,
+    /// Similarity to the reference run, if there is one
+    Similarity,
+    /// Short descriptions of the controls
+    Hints,
+}
+
+/// Fields shown by default, in order.
+pub const DEFAULT_STATUS_FIELDS: [StatusField; 12] = [
+    StatusField::Alive,
+    StatusField::Generation,
+    StatusField::Step,
+    StatusField::Timeout,
+    StatusField::RuleNoise,
+    StatusField::Topology,
+    StatusField::Power,
+    StatusField::Density,
+    StatusField::Seed,
+    StatusField::Pattern,
+    StatusField::Similarity,
+    StatusField::Hints,
+];
+
+/// Which fields the status bar shows. A status bar without fields is hidden,
+/// and the grid takes up its row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusBar {
+    fields: Vec<StatusField>,
+}
+
+impl Default for StatusBar {
+    fn default() -> Self {
+        StatusBar::new(DEFAULT_STATUS_FIELDS.to_vec())
+    }
+}
+
+impl StatusBar {
+    pub fn new(fields: Vec<StatusField>) -> StatusBar {
+        StatusBar { fields }
+    }
+
+    pub fn hidden() -> StatusBar {
+        StatusBar::new(vec![])
+    }
+
+    pub fn fields(&self) -> &[StatusField] {
+        &self.fields
+    }
+
+    pub fn is_visible(&self) -> bool {
+        !self.fields.is_empty()
+    }
+
+    pub fn height(&self) -> usize {
+        //! Number of rows the status bar takes up.
+        self.is_visible() as usize
+    }
+}
+
+pub fn fit_to_width(text: &str, width: usize) -> String {
+    //! Truncates text to the given number of columns, ending it with `...` if
+    //! anything was cut off.
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    if width <= 3 {
+        return text.chars().take(width).collect();
+    }
+    let mut truncated: String = text.chars().take(width - 3).collect();
+    truncated.push_str("...");
+    truncated
+}
@@ -0,0 +1,118 @@
+//! This module contains the `svg` subcommand, which simulates a pattern
+//! without touching the terminal and writes it as a self-contained animated
+//! SVG: one rectangle per cell that is ever alive, whose opacity is
+//! keyframed to switch it on and off in each generation.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use super::conway::{new_grid, Cell, Stepper};
+use super::pattern::Pattern;
+use super::topology::Topology;
+
+/// How to animate the pattern.
+#[derive(Debug, Clone)]
+pub struct SvgOptions {
+    /// Number of generations in the animation
+    pub generations: usize,
+    /// Number of dead cells added around the pattern on each side, to give
+    /// it room to grow
+    pub padding: usize,
+    /// How the edges of the padded grid are joined
+    pub topology: Topology,
+    /// Width and height of each cell, in pixels
+    pub cell_size: usize,
+    /// Milliseconds each generation is shown for
+    pub frame_ms: u64,
+}
+
+pub fn run(input: &str, output: Option<&str>, options: &SvgOptions) -> Result<()> {
+    //! Reads a pattern file and writes its animation to a file, or to stdout.
+    let pattern: Pattern = Pattern::from_file(input)?;
+    let frames: Vec<Vec<Vec<bool>>> = simulate(&pattern, options);
+    let svg: String = render(&frames, options);
+    match output {
+        Some(output) => {
+            std::fs::write(output, svg).with_context(|| format!("Failed to write '{}'", output))
+        }
+        None => {
+            print!("{}", svg);
+            Ok(())
+        }
+    }
+}
+
+fn simulate(pattern: &Pattern, options: &SvgOptions) -> Vec<Vec<Vec<bool>>> {
+    //! Runs the pattern on a padded grid, returning which cells are alive in
+    //! each generation.
+    let padding: usize = options.padding;
+    let mut grid: Vec<Vec<Cell>> = new_grid(
+        pattern.rows.max(1) + 2 * padding,
+        pattern.cols.max(1) + 2 * padding,
+    );
+    for (i, j) in &pattern.cells {
+        grid[i + padding][j + padding].set_alive();
+    }
+    let mut stepper: Stepper = Stepper::default();
+    let mut frames: Vec<Vec<Vec<bool>>> = vec![];
+    for _ in 0..options.generations.max(1) {
+        frames.push(
+            grid.iter()
+                .map(|row| row.iter().map(|cell| cell.is_alive()).collect())
+                .collect(),
+        );
+        stepper.step(&mut grid, options.topology);
+    }
+    frames
+}
+
+fn render(frames: &[Vec<Vec<bool>>], options: &SvgOptions) -> String {
+    //! Writes the frames as an SVG that loops through them forever.
+    let (nrows, ncols) = (frames[0].len(), frames[0][0].len());
+    let size: usize = options.cell_size;
+    let duration: u64 = options.frame_ms * frames.len() as u64;
+
+    // only cells that are alive at some point need a rectangle
+    let cells: BTreeSet<(usize, usize)> = frames
+        .iter()
+        .flat_map(|frame| {
+            frame.iter().enumerate().flat_map(|(i, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, alive)| **alive)
+                    .map(move |(j, _)| (i, j))
+            })
+        })
+        .collect();
+
+    let mut svg: String = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#,
+        ncols * size,
+        nrows * size,
+        ncols * size,
+        nrows * size
+    );
+    let _ = writeln!(svg, r##"<rect width="100%" height="100%" fill="#fff"/>"##);
+    for (i, j) in cells {
+        let values: Vec<&str> = frames
+            .iter()
+            .map(|frame| if frame[i][j] { "1" } else { "0" })
+            .collect();
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#000" opacity="{}"><animate attributeName="opacity" values="{}" dur="{}ms" calcMode="discrete" repeatCount="indefinite"/></rect>"##,
+            j * size,
+            i * size,
+            size,
+            size,
+            values[0],
+            values.join(";"),
+            duration
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
@@ -0,0 +1,195 @@
+//! This module contains helpers to hand the terminal back, however the game
+//! exits.
+//!
+//! While ncurses has the terminal, a `Screen` guard is alive, which ends
+//! ncurses when it is dropped, whether the run ended normally, with an error
+//! or with a panic. The panic hook ends ncurses before the panic message is
+//! printed, so that it can be read.
+//!
+//! A marker file containing the process id is also kept in the temporary
+//! directory. If the game is killed before it can clean up, the marker is left
+//! behind, and the next run resets the terminal before starting.
+
+use anyhow::{anyhow, Result};
+use ncurses::{curs_set, endwin, initscr, CURSOR_VISIBILITY};
+use std::fs::{self, File};
+use std::io::{IsTerminal, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::paint;
+
+/// Escape sequences that undo what a crashed ncurses program leaves behind:
+/// reset colors and attributes, show the cursor, disable mouse reporting,
+/// and leave the alternate screen.
+const RESET_SEQUENCES: &str = "\x1b[0m\x1b[?25h\x1b[?1000l\x1b[?1002l\x1b[?1003l\x1b[?1049l";
+
+/// Whether ncurses has the terminal, so that it is only ended once.
+static SCREEN_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// The terminal, while ncurses has it. Dropping it hands the terminal back.
+#[derive(Debug)]
+pub struct Screen {
+    /// Whether the terminal reports mouse drags, which has to be turned off
+    mouse: bool,
+    /// Piped standard output to restore, if the terminal took its place
+    piped_stdout: Option<i32>,
+}
+
+impl Screen {
+    pub fn start(mouse: bool, piped_stdout: Option<i32>) -> Screen {
+        //! Leaves a marker behind in case the run is killed, initializes
+        //! ncurses and hides the cursor. Mouse drags are turned off again and
+        //! the piped output restored when the screen is dropped.
+        mark_running();
+        initscr();
+        SCREEN_ACTIVE.store(true, Ordering::SeqCst);
+        curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+        Screen {
+            mouse,
+            piped_stdout,
+        }
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        end_screen();
+        if self.mouse {
+            paint::disable_mouse();
+        }
+        clear_running();
+        restore_stdout(self.piped_stdout);
+    }
+}
+
+fn end_screen() {
+    //! Shows the cursor again and ends ncurses, unless it has already ended.
+    if SCREEN_ACTIVE.swap(false, Ordering::SeqCst) {
+        curs_set(CURSOR_VISIBILITY::CURSOR_VISIBLE);
+        endwin();
+    }
+}
+
+pub fn install_panic_hook() {
+    //! Makes panics end ncurses before their message is printed, which would
+    //! otherwise be drawn over or lost with the alternate screen.
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        end_screen();
+        default_hook(info);
+    }));
+}
+
+pub fn reset() -> Result<()> {
+    //! Restores the terminal: emits the reset escape sequences and restores
+    //! line buffering and echo with `stty sane`. When stdout is piped, the
+    //! sequences are sent to the terminal instead, to keep them out of the
+    //! output.
+    if std::io::stdout().is_terminal() {
+        let mut stdout = std::io::stdout();
+        stdout.write_all(RESET_SEQUENCES.as_bytes())?;
+        stdout.flush()?;
+    } else if let Ok(mut tty) = fs::OpenOptions::new().write(true).open("/dev/tty") {
+        tty.write_all(RESET_SEQUENCES.as_bytes())?;
+    }
+    // stty operates on its stdin, which must be the terminal; it is not
+    // available everywhere, so failures are ignored
+    if std::io::stdin().is_terminal() {
+        let _ = Command::new("stty")
+            .arg("sane")
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::null())
+            .status();
+    }
+    Ok(())
+}
+
+fn marker_path() -> PathBuf {
+    //! Path of the marker file for the current user.
+    let user: String = std::env::var("USER").unwrap_or_else(|_| "user".to_string());
+    std::env::temp_dir().join(format!("conway-{}.running", user))
+}
+
+fn is_running(pid: u32) -> bool {
+    //! Whether a process with the given id is still running. Only known on
+    //! systems with `/proc`; elsewhere processes are assumed to have exited.
+    pid == std::process::id() || PathBuf::from(format!("/proc/{}", pid)).exists()
+}
+
+pub fn recover_stale_session() -> Result<bool> {
+    //! Resets the terminal if a previous run exited without cleaning up.
+    //! Returns whether anything was recovered.
+    let path: PathBuf = marker_path();
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return Ok(false);
+    };
+    match contents.trim().parse::<u32>() {
+        // another instance is running in some other terminal
+        Ok(pid) if is_running(pid) => Ok(false),
+        _ => {
+            reset()?;
+            let _ = fs::remove_file(&path);
+            Ok(true)
+        }
+    }
+}
+
+fn mark_running() {
+    //! Leaves a marker behind in case this run doesn't exit cleanly.
+    let _ = fs::write(marker_path(), std::process::id().to_string());
+}
+
+pub fn reopen_tty() -> Result<()> {
+    //! Makes the terminal the standard input again, after a piped input has
+    //! been read, so that ncurses can read keys from it.
+    let tty: File = File::open("/dev/tty")
+        .map_err(|e| anyhow!("Failed to open the terminal for input: {}", e))?;
+    if unsafe { libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) } < 0 {
+        return Err(anyhow!(
+            "Failed to read input from the terminal: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+pub fn redirect_stdout() -> Result<Option<i32>> {
+    //! Makes the terminal the standard output while the game is drawn, when
+    //! the output is piped to another program, so that only what is printed
+    //! after the game ends goes down the pipe. Returns the piped output, to
+    //! be restored with `restore_stdout`.
+    if std::io::stdout().is_terminal() {
+        return Ok(None);
+    }
+    let tty: File = fs::OpenOptions::new()
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|e| anyhow!("Failed to open the terminal for output: {}", e))?;
+    let piped: i32 = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if piped < 0 || unsafe { libc::dup2(tty.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+        return Err(anyhow!(
+            "Failed to draw on the terminal: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(Some(piped))
+}
+
+pub fn restore_stdout(piped: Option<i32>) {
+    //! Points the standard output back to where it was piped.
+    if let Some(piped) = piped {
+        let _ = std::io::stdout().flush();
+        unsafe {
+            libc::dup2(piped, libc::STDOUT_FILENO);
+            libc::close(piped);
+        }
+    }
+}
+
+fn clear_running() {
+    //! Removes the marker left by `mark_running`, after a clean exit.
+    let _ = fs::remove_file(marker_path());
+}
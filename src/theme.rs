@@ -0,0 +1,163 @@
+//! This module contains the Theme struct, which controls the colors used to
+//! draw each part of the screen.
+//!
+//! A theme is either one of the built-in themes, selected by name, or a
+//! user-defined theme loaded from a file. Theme files contain one
+//! `<element> = <foreground> <background>` entry per line, for example:
+//!
+//! ```text
+//! # comments start with '#'
+//! alive = green black
+//! dead = default
+//! status = black green
+//! selection = black white
+//! ```
+//!
+//! Elements set to `default` use the terminal's own colors, and elements that
+//! are missing use the colors of the default theme.
+
+use anyhow::{anyhow, Context, Result};
+use std::path::Path;
+
+use super::window::{Color, ColorPair};
+
+/// Names of the built-in themes, in the order they are listed to the user.
+pub const BUILTIN_THEMES: [&str; 5] = ["default", "matrix", "ocean", "amber", "inverted"];
+
+/// The colors used to draw each part of the screen.
+/// `None` means the terminal's default colors are used.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// Name of the theme
+    pub name: String,
+    /// Color of alive cells
+    pub alive: Option<ColorPair>,
+    /// Color of dead cells
+    pub dead: Option<ColorPair>,
+    /// Color of the status bar
+    pub status: Option<ColorPair>,
+    /// Color of the selected item in the demo sidebar
+    pub selection: Option<ColorPair>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: "default".to_string(),
+            alive: None,
+            dead: None,
+            status: None,
+            selection: Some(ColorPair::new(Color::Black, Color::White)),
+        }
+    }
+}
+
+impl Theme {
+    pub fn builtin(name: &str) -> Option<Theme> {
+        //! Returns the built-in theme with the given name, if there is one.
+        let pair = |fg, bg| Some(ColorPair::new(fg, bg));
+        let theme = match name {
+            "default" => Theme::default(),
+            "matrix" => Theme {
+                name: name.to_string(),
+                alive: pair(Color::Green, Color::Black),
+                dead: pair(Color::Green, Color::Black),
+                status: pair(Color::Black, Color::Green),
+                selection: pair(Color::Black, Color::Green),
+            },
+            "ocean" => Theme {
+                name: name.to_string(),
+                alive: pair(Color::Cyan, Color::Blue),
+                dead: pair(Color::Cyan, Color::Blue),
+                status: pair(Color::White, Color::Blue),
+                selection: pair(Color::Blue, Color::Cyan),
+            },
+            "amber" => Theme {
+                name: name.to_string(),
+                alive: pair(Color::Yellow, Color::Black),
+                dead: pair(Color::Yellow, Color::Black),
+                status: pair(Color::Black, Color::Yellow),
+                selection: pair(Color::Black, Color::Yellow),
+            },
+            "inverted" => Theme {
+                name: name.to_string(),
+                alive: pair(Color::Black, Color::White),
+                dead: pair(Color::Black, Color::White),
+                status: pair(Color::White, Color::Black),
+                selection: pair(Color::White, Color::Black),
+            },
+            _ => return None,
+        };
+        Some(theme)
+    }
+
+    pub fn load(name_or_path: &str) -> Result<Theme> {
+        //! Loads a theme by built-in name, or from a theme file if no built-in
+        //! theme has that name.
+        if let Some(theme) = Theme::builtin(name_or_path) {
+            return Ok(theme);
+        }
+        let path: &Path = Path::new(name_or_path);
+        if !path.exists() {
+            return Err(anyhow!(
+                "Unknown theme '{}'. Built-in themes are: {}",
+                name_or_path,
+                BUILTIN_THEMES.join(", ")
+            ));
+        }
+        let contents: String = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read theme file '{}'", name_or_path))?;
+        let name: String = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| name_or_path.to_string());
+        Theme::parse(&name, &contents)
+            .with_context(|| format!("Invalid theme file '{}'", name_or_path))
+    }
+
+    pub fn parse(name: &str, contents: &str) -> Result<Theme> {
+        //! Parses the contents of a theme file. Elements that are not listed
+        //! keep the colors of the default theme.
+        let mut theme: Theme = Theme {
+            name: name.to_string(),
+            ..Theme::default()
+        };
+        for (lineno, line) in contents.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (element, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("line {}: expected '<element> = <colors>'", lineno + 1))?;
+            let colors: Option<ColorPair> =
+                parse_color_pair(value.trim()).with_context(|| format!("line {}", lineno + 1))?;
+            match element.trim() {
+                "alive" => theme.alive = colors,
+                "dead" => theme.dead = colors,
+                "status" => theme.status = colors,
+                "selection" => theme.selection = colors,
+                other => return Err(anyhow!("line {}: unknown element '{}'", lineno + 1, other)),
+            }
+        }
+        Ok(theme)
+    }
+}
+
+fn parse_color_pair(value: &str) -> Result<Option<ColorPair>> {
+    //! Parses `<foreground> <background>`, or `default` for the terminal's colors.
+    if value == "default" {
+        return Ok(None);
+    }
+    let colors: Vec<&str> = value.split_whitespace().collect();
+    if colors.len() != 2 {
+        return Err(anyhow!(
+            "expected '<foreground> <background>' or 'default', got '{}'",
+            value
+        ));
+    }
+    Ok(Some(ColorPair::new(
+        colors[0].parse::<Color>()?,
+        colors[1].parse::<Color>()?,
+    )))
+}
@@ -0,0 +1,191 @@
+//! This module contains the topologies of the grid, which decide how its
+//! edges are joined, and so which cells are neighbors across them.
+
+use std::fmt;
+
+/// How the edges of the grid are joined.
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum Topology {
+    /// Opposite edges are joined, as on a doughnut
+    #[default]
+    Torus,
+    /// Nothing lies beyond the edges; cells there are always dead
+    Plane,
+    /// Left and right edges are joined; top and bottom edges are joined with
+    /// a twist, swapping left and right
+    Klein,
+    /// Both pairs of opposite edges are joined with a twist, making the real
+    /// projective plane
+    CrossSurface,
+    /// The edges reflect the cells along them
+    Mirror,
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Topology::Torus => write!(f, "torus"),
+            Topology::Plane => write!(f, "plane"),
+            Topology::Klein => write!(f, "klein"),
+            Topology::CrossSurface => write!(f, "cross-surface"),
+            Topology::Mirror => write!(f, "mirror"),
+        }
+    }
+}
+
+impl Topology {
+    pub fn locate(&self, i: isize, j: isize, nrows: usize, ncols: usize) -> Option<(usize, usize)> {
+        //! Finds the cell at row `i` and column `j` of a grid of `nrows` by
+        //! `ncols` cells, where the position may lie beyond the edges, by
+        //! less than the size of the grid. Returns `None` if there is no cell
+        //! there.
+        let (rows, cols) = (nrows as isize, ncols as isize);
+        let beyond_rows: bool = !(0..rows).contains(&i);
+        let beyond_cols: bool = !(0..cols).contains(&j);
+        let (i, j) = match self {
+            Topology::Torus => (i, j),
+            Topology::Plane if beyond_rows || beyond_cols => return None,
+            Topology::Plane => (i, j),
+            Topology::Klein if beyond_rows => (i, cols - 1 - j),
+            Topology::Klein => (i, j),
+            Topology::CrossSurface => (
+                if beyond_cols { rows - 1 - i } else { i },
+                if beyond_rows { cols - 1 - j } else { j },
+            ),
+            Topology::Mirror => (reflect(i, rows), reflect(j, cols)),
+        };
+        Some((i.rem_euclid(rows) as usize, j.rem_euclid(cols) as usize))
+    }
+
+    pub fn joins_rows(&self) -> bool {
+        //! Whether the top and bottom edges are joined, so that the last row
+        //! lies next to the first.
+        matches!(
+            self,
+            Topology::Torus | Topology::Klein | Topology::CrossSurface
+        )
+    }
+}
+
+fn reflect(i: isize, size: isize) -> isize {
+    //! Reflects a position beyond the edges of a row or column of `size`
+    //! cells back into it, the cells along the edges facing themselves.
+    let reflected: isize = if i < 0 {
+        -1 - i
+    } else if i >= size {
+        2 * size - 1 - i
+    } else {
+        i
+    };
+    reflected.clamp(0, size - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Positions around a 4 by 5 grid: the corners beyond it, the middle of
+    /// each edge beyond it, and a cell inside.
+    const POSITIONS: [(isize, isize); 9] = [
+        (-1, -1),
+        (-1, 5),
+        (4, -1),
+        (4, 5),
+        (-1, 2),
+        (4, 2),
+        (1, -1),
+        (1, 5),
+        (2, 3),
+    ];
+
+    /// Where each of the positions is found, in order.
+    type Located = [Option<(usize, usize)>; 9];
+
+    #[test]
+    fn corners_and_edges_are_located_on_every_topology() {
+        let table: [(Topology, Located); 5] = [
+            (
+                Topology::Torus,
+                [
+                    Some((3, 4)),
+                    Some((3, 0)),
+                    Some((0, 4)),
+                    Some((0, 0)),
+                    Some((3, 2)),
+                    Some((0, 2)),
+                    Some((1, 4)),
+                    Some((1, 0)),
+                    Some((2, 3)),
+                ],
+            ),
+            (
+                Topology::Plane,
+                [None, None, None, None, None, None, None, None, Some((2, 3))],
+            ),
+            (
+                Topology::Klein,
+                [
+                    Some((3, 0)),
+                    Some((3, 4)),
+                    Some((0, 0)),
+                    Some((0, 4)),
+                    Some((3, 2)),
+                    Some((0, 2)),
+                    Some((1, 4)),
+                    Some((1, 0)),
+                    Some((2, 3)),
+                ],
+            ),
+            (
+                Topology::CrossSurface,
+                [
+                    Some((0, 0)),
+                    Some((0, 4)),
+                    Some((3, 0)),
+                    Some((3, 4)),
+                    Some((3, 2)),
+                    Some((0, 2)),
+                    Some((2, 4)),
+                    Some((2, 0)),
+                    Some((2, 3)),
+                ],
+            ),
+            (
+                Topology::Mirror,
+                [
+                    Some((0, 0)),
+                    Some((0, 4)),
+                    Some((3, 0)),
+                    Some((3, 4)),
+                    Some((0, 2)),
+                    Some((3, 2)),
+                    Some((1, 0)),
+                    Some((1, 4)),
+                    Some((2, 3)),
+                ],
+            ),
+        ];
+        for (topology, expected) in table {
+            for ((i, j), expected) in POSITIONS.into_iter().zip(expected) {
+                assert_eq!(
+                    topology.locate(i, j, 4, 5),
+                    expected,
+                    "{} at {},{}",
+                    topology,
+                    i,
+                    j
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn only_joined_rows_wrap_around() {
+        for topology in [Topology::Torus, Topology::Klein, Topology::CrossSurface] {
+            assert!(topology.joins_rows(), "{}", topology);
+        }
+        for topology in [Topology::Plane, Topology::Mirror] {
+            assert!(!topology.joins_rows(), "{}", topology);
+        }
+    }
+}
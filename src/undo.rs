@@ -0,0 +1,137 @@
+//! This module contains the undo history of the edits made to the grid.
+
+use super::conway::Cell;
+
+/// Maximum number of edits that can be undone.
+const UNDO_LIMIT: usize = 100;
+
+/// An edit of a rectangular region of the grid, with the region's cells
+/// before and after the edit.
+#[derive(Debug, Clone)]
+struct Edit {
+    /// Top-left corner (row, column) of the edited region
+    top_left: (usize, usize),
+    /// Cells of the region before the edit
+    before: Vec<Vec<Cell>>,
+    /// Cells of the region after the edit
+    after: Vec<Vec<Cell>>,
+}
+
+/// Edits that can be undone and redone, most recent last.
+#[derive(Debug, Clone, Default)]
+pub struct UndoHistory {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl UndoHistory {
+    pub fn new() -> UndoHistory {
+        UndoHistory::default()
+    }
+
+    pub fn edit<F: FnOnce(&mut [Vec<Cell>])>(
+        &mut self,
+        grid: &mut [Vec<Cell>],
+        top_left: (usize, usize),
+        bottom_right: (usize, usize),
+        edit: F,
+    ) {
+        //! Applies an edit that only changes cells within the rectangle given
+        //! by its inclusive corners, recording it so it can be undone.
+        let before: Vec<Vec<Cell>> = region(grid, top_left, bottom_right);
+        edit(grid);
+        let after: Vec<Vec<Cell>> = region(grid, top_left, bottom_right);
+        self.undo.push(Edit {
+            top_left,
+            before,
+            after,
+        });
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    pub fn continue_edit<F: FnOnce(&mut [Vec<Cell>])>(
+        &mut self,
+        grid: &mut [Vec<Cell>],
+        top_left: (usize, usize),
+        bottom_right: (usize, usize),
+        edit: F,
+    ) {
+        //! Applies an edit that carries on the most recent one, like the next
+        //! part of a brush stroke, so that both are undone together.
+        let Some(last) = self.undo.pop() else {
+            return self.edit(grid, top_left, bottom_right, edit);
+        };
+        let last_bottom_right: (usize, usize) = (
+            last.top_left.0 + last.before.len() - 1,
+            last.top_left.1 + last.before[0].len() - 1,
+        );
+        let top_left: (usize, usize) = (
+            top_left.0.min(last.top_left.0),
+            top_left.1.min(last.top_left.1),
+        );
+        let bottom_right: (usize, usize) = (
+            bottom_right.0.max(last_bottom_right.0),
+            bottom_right.1.max(last_bottom_right.1),
+        );
+        // the cells as they were before the most recent edit
+        let mut before: Vec<Vec<Cell>> = region(grid, top_left, bottom_right);
+        let offset: (usize, usize) = (last.top_left.0 - top_left.0, last.top_left.1 - top_left.1);
+        restore(&mut before, offset, &last.before);
+        edit(grid);
+        let after: Vec<Vec<Cell>> = region(grid, top_left, bottom_right);
+        self.undo.push(Edit {
+            top_left,
+            before,
+            after,
+        });
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self, grid: &mut [Vec<Cell>]) -> bool {
+        //! Reverts the most recent edit. Returns false if there was nothing to undo.
+        let Some(edit) = self.undo.pop() else {
+            return false;
+        };
+        restore(grid, edit.top_left, &edit.before);
+        self.redo.push(edit);
+        true
+    }
+
+    pub fn redo(&mut self, grid: &mut [Vec<Cell>]) -> bool {
+        //! Reapplies the most recently undone edit. Returns false if there was
+        //! nothing to redo.
+        let Some(edit) = self.redo.pop() else {
+            return false;
+        };
+        restore(grid, edit.top_left, &edit.after);
+        self.undo.push(edit);
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+fn region(
+    grid: &[Vec<Cell>],
+    top_left: (usize, usize),
+    bottom_right: (usize, usize),
+) -> Vec<Vec<Cell>> {
+    //! Copies the cells within a rectangle of the grid.
+    grid[top_left.0..=bottom_right.0]
+        .iter()
+        .map(|row| row[top_left.1..=bottom_right.1].to_vec())
+        .collect()
+}
+
+fn restore(grid: &mut [Vec<Cell>], top_left: (usize, usize), cells: &[Vec<Cell>]) {
+    //! Copies cells back into the grid, with their top-left corner at `top_left`.
+    for (i, row) in cells.iter().enumerate() {
+        grid[top_left.0 + i][top_left.1..top_left.1 + row.len()].copy_from_slice(row);
+    }
+}
@@ -0,0 +1,311 @@
+//! This module contains the `verify` subcommand, which checks the engine
+//! against fixtures of known evolutions, without touching the terminal.
+//!
+//! A fixture is a text file giving a grid, how many generations to run it
+//! for, and the grid it must end up as. Both grids are written in the seed
+//! file format, and their size is the size of the whole simulated grid:
+//!
+//! ```text
+//! # a blinker turns back and forth
+//! generations: 1
+//! topology: plane
+//! before:
+//! .....
+//! ..*..
+//! ..*..
+//! ..*..
+//! .....
+//! after:
+//! .....
+//! .....
+//! .***.
+//! .....
+//! .....
+//! ```
+//!
+//! `topology` can be left out, in which case the grid is a torus.
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+
+use super::conway::{new_grid, Cell, Engine, Stepper};
+use super::topology::Topology;
+
+/// Extension of fixture files, which are looked for in directories.
+const EXTENSION: &str = "fixture";
+
+/// A known evolution of a grid.
+#[derive(Debug, Clone)]
+struct Fixture {
+    /// Number of generations between the two grids
+    generations: u64,
+    /// How the edges of the grid are joined
+    topology: Topology,
+    /// Which cells are alive at the start
+    before: Vec<Vec<bool>>,
+    /// Which cells must be alive after the generations have run
+    after: Vec<Vec<bool>>,
+}
+
+impl Fixture {
+    fn parse(text: &str) -> Result<Fixture> {
+        let mut generations: Option<u64> = None;
+        let mut topology: Topology = Topology::default();
+        let mut before: Option<Vec<Vec<bool>>> = None;
+        let mut after: Option<Vec<Vec<bool>>> = None;
+        // the grid whose rows are being read, if any
+        let mut section: Option<&mut Option<Vec<Vec<bool>>>> = None;
+
+        for (n, line) in text.lines().enumerate() {
+            let line: &str = line.trim_end();
+            if line.starts_with('#') || (line.is_empty() && section.is_none()) {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once(':') {
+                let value: &str = value.trim();
+                match key.trim() {
+                    "generations" => {
+                        generations = Some(value.parse().map_err(|_| {
+                            anyhow!("line {}: invalid number of generations '{}'", n + 1, value)
+                        })?)
+                    }
+                    "topology" => {
+                        topology = Topology::from_str(value, true)
+                            .map_err(|_| anyhow!("line {}: unknown topology '{}'", n + 1, value))?
+                    }
+                    "before" => section = Some(&mut before),
+                    "after" => section = Some(&mut after),
+                    key => return Err(anyhow!("line {}: unknown key '{}'", n + 1, key)),
+                }
+                if let Some(grid) = section.as_mut() {
+                    grid.get_or_insert_with(Vec::new);
+                }
+                continue;
+            }
+            match section.as_mut().and_then(|grid| grid.as_mut()) {
+                Some(grid) => grid.push(line.chars().map(|c| c == '*').collect()),
+                None => return Err(anyhow!("line {}: expected 'before:' or 'after:'", n + 1)),
+            }
+        }
+
+        let generations: u64 = generations.ok_or_else(|| anyhow!("missing 'generations:'"))?;
+        let before: Vec<Vec<bool>> =
+            trim_rows(before.ok_or_else(|| anyhow!("missing 'before:' grid"))?);
+        let after: Vec<Vec<bool>> =
+            trim_rows(after.ok_or_else(|| anyhow!("missing 'after:' grid"))?);
+        if size(&before) != size(&after) {
+            let (before_rows, before_cols) = size(&before);
+            let (after_rows, after_cols) = size(&after);
+            return Err(anyhow!(
+                "the grids differ in size: {}x{} before, {}x{} after",
+                before_cols,
+                before_rows,
+                after_cols,
+                after_rows
+            ));
+        }
+        if before.is_empty() {
+            return Err(anyhow!("the grids are empty"));
+        }
+        Ok(Fixture {
+            generations,
+            topology,
+            before,
+            after,
+        })
+    }
+
+    fn run(&self, engine: Engine) -> Result<Vec<Vec<bool>>> {
+        //! Runs the engine on the starting grid, returning the grid it ends
+        //! up as.
+        let (nrows, ncols) = size(&self.before);
+        let mut grid: Vec<Vec<Cell>> = new_grid(nrows, ncols);
+        for (i, row) in self.before.iter().enumerate() {
+            for (j, alive) in row.iter().enumerate() {
+                if *alive {
+                    grid[i][j].set_alive();
+                }
+            }
+        }
+        let mut stepper: Stepper = Stepper::new(engine)?;
+        for _ in 0..self.generations {
+            stepper.step(&mut grid, self.topology);
+        }
+        Ok(grid
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.is_alive()).collect())
+            .collect())
+    }
+}
+
+fn trim_rows(mut rows: Vec<Vec<bool>>) -> Vec<Vec<bool>> {
+    //! Drops the blank lines after a grid, and pads its rows to the same
+    //! width.
+    while rows.last().is_some_and(|row| row.is_empty()) {
+        rows.pop();
+    }
+    let ncols: usize = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    for row in &mut rows {
+        row.resize(ncols, false);
+    }
+    rows
+}
+
+fn size(rows: &[Vec<bool>]) -> (usize, usize) {
+    (rows.len(), rows.first().map_or(0, |row| row.len()))
+}
+
+fn fixture_paths(path: &str) -> Result<Vec<PathBuf>> {
+    //! Finds the fixtures in a directory and its subdirectories, or returns
+    //! the path itself if it is a file.
+    let path: &Path = Path::new(path);
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut paths: Vec<PathBuf> = vec![];
+    let entries = std::fs::read_dir(path)
+        .with_context(|| format!("Failed to read directory '{}'", path.display()))?;
+    for entry in entries {
+        let entry: PathBuf = entry?.path();
+        if entry.is_dir() {
+            paths.extend(fixture_paths(&entry.to_string_lossy())?);
+        } else if entry
+            .extension()
+            .is_some_and(|extension| extension == EXTENSION)
+        {
+            paths.push(entry);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn diff(expected: &[Vec<bool>], actual: &[Vec<bool>]) -> String {
+    //! Shows the expected and actual grids side by side, marking the rows
+    //! that differ.
+    let show = |row: &[bool]| -> String {
+        row.iter()
+            .map(|alive| if *alive { '*' } else { '.' })
+            .collect()
+    };
+    let width: usize = size(expected).1.max("expected".len());
+    let mut text: String = format!("    {:width$}   actual\n", "expected", width = width);
+    for (expected, actual) in expected.iter().zip(actual) {
+        let marker: char = if expected == actual { ' ' } else { '>' };
+        text.push_str(&format!(
+            "  {} {:width$}   {}\n",
+            marker,
+            show(expected),
+            show(actual),
+            width = width
+        ));
+    }
+    text
+}
+
+pub fn run(paths: &[String], engine: Engine) -> Result<()> {
+    //! Checks every fixture in the given files and directories on the given
+    //! engine, printing a diff for each one it doesn't reproduce. Fails if any fixture
+    //! is invalid or doesn't match.
+    let mut fixtures: Vec<PathBuf> = vec![];
+    for path in paths {
+        fixtures.extend(fixture_paths(path)?);
+    }
+    if fixtures.is_empty() {
+        return Err(anyhow!("No .{} files found", EXTENSION));
+    }
+
+    let mut failed: usize = 0;
+    for path in &fixtures {
+        let fixture: Result<Fixture> = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read '{}'", path.display()))
+            .and_then(|text| Fixture::parse(&text));
+        let fixture: Fixture = match fixture {
+            Ok(fixture) => fixture,
+            Err(e) => {
+                println!("error {}: {}", path.display(), e);
+                failed += 1;
+                continue;
+            }
+        };
+        let actual: Vec<Vec<bool>> = fixture.run(engine)?;
+        if actual == fixture.after {
+            println!("ok    {}", path.display());
+        } else {
+            let wrong: usize = fixture
+                .after
+                .iter()
+                .flatten()
+                .zip(actual.iter().flatten())
+                .filter(|(expected, actual)| expected != actual)
+                .count();
+            println!(
+                "FAIL  {}: {} cells differ after {} generations",
+                path.display(),
+                wrong,
+                fixture.generations
+            );
+            print!("{}", diff(&fixture.after, &actual));
+            failed += 1;
+        }
+    }
+
+    println!("{} passed, {} failed", fixtures.len() - failed, failed);
+    if failed > 0 {
+        return Err(anyhow!("{} of {} fixtures failed", failed, fixtures.len()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Directory of the fixtures shipped with the crate.
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+
+    #[test]
+    fn the_engine_reproduces_every_fixture() {
+        let paths: Vec<PathBuf> = fixture_paths(FIXTURES).unwrap();
+        assert!(!paths.is_empty());
+        for path in paths {
+            let text: String = std::fs::read_to_string(&path).unwrap();
+            let fixture: Fixture = Fixture::parse(&text).unwrap();
+            let actual: Vec<Vec<bool>> = fixture.run(Engine::Cpu).unwrap();
+            assert!(
+                actual == fixture.after,
+                "{}\n{}",
+                path.display(),
+                diff(&fixture.after, &actual)
+            );
+        }
+    }
+
+    #[test]
+    fn fixtures_default_to_a_torus_and_pad_their_rows() {
+        let fixture: Fixture =
+            Fixture::parse("generations: 2\nbefore:\n*\n.*\n\nafter:\n..\n..\n\n").unwrap();
+        assert_eq!(fixture.generations, 2);
+        assert_eq!(fixture.topology, Topology::Torus);
+        assert_eq!(fixture.before, vec![vec![true, false], vec![false, true]]);
+        assert_eq!(size(&fixture.after), (2, 2));
+    }
+
+    #[test]
+    fn invalid_fixtures_are_refused() {
+        for text in [
+            "before:\n*\nafter:\n*\n",
+            "generations: 1\nafter:\n*\n",
+            "generations: 1\nbefore:\n*\n",
+            "generations: many\nbefore:\n*\nafter:\n*\n",
+            "generations: 1\ntopology: sphere\nbefore:\n*\nafter:\n*\n",
+            "generations: 1\ncolor: red\nbefore:\n*\nafter:\n*\n",
+            "generations: 1\n*\nbefore:\n*\nafter:\n*\n",
+            "generations: 1\nbefore:\n**\nafter:\n*\n*\n",
+            "generations: 1\nbefore:\nafter:\n",
+        ] {
+            assert!(Fixture::parse(text).is_err(), "{}", text);
+        }
+    }
+}
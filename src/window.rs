@@ -2,8 +2,12 @@
 //!
 //! The Window struct is a wrapper around the ncurses WINDOW struct.
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use ncurses::*;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use unicode_width::UnicodeWidthChar;
 
 pub enum ArrowKeys {
     Up = 65,
@@ -12,19 +16,158 @@ pub enum ArrowKeys {
     Left = 68,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Color {
-    Black = 0,
-    Red = 1,
-    Green = 2,
-    Yellow = 3,
-    Blue = 4,
-    Magenta = 5,
-    Cyan = 6,
-    White = 7,
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    /// A 24-bit color. Only shown as-is on terminals that can redefine
+    /// colors, otherwise the nearest of the eight basic colors is used.
+    Rgb(u8, u8, u8),
 }
 
-#[derive(Clone, Copy)]
+impl Color {
+    fn basic_number(&self) -> Option<i16> {
+        //! Returns the ncurses color number of the eight basic colors.
+        match self {
+            Color::Black => Some(COLOR_BLACK),
+            Color::Red => Some(COLOR_RED),
+            Color::Green => Some(COLOR_GREEN),
+            Color::Yellow => Some(COLOR_YELLOW),
+            Color::Blue => Some(COLOR_BLUE),
+            Color::Magenta => Some(COLOR_MAGENTA),
+            Color::Cyan => Some(COLOR_CYAN),
+            Color::White => Some(COLOR_WHITE),
+            Color::Rgb(..) => None,
+        }
+    }
+
+    pub fn nearest_basic(&self) -> Color {
+        //! Approximates the color with one of the eight basic colors by
+        //! thresholding each channel.
+        match *self {
+            Color::Rgb(r, g, b) => match (r >= 128, g >= 128, b >= 128) {
+                (false, false, false) => Color::Black,
+                (true, false, false) => Color::Red,
+                (false, true, false) => Color::Green,
+                (true, true, false) => Color::Yellow,
+                (false, false, true) => Color::Blue,
+                (true, false, true) => Color::Magenta,
+                (false, true, true) => Color::Cyan,
+                (true, true, true) => Color::White,
+            },
+            basic => basic,
+        }
+    }
+
+    pub fn lerp(from: (u8, u8, u8), to: (u8, u8, u8), t: f32) -> Color {
+        //! Linearly interpolates between two RGB colors, with `t` in 0..=1.
+        let t: f32 = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Color::Rgb(mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+    }
+}
+
+pub fn supports_true_color() -> bool {
+    //! Whether the terminal can redefine colors, which is needed to show
+    //! `Color::Rgb` colors as-is.
+    has_colors() && can_change_color() && COLORS() > 16
+}
+
+pub fn glyph_width(c: char) -> usize {
+    //! Number of columns of the terminal a character takes up: 2 for wide
+    //! characters such as most emoji, 0 for combining and control characters.
+    c.width().unwrap_or(0)
+}
+
+pub fn supports_rgb() -> bool {
+    //! Whether `Color::Rgb` colors can be shown reasonably faithfully, either
+    //! as-is or approximated with a 256-color palette.
+    supports_true_color() || (has_colors() && COLORS() >= 256)
+}
+
+/// The 16 standard colors as shown by xterm, in ncurses color number order.
+const STANDARD_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Channel levels of the 6x6x6 color cube in the 256-color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    //! Squared euclidean distance between two RGB colors.
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn nearest_palette_color(rgb: (u8, u8, u8), colors: i32) -> i16 {
+    //! Returns the number of the palette entry closest to the RGB color, for
+    //! a terminal with the standard palette of `colors` colors (8, 16 or 256).
+    let standard: usize = if colors >= 16 { 16 } else { 8 };
+    let mut best: (u32, i16) = STANDARD_COLORS[..standard]
+        .iter()
+        .enumerate()
+        .map(|(n, c)| (distance(rgb, *c), n as i16))
+        .min()
+        .unwrap_or((0, COLOR_WHITE));
+    if colors >= 256 {
+        // closest entry of the color cube (16-231)
+        let level = |c: u8| {
+            (0..CUBE_LEVELS.len())
+                .min_by_key(|i| (CUBE_LEVELS[*i] as i32 - c as i32).abs())
+                .unwrap_or(0)
+        };
+        let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+        let cube: (u8, u8, u8) = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+        best = best.min((distance(rgb, cube), (16 + 36 * r + 6 * g + b) as i16));
+        // closest entry of the grayscale ramp (232-255)
+        let average: i32 = (rgb.0 as i32 + rgb.1 as i32 + rgb.2 as i32) / 3;
+        let step: i32 = ((average - 8).max(0) / 10).min(23);
+        let gray: u8 = (8 + step * 10) as u8;
+        best = best.min((distance(rgb, (gray, gray, gray)), (232 + step) as i16));
+    }
+    best.1
+}
+
+impl FromStr for Color {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "black" => Ok(Color::Black),
+            "red" => Ok(Color::Red),
+            "green" => Ok(Color::Green),
+            "yellow" => Ok(Color::Yellow),
+            "blue" => Ok(Color::Blue),
+            "magenta" => Ok(Color::Magenta),
+            "cyan" => Ok(Color::Cyan),
+            "white" => Ok(Color::White),
+            _ => Err(anyhow!("unknown color '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ColorPair {
     foreground: Color,
     background: Color,
@@ -37,6 +180,110 @@ impl ColorPair {
             background,
         }
     }
+
+    pub fn foreground(&self) -> Color {
+        self.foreground
+    }
+
+    pub fn background(&self) -> Color {
+        self.background
+    }
+}
+
+/// Keeps track of which ncurses color pair number has been initialized for
+/// each `ColorPair`, so that every combination is only initialized once and
+/// different colors can be on screen at the same time. RGB colors are
+/// likewise given their own color numbers, above the 16 standard colors, or
+/// mapped to the nearest entry of the terminal's palette if it can't
+/// redefine colors.
+///
+/// When the terminal runs out of color pairs, the least recently used pair
+/// is recycled.
+///
+/// Color pairs are global to ncurses, so a single palette is shared by all windows.
+struct Palette {
+    pairs: HashMap<ColorPair, i16>,
+    colors: HashMap<(u8, u8, u8), i16>,
+    /// Value of `clock` when each color pair was last used
+    last_used: HashMap<ColorPair, u64>,
+    /// Incremented every time a color pair is used
+    clock: u64,
+}
+
+static PALETTE: Mutex<Option<Palette>> = Mutex::new(None);
+
+impl Palette {
+    fn pair_number(&mut self, color_pair: &ColorPair) -> i16 {
+        //! Returns the pair number for the given colors, initializing a new
+        //! pair the first time a combination is used.
+        self.clock += 1;
+        self.last_used.insert(*color_pair, self.clock);
+        if let Some(number) = self.pairs.get(color_pair) {
+            return *number;
+        }
+        // pair 0 is reserved for the terminal's default colors
+        let max_pairs: i32 = COLOR_PAIRS().min(i16::MAX as i32);
+        let number: i16 = if (self.pairs.len() as i32 + 1) < max_pairs {
+            self.pairs.len() as i16 + 1
+        } else {
+            self.recycle_pair()
+        };
+        let foreground: i16 = self.color_number(color_pair.foreground);
+        let background: i16 = self.color_number(color_pair.background);
+        init_pair(number, foreground, background);
+        self.pairs.insert(*color_pair, number);
+        number
+    }
+
+    fn recycle_pair(&mut self) -> i16 {
+        //! Frees up the least recently used color pair, returning its number.
+        let oldest: Option<ColorPair> = self
+            .pairs
+            .keys()
+            .min_by_key(|pair| self.last_used.get(pair).copied().unwrap_or(0))
+            .copied();
+        match oldest {
+            Some(pair) => {
+                self.last_used.remove(&pair);
+                self.pairs.remove(&pair).unwrap_or(1)
+            }
+            None => 1,
+        }
+    }
+
+    fn color_number(&mut self, color: Color) -> i16 {
+        //! Returns the color number for the given color, defining a new color
+        //! the first time an RGB color is used. Falls back to the nearest
+        //! entry of the terminal's palette if it can't define any more colors.
+        let Color::Rgb(r, g, b) = color else {
+            return color.basic_number().unwrap_or(COLOR_WHITE);
+        };
+        if let Some(number) = self.colors.get(&(r, g, b)) {
+            return *number;
+        }
+        let number: i32 = 16 + self.colors.len() as i32;
+        if !supports_true_color() || number >= COLORS() || number > i16::MAX as i32 {
+            return nearest_palette_color((r, g, b), COLORS());
+        }
+        // ncurses color components range from 0 to 1000
+        let scale = |c: u8| (c as i32 * 1000 / 255) as i16;
+        init_color(number as i16, scale(r), scale(g), scale(b));
+        self.colors.insert((r, g, b), number as i16);
+        number as i16
+    }
+}
+
+fn color_pair_number(color_pair: &ColorPair) -> i16 {
+    //! Looks up the pair number for the given colors in the shared palette.
+    let mut palette = PALETTE.lock().unwrap_or_else(|e| e.into_inner());
+    palette
+        .get_or_insert_with(|| Palette {
+            pairs: HashMap::new(),
+            colors: HashMap::new(),
+            last_used: HashMap::new(),
+            clock: 0,
+        })
+        .pair_number(color_pair)
 }
 
 pub struct Window {
@@ -48,8 +295,10 @@ pub struct Window {
 }
 
 impl Window {
-    pub fn new(rows: i32, cols: i32, x: i32, y: i32) -> Self {
-        let win: *mut i8 = newwin(rows, cols, x, y);
+    pub fn new(rows: i32, cols: i32, y: i32, x: i32) -> Self {
+        //! Creates a window with its top-left corner at screen row `y` and
+        //! column `x`.
+        let win: *mut i8 = newwin(rows, cols, y, x);
         let new_window: Self = Self {
             win,
             rows,
@@ -61,6 +310,19 @@ impl Window {
         new_window
     }
 
+    pub fn centered(parent: &Window, rows: i32, cols: i32) -> Self {
+        //! Creates a window in the middle of another one, shrunk to fit in it
+        //! if needed.
+        let (rows, cols) = (rows.min(parent.rows), cols.min(parent.cols));
+        let (top, left) = parent.position();
+        Window::new(
+            rows,
+            cols,
+            top + (parent.rows - rows) / 2,
+            left + (parent.cols - cols) / 2,
+        )
+    }
+
     pub fn draw_border(&self) -> Result<()> {
         //! Draws a border along the right-hand side of the window.
         for i in 0..self.rows {
@@ -70,6 +332,17 @@ impl Window {
         Ok(())
     }
 
+    pub fn draw_box(&self) {
+        //! Draws a border all around the window.
+        box_(self.win, 0, 0);
+    }
+
+    pub fn touch(&self) {
+        //! Marks the whole window as changed, so that the next refresh redraws
+        //! it over whatever was drawn on top of it.
+        touchwin(self.win);
+    }
+
     pub fn refresh(&self) {
         wrefresh(self.win);
     }
@@ -80,14 +353,26 @@ impl Window {
 
     pub fn print(&self, x: i32, y: i32, s: &str, color_pair: Option<&ColorPair>) -> Result<()> {
         //! Prints a string to the window at the specified x and y coordinates.
-        if let Some(color) = color_pair {
-            init_pair(1, color.foreground as i16, color.background as i16);
-            wattron(self.win, COLOR_PAIR(1));
-            mvwprintw(self.win, y, x, s)?;
-            wattroff(self.win, COLOR_PAIR(1));
-        } else {
-            mvwprintw(self.win, y, x, s)?;
+        self.print_attr(x, y, s, color_pair, A_NORMAL)
+    }
+
+    pub fn print_attr(
+        &self,
+        x: i32,
+        y: i32,
+        s: &str,
+        color_pair: Option<&ColorPair>,
+        attr: attr_t,
+    ) -> Result<()> {
+        //! Prints a string to the window at the specified x and y coordinates,
+        //! with the given attributes (e.g. `A_DIM`) turned on.
+        let mut attrs: attr_t = attr;
+        if let Some(color) = color_pair.filter(|_| has_colors()) {
+            attrs |= COLOR_PAIR(color_pair_number(color));
         }
+        wattron(self.win, attrs);
+        mvwprintw(self.win, y, x, s)?;
+        wattroff(self.win, attrs);
         Ok(())
     }
 
@@ -127,6 +412,42 @@ impl Window {
         mvwin(self.win, self.y, self.x);
     }
 
+    pub fn position(&self) -> (i32, i32) {
+        //! Returns the screen row and column of the window's top-left corner.
+        let (mut row, mut col) = (0, 0);
+        getbegyx(self.win, &mut row, &mut col);
+        (row, col)
+    }
+
+    pub fn read_row(&self, row: i32) -> Vec<(char, attr_t)> {
+        //! Reads back what has been drawn on a row of the window: the
+        //! character and attributes in each column.
+        let mut buffer: Vec<u8> = vec![0; self.cols as usize * 4 + 1];
+        // the ncurses crate's own wrapper reads past what ncurses writes, so
+        // the buffer is handed over directly
+        unsafe {
+            ll::mvwinnstr(
+                self.win,
+                row,
+                0,
+                buffer.as_mut_ptr() as ll::char_p,
+                buffer.len() as i32 - 1,
+            );
+        }
+        let len: usize = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+        let mut cells: Vec<(char, attr_t)> = Vec::new();
+        // wide characters take up more than one column
+        let mut col: i32 = 0;
+        for c in String::from_utf8_lossy(&buffer[..len]).chars() {
+            if col >= self.cols {
+                break;
+            }
+            cells.push((c, mvwinch(self.win, row, col) & !A_CHARTEXT));
+            col += glyph_width(c).max(1) as i32;
+        }
+        cells
+    }
+
     pub fn get_rows(&self) -> i32 {
         self.rows
     }
@@ -135,3 +456,9 @@ impl Window {
         self.cols
     }
 }
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        delwin(self.win);
+    }
+}